@@ -26,12 +26,7 @@ fn main() {
         // Run through packets until we get to one with a payload.
         payload = match reader.next_payload() {
             Ok(payload) => payload.expect("No valid complete TS payload found"),
-            Err(e) => {
-                if e.is::<std::io::Error>() {
-                    
-                }
-                panic!("An error was hit!: {}", e);
-            }
+            Err(e) => panic!("An error was hit!: {}", e),
         };
 
         if search.search_in(&payload).is_some() {
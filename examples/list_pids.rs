@@ -1,6 +1,6 @@
 use clap::Parser;
 use ts_analyzer::reader::TSReader;
-use std::{collections::HashSet, fs::File, io::BufReader, process::ExitCode};
+use std::{fs::File, io::BufReader, process::ExitCode};
 use log::{debug, info};
 
 #[derive(Parser, Debug)]
@@ -27,34 +27,21 @@ fn main() -> ExitCode {
 
     info!("Starting laser video sorter");
 
-    // List of PIDs in the video
-    let mut pids = HashSet::new();
-
     // Boilerplate to create a TSReader object
     let f = File::open(video).expect("Couldn't open file");
     let buf_reader = BufReader::new(f);
     let mut reader = TSReader::new(video, buf_reader).expect("Transport Stream file contains no SYNC bytes.");
 
-    loop {
-        // Check to see if any of the KLV data indicates that the laser is on
-        let packet = match reader.next_packet() {
-            Ok(packet) => packet,
-            Err(e) => panic!("Could not get payload due to error: {}", e),
-        };
-
-        // If `None` is returned then we have finished reading the file.
-        let Some(packet) = packet else {
-            debug!("Finished reading file [{}]", video);
-            break;
-        };
-
-        pids.insert(packet.header().pid());
-    }
+    let stats = match reader.collect_stats() {
+        Ok(stats) => stats,
+        Err(e) => panic!("Could not get packet due to error: {}", e),
+    };
+    debug!("Finished reading file [{}]", video);
+    info!("Read {} packets ({} bytes)", reader.packets_read(), reader.bytes_read());
 
-    let mut pids: Vec<u16> = Vec::from_iter(pids);
-    pids.sort();
+    let pids: Vec<u16> = stats.pid_packets().keys().copied().collect();
 
     println!("PIDs in video file [{}]:\n{:#?}", video, pids);
 
-    return ExitCode::from(0);
+    ExitCode::from(0)
 }
\ No newline at end of file
@@ -0,0 +1,432 @@
+//! A module for reading the transport stream from an async source.
+//!
+//! [`AsyncTSReader`] mirrors the packet reading of [`TSReader`] for sources that implement tokio's
+//! [`AsyncRead`] and [`AsyncSeek`], so a transport stream can be read inside a task without
+//! blocking the runtime on file I/O. The SYNC byte search and packet parsing are shared with
+//! [`TSReader`], so both readers find the same packets in the same source, and a packet whose SYNC
+//! byte is missing is handled the same way. Requires the `async` feature.
+//!
+//! [`TSReader`]: crate::reader::TSReader
+//! [`AsyncRead`]: tokio::io::AsyncRead
+//! [`AsyncSeek`]: tokio::io::AsyncSeek
+use std::error::Error;
+use std::io::{self, ErrorKind, SeekFrom};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+use crate::errors::no_sync_byte_found::NoSyncByteFound;
+use crate::errors::sync_lost::SyncLost;
+use crate::packet::TSPacket;
+use crate::packet::header::SYNC_BYTE;
+use crate::packet::layout::{
+    detect_layout, find_sync, packet_start, parse_packet, probe_buffer_len, PacketLayout,
+    SYNC_SEARCH_CHUNK_SIZE,
+};
+
+#[cfg(feature = "log")]
+use log::{info,debug,trace,warn};
+
+/// Struct used for reading packets from an async transport stream source.
+///
+/// See [`TSReader`](crate::reader::TSReader) for the synchronous equivalent, which also tracks
+/// payloads, program tables, and statistics.
+pub struct AsyncTSReader<R> {
+    /// Filename for the file being read. Only really used for logging.
+    #[cfg_attr(not(feature = "log"), allow(dead_code))]
+    filename: String,
+    /// Reader for the transport stream data.
+    reader: R,
+    /// Bytes that were read from the reader while searching for the first SYNC byte but haven't
+    /// been consumed yet. These are read before any more data is read from `reader`.
+    pending: Vec<u8>,
+    /// Sync byte alignment. A Sync byte should be found every `layout.stride()` away.
+    sync_alignment: u64,
+    /// Layout of the packets in the transport stream file.
+    layout: PacketLayout,
+    /// Byte offset in the file of the next packet to be read.
+    offset: u64,
+    /// Counter of the number of packets read.
+    packets_read: u64,
+    /// Counter of the number of bytes read from the transport stream packets.
+    bytes_read: u64,
+    /// PIDs that should be returned by [`AsyncTSReader::next_packet`].
+    ///
+    /// If empty, all PIDs are returned.
+    tracked_pids: Vec<u16>,
+    /// Whether reading scans forward for the next SYNC byte when a packet doesn't start with one,
+    /// instead of returning a [`SyncLost`] error.
+    resync_on_error: bool,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncTSReader<R> {
+    /// Create a new AsyncTSReader instance using the given async source.
+    ///
+    /// The packet layout is detected the same way as
+    /// [`TSReader::new`](crate::reader::TSReader::new), and sources that are too short to detect
+    /// are read as plain transport stream packets.
+    /// # Parameters
+    /// - `filename`: name of the source. Only used for logging.
+    /// - `reader`: an async reader that contains transport stream data.
+    pub async fn new(filename: &str, mut reader: R) -> Result<Self, Box<dyn Error>> {
+        let start = reader.stream_position().await?;
+        let mut buf = Vec::with_capacity(probe_buffer_len());
+        (&mut reader).take(buf.capacity() as u64).read_to_end(&mut buf).await?;
+        let layout = detect_layout(&buf).unwrap_or_default();
+        reader.seek(SeekFrom::Start(start)).await?;
+
+        #[cfg(feature = "log")]
+        debug!("Detected packet layout {:?} for file {}", layout, filename);
+
+        Self::with_layout(filename, reader, layout).await
+    }
+
+    /// Create a new AsyncTSReader instance using the given async source and packet layout.
+    ///
+    /// See [`TSReader::with_layout`](crate::reader::TSReader::with_layout).
+    /// # Parameters
+    /// - `filename`: name of the source. Only used for logging.
+    /// - `reader`: an async reader that contains transport stream data.
+    /// - `layout`: the layout of each packet in the file.
+    pub async fn with_layout(
+        filename: &str,
+        mut reader: R,
+        layout: PacketLayout,
+    ) -> Result<Self, Box<dyn Error>> {
+        let stride = layout.stride();
+
+        // Bytes that have been read from the reader but not consumed. `discarded` is the number of
+        // bytes that came before the start of this buffer, so `discarded + idx` is the offset of
+        // `buf[idx]` in the stream.
+        let mut buf: Vec<u8> = Vec::new();
+        let mut discarded: u64 = 0;
+
+        // A packet too close to the start for its prefix is skipped by `packet_start`.
+        let search = search_for_sync(&mut reader, &mut buf, &mut discarded, 0, stride, 0).await?;
+        let Some(idx) = search else {
+            #[cfg(feature = "log")]
+            debug!("Could not find SYNC byte in file {}", filename);
+            return Err(Box::new(NoSyncByteFound));
+        };
+
+        let sync_offset = discarded + idx as u64;
+
+        #[cfg(feature = "log")]
+        trace!("SYNC found at position {} for file {}", sync_offset + 1, filename);
+
+        let packet_start = packet_start(sync_offset, layout);
+        buf.drain(..(packet_start - discarded) as usize);
+
+        Ok(AsyncTSReader {
+            filename: filename.to_string(),
+            reader,
+            pending: buf,
+            sync_alignment: sync_offset + 1,
+            layout,
+            offset: packet_start,
+            packets_read: 0,
+            bytes_read: 0,
+            tracked_pids: Vec::new(),
+            resync_on_error: false,
+        })
+    }
+
+    /// Read the next packet from the transport stream file.
+    ///
+    /// This function returns `None` for any `Err` in order to prevent the need for `.unwrap()`
+    /// calls in more concise code.
+    /// # Returns
+    /// `Some(TSPacket)` if the next transport stream packet could be parsed from the file.
+    /// `None` if the next transport stream packet could not be parsed from the file for any
+    /// reason. This includes if the entire file has been fully read.
+    pub async fn next_packet_unchecked(&mut self) -> Option<TSPacket> {
+        self.next_packet().await.unwrap_or(None)
+    }
+
+    /// Read the next packet from the transport stream file.
+    /// # Returns
+    /// `Ok(Some(TSPacket))` if the next transport stream packet could be parsed from the file.
+    /// `Ok(None)` if there was no issue reading the file and no more TS packets can be read.
+    pub async fn next_packet(&mut self) -> Result<Option<TSPacket>, Box<dyn Error>> {
+        loop {
+            let Some(packet) = self.read_packet().await? else {
+                return Ok(None);
+            };
+
+            // We should only return a packet if it is in the tracked PIDs (or there are no tracked
+            // PIDs)
+            if ! self.tracked_pids.is_empty() && ! self.tracked_pids.contains(&packet.header().pid()) {
+                continue
+            }
+
+            return Ok(Some(packet));
+        }
+    }
+
+    /// Read the next packet from the transport stream file regardless of which PIDs are tracked.
+    async fn read_packet(&mut self) -> Result<Option<TSPacket>, Box<dyn Error>> {
+        let mut packet_buf = vec![0; self.layout.stride()];
+
+        loop {
+            // Bytes left over from a SYNC byte search are used up before any more are read from
+            // the reader.
+            let count = self.pending.len().min(packet_buf.len());
+            packet_buf[..count].copy_from_slice(&self.pending[..count]);
+            self.pending.drain(..count);
+
+            if let Err(e) = self.reader.read_exact(&mut packet_buf[count..]).await {
+                if e.kind() == ErrorKind::UnexpectedEof {
+                    #[cfg(feature = "log")]
+                    info!("Finished reading file {}", self.filename);
+                    return Ok(None);
+                }
+
+                return Err(Box::new(e));
+            }
+
+            // The alignment can drift partway through a file, so every packet has to be checked.
+            if packet_buf[self.layout.prefix()] == SYNC_BYTE {
+                break
+            }
+
+            self.pending.splice(..0, packet_buf.iter().copied());
+            if ! self.handle_sync_lost().await? {
+                return Ok(None);
+            }
+        }
+
+        let current_offset = self.offset;
+        self.packets_read += 1;
+        self.bytes_read += packet_buf.len() as u64;
+        self.offset += packet_buf.len() as u64;
+        #[cfg(feature = "log")]
+        trace!("Read packet at offset {} in file {}", current_offset, self.filename);
+
+        parse_packet(&packet_buf, self.layout, current_offset).map(Some)
+    }
+
+    /// Handle the packet at the current offset not starting with a SYNC byte.
+    ///
+    /// See [`TSReader::set_resync_on_error`](crate::reader::TSReader::set_resync_on_error). The
+    /// bytes of the packet have to be at the front of `pending`. They are left there when a
+    /// `SyncLost` error is returned, so the reader stays at the packet.
+    /// # Returns
+    /// `Ok(true)` if the reader resynced and the packet there should be read instead.
+    /// `Ok(false)` if the end of the stream was reached while resyncing.
+    async fn handle_sync_lost(&mut self) -> Result<bool, Box<dyn Error>> {
+        if ! self.resync_on_error {
+            #[cfg(feature = "log")]
+            debug!("Lost SYNC byte at offset {} in file {}", self.offset, self.filename);
+            return Err(Box::new(SyncLost { offset: self.offset }));
+        }
+
+        #[cfg(feature = "log")]
+        warn!("Lost SYNC byte at offset {} in file {}, scanning for the next one", self.offset,
+            self.filename);
+
+        let stride = self.layout.stride();
+        let prefix = self.layout.prefix();
+        let mut buf = std::mem::take(&mut self.pending);
+        let mut start = self.offset;
+
+        let search = search_for_sync(&mut self.reader, &mut buf, &mut start, prefix + 1, stride,
+            prefix).await?;
+        let Some(idx) = search else {
+            self.offset = start + buf.len() as u64;
+            return Ok(false);
+        };
+
+        let packet_start = idx - prefix;
+        self.pending = buf.split_off(packet_start);
+        self.offset = start + packet_start as u64;
+
+        #[cfg(feature = "log")]
+        debug!("Found SYNC byte again at offset {} in file {}", self.offset + prefix as u64,
+            self.filename);
+
+        Ok(true)
+    }
+}
+
+impl<R> AsyncTSReader<R> {
+    /// Return the number of packets that have been read from the file, regardless of which PIDs
+    /// are being tracked.
+    pub fn packets_read(&self) -> u64 {
+        self.packets_read
+    }
+
+    /// Return the number of bytes of transport stream packets that have been read from the file.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Return the alignment of the SYNC bytes in this reader.
+    pub fn sync_byte_alignment(&self) -> u64 {
+        self.sync_alignment
+    }
+
+    /// Return the layout of the packets being read.
+    pub fn packet_layout(&self) -> PacketLayout {
+        self.layout
+    }
+
+    /// Add a PID to the tracking list.
+    ///
+    /// Only tracked PIDs are returned by [`AsyncTSReader::next_packet`]. If no PIDs are tracked,
+    /// all PIDs are returned.
+    pub fn add_tracked_pid(&mut self, pid: u16) {
+        self.tracked_pids.push(pid);
+    }
+
+    /// Remove a PID from the tracking list.
+    pub fn remove_tracked_pid(&mut self, pid: u16) {
+        self.tracked_pids.retain(|tracked_pid| *tracked_pid != pid);
+    }
+
+    /// Set whether reading scans forward for the next SYNC byte when a packet doesn't start with
+    /// one.
+    ///
+    /// See [`TSReader::set_resync_on_error`](crate::reader::TSReader::set_resync_on_error). When
+    /// disabled, reading a packet that doesn't start with a SYNC byte returns a [`SyncLost`] error
+    /// and the reader stays at that packet, so enabling this afterwards continues from there.
+    /// Disabled by default.
+    pub fn set_resync_on_error(&mut self, resync: bool) {
+        self.resync_on_error = resync;
+    }
+}
+
+/// Read from `reader` into `buf` until a SYNC byte that has another SYNC byte 1 stride after it is
+/// found at or after `buf[idx]`.
+///
+/// This is the async equivalent of the search that [`TSReader`](crate::reader::TSReader) uses.
+/// `start` is the byte offset in the stream of `buf[0]`. Only 1 stride is kept before the byte
+/// being checked, so older bytes are dropped from `buf` and `start` is moved forward to match. SYNC
+/// bytes with fewer than `prefix` bytes before them in `buf` are skipped.
+/// # Returns
+/// `Ok(Some(usize))` with the index of the SYNC byte in `buf`.
+/// `Ok(None)` if the end of the stream was reached first.
+async fn search_for_sync<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    start: &mut u64,
+    mut idx: usize,
+    stride: usize,
+    prefix: usize,
+) -> io::Result<Option<usize>> {
+    let mut eof = false;
+    loop {
+        match find_sync(buf, idx, stride) {
+            Ok(sync_idx) if sync_idx >= prefix => return Ok(Some(sync_idx)),
+            Ok(sync_idx) => {
+                idx = sync_idx + 1;
+                continue
+            },
+            Err(resume_idx) => idx = resume_idx,
+        }
+
+        if eof {
+            return Ok(None);
+        }
+
+        if idx >= 2 * stride {
+            buf.drain(..idx - stride);
+            *start += (idx - stride) as u64;
+            idx = stride;
+        }
+
+        let count = reader.take(SYNC_SEARCH_CHUNK_SIZE).read_to_end(buf).await?;
+        eof = count == 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::packet::PACKET_SIZE;
+    use crate::reader::TSReader;
+    use super::*;
+
+    /// Build a source with some junk bytes followed by a packet on each of the given PIDs.
+    fn source(pids: &[u16]) -> Vec<u8> {
+        let mut buf = vec![0xFF; 5];
+        for (index, pid) in pids.iter().enumerate() {
+            let mut packet = vec![0; PACKET_SIZE];
+            packet[..4].copy_from_slice(&[SYNC_BYTE, (pid >> 8) as u8, *pid as u8,
+                0x10 | index as u8]);
+            buf.extend(packet);
+        }
+        buf
+    }
+
+    #[tokio::test]
+    async fn next_packet() {
+        let data = source(&[0x100, 0x101, 0x100]);
+        let mut reader = AsyncTSReader::new("test", Cursor::new(data.clone())).await.unwrap();
+        assert_eq!(reader.sync_byte_alignment(), 6, "SYNC byte alignment is incorrect");
+        assert_eq!(reader.packet_layout(), PacketLayout::TS, "Packet layout is incorrect");
+
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet().await.unwrap() {
+            packets.push((packet.header().pid(), packet.file_offset()));
+        }
+        assert_eq!(packets, vec![(0x100, Some(5)), (0x101, Some(193)), (0x100, Some(381))],
+            "Packets read are incorrect");
+        assert_eq!(reader.packets_read(), 3, "Packets read count is incorrect");
+        assert_eq!(reader.bytes_read(), 3 * PACKET_SIZE as u64, "Bytes read count is incorrect");
+
+        // The synchronous reader should find the same packets in the same source.
+        let mut sync_reader = TSReader::new("test", Cursor::new(data)).unwrap();
+        let sync_packets: Vec<_> = sync_reader.packets_unchecked()
+            .map(|packet| (packet.header().pid(), packet.file_offset()))
+            .collect();
+        assert_eq!(sync_packets, packets, "Packets read by the sync reader are incorrect");
+    }
+
+    #[tokio::test]
+    async fn next_packet_tracked_pids() {
+        let mut reader = AsyncTSReader::new("test", Cursor::new(source(&[0x100, 0x101, 0x100])))
+            .await.unwrap();
+        reader.add_tracked_pid(0x101);
+
+        let mut pids = Vec::new();
+        while let Some(packet) = reader.next_packet_unchecked().await {
+            pids.push(packet.header().pid());
+        }
+        assert_eq!(pids, vec![0x101], "Packets read are incorrect");
+        assert_eq!(reader.packets_read(), 3, "Packets read count is incorrect");
+    }
+
+    #[tokio::test]
+    async fn new_no_sync_byte() {
+        let result = AsyncTSReader::new("test", Cursor::new(vec![0xFF; 1000])).await;
+        assert!(result.is_err(), "Reader without a SYNC byte is incorrect");
+    }
+
+    #[tokio::test]
+    async fn next_packet_sync_lost() {
+        // Junk bytes after the second packet knock the rest of the packets out of alignment.
+        let mut data = source(&[0x100, 0x101]);
+        data.extend([0xFF; 10]);
+        data.extend(source(&[0x102, 0x103]).split_off(5));
+        let lost_offset = 5 + 2 * PACKET_SIZE as u64;
+
+        let mut reader = AsyncTSReader::new("test", Cursor::new(data)).await.unwrap();
+        for pid in [0x100, 0x101] {
+            let packet = reader.next_packet().await.unwrap().unwrap();
+            assert_eq!(packet.header().pid(), pid, "Packet before losing SYNC is incorrect");
+        }
+
+        let error = reader.next_packet().await.expect_err("Losing SYNC was not detected");
+        let sync_lost = error.downcast_ref::<crate::SyncLost>().expect("Error is incorrect");
+        assert_eq!(sync_lost.offset, lost_offset, "Offset of the lost SYNC byte is incorrect");
+        assert!(reader.next_packet().await.is_err(), "Reader moved past the lost SYNC byte");
+
+        reader.set_resync_on_error(true);
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet().await.unwrap() {
+            packets.push((packet.header().pid(), packet.file_offset()));
+        }
+        let resync_offset = lost_offset + 10;
+        assert_eq!(packets,
+            vec![(0x102, Some(resync_offset)), (0x103, Some(resync_offset + PACKET_SIZE as u64))],
+            "Packets after resync are incorrect");
+        assert_eq!(reader.packets_read(), 4, "Packets read count is incorrect");
+    }
+}
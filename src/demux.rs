@@ -0,0 +1,124 @@
+//! Automatic demultiplexing of a transport stream into PES packets and PSI sections by PID.
+//!
+//! Each PID is classified as carrying PES or PSI from the stream type that its PMT lists for it,
+//! and from the contents of its payloads when no PMT lists it.
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::pes::{PesPacket, PES_START_CODE_PREFIX};
+
+/// Stream types that carry private sections instead of PES packets.
+const SECTION_STREAM_TYPES: [u8; 5] = [0x05, 0x0B, 0x0C, 0x0D, 0x86];
+
+/// Table ID that is only used as stuffing after the last section in a payload.
+const STUFFING_TABLE_ID: u8 = 0xFF;
+
+/// The kind of data that the payloads of a PID carry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadKind {
+    /// Packetized elementary stream packets, such as audio or video.
+    Pes,
+    /// Program specific information sections, such as the PAT, a PMT, or SCTE-35 splice info.
+    Psi,
+    /// The payload doesn't look like either.
+    Unknown,
+}
+
+impl PayloadKind {
+    /// Return the kind of data carried by an elementary stream with this stream type.
+    ///
+    /// Stream types for private sections and SCTE-35 carry PSI sections. Every other stream type
+    /// carries PES packets.
+    pub fn from_stream_type(stream_type: u8) -> Self {
+        if SECTION_STREAM_TYPES.contains(&stream_type) {
+            PayloadKind::Psi
+        } else {
+            PayloadKind::Pes
+        }
+    }
+}
+
+/// Guess the kind of data carried by a payload from its bytes.
+///
+/// The payload should be the raw data of a packet with the `PUSI` flag set, including the payload
+/// pointer if there is one. A payload that starts with the PES start code prefix is a PES packet,
+/// and one whose payload pointer leads to a table ID is a PSI section.
+/// # Parameters
+/// - `raw`: raw payload data of a packet that starts a payload.
+pub fn classify_payload(raw: &[u8]) -> PayloadKind {
+    if raw.starts_with(&PES_START_CODE_PREFIX) {
+        return PayloadKind::Pes
+    }
+
+    let Some(&pointer) = raw.first() else {
+        return PayloadKind::Unknown
+    };
+
+    match raw.get(1 + pointer as usize) {
+        Some(&table_id) if table_id != STUFFING_TABLE_ID => PayloadKind::Psi,
+        _ => PayloadKind::Unknown,
+    }
+}
+
+/// Everything that was demultiplexed from a single PID.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DemuxedStream {
+    /// PES packets in the order they were read.
+    Pes(Vec<PesPacket>),
+    /// Complete PSI sections in the order they were read.
+    Psi(Vec<Box<[u8]>>),
+}
+
+/// The PES packets and PSI sections of every PID in a transport stream.
+///
+/// This is returned by [`crate::reader::TSReader::auto_demux`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DemuxResult {
+    /// Demultiplexed data of every PID that could be classified.
+    streams: BTreeMap<u16, DemuxedStream>,
+}
+
+impl DemuxResult {
+    /// Build the result from the demultiplexed data of every PID.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(streams: BTreeMap<u16, DemuxedStream>) -> Self {
+        DemuxResult { streams }
+    }
+
+    /// Return the demultiplexed data of every PID, ordered by PID.
+    pub fn streams(&self) -> &BTreeMap<u16, DemuxedStream> {
+        &self.streams
+    }
+
+    /// Return the demultiplexed data of a PID.
+    ///
+    /// Returns `None` if the PID wasn't found or couldn't be classified.
+    pub fn stream(&self, pid: u16) -> Option<&DemuxedStream> {
+        self.streams.get(&pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(&[0x00, 0x00, 0x01, 0xE0], PayloadKind::Pes; "PES start code")]
+    #[test_case(&[0x00, 0x00, 0xB0, 0x0D], PayloadKind::Psi; "PAT section")]
+    #[test_case(&[0x02, 0xAA, 0xBB, 0x02, 0xB0], PayloadKind::Psi; "Section after pointer")]
+    #[test_case(&[0x00, 0xFF, 0xFF], PayloadKind::Unknown; "Stuffing")]
+    #[test_case(&[0x05, 0x00], PayloadKind::Unknown; "Pointer past end")]
+    #[test_case(&[], PayloadKind::Unknown; "Empty")]
+    fn classify(raw: &[u8], expected: PayloadKind) {
+        assert_eq!(classify_payload(raw), expected, "Payload kind is incorrect");
+    }
+
+    #[test_case(0x1B, PayloadKind::Pes; "H.264")]
+    #[test_case(0x0F, PayloadKind::Pes; "AAC")]
+    #[test_case(0x86, PayloadKind::Psi; "SCTE-35")]
+    #[test_case(0x05, PayloadKind::Psi; "Private sections")]
+    fn from_stream_type(stream_type: u8, expected: PayloadKind) {
+        assert_eq!(PayloadKind::from_stream_type(stream_type), expected, "Payload kind is incorrect");
+    }
+}
@@ -0,0 +1,24 @@
+//! Error that is thrown when an adaptation field, or a field inside of it, is longer than the bytes
+//! that are available for it.
+use core::fmt;
+
+/// Error that is thrown when an adaptation field, or a field inside of it, is longer than the bytes
+/// that are available for it.
+///
+/// This usually means that the stream is corrupt or that the packet boundaries are misaligned.
+#[derive(Debug, Clone)]
+pub struct AdaptationFieldOverrun {
+    /// Number of bytes after the adaptation field length byte that were needed.
+    pub length: usize,
+    /// Number of bytes after the adaptation field length byte that were available.
+    pub available: usize,
+}
+
+impl core::error::Error for AdaptationFieldOverrun {}
+
+impl fmt::Display for AdaptationFieldOverrun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "adaptation field needs [{}] bytes but only [{}] are available", self.length,
+               self.available)
+    }
+}
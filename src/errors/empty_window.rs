@@ -0,0 +1,19 @@
+//! Error that is thrown when a byte window does not contain a complete packet.
+use core::fmt;
+
+/// Error that is thrown when a byte window does not contain a complete packet.
+#[derive(Debug, Clone)]
+pub struct EmptyWindow {
+    /// Byte offset that the window starts at.
+    pub start: u64,
+    /// Byte offset that the window ends at.
+    pub end: u64,
+}
+
+impl core::error::Error for EmptyWindow {}
+
+impl fmt::Display for EmptyWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no complete packet found in window [{}, {})", self.start, self.end)
+    }
+}
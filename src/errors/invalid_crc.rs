@@ -0,0 +1,17 @@
+//! Error that is thrown when the CRC at the end of a PSI section does not match the section data.
+use core::fmt;
+
+/// Error that is thrown when the CRC at the end of a PSI section does not match the section data.
+#[derive(Debug, Clone)]
+pub struct InvalidCrc {
+    /// CRC that was stored at the end of the section.
+    pub crc: u32,
+}
+
+impl core::error::Error for InvalidCrc {}
+
+impl fmt::Display for InvalidCrc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "section CRC [{:08X}] does not match the section data", self.crc)
+    }
+}
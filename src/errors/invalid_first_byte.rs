@@ -1,5 +1,5 @@
 //! Define invalid_first_byte used in the parsing of the transport stream.
-use std::fmt;
+use core::fmt;
 
 /// Error that is thrown when trying to parse a byte array as a transport stream packet, but it
 /// doesn't start with a `SYNC_BYTE`.
@@ -9,7 +9,7 @@ pub struct InvalidFirstByte {
     pub byte: u8,
 }
 
-impl std::error::Error for InvalidFirstByte {}
+impl core::error::Error for InvalidFirstByte {}
 
 impl fmt::Display for InvalidFirstByte {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
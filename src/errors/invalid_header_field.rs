@@ -0,0 +1,21 @@
+//! Error that is thrown when a field of a transport stream packet header has a value that doesn't
+//! fit in the field.
+use core::fmt;
+
+/// Error that is thrown when a field of a transport stream packet header has a value that doesn't
+/// fit in the field.
+#[derive(Debug, Clone)]
+pub struct InvalidHeaderField {
+    /// Name of the field.
+    pub field: &'static str,
+    /// Value that was given for the field.
+    pub value: u16,
+}
+
+impl core::error::Error for InvalidHeaderField {}
+
+impl fmt::Display for InvalidHeaderField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid value [{}] for header field [{}]", self.value, self.field)
+    }
+}
@@ -1,6 +1,6 @@
 //! Error that is thrown when the payload pointer is larger than possible could possibly fit in
 //! the remainder of the packet.
-use std::fmt;
+use core::fmt;
 
 /// Error that is thrown when the payload pointer is larger than possible could possibly fit in
 /// the remainder of the packet.
@@ -10,7 +10,7 @@ pub struct InvalidPayloadPointer {
     pub remainder: u8,
 }
 
-impl std::error::Error for InvalidPayloadPointer {}
+impl core::error::Error for InvalidPayloadPointer {}
 
 impl fmt::Display for InvalidPayloadPointer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
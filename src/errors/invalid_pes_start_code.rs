@@ -0,0 +1,19 @@
+//! Error that is thrown when trying to parse a PES packet that does not start with the PES start
+//! code prefix.
+use core::fmt;
+
+/// Error that is thrown when trying to parse a PES packet that does not start with the PES start
+/// code prefix.
+#[derive(Debug, Clone)]
+pub struct InvalidPesStartCode {
+    /// The first 3 bytes of the data that was being parsed.
+    pub prefix: [u8; 3],
+}
+
+impl core::error::Error for InvalidPesStartCode {}
+
+impl fmt::Display for InvalidPesStartCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid PES start code prefix: {:02X?}", self.prefix)
+    }
+}
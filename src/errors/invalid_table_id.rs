@@ -0,0 +1,19 @@
+//! Error that is thrown when a PSI section has a different table ID than the table being parsed.
+use core::fmt;
+
+/// Error that is thrown when a PSI section has a different table ID than the table being parsed.
+#[derive(Debug, Clone)]
+pub struct InvalidTableId {
+    /// Table ID of the table being parsed.
+    pub expected: u8,
+    /// Table ID that was found in the section.
+    pub found: u8,
+}
+
+impl core::error::Error for InvalidTableId {}
+
+impl fmt::Display for InvalidTableId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected table ID [{}] but found [{}]", self.expected, self.found)
+    }
+}
@@ -0,0 +1,17 @@
+//! Error that is thrown when no PCR could be found for a stream that needs to be timed.
+use core::fmt;
+
+/// Error that is thrown when no PCR could be found for a stream that needs to be timed.
+#[derive(Debug, Clone)]
+pub struct MissingPcr {
+    /// PID of the stream that needed timing information.
+    pub pid: u16,
+}
+
+impl core::error::Error for MissingPcr {}
+
+impl fmt::Display for MissingPcr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no PCR was found for the program containing PID [{}]", self.pid)
+    }
+}
@@ -1,11 +1,11 @@
 //! Error that is thrown when trying read a payload from a packet without a payload.
-use std::fmt;
+use core::fmt;
 
 /// Error that is thrown when trying read a payload from a packet without a payload.
 #[derive(Debug, Clone)]
 pub struct NoPayload;
 
-impl std::error::Error for NoPayload {}
+impl core::error::Error for NoPayload {}
 
 impl fmt::Display for NoPayload {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -0,0 +1,20 @@
+//! Error that is thrown when there are fewer bytes available than a structure says it contains.
+use core::fmt;
+
+/// Error that is thrown when there are fewer bytes available than a structure says it contains.
+#[derive(Debug, Clone)]
+pub struct NotEnoughData {
+    /// Number of bytes that were needed.
+    pub expected: usize,
+    /// Number of bytes that were available.
+    pub found: usize,
+}
+
+impl core::error::Error for NotEnoughData {}
+
+impl fmt::Display for NotEnoughData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected at least [{}] bytes but only [{}] were available", self.expected,
+               self.found)
+    }
+}
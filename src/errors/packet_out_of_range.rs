@@ -0,0 +1,20 @@
+//! Error that is thrown when a packet number is past the end of the file.
+use core::fmt;
+
+/// Error that is thrown when a packet number is past the end of the file.
+#[derive(Debug, Clone)]
+pub struct PacketOutOfRange {
+    /// Packet number that was requested.
+    pub index: u64,
+    /// Number of complete packets in the file.
+    pub packets: u64,
+}
+
+impl core::error::Error for PacketOutOfRange {}
+
+impl fmt::Display for PacketOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "packet {} is past the end of the file, which has {} complete packets",
+            self.index, self.packets)
+    }
+}
@@ -0,0 +1,17 @@
+//! Error that is thrown when trying to reassemble a payload from a packet that is scrambled.
+use core::fmt;
+
+/// Error that is thrown when trying to reassemble a payload from a packet that is scrambled.
+#[derive(Debug, Clone)]
+pub struct ScrambledPayload {
+    /// PID of the scrambled packet.
+    pub pid: u16,
+}
+
+impl core::error::Error for ScrambledPayload {}
+
+impl fmt::Display for ScrambledPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "payload of packet on PID [{}] is scrambled", self.pid)
+    }
+}
\ No newline at end of file
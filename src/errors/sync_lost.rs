@@ -0,0 +1,20 @@
+//! Error that is thrown when a packet in the middle of a file doesn't start with a SYNC byte.
+use core::fmt;
+
+/// Error that is thrown when a packet in the middle of a file doesn't start with a SYNC byte.
+///
+/// This happens when the alignment of the packets drifts partway through a file, such as in a
+/// concatenation of two captures or after a corrupt region.
+#[derive(Debug, Clone)]
+pub struct SyncLost {
+    /// Byte offset in the file of the packet that didn't start with a SYNC byte.
+    pub offset: u64,
+}
+
+impl core::error::Error for SyncLost {}
+
+impl fmt::Display for SyncLost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SYNC byte was lost at offset [{}]", self.offset)
+    }
+}
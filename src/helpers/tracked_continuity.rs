@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use crate::packet::header::CONTINUITY_COUNTER_MODULUS;
+use crate::packet::{TSPacket, NULL_PACKET_PID};
+use crate::stats::{ContinuityAnomaly, ContinuityBreakdown};
+
+#[cfg(feature = "log")]
+use log::debug;
+
+/// Continuity state of a single PID.
+#[derive(Clone, Copy)]
+struct PidContinuity {
+    /// Continuity counter of the most recent packet on the PID.
+    continuity_counter: u8,
+    /// Continuity counter that the next packet with a payload on the PID should have.
+    expected_next_cc: u8,
+    /// Set when the most recent packet repeated the continuity counter of the one before it.
+    ///
+    /// A packet may only be sent twice in a row, so a second repeat is an error.
+    repeated: bool,
+}
+
+/// Checks that the continuity counter of every PID increments as expected.
+///
+/// The continuity counter increments by `1` for every packet with a payload. Packets without a
+/// payload repeat the continuity counter of the packet before them, and a packet with a payload may
+/// be sent twice in a row with the same continuity counter. Null packets are not checked.
+#[derive(Default)]
+pub(crate) struct TrackedContinuity {
+    /// Continuity state of every PID that has been seen.
+    pids: HashMap<u16, PidContinuity>,
+    /// Number of unexpected continuity counters on each PID.
+    errors: HashMap<u16, u64>,
+    /// Number of unexpected continuity counters on each PID that were flagged by the discontinuity
+    /// indicator. These are not counted as errors.
+    flagged_discontinuities: HashMap<u16, u64>,
+    /// Counts of each kind of anomaly on each PID.
+    breakdowns: HashMap<u16, ContinuityBreakdown>,
+}
+
+impl TrackedContinuity {
+    /// Create a new continuity tracker.
+    pub fn new() -> Self {
+        TrackedContinuity::default()
+    }
+
+    /// Check the continuity counter of this packet against the packets before it on the same PID.
+    ///
+    /// Returns the anomaly if the continuity counter wasn't the one expected.
+    pub fn observe(&mut self, packet: &TSPacket) -> Option<ContinuityAnomaly> {
+        let anomaly = self.classify(packet)?;
+        let pid = packet.header().pid();
+        self.breakdowns.entry(pid).or_default().observe(anomaly);
+
+        match anomaly {
+            ContinuityAnomaly::Duplicate => {},
+            ContinuityAnomaly::Drop(_) => *self.errors.entry(pid).or_default() += 1,
+            ContinuityAnomaly::Discontinuity => {
+                *self.flagged_discontinuities.entry(pid).or_default() += 1
+            },
+        }
+
+        Some(anomaly)
+    }
+
+    /// Update the continuity state of the packet's PID and classify how its continuity counter
+    /// differed from the expected one.
+    fn classify(&mut self, packet: &TSPacket) -> Option<ContinuityAnomaly> {
+        let header = packet.header();
+        let pid = header.pid();
+        if pid == NULL_PACKET_PID {
+            return None
+        }
+
+        let continuity_counter = header.continuity_counter();
+        let current = PidContinuity {
+            continuity_counter,
+            expected_next_cc: packet.expected_next_cc(),
+            repeated: false,
+        };
+        let previous = self.pids.insert(pid, current)?;
+
+        let expected = if header.has_payload() {
+            previous.expected_next_cc
+        } else {
+            previous.continuity_counter
+        };
+
+        if continuity_counter == expected {
+            return None
+        }
+
+        // The continuity counter may take any value after a flagged discontinuity, so a repeat
+        // there isn't a duplicate packet.
+        if packet.data_adaptation_field().is_some_and(|af| af.discontinuity_indicator()) {
+            return Some(ContinuityAnomaly::Discontinuity)
+        }
+
+        if header.has_payload() && continuity_counter == previous.continuity_counter
+            && ! previous.repeated {
+            self.pids.insert(pid, PidContinuity { repeated: true, ..current });
+            return Some(ContinuityAnomaly::Duplicate)
+        }
+
+        #[cfg(feature = "log")]
+        debug!("Continuity counter on PID {} was {} but {} was expected", pid, continuity_counter,
+            expected);
+        let lost = (continuity_counter + CONTINUITY_COUNTER_MODULUS - expected)
+            % CONTINUITY_COUNTER_MODULUS;
+        Some(ContinuityAnomaly::Drop(lost))
+    }
+
+    /// Forget the continuity counters of the most recent packets so that the next packet on every
+    /// PID isn't checked against them. The error counts are kept.
+    ///
+    /// This should be run when the reader jumps to a different part of the stream.
+    pub fn forget_counters(&mut self) {
+        self.pids.clear();
+    }
+
+    /// Return the continuity counter of the most recent packet on this PID.
+    ///
+    /// Returns `None` if no packets have been seen on the PID.
+    pub fn last_continuity_counter(&self, pid: u16) -> Option<u8> {
+        self.pids.get(&pid).map(|continuity| continuity.continuity_counter)
+    }
+
+    /// Return the number of unexpected continuity counters on each PID that weren't flagged by the
+    /// discontinuity indicator.
+    pub fn errors(&self) -> &HashMap<u16, u64> {
+        &self.errors
+    }
+
+    /// Return the number of unexpected continuity counters on each PID that were flagged by the
+    /// discontinuity indicator.
+    pub fn flagged_discontinuities(&self) -> &HashMap<u16, u64> {
+        &self.flagged_discontinuities
+    }
+
+    /// Return the counts of each kind of anomaly on each PID.
+    pub fn breakdowns(&self) -> &HashMap<u16, ContinuityBreakdown> {
+        &self.breakdowns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::PACKET_SIZE;
+    use test_case::test_case;
+
+    /// Build a packet with the given continuity counter and discontinuity indicator.
+    ///
+    /// Packets with the discontinuity indicator set have an adaptation field before the payload.
+    fn packet(continuity_counter: u8, has_payload: bool, discontinuity: bool) -> TSPacket {
+        let mut buf = vec![0; PACKET_SIZE];
+        let adaptation_field_control = match (discontinuity || ! has_payload, has_payload) {
+            (true, true) => 0x30,
+            (true, false) => 0x20,
+            _ => 0x10,
+        };
+        let flags = adaptation_field_control | continuity_counter;
+        buf[..4].copy_from_slice(&[0x47, 0x01, 0x00, flags]);
+        if adaptation_field_control != 0x10 {
+            let length = if has_payload { 1 } else { 183 };
+            buf[4..6].copy_from_slice(&[length, if discontinuity { 0x80 } else { 0x00 }]);
+        }
+        TSPacket::from_bytes(&buf).unwrap()
+    }
+
+    #[test_case(&[(0, true, false), (1, true, false), (2, true, false)], 0, 0; "Continuous")]
+    #[test_case(&[(14, true, false), (15, true, false), (0, true, false)], 0, 0; "Wrapped")]
+    #[test_case(&[(0, true, false), (2, true, false)], 1, 0; "Gap")]
+    #[test_case(&[(0, true, false), (2, true, true)], 0, 1; "Flagged gap")]
+    #[test_case(&[(0, true, false), (0, true, false), (1, true, false)], 0, 0; "Duplicate")]
+    #[test_case(&[(0, true, false), (0, true, false), (0, true, false)], 1, 0; "Repeated twice")]
+    #[test_case(&[(0, true, false), (0, false, false), (1, true, false)], 0, 0; "No payload")]
+    #[test_case(&[(0, true, false), (1, false, false)], 1, 0; "No payload incremented")]
+    fn observe(packets: &[(u8, bool, bool)], errors: u64, flagged: u64) {
+        let mut continuity = TrackedContinuity::new();
+        for (continuity_counter, has_payload, discontinuity) in packets {
+            continuity.observe(&packet(*continuity_counter, *has_payload, *discontinuity));
+        }
+
+        assert_eq!(continuity.errors().get(&0x100).copied().unwrap_or_default(), errors,
+            "Number of continuity errors is incorrect");
+        assert_eq!(continuity.flagged_discontinuities().get(&0x100).copied().unwrap_or_default(),
+            flagged, "Number of flagged discontinuities is incorrect");
+    }
+
+    #[test_case(&[(0, true, false), (1, true, false)], None; "Continuous")]
+    #[test_case(&[(0, true, false), (0, true, false)], Some(ContinuityAnomaly::Duplicate);
+        "Duplicate")]
+    #[test_case(&[(0, true, false), (2, true, false)], Some(ContinuityAnomaly::Drop(1)); "Drop")]
+    #[test_case(&[(14, true, false), (3, true, false)], Some(ContinuityAnomaly::Drop(4));
+        "Wrapped drop")]
+    #[test_case(&[(0, true, false), (0, true, false), (0, true, false)],
+        Some(ContinuityAnomaly::Drop(15)); "Repeated twice")]
+    #[test_case(&[(0, true, false), (5, true, true)], Some(ContinuityAnomaly::Discontinuity);
+        "Flagged discontinuity")]
+    #[test_case(&[(0, true, false), (0, true, true)], Some(ContinuityAnomaly::Discontinuity);
+        "Flagged repeat")]
+    fn observe_anomaly(packets: &[(u8, bool, bool)], expected: Option<ContinuityAnomaly>) {
+        let mut continuity = TrackedContinuity::new();
+        let mut anomaly = None;
+        for (continuity_counter, has_payload, discontinuity) in packets {
+            let packet = packet(*continuity_counter, *has_payload, *discontinuity);
+            anomaly = continuity.observe(&packet);
+        }
+
+        assert_eq!(anomaly, expected, "Anomaly is incorrect");
+    }
+
+    #[test]
+    fn breakdowns() {
+        // A duplicate, a drop of 3 packets, a flagged discontinuity, and a drop of 1 packet.
+        let packets = [
+            (0, true, false), (0, true, false), (4, true, false), (9, true, true),
+            (11, true, false),
+        ];
+        let mut continuity = TrackedContinuity::new();
+        for (continuity_counter, has_payload, discontinuity) in packets {
+            continuity.observe(&packet(continuity_counter, has_payload, discontinuity));
+        }
+
+        let breakdown = continuity.breakdowns()[&0x100];
+        assert_eq!(breakdown.duplicates(), 1, "Number of duplicates is incorrect");
+        assert_eq!(breakdown.drops(), 2, "Number of drops is incorrect");
+        assert_eq!(breakdown.dropped_packets(), 4, "Number of dropped packets is incorrect");
+        assert_eq!(breakdown.discontinuities(), 1, "Number of discontinuities is incorrect");
+        assert_eq!(continuity.errors()[&0x100], 2, "Number of continuity errors is incorrect");
+    }
+}
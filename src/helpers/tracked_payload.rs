@@ -1,23 +1,30 @@
-use std::error::Error;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
 use crate::errors::no_payload::NoPayload;
-use crate::packet::payload::{self, TSPayload};
+use crate::errors::scrambled_payload::ScrambledPayload;
+use crate::packet::header::CONTINUITY_COUNTER_MODULUS;
+use crate::packet::payload::TSPayload;
 use crate::packet::TSPacket;
+use crate::packet::payload::CompletedPayload;
 
 #[cfg(feature = "log")]
-use log::trace;
-use memmem::{Searcher, TwoWaySearcher};
+use log::{debug, trace};
 
 pub(crate) struct TrackedPayload {
     /// PID of the packet that these payloads belong to.
     pid: u16,
-    /// Payloads that we have parsed so far.
+    /// Payloads that we have parsed so far along with the file offset of the packet that they
+    /// came from and whether that packet had the discontinuity indicator set.
     /// 
     /// These are stored in the order that they have been read from the file.
-    payloads: Vec<TSPayload>,
+    payloads: Vec<(TSPayload, u64, bool)>,
 }
 
 impl TrackedPayload {
     /// Create a new tracked payload
+    #[cfg(test)]
     pub fn new(pid: u16) -> Self {
         TrackedPayload {
             pid,
@@ -28,15 +35,23 @@ impl TrackedPayload {
     /// Create a tracked payload object from a packet.
     ///
     /// This initializes the object with only the payload data of the packet that was passed in.
-    pub fn from_packet(packet: &TSPacket) -> Result<Self, Box<dyn Error>> {
+    /// Scrambled payloads can't be reassembled, so a scrambled packet returns an error.
+    pub fn from_packet(packet: &TSPacket, offset: u64) -> Result<Self, Box<dyn Error>> {
+        if packet.is_scrambled() {
+            return Err(Box::new(ScrambledPayload { pid: packet.header().pid() }))
+        }
+
         let payload = match packet.payload() {
             Some(payload) => payload,
             None => return Err(Box::new(NoPayload))
         };
 
+        let discontinuity = packet.data_adaptation_field()
+            .is_some_and(|af| af.discontinuity_indicator());
+
         Ok(TrackedPayload {
             pid: packet.header().pid(),
-            payloads: vec!(payload)
+            payloads: vec!((payload, offset, discontinuity))
         })
     }
 
@@ -44,32 +59,52 @@ impl TrackedPayload {
     /// 
     /// If there are no payloads currently stored, and we are trying to add a payload that does not
     /// have the `PUSI` set, we do not add it as we will not be able to extract a full payload
-    /// without the first payload that has the `PUSI` set.
-    pub fn add(&mut self, payload: &TSPayload) {
+    /// without the first payload that has the `PUSI` set. A payload that repeats the continuity
+    /// counter and data of the payload before it is a duplicate packet and is not added either,
+    /// unless its packet has the discontinuity indicator set. The continuity counter may take any
+    /// value after a flagged discontinuity, so a repeat there is new data.
+    /// # Parameters
+    /// - `payload`: payload of the packet to add.
+    /// - `offset`: byte offset in the file of the packet that the payload came from.
+    /// - `discontinuity`: whether the packet had the discontinuity indicator set.
+    pub fn add(&mut self, payload: &TSPayload, offset: u64, discontinuity: bool) {
         if ! payload.is_start() && self.payloads.is_empty() {
             return;
         }
 
-        self.payloads.push(payload.clone());
+        if ! discontinuity && self.payloads.last().is_some_and(|(last, _, _)| last == payload) {
+            return;
+        }
+
+        self.payloads.push((payload.clone(), offset, discontinuity));
     }
 
     /// Adds raw payload bytes from a TSPayload object and returns a completed payload if one exists
-    pub fn add_and_get_complete(&mut self, payload: &TSPayload) -> Option<Box<[u8]>> {
-        self.add(payload);
-
-        return self.get_completed();
+    pub fn add_and_get_complete(
+        &mut self,
+        payload: &TSPayload,
+        offset: u64,
+        discontinuity: bool,
+    ) -> Option<CompletedPayload> {
+        self.add(payload, offset, discontinuity);
+
+        self.get_completed()
     }
 
     /// Check to see if there is a completed payload in the payloads vector and return the completed
-    /// payload data if there is.
-    pub fn get_completed(&mut self) -> Option<Box<[u8]>> {
+    /// payload if there is.
+    ///
+    /// The completed payload carries the file offset of the packet that started it. If the
+    /// continuity counters of the packets that make up the payload don't increment by `1`, a packet
+    /// was lost partway through it, so the payload is discarded instead of being returned corrupt.
+    /// A jump into a packet with the discontinuity indicator set isn't a gap, since the continuity
+    /// counter may take any value there.
+    pub fn get_completed(&mut self) -> Option<CompletedPayload> {
         // Find the first payload with a start index.
-        let Some(start_partial_payload) = self.payloads.iter().position(|payload| payload.is_start()) else {
-            return None;
-        };
-        let Some(end_partial_payload) = self.payloads.iter().rposition(|payload| payload.is_start()) else {
-            return None;
-        };
+        let start_partial_payload = self.payloads.iter()
+            .position(|(payload, _, _)| payload.is_start())?;
+        let end_partial_payload = self.payloads.iter()
+            .rposition(|(payload, _, _)| payload.is_start())?;
 
         // If the indices are the same then we cannot determine if all the payload data has been
         // found.
@@ -77,6 +112,17 @@ impl TrackedPayload {
             return None;
         }
 
+        let continuous = self.payloads[start_partial_payload..=end_partial_payload]
+            .windows(2)
+            .all(|pair| pair[1].2 || pair[1].0.continuity_counter()
+                == (pair[0].0.continuity_counter() + 1) % CONTINUITY_COUNTER_MODULUS);
+        if ! continuous {
+            #[cfg(feature = "log")]
+            debug!("Discarding payload on PID {} with a continuity counter gap", self.pid);
+            self.payloads.drain(start_partial_payload..end_partial_payload);
+            return None;
+        }
+
         // This payload is complete.
         #[cfg(feature = "log")]
         trace!("Payload is complete. Stitching data together.");
@@ -84,14 +130,16 @@ impl TrackedPayload {
         let mut data_vec = vec![];
         
         // Retrieve the data after the start index from the first partial payload.
-        let start_data = self.payloads[start_partial_payload].get_start_data().unwrap();
+        let (start_payload, start_offset, _) = &self.payloads[start_partial_payload];
+        let start_offset = *start_offset;
+        let start_data = start_payload.get_start_data().unwrap();
         #[cfg(feature = "log")]
         trace!("Stitching data to payload [{:02X?}].", start_data);
         data_vec.push(start_data);
 
 
         for idx in start_partial_payload+1..=end_partial_payload {
-            let data = self.payloads[idx].get_current_data();
+            let data = self.payloads[idx].0.get_current_data();
 
             #[cfg(feature = "log")]
             trace!("Stitching data to payload [{:02X?}].", data);
@@ -114,7 +162,7 @@ impl TrackedPayload {
         #[cfg(feature = "log")]
         trace!("Completed payload data: {:2X?}", payload_data);
 
-        return Some(payload_data)
+        Some(CompletedPayload::new(self.pid, start_offset, payload_data))
     }
 
     /// Get the PID of the payload being tracked
@@ -136,7 +184,7 @@ mod tests {
         
         let payload = TSPayload::from_bytes(pusi, 0, Box::new(raw_data));
         let mut tp = TrackedPayload::new(0);
-        tp.add(&payload);
+        tp.add(&payload, 0, false);
 
         assert_eq!(tp.payloads.len(), expected_len, "Tracked payloads is not the expected length");
     }
@@ -148,20 +196,22 @@ mod tests {
         let raw_data = [2, 1, 2, 3, 4];
         let expected_data: Box<[u8]> = Box::new([3, 4, 1, 2]);
         let payload1 = TSPayload::from_bytes(true, 0, Box::new(raw_data));
-        let payload2 = TSPayload::from_bytes(true, 0, Box::new(raw_data));
+        let payload2 = TSPayload::from_bytes(true, 1, Box::new(raw_data));
 
-        tp.add(&payload1);
+        tp.add(&payload1, 0, false);
 
         assert!(tp.get_completed().is_none(), "Payload is completed when it shouldn't be");
 
-        tp.add(&payload2);
+        tp.add(&payload2, 188, false);
 
         let completed_payload = tp.get_completed();
 
         assert!(completed_payload.is_some(), "Payload is not completed");
 
-        let data = completed_payload.unwrap();
+        let completed_payload = completed_payload.unwrap();
+        let data = completed_payload.data();
         assert!(data.iter().eq(expected_data.iter()), "Completed packet data is incorrect: {:?}", data);
+        assert_eq!(completed_payload.offset(), 0, "Completed payload offset is incorrect");
 
         // Verify that only the last packet's payload remains in the tracked payload vector.
         assert_eq!(tp.payloads.len(), 1, "Returned payloads are still being tracked");
@@ -174,24 +224,135 @@ mod tests {
         let raw_data = [2, 1, 2, 3, 4];
         let expected_data: Box<[u8]> = Box::new([3, 4, 2, 1, 2, 3, 4, 1, 2]);
         let payload1 = TSPayload::from_bytes(true, 0, Box::new(raw_data));
-        let payload2 = TSPayload::from_bytes(false, 0, Box::new(raw_data));
-        let payload3 = TSPayload::from_bytes(true, 0, Box::new(raw_data));
+        let payload2 = TSPayload::from_bytes(false, 1, Box::new(raw_data));
+        let payload3 = TSPayload::from_bytes(true, 2, Box::new(raw_data));
 
-        tp.add(&payload1);
-        tp.add(&payload2);
+        tp.add(&payload1, 0, false);
+        tp.add(&payload2, 188, false);
 
         assert!(tp.get_completed().is_none(), "Payload is completed when it shouldn't be");
 
-        tp.add(&payload3);
+        tp.add(&payload3, 376, false);
 
         let completed_payload = tp.get_completed();
 
         assert!(completed_payload.is_some(), "Payload is not completed");
 
-        let data = completed_payload.unwrap();
+        let completed_payload = completed_payload.unwrap();
+        let data = completed_payload.data();
         assert!(data.iter().eq(expected_data.iter()), "Completed packet data is incorrect: {:?}", data);
+        assert_eq!(completed_payload.offset(), 0, "Completed payload offset is incorrect");
 
         // Verify that only the last packet's payload remains in the tracked payload vector.
         assert_eq!(tp.payloads.len(), 1, "Returned payloads are still being tracked");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn get_completed_offset () {
+        let mut tp = TrackedPayload::new(0);
+
+        let raw_data = [2, 1, 2, 3, 4];
+        let payload1 = TSPayload::from_bytes(true, 0, Box::new(raw_data));
+        let payload2 = TSPayload::from_bytes(true, 1, Box::new(raw_data));
+        let payload3 = TSPayload::from_bytes(true, 2, Box::new(raw_data));
+
+        tp.add(&payload1, 0, false);
+        assert!(tp.add_and_get_complete(&payload2, 188, false).is_some(),
+            "Payload is not completed");
+
+        // The second payload started in the packet at offset 188.
+        let completed_payload = tp.add_and_get_complete(&payload3, 376, false);
+        assert_eq!(completed_payload.map(|p| p.offset()), Some(188),
+            "Completed payload offset is incorrect");
+    }
+
+    #[test]
+    fn get_completed_cc_gap () {
+        let mut tp = TrackedPayload::new(0);
+
+        // The packet with continuity counter 2 was lost partway through the first payload.
+        let raw_data = [2, 1, 2, 3, 4];
+        let payload1 = TSPayload::from_bytes(true, 0, Box::new(raw_data));
+        let payload2 = TSPayload::from_bytes(false, 1, Box::new(raw_data));
+        let payload3 = TSPayload::from_bytes(true, 3, Box::new(raw_data));
+        let payload4 = TSPayload::from_bytes(true, 4, Box::new(raw_data));
+
+        tp.add(&payload1, 0, false);
+        tp.add(&payload2, 188, false);
+        assert!(tp.add_and_get_complete(&payload3, 376, false).is_none(),
+            "Payload with a continuity counter gap was completed");
+        assert_eq!(tp.payloads.len(), 1, "Discarded payloads are still being tracked");
+
+        // The payload that started after the gap is still completed.
+        let completed_payload = tp.add_and_get_complete(&payload4, 564, false);
+        assert_eq!(completed_payload.map(|p| p.offset()), Some(376),
+            "Payload after the gap is incorrect");
+    }
+
+    #[test]
+    fn get_completed_cc_jump_at_discontinuity () {
+        let mut tp = TrackedPayload::new(0);
+
+        // The continuity counter jumps from 1 to 7, but the packet is flagged by the discontinuity
+        // indicator, so nothing was lost.
+        let raw_data = [2, 1, 2, 3, 4];
+        let payload1 = TSPayload::from_bytes(true, 0, Box::new(raw_data));
+        let payload2 = TSPayload::from_bytes(false, 1, Box::new(raw_data));
+        let payload3 = TSPayload::from_bytes(false, 7, Box::new(raw_data));
+        let payload4 = TSPayload::from_bytes(true, 8, Box::new(raw_data));
+
+        tp.add(&payload1, 0, false);
+        tp.add(&payload2, 188, false);
+        tp.add(&payload3, 376, true);
+        let completed_payload = tp.add_and_get_complete(&payload4, 564, false);
+        assert_eq!(completed_payload.map(|p| p.data().len()), Some(14),
+            "Payload with a flagged continuity counter jump is incorrect");
+    }
+
+    #[test]
+    fn add_duplicate () {
+        let mut tp = TrackedPayload::new(0);
+
+        let raw_data = [2, 1, 2, 3, 4];
+        let payload1 = TSPayload::from_bytes(true, 0, Box::new(raw_data));
+        let payload2 = TSPayload::from_bytes(false, 1, Box::new(raw_data));
+        let payload3 = TSPayload::from_bytes(true, 2, Box::new(raw_data));
+
+        tp.add(&payload1, 0, false);
+        tp.add(&payload2, 188, false);
+        tp.add(&payload2, 376, false);
+        assert_eq!(tp.payloads.len(), 2, "Duplicate payload was added");
+
+        let completed_payload = tp.add_and_get_complete(&payload3, 564, false);
+        assert_eq!(completed_payload.map(|p| p.data().len()), Some(9),
+            "Payload with a duplicate packet is incorrect");
+    }
+
+    #[test]
+    fn add_repeat_after_discontinuity () {
+        let mut tp = TrackedPayload::new(0);
+
+        // The repeated continuity counter is flagged by the discontinuity indicator, so it is new
+        // data rather than a duplicate packet.
+        let raw_data = [2, 1, 2, 3, 4];
+        let payload1 = TSPayload::from_bytes(true, 0, Box::new(raw_data));
+        let payload2 = TSPayload::from_bytes(false, 1, Box::new(raw_data));
+
+        tp.add(&payload1, 0, false);
+        tp.add(&payload2, 188, false);
+        tp.add(&payload2, 376, true);
+        assert_eq!(tp.payloads.len(), 3, "Payload after a discontinuity was dropped");
+    }
+
+    #[test]
+    fn from_packet_scrambled () {
+        // The payload is scrambled with the even key.
+        let mut buf = vec![0xAA; crate::packet::PACKET_SIZE];
+        buf[..5].copy_from_slice(&[0x47, 0x41, 0x00, 0x90, 0x00]);
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+
+        let result = TrackedPayload::from_packet(&packet, 0);
+        assert!(result.is_err_and(|e| e.is::<ScrambledPayload>()),
+            "Scrambled payload was tracked");
+    }
+}
@@ -0,0 +1,111 @@
+use crate::packet::adaptation_field::pcr_difference;
+use crate::packet::layout::ARRIVAL_TIMESTAMP_WRAP;
+
+#[cfg(feature = "log")]
+use log::debug;
+
+/// Number of parts per million in a ratio of `1`.
+const PARTS_PER_MILLION: f64 = 1_000_000.0;
+
+/// Measures how fast the PCR of a stream advances compared to the arrival timestamps of its
+/// packets.
+///
+/// Only the PCRs of the first PID that a PCR is seen on are measured, since each program has its
+/// own clock.
+#[derive(Default)]
+pub(crate) struct TrackedPcrClock {
+    /// PID whose PCRs are being measured.
+    pid: Option<u16>,
+    /// Arrival timestamp and PCR of the most recent PCR packet.
+    last: Option<(u32, u64)>,
+    /// Total 27MHz ticks that the arrival timestamps advanced by between measured PCR packets.
+    arrival_elapsed: u64,
+    /// Total 27MHz ticks that the PCR advanced by between measured PCR packets.
+    pcr_elapsed: i64,
+}
+
+impl TrackedPcrClock {
+    /// Create a new PCR clock tracker.
+    pub fn new() -> Self {
+        TrackedPcrClock::default()
+    }
+
+    /// Add the time between the last PCR packet and this one to the measurement.
+    ///
+    /// When the PCR is flagged as discontinuous the time leading up to it is left out, since the
+    /// PCR is allowed to jump there.
+    pub fn observe(&mut self, pid: u16, arrival_timestamp: u32, pcr: u64, discontinuity: bool) {
+        if *self.pid.get_or_insert(pid) != pid {
+            return
+        }
+
+        let Some((last_arrival_timestamp, last_pcr)) = self.last.replace((arrival_timestamp, pcr))
+        else {
+            return
+        };
+
+        if discontinuity {
+            #[cfg(feature = "log")]
+            debug!("Skipping PCR discontinuity on PID {} in clock measurement", pid);
+            return
+        }
+
+        let arrival_elapsed = (arrival_timestamp as u64 + ARRIVAL_TIMESTAMP_WRAP
+            - last_arrival_timestamp as u64) % ARRIVAL_TIMESTAMP_WRAP;
+        self.arrival_elapsed += arrival_elapsed;
+        self.pcr_elapsed += pcr_difference(last_pcr, pcr);
+    }
+
+    /// Forget the most recent PCR packet so that the gap before the next one isn't measured.
+    pub fn forget_last(&mut self) {
+        self.last = None;
+    }
+
+    /// Return how far the PCR frequency is from the frequency of the arrival timestamps in parts
+    /// per million.
+    ///
+    /// Returns `None` if the arrival timestamps haven't advanced between two PCR packets yet.
+    pub fn frequency_ppm(&self) -> Option<f64> {
+        if self.arrival_elapsed == 0 {
+            return None
+        }
+
+        Some((self.pcr_elapsed as f64 / self.arrival_elapsed as f64 - 1.0) * PARTS_PER_MILLION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::adaptation_field::PCR_WRAP;
+
+    #[test]
+    fn frequency_ppm() {
+        let mut clock = TrackedPcrClock::new();
+        assert_eq!(clock.frequency_ppm(), None, "Frequency was measured without any PCRs");
+
+        // The PCR runs 10ppm fast, and both the PCR and the arrival timestamps wrap.
+        let pcr_start = PCR_WRAP - 1_350_000;
+        let arrival_start = ARRIVAL_TIMESTAMP_WRAP as u32 - 1_350_000;
+        for i in 0..10u64 {
+            let arrival_timestamp = (arrival_start as u64 + i * 2_700_000) % ARRIVAL_TIMESTAMP_WRAP;
+            let pcr = (pcr_start + i * 2_700_027) % PCR_WRAP;
+            clock.observe(0x100, arrival_timestamp as u32, pcr, false);
+            clock.observe(0x200, arrival_timestamp as u32, 0, false);
+        }
+
+        let ppm = clock.frequency_ppm().unwrap();
+        assert!((ppm - 10.0).abs() < 1e-6, "Frequency offset is incorrect: {}", ppm);
+    }
+
+    #[test]
+    fn frequency_ppm_discontinuity() {
+        let mut clock = TrackedPcrClock::new();
+        clock.observe(0x100, 0, 0, false);
+        clock.observe(0x100, 2_700_000, 2_700_000, false);
+        clock.observe(0x100, 5_400_000, 900_000_000, true);
+        clock.observe(0x100, 8_100_000, 902_700_000, false);
+
+        assert_eq!(clock.frequency_ppm(), Some(0.0), "Discontinuity was measured");
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::packet::TSPacket;
+use crate::psi::pat::ProgramAssociationTable;
+use crate::psi::pmt::ProgramMapTable;
+use crate::psi::section::SectionAssembler;
+use crate::psi::PAT_PID;
+
+#[cfg(feature = "log")]
+use log::debug;
+
+/// Keeps track of the most recent PAT and PMTs seen while reading a transport stream.
+#[derive(Default)]
+pub(crate) struct TrackedPrograms {
+    /// Assembles the sections sent on the PAT PID.
+    pat_assembler: SectionAssembler,
+    /// The most recent PAT that has been parsed.
+    pat: Option<ProgramAssociationTable>,
+    /// Raw bytes of the section that the most recent PAT was parsed from.
+    pat_section: Option<Box<[u8]>>,
+    /// Assembles the sections sent on each PMT PID listed in the PAT.
+    pmt_assemblers: HashMap<u16, SectionAssembler>,
+    /// The most recent PMT that has been parsed for each program number.
+    pmts: HashMap<u16, ProgramMapTable>,
+}
+
+impl TrackedPrograms {
+    /// Create a new program tracker.
+    pub fn new() -> Self {
+        TrackedPrograms::default()
+    }
+
+    /// Update the tracked tables with the contents of this packet if it carries a PAT or PMT.
+    pub fn observe(&mut self, packet: &TSPacket) {
+        let pid = packet.header().pid();
+        let Some(payload) = packet.payload() else {
+            return
+        };
+
+        if pid == PAT_PID {
+            for section in self.pat_assembler.push(&payload) {
+                match ProgramAssociationTable::from_bytes(&section) {
+                    Ok(pat) if pat.current_next_indicator() => {
+                        self.update_pat(pat);
+                        self.pat_section = Some(section);
+                    },
+                    Ok(_) => {},
+                    #[cfg_attr(not(feature = "log"), allow(unused_variables))]
+                    Err(e) => {
+                        #[cfg(feature = "log")]
+                        debug!("Could not parse PAT section: {}", e);
+                    },
+                }
+            }
+            return
+        }
+
+        let Some(assembler) = self.pmt_assemblers.get_mut(&pid) else {
+            return
+        };
+
+        for section in assembler.push(&payload) {
+            match ProgramMapTable::from_bytes(&section) {
+                Ok(pmt) if pmt.current_next_indicator() => {
+                    self.pmts.insert(pmt.program_number(), pmt);
+                },
+                Ok(_) => {},
+                #[cfg_attr(not(feature = "log"), allow(unused_variables))]
+                Err(e) => {
+                    #[cfg(feature = "log")]
+                    debug!("Could not parse PMT section on PID {}: {}", pid, e);
+                },
+            }
+        }
+    }
+
+    /// Start tracking the PMT PIDs listed in a newly parsed PAT.
+    fn update_pat(&mut self, pat: ProgramAssociationTable) {
+        let pmt_pids: Vec<u16> = pat.programs().iter().map(|(_, pid)| *pid).collect();
+        self.pmt_assemblers.retain(|pid, _| pmt_pids.contains(pid));
+        for pid in pmt_pids {
+            self.pmt_assemblers.entry(pid).or_default();
+        }
+
+        self.pmts.retain(|program_number, _| pat.pmt_pid(*program_number).is_some());
+        self.pat = Some(pat);
+    }
+
+    /// Return the most recent PAT.
+    pub fn pat(&self) -> Option<&ProgramAssociationTable> {
+        self.pat.as_ref()
+    }
+
+    /// Return the raw bytes of the section that the most recent PAT was parsed from.
+    pub fn pat_section(&self) -> Option<&[u8]> {
+        self.pat_section.as_deref()
+    }
+
+    /// Return the most recent PMT of every program.
+    pub fn pmts(&self) -> impl Iterator<Item = &ProgramMapTable> {
+        self.pmts.values()
+    }
+
+    /// Return the most recent PMT of a program.
+    pub fn pmt(&self, program_number: u16) -> Option<&ProgramMapTable> {
+        self.pmts.get(&program_number)
+    }
+
+    /// Return if this PID carries the PAT or one of the PMTs listed in it.
+    pub fn is_psi_pid(&self, pid: u16) -> bool {
+        pid == PAT_PID || self.pmt_assemblers.contains_key(&pid)
+    }
+
+    /// Return the PCR PID of the program that contains the elementary stream on this PID.
+    pub fn pcr_pid_for(&self, pid: u16) -> Option<u16> {
+        self.pmts()
+            .find(|pmt| pmt.streams().iter().any(|stream| stream.elementary_pid() == pid))
+            .and_then(|pmt| pmt.pcr_pid())
+    }
+
+    /// Return every PID that is referenced by the tracked PAT and PMTs.
+    ///
+    /// This includes the network PID, the PMT PIDs, the PCR PIDs, and the elementary stream PIDs.
+    /// The PAT PID itself is only included once a PAT has been found.
+    pub fn referenced_pids(&self) -> BTreeSet<u16> {
+        let mut pids = BTreeSet::new();
+        let Some(pat) = &self.pat else {
+            return pids
+        };
+
+        pids.insert(PAT_PID);
+        pids.extend(pat.network_pid());
+        pids.extend(pat.programs().iter().map(|(_, pid)| *pid));
+
+        for pmt in self.pmts() {
+            pids.extend(pmt.pcr_pid());
+            pids.extend(pmt.streams().iter().map(|stream| stream.elementary_pid()));
+        }
+
+        pids
+    }
+
+    /// Return if a PAT has been found and a PMT has been found for every program in it.
+    pub fn is_complete(&self) -> bool {
+        let Some(pat) = &self.pat else {
+            return false
+        };
+
+        pat.programs().iter().all(|(program_number, _)| self.pmts.contains_key(program_number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::PACKET_SIZE;
+
+    /// Build a packet which carries a whole PSI section.
+    fn psi_packet(pid: u16, section: &[u8]) -> TSPacket {
+        let mut buf = vec![0xFF; PACKET_SIZE];
+        buf[..5].copy_from_slice(&[0x47, 0x40 | (pid >> 8) as u8, pid as u8, 0x10, 0x00]);
+        buf[5..5 + section.len()].copy_from_slice(section);
+        TSPacket::from_bytes(&buf).unwrap()
+    }
+
+    #[test]
+    fn referenced_pids() {
+        let pat = [
+            0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1,
+            0x04, 0xB2,
+        ];
+        let pmt = [
+            0x02, 0xB0, 0x17, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x00, 0xF0, 0x00, 0x1B, 0xE1,
+            0x00, 0xF0, 0x00, 0x0F, 0xE1, 0x01, 0xF0, 0x00, 0x2F, 0x44, 0xB9, 0x9B,
+        ];
+
+        let mut programs = TrackedPrograms::new();
+        assert!(programs.referenced_pids().is_empty(), "PIDs are referenced without a PAT");
+
+        programs.observe(&psi_packet(PAT_PID, &pat));
+        programs.observe(&psi_packet(0x1000, &pmt));
+
+        let pids: Vec<u16> = programs.referenced_pids().into_iter().collect();
+        assert_eq!(pids, vec![PAT_PID, 0x100, 0x101, 0x1000], "Referenced PIDs are incorrect");
+    }
+}
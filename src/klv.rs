@@ -0,0 +1,66 @@
+//! KLV metadata that has been correlated with the video of its program.
+//!
+//! Programs that follow MISB ST 1402 carry KLV metadata on a PES stream of their own next to the
+//! video. Synchronous metadata has a PTS of its own, while asynchronous metadata doesn't.
+use alloc::boxed::Box;
+
+/// A unit of KLV metadata read from a single PES packet, along with the video PTS it belongs to.
+///
+/// These are returned by [`crate::reader::TSReader::klv_timeline`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KlvUnit {
+    /// Byte offset in the file of the packet that started the PES packet.
+    offset: u64,
+    /// PTS of the PES packet that carried the metadata in 90kHz ticks.
+    pts: Option<u64>,
+    /// PTS of the video frame that the metadata was matched to in 90kHz ticks.
+    video_pts: Option<u64>,
+    /// Data of the PES packet that carried the metadata.
+    data: Box<[u8]>,
+}
+
+impl KlvUnit {
+    /// Create a new KLV unit.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(
+        offset: u64,
+        pts: Option<u64>,
+        video_pts: Option<u64>,
+        data: Box<[u8]>,
+    ) -> Self {
+        KlvUnit {
+            offset,
+            pts,
+            video_pts,
+            data,
+        }
+    }
+
+    /// Return the byte offset in the file of the packet that started the PES packet.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the PTS of the PES packet that carried the metadata in 90kHz ticks.
+    ///
+    /// Returns `None` for asynchronous metadata, which is sent without a PTS.
+    pub fn pts(&self) -> Option<u64> {
+        self.pts
+    }
+
+    /// Return the PTS of the video frame that the metadata was matched to in 90kHz ticks.
+    ///
+    /// Returns `None` if no video frame was close enough to the metadata, or if no video frame
+    /// came before asynchronous metadata.
+    pub fn video_pts(&self) -> Option<u64> {
+        self.video_pts
+    }
+
+    /// Return the data of the PES packet that carried the metadata.
+    ///
+    /// For synchronous metadata this starts with the 5 byte metadata access unit cell header
+    /// that comes before the KLV data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
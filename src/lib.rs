@@ -1,59 +1,140 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![forbid(unsafe_code)]
 // Use these checks when closer to complete. They're a bit too strict for early development.
 // #![deny(future_incompatible, missing_docs, rust_2018_idioms, unused, warnings)]
 #![deny(future_incompatible, missing_docs, rust_2018_idioms)]
 
 //! This crate is used to read the payload data from a given transport stream.
+//!
+//! # Byte and bit order
+//!
+//! Every multi-byte field in a transport stream (PIDs, PCRs, lengths, etc.) is big-endian and bits
+//! are numbered starting at the most significant bit of the first byte. All fields are parsed with
+//! `BitVec<u8, Msb0>` and read with `load_be` so that the result is the same on any platform.
+//!
+//! # `no_std` support
+//!
+//! Only the `reader`, `async_reader`, and `segment` modules need `std`, for their I/O. Everything
+//! else, including parsing packets, PSI tables, and PES packets, and reading packets and payloads
+//! from a byte slice with `slice_reader`, only needs `alloc`. Disable the default `std` feature to
+//! use these on targets without `std`.
 
-use std::{error::Error, fmt::Display};
+extern crate alloc;
 
 // Include the README in the doc-tests.
+#[cfg(feature = "std")]
 #[doc = include_str!("../README.md")]
-
 pub mod reader;
 
+#[cfg(feature = "async")]
+pub mod async_reader;
+
+pub mod slice_reader;
+
 pub mod packet;
 
+pub mod stats;
+
+pub mod psi;
+
+pub mod pes;
+
+pub mod demux;
+
+pub mod klv;
+
+#[cfg(feature = "std")]
+pub mod segment;
+
+pub mod splice;
+
+pub mod program;
+
 mod errors {
+    pub mod adaptation_field_overrun;
     pub mod invalid_first_byte;
+    pub mod invalid_header_field;
     pub mod no_sync_byte_found;
     pub mod no_payload;
+    pub mod scrambled_payload;
     pub mod payload_is_not_start;
     pub mod invalid_payload_pointer;
+    pub mod invalid_table_id;
+    pub mod invalid_crc;
+    pub mod not_enough_data;
+    pub mod invalid_pes_start_code;
+    #[cfg(feature = "std")]
+    pub mod missing_pcr;
+    #[cfg(feature = "std")]
+    pub mod empty_window;
+    #[cfg(feature = "std")]
+    pub mod packet_out_of_range;
+    #[cfg(feature = "std")]
+    pub mod sync_lost;
 }
 
 mod helpers {
+    #[cfg(feature = "std")]
+    pub mod tracked_continuity;
     pub mod tracked_payload;
+    #[cfg(feature = "std")]
+    pub mod tracked_pcr_clock;
+    #[cfg(feature = "std")]
+    pub mod tracked_programs;
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum TransportScramblingControl {
+// Errors that callers may want to downcast to and act on.
+pub use errors::adaptation_field_overrun::AdaptationFieldOverrun;
+#[cfg(feature = "std")]
+pub use errors::empty_window::EmptyWindow;
+pub use errors::invalid_header_field::InvalidHeaderField;
+pub use errors::no_sync_byte_found::NoSyncByteFound;
+#[cfg(feature = "std")]
+pub use errors::missing_pcr::MissingPcr;
+#[cfg(feature = "std")]
+pub use errors::packet_out_of_range::PacketOutOfRange;
+pub use errors::scrambled_payload::ScrambledPayload;
+#[cfg(feature = "std")]
+pub use errors::sync_lost::SyncLost;
+
+/// Transport scrambling control values found in the header of a transport stream packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TransportScramblingControl {
+    /// The payload is not scrambled.
     NoScrambling = 0,
+    /// Reserved for future use.
     Reserved = 1,
+    /// The payload is scrambled with the even key.
     EvenKey = 2,
+    /// The payload is scrambled with the odd key.
     OddKey = 3,
 }
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum AdaptationFieldControl {
+
+/// Adaptation field control values found in the header of a transport stream packet.
+///
+/// # Example
+///
+/// ```
+/// use ts_analyzer::AdaptationFieldControl;
+/// use ts_analyzer::packet::header::TSHeader;
+///
+/// let header = TSHeader::from_bytes(&[0x47, 0x41, 0x00, 0x30]).unwrap();
+/// let description = match header.adaptation_field_control() {
+///     AdaptationFieldControl::Payload => "payload only",
+///     AdaptationFieldControl::AdaptationField => "adaptation field only",
+///     AdaptationFieldControl::AdaptationAndPayload => "adaptation field and payload",
+///     AdaptationFieldControl::Reserved => "reserved",
+/// };
+/// assert_eq!(description, "adaptation field and payload");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AdaptationFieldControl {
+    /// Reserved for future use.
     Reserved = 0,
+    /// The packet only contains a payload.
     Payload = 1,
+    /// The packet only contains an adaptation field.
     AdaptationField = 2,
+    /// The packet contains an adaptation field followed by a payload.
     AdaptationAndPayload = 3,
 }
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum Errors {
-    InvalidFirstByte(u8),
-
-}
-
-impl Error for Errors {}
-
-impl Display for Errors {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Errors::InvalidFirstByte(invalid_byte) => 
-                write!(f, "invalid first byte for packet: [{}]", invalid_byte),
-        }
-    }
-}
\ No newline at end of file
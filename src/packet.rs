@@ -2,15 +2,21 @@
 pub mod payload;
 pub mod header;
 pub mod adaptation_field;
+pub mod adaptation_extension;
+pub mod layout;
 
+use alloc::boxed::Box;
 use crate::errors::invalid_payload_pointer::InvalidPayloadPointer;
-use crate::packet::adaptation_field::DataAdaptationField;
-use crate::packet::header::TSHeader;
+use crate::errors::not_enough_data::NotEnoughData;
+use crate::packet::adaptation_field::{DataAdaptationField, STUFFING_BYTE};
+use crate::packet::header::{TSHeader, TSHeaderBuilder, CONTINUITY_COUNTER_MODULUS};
 use adaptation_field::{AdaptationField, StuffingAdaptationField};
 use bitvec::prelude::*;
-use std::error::Error;
+use core::error::Error;
+use core::fmt::{Display, Formatter};
 
 use crate::packet::payload::TSPayload;
+use crate::{AdaptationFieldControl, TransportScramblingControl};
 #[cfg(feature = "log")]
 use log::trace;
 
@@ -19,9 +25,30 @@ pub(crate) const PACKET_SIZE: usize = 188;
 /// The length of a transport stream packet is 4 bytes in size.
 pub const HEADER_SIZE: u8 = 4;
 
+/// Null packets are sent on this PID to pad the stream. They never carry useful payload data.
+pub const NULL_PACKET_PID: u16 = 0x1FFF;
+
+/// The most bits that can be read at once by [`header::TSHeader::raw_bits`] and
+/// [`adaptation_field::DataAdaptationField::raw_bits`].
+pub const MAX_RAW_BITS: usize = 32;
+
+/// Read an arbitrary range of bits from raw bytes as a big-endian value.
+///
+/// Bits are numbered starting at the most significant bit of the first byte (MSB0). Returns `None`
+/// if the range is empty, longer than [`MAX_RAW_BITS`], or extends past the end of the bytes.
+pub(crate) fn raw_bits(buf: &[u8], start: usize, len: usize) -> Option<u32> {
+    let end = start.checked_add(len)?;
+    if len == 0 || len > MAX_RAW_BITS || end > buf.len() * 8 {
+        return None
+    }
+
+    Some(buf.view_bits::<Msb0>()[start..end].load_be())
+}
+
 /// All of this information is shamelessly stolen from wikipedia, my lord and savior.
 /// This [article](https://en.wikipedia.org/wiki/MPEG_transport_stream) in particular. Please donate
 /// to wikipedia if you have the means.
+#[derive(Clone, Debug, PartialEq)]
 pub struct TSPacket {
     /// Header object which tracks header attributes of the packet
     header: TSHeader,
@@ -31,21 +58,45 @@ pub struct TSPacket {
     /// Payload field data. This field will be `None` when the adaptation field control field has
     /// a `0` in the LSB place.
     payload: Option<TSPayload>,
+    /// The raw bytes that this packet was parsed from.
+    raw: Box<[u8; PACKET_SIZE]>,
+    /// Arrival timestamp from the 4-byte prefix of M2TS packets.
+    ///
+    /// Is `None` if the packet wasn't read from an M2TS file.
+    arrival_timestamp: Option<u32>,
+    /// Forward error correction bytes that came after the packet, such as Reed-Solomon parity.
+    ///
+    /// Is `None` if the packet wasn't read from a file with bytes after each packet.
+    fec_bytes: Option<Box<[u8]>>,
+    /// Byte offset in the file that the packet started at.
+    ///
+    /// Is `None` if the packet wasn't read by a reader.
+    file_offset: Option<u64>,
 }
 
 impl TSPacket {
+    /// Return a builder for a packet with the given header.
+    ///
+    /// See [`TSPacketBuilder`].
+    pub fn builder(header: TSHeaderBuilder) -> TSPacketBuilder {
+        TSPacketBuilder::new(header)
+    }
+
     /// Create a TSPacket from a byte array.
-    pub fn from_bytes(buf: &mut [u8]) -> Result<TSPacket, Box<dyn Error>> {
+    pub fn from_bytes(buf: &[u8]) -> Result<TSPacket, Box<dyn Error>> {
         let buffer_length = buf.len();
+        if buffer_length < HEADER_SIZE as usize {
+            return Err(Box::new(NotEnoughData {
+                expected: HEADER_SIZE as usize,
+                found: buffer_length,
+            }))
+        }
         let header_bytes = Box::from(buf[0..HEADER_SIZE as usize].to_vec());
 
         #[cfg(feature = "log")]
         trace!("Parsing TSPacket from raw bytes: {:02X?}", buf);
 
-        let header = match TSHeader::from_bytes(&header_bytes) {
-            Ok(header) => header,
-            Err(e) => return Err(e),
-        };
+        let header = TSHeader::from_bytes(&header_bytes)?;
 
         // This number comes from the fact that the TS header is always 4 bytes wide and the
         // adaptation field always comes directly after the header if it is present.
@@ -59,10 +110,12 @@ impl TSPacket {
 
             // Get the length of the adaptation field. If it's `0` then this is a stuffing
             // adaptation field.
-            let length = buf[read_idx];
+            let Some(&length) = buf.get(read_idx) else {
+                return Err(Box::new(NotEnoughData { expected: read_idx + 1, found: buffer_length }))
+            };
 
             if length != 0 {
-                let af = DataAdaptationField::from_bytes(&mut buf[read_idx..buffer_length]);
+                let af = DataAdaptationField::from_bytes(&buf[read_idx..buffer_length])?;
 
                 // Add 1 because the adaptation field length is 1 byte long
                 read_idx += af.adaptation_field_length() as usize + 1;
@@ -87,9 +140,24 @@ impl TSPacket {
                 BitVec::<u8, Msb0>::from_slice(&buf[read_idx..buf.len()]).as_raw_slice()
             );
 
-            let remainder = (PACKET_SIZE - read_idx) as u8;
-            if header.pusi() && payload_bytes[0] > remainder {
-                return Err(Box::new(InvalidPayloadPointer { pointer: payload_bytes[0], remainder }))
+            // The payload pointer is the first byte of the payload, so there has to be at least 1
+            // byte after the adaptation field when the `PUSI` flag is set.
+            if header.pusi() {
+                let Some(&pointer) = payload_bytes.first() else {
+                    return Err(Box::new(NotEnoughData {
+                        expected: read_idx + 1,
+                        found: buffer_length,
+                    }))
+                };
+
+                // Only the bytes after the pointer itself can be pointed into. The remainder is
+                // taken from the buffer, which may be longer than a packet.
+                let remainder = payload_bytes.len();
+                if pointer as usize >= remainder {
+                    // The pointer is at least the remainder, so the remainder fits in a byte.
+                    let remainder = remainder as u8;
+                    return Err(Box::new(InvalidPayloadPointer { pointer, remainder }))
+                }
             }
 
             Some(TSPayload::from_bytes(header.pusi(), header.continuity_counter(), payload_bytes))
@@ -97,11 +165,21 @@ impl TSPacket {
             None
         };
 
+        // Keep a copy of the original bytes so that the packet can be passed through verbatim.
+        // Bytes missing from a short buffer are filled with stuffing.
+        let mut raw = Box::new([STUFFING_BYTE; PACKET_SIZE]);
+        let raw_length = buffer_length.min(PACKET_SIZE);
+        raw[..raw_length].copy_from_slice(&buf[..raw_length]);
+
         // Payload data should now start at the read_idx.
         let packet = TSPacket {
             header,
             adaptation_field,
             payload,
+            raw,
+            arrival_timestamp: None,
+            fec_bytes: None,
+            file_offset: None,
         };
 
         Ok(packet)
@@ -109,7 +187,7 @@ impl TSPacket {
 
     /// Returns the header object of this packet
     pub fn header(&self) -> TSHeader {
-        self.header.clone()
+        self.header
     }
 
     /// Returns if the packet has adaptation field data.
@@ -122,6 +200,25 @@ impl TSPacket {
         self.header.has_payload()
     }
 
+    /// Returns if the payload of the packet is scrambled with the even or odd key.
+    ///
+    /// The payload of a scrambled packet can't be read without descrambling it first, so parsing
+    /// it as PES or KLV data gives garbage.
+    pub fn is_scrambled(&self) -> bool {
+        matches!(self.header.tsc(),
+            TransportScramblingControl::EvenKey | TransportScramblingControl::OddKey)
+    }
+
+    /// Return the continuity counter that the next packet with a payload on the same PID should
+    /// have.
+    ///
+    /// The continuity counter counts packets with a payload from `0` to `15` and then wraps back to
+    /// `0`. Packets without a payload keep the continuity counter of the packet before them
+    /// instead, as does a packet with a payload that is sent twice in a row.
+    pub fn expected_next_cc(&self) -> u8 {
+        (self.header.continuity_counter() + 1) % CONTINUITY_COUNTER_MODULUS
+    }
+
     /// Return the adaptation field data.
     pub fn adaptation_field(&self) -> Option<AdaptationField> {
         self.adaptation_field.clone()
@@ -131,18 +228,247 @@ impl TSPacket {
     pub fn payload(&self) -> Option<TSPayload> {
         self.payload.clone()
     }
+
+    /// Return the arrival timestamp of the packet in 27MHz ticks.
+    ///
+    /// This comes from the 4-byte prefix of M2TS packets, such as those from Blu-ray discs and
+    /// camcorders, and wraps back to `0` after
+    /// [`ARRIVAL_TIMESTAMP_WRAP`](layout::ARRIVAL_TIMESTAMP_WRAP). Returns `None` if the packet
+    /// wasn't read from an M2TS file.
+    pub fn arrival_timestamp(&self) -> Option<u32> {
+        self.arrival_timestamp
+    }
+
+    /// Set the arrival timestamp that was read from the prefix of the packet.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn set_arrival_timestamp(&mut self, arrival_timestamp: Option<u32>) {
+        self.arrival_timestamp = arrival_timestamp;
+    }
+
+    /// Return the forward error correction bytes that came after the packet.
+    ///
+    /// These are the 16 bytes of Reed-Solomon parity of 204-byte packets from broadcast capture
+    /// equipment. Returns `None` if the packet wasn't read from a file with bytes after each
+    /// packet.
+    pub fn fec_bytes(&self) -> Option<&[u8]> {
+        self.fec_bytes.as_deref()
+    }
+
+    /// Set the forward error correction bytes that were read after the packet.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn set_fec_bytes(&mut self, fec_bytes: Option<Box<[u8]>>) {
+        self.fec_bytes = fec_bytes;
+    }
+
+    /// Return the byte offset in the file that the packet started at.
+    ///
+    /// This counts from the start of the file, including any bytes before the first SYNC byte,
+    /// and points at the start of the packet's prefix for layouts that have one. It is useful for
+    /// finding a corrupt packet in a hex editor. Returns `None` if the packet wasn't read by a
+    /// [`TSReader`](crate::reader::TSReader).
+    pub fn file_offset(&self) -> Option<u64> {
+        self.file_offset
+    }
+
+    /// Set the byte offset in the file that the packet started at.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn set_file_offset(&mut self, file_offset: u64) {
+        self.file_offset = Some(file_offset);
+    }
+
+    /// Serialize the packet back into the bytes of a transport stream packet.
+    ///
+    /// The header is followed by the adaptation field, whether it carries data or is a single
+    /// stuffing byte, and then the payload with its payload pointer when the `PUSI` flag is set.
+    /// Any bytes left at the end are filled with `0xFF` stuffing. This is the inverse of
+    /// [`TSPacket::from_bytes`] for the packets it can parse, except that reserved bytes at the
+    /// end of an adaptation extension are written as stuffing.
+    /// # Returns
+    /// A `NotEnoughData` error if the adaptation field and payload don't fit in a packet.
+    pub fn to_bytes(&self) -> Result<[u8; PACKET_SIZE], Box<dyn Error>> {
+        let mut buf = [STUFFING_BYTE; PACKET_SIZE];
+        buf[..HEADER_SIZE as usize].copy_from_slice(self.header.raw_bytes());
+        let mut write_idx = HEADER_SIZE as usize;
+
+        let payload = self.payload.as_ref().map(TSPayload::raw_data).unwrap_or_default();
+        match &self.adaptation_field {
+            Some(AdaptationField::Data(af)) => {
+                let af_bytes = af.to_bytes(payload.len())?;
+                buf[write_idx..write_idx + af_bytes.len()].copy_from_slice(&af_bytes);
+                write_idx += af_bytes.len();
+            },
+            Some(AdaptationField::Stuffing(_)) => {
+                // A stuffing adaptation field is just its length field set to `0`.
+                buf[write_idx] = 0;
+                write_idx += 1;
+            },
+            None => {},
+        }
+
+        let available = PACKET_SIZE - write_idx;
+        if payload.len() > available {
+            return Err(Box::new(NotEnoughData { expected: payload.len(), found: available }))
+        }
+        buf[write_idx..write_idx + payload.len()].copy_from_slice(&payload);
+
+        Ok(buf)
+    }
+
+    /// Set the transport error indicator of this packet.
+    ///
+    /// This updates the raw bytes of the packet along with its header, so that a packet whose
+    /// errors were corrected by another tool can be written back out with the flag cleared.
+    pub fn set_tei(&mut self, tei: bool) {
+        self.header.set_tei(tei);
+        self.raw[..HEADER_SIZE as usize].copy_from_slice(self.header.raw_bytes());
+    }
+
+    /// Return the raw bytes that this packet was parsed from.
+    ///
+    /// Unlike [`TSPacket::to_bytes`], these are the exact bytes that were read, so forwarding them
+    /// keeps every field intact, including the ones that this crate doesn't parse yet. The only
+    /// change made to them is by [`TSPacket::set_tei`]. Every packet keeps its own copy of these
+    /// bytes, which costs 188 bytes of memory per packet.
+    pub fn raw_bytes(&self) -> &[u8; PACKET_SIZE] {
+        &self.raw
+    }
+
+    /// Return the data adaptation field of this packet if it has one.
+    ///
+    /// Returns `None` if the packet has no adaptation field or only has a stuffing adaptation field.
+    pub fn data_adaptation_field(&self) -> Option<&DataAdaptationField> {
+        match &self.adaptation_field {
+            Some(AdaptationField::Data(af)) => Some(af),
+            _ => None,
+        }
+    }
+
+    /// Return the program clock reference in 27MHz ticks if this packet carries one.
+    ///
+    /// The PCR is read from the adaptation field whether or not the packet also carries a payload,
+    /// since a PCR is often sent in the adaptation field of video packets rather than on a PID of
+    /// its own.
+    pub fn pcr(&self) -> Option<u64> {
+        self.data_adaptation_field()?.pcr()
+    }
+
+    /// Return if the adaptation field indicates that the stream may be decoded without errors
+    /// starting at this packet.
+    pub fn is_random_access_point(&self) -> bool {
+        self.data_adaptation_field().is_some_and(|af| af.random_access_indicator())
+    }
+}
+
+/// Builder for a [`TSPacket`], created with [`TSPacket::builder`].
+///
+/// The adaptation field control of the header is set from whether an adaptation field and a payload
+/// are attached, so it doesn't need to be set on the header builder. A packet with neither is
+/// filled with a payload of stuffing bytes, like a null packet.
+#[derive(Clone, Debug)]
+pub struct TSPacketBuilder {
+    /// Builder for the header of the packet.
+    header: TSHeaderBuilder,
+    /// Adaptation field of the packet.
+    adaptation_field: Option<DataAdaptationField>,
+    /// Payload bytes of the packet, including the payload pointer when the `PUSI` flag is set.
+    payload: Option<Box<[u8]>>,
+}
+
+impl TSPacketBuilder {
+    /// Create a builder for a packet with the given header and nothing after it.
+    pub fn new(header: TSHeaderBuilder) -> Self {
+        TSPacketBuilder {
+            header,
+            adaptation_field: None,
+            payload: None,
+        }
+    }
+
+    /// Attach an adaptation field to the packet.
+    ///
+    /// The adaptation field length is recomputed when the packet is built so that the adaptation
+    /// field fills the packet around the payload.
+    pub fn adaptation_field(mut self, adaptation_field: DataAdaptationField) -> Self {
+        self.adaptation_field = Some(adaptation_field);
+        self
+    }
+
+    /// Attach a payload to the packet.
+    ///
+    /// These are the payload bytes exactly as they appear in the packet, so they start with the
+    /// payload pointer when the `PUSI` flag is set. See [`TSPayload::raw_data`].
+    pub fn payload(mut self, payload: &[u8]) -> Self {
+        self.payload = Some(Box::from(payload));
+        self
+    }
+
+    /// Create the packet.
+    ///
+    /// The packet is serialized with [`TSPacket::to_bytes`] and parsed again, so the returned
+    /// packet is exactly what would be read from a file.
+    /// # Returns
+    /// An error if a header value doesn't fit in its field, or if the adaptation field and payload
+    /// don't fit in a packet.
+    pub fn build(self) -> Result<TSPacket, Box<dyn Error>> {
+        let adaptation_field_control = match (&self.adaptation_field, &self.payload) {
+            (Some(_), Some(_)) => AdaptationFieldControl::AdaptationAndPayload,
+            (Some(_), None) => AdaptationFieldControl::AdaptationField,
+            (None, _) => AdaptationFieldControl::Payload,
+        };
+        let header = self.header.adaptation_field_control(adaptation_field_control).build()?;
+
+        // The payload pointer has to be there when the `PUSI` flag is set.
+        if header.pusi() && self.payload.as_ref().is_some_and(|payload| payload.is_empty()) {
+            return Err(Box::new(NotEnoughData { expected: 1, found: 0 }))
+        }
+
+        let packet = TSPacket {
+            header,
+            adaptation_field: self.adaptation_field.map(AdaptationField::Data),
+            payload: self.payload.map(|payload| {
+                TSPayload::from_bytes(header.pusi(), header.continuity_counter(), payload)
+            }),
+            raw: Box::new([STUFFING_BYTE; PACKET_SIZE]),
+            arrival_timestamp: None,
+            fec_bytes: None,
+            file_offset: None,
+        };
+
+        TSPacket::from_bytes(&packet.to_bytes()?)
+    }
+}
+
+impl Display for TSPacket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.header)?;
+
+        match &self.adaptation_field {
+            Some(AdaptationField::Data(af)) => write!(f, "\nAdaptation Field: data{}", af)?,
+            Some(AdaptationField::Stuffing(_)) => write!(f, "\nAdaptation Field: stuffing")?,
+            None => write!(f, "\nAdaptation Field: none")?,
+        }
+
+        match &self.payload {
+            Some(payload) => write!(f, "\nPayload Length: {}", payload.data().len()),
+            None => write!(f, "\nPayload: none"),
+        }
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use crate::AdaptationFieldControl;
 
     use super::*;
     use test_case::test_case;
 
+    /// Raw packet bytes, the expected adaptation field control, and the expected first payload
+    /// bytes.
+    pub(crate) type PacketFixture = (Box<[u8]>, AdaptationFieldControl, Box<[u8]>);
+
     // The original error I got from this packet was: `range end index 224 out of range for slice of
     // length 24`. Want to keep it as a historical test case.
-    fn packet_1() -> (Box<[u8]>, AdaptationFieldControl, Box<[u8]>) {
+    pub(crate) fn packet_1() -> PacketFixture {
         let packet = [
             0x47, 0x41, 0x02, 0x10, // Header
             0x00, // Payload start indicator
@@ -161,10 +487,10 @@ mod tests {
             0x99, 0x1A, 0x02, 0x01, 0x7B, 0x1B, 0x02, 0x00, 0x75, 0x1C, 0x02, 0xFF, 0xF1, 0x1D,
             0x02, 0x02 // Payload
         ];
-        return (Box::new(packet), crate::AdaptationFieldControl::Payload, Box::new([0x00, 0x01, 0xFC, 0x01]));
+        (Box::new(packet), crate::AdaptationFieldControl::Payload, Box::new([0x00, 0x01, 0xFC, 0x01]))
     }
 
-    fn packet_2() -> (Box<[u8]>, AdaptationFieldControl, Box<[u8]>) {
+    fn packet_2() -> PacketFixture {
         let packet = [
             0x47, 0x01, 0x02, 0x31, // Header
             0x59, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
@@ -183,18 +509,241 @@ mod tests {
             0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, 0xF9, 0x05 // Payload
         ];
 
-        return (Box::new(packet), crate::AdaptationFieldControl::AdaptationAndPayload, Box::new([0x03, 0x1E, 0x02, 0xFE]));
+        (Box::new(packet), crate::AdaptationFieldControl::AdaptationAndPayload, Box::new([0x03, 0x1E, 0x02, 0xFE]))
     }
 
     #[test_case(packet_2)]
     #[test_case(packet_1)]
-    fn from_bytes(packet: fn() -> (Box<[u8]>, AdaptationFieldControl, Box<[u8]>)) {
-        let (mut buf, adaptation_field_control, first_packet_bytes) = packet();
-        let packet = TSPacket::from_bytes(&mut buf).unwrap();
+    fn from_bytes(packet: fn() -> PacketFixture) {
+        let (buf, adaptation_field_control, first_packet_bytes) = packet();
+        let packet = TSPacket::from_bytes(&buf).unwrap();
         
         assert_eq!(packet.header().adaptation_field_control(), adaptation_field_control, "Transport Error Indicator is incorrect");
 
         let real_first_bytes: Box<[u8]> = packet.payload().unwrap().data()[0..first_packet_bytes.len()].into();
         assert!(real_first_bytes.iter().eq(first_packet_bytes.iter()), "First payload bytes are incorrect: {:02X?}", real_first_bytes);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn pcr_with_payload() {
+        let mut adaptation_only = vec![0xFF; PACKET_SIZE];
+        adaptation_only[..12].copy_from_slice(&[0x47, 0x01, 0x00, 0x20, 0xB7, 0x10, 0x00, 0x00,
+            0x2A, 0x30, 0x7E, 0x96]);
+        let expected = TSPacket::from_bytes(&adaptation_only).unwrap().pcr();
+        assert!(expected.is_some(), "PCR of adaptation only packet is missing");
+
+        // The same PCR in a packet that also carries PES data.
+        let mut with_payload = vec![0xAA; PACKET_SIZE];
+        with_payload[..16].copy_from_slice(&[0x47, 0x01, 0x00, 0x30, 0x07, 0x10, 0x00, 0x00,
+            0x2A, 0x30, 0x7E, 0x96, 0x00, 0x00, 0x01, 0xE0]);
+        let packet = TSPacket::from_bytes(&with_payload).unwrap();
+
+        assert_eq!(packet.header().adaptation_field_control(),
+            AdaptationFieldControl::AdaptationAndPayload, "Adaptation field control is incorrect");
+        assert_eq!(packet.pcr(), expected, "PCR is incorrect");
+        let payload = packet.payload().unwrap();
+        assert_eq!(payload.data().len(), PACKET_SIZE - 12, "Payload length is incorrect");
+        assert_eq!(&payload.data()[..4], &[0x00, 0x00, 0x01, 0xE0], "Payload is incorrect");
+    }
+
+    #[test]
+    fn payload_after_adaptation_extension() {
+        // An adaptation field with a seamless splice extension followed by PES data.
+        let mut buf = vec![0xAA; PACKET_SIZE];
+        buf[..16].copy_from_slice(&[0x47, 0x01, 0x00, 0x30, 0x08, 0x01, 0x06, 0x20, 0xA9, 0x8D,
+            0x15, 0xCF, 0x13, 0x00, 0x00, 0x01]);
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+
+        let extension = packet.data_adaptation_field()
+            .and_then(|af| af.adaptation_extension())
+            .expect("Adaptation extension is missing");
+        assert_eq!(extension.seamless_splice().map(|splice| splice.dts_next_au()),
+            Some(0x123456789), "DTS of next AU is incorrect");
+        let payload = packet.payload().unwrap();
+        assert_eq!(payload.data().len(), PACKET_SIZE - 13, "Payload length is incorrect");
+        assert_eq!(&payload.data()[..3], &[0x00, 0x00, 0x01], "Payload is incorrect");
+    }
+
+    #[test_case(0x10, 1; "First")]
+    #[test_case(0x1E, 15; "Last")]
+    #[test_case(0x1F, 0; "Wrapped")]
+    fn expected_next_cc(flags: u8, expected: u8) {
+        let mut buf = vec![0; PACKET_SIZE];
+        buf[..4].copy_from_slice(&[0x47, 0x01, 0x00, flags]);
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+        assert_eq!(packet.expected_next_cc(), expected,
+            "Expected next continuity counter is incorrect");
+    }
+
+    #[test_case(packet_2)]
+    #[test_case(packet_1)]
+    fn to_bytes(packet: fn() -> PacketFixture) {
+        let (buf, _, _) = packet();
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+
+        let bytes = packet.to_bytes().unwrap();
+        assert_eq!(&bytes[..], &buf[..], "Serialized packet is incorrect");
+        assert_eq!(TSPacket::from_bytes(&bytes).unwrap(), packet,
+            "Round trip packet is incorrect");
+    }
+
+    #[test_case(packet_2)]
+    #[test_case(packet_1)]
+    fn raw_bytes(packet: fn() -> PacketFixture) {
+        let (buf, _, _) = packet();
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+        assert_eq!(&packet.raw_bytes()[..], &buf[..], "Raw bytes are incorrect");
+    }
+
+    #[test]
+    fn raw_bytes_adaptation_extension() {
+        // The reserved bytes at the end of the adaptation extension aren't parsed, so they are only
+        // kept by the raw bytes.
+        let mut buf = vec![0xAA; PACKET_SIZE];
+        buf[..10].copy_from_slice(&[0x47, 0x01, 0x00, 0x30, 0x05, 0x01, 0x03, 0x1F, 0xAB, 0xCD]);
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+        assert_eq!(&packet.raw_bytes()[..], &buf[..], "Raw bytes are incorrect");
+    }
+
+    #[test]
+    fn to_bytes_stuffing_adaptation_field() {
+        let mut buf = vec![0xAA; PACKET_SIZE];
+        buf[..5].copy_from_slice(&[0x47, 0x01, 0x00, 0x30, 0x00]);
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+        assert_eq!(&packet.to_bytes().unwrap()[..], &buf[..], "Serialized packet is incorrect");
+    }
+
+    #[test_case(0x10, false; "No scrambling")]
+    #[test_case(0x50, false; "Reserved")]
+    #[test_case(0x90, true; "Even key")]
+    #[test_case(0xD0, true; "Odd key")]
+    fn is_scrambled(flags: u8, expected: bool) {
+        let mut buf = vec![0xAA; PACKET_SIZE];
+        buf[..4].copy_from_slice(&[0x47, 0x41, 0x00, flags]);
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+        assert_eq!(packet.is_scrambled(), expected, "Scrambling is incorrect");
+    }
+
+    #[test]
+    fn set_tei() {
+        let mut buf = vec![0xAA; PACKET_SIZE];
+        buf[..4].copy_from_slice(&[0x47, 0xC1, 0x00, 0x17]);
+        let mut packet = TSPacket::from_bytes(&buf).unwrap();
+        packet.set_tei(false);
+        assert!(! packet.header().tei(), "Transport Error Indicator is incorrect");
+
+        let raw = packet.raw_bytes().to_vec();
+        let parsed = TSPacket::from_bytes(&raw).unwrap();
+        assert!(! parsed.header().tei(), "Rewritten Transport Error Indicator is incorrect");
+        assert!(parsed.header().pusi(), "Payload Unit Start Indicator is incorrect");
+        assert_eq!(parsed.header().pid(), 0x100, "PID is incorrect");
+        assert_eq!(parsed.header().continuity_counter(), 7, "Continuity Counter is incorrect");
+        assert_eq!(&raw[4..], &buf[4..], "Packet data after the header changed");
+    }
+
+    #[test_case(0x20; "Adaptation field only")]
+    #[test_case(0x30; "Adaptation field and payload")]
+    fn from_bytes_adaptation_field_overrun(flags: u8) {
+        let mut buf = vec![0xFF; PACKET_SIZE];
+        buf[..6].copy_from_slice(&[0x47, 0x01, 0x00, flags, 200, 0x00]);
+        assert!(TSPacket::from_bytes(&buf).is_err(), "Packet result is incorrect");
+    }
+
+    #[test_case(&[0x47, 0x01, 0x00, 0x30, 0x06, 0x10, 0x00]; "PCR")]
+    #[test_case(&[0x47, 0x01, 0x00, 0x30, 0x02, 0x02, 0x05]; "Transport private data")]
+    #[test_case(&[0x47, 0x01, 0x00, 0x30, 0x01, 0x04]; "Splice countdown")]
+    fn from_bytes_field_overrun(start: &[u8]) {
+        let mut buf = vec![0xFF; PACKET_SIZE];
+        buf[..start.len()].copy_from_slice(start);
+        assert!(TSPacket::from_bytes(&buf).is_err(), "Packet result is incorrect");
+    }
+
+    #[test_case(&[0x47, 0x41, 0x00, 0x10, 184]; "No adaptation field")]
+    #[test_case(&[0x47, 0x41, 0x00, 0x30, 0x00, 183]; "Stuffing adaptation field")]
+    fn from_bytes_pointer_past_packet(start: &[u8]) {
+        let mut buf = vec![0xFF; PACKET_SIZE];
+        buf[..start.len()].copy_from_slice(start);
+        let error = TSPacket::from_bytes(&buf).expect_err("Packet result is incorrect");
+        assert!(error.downcast_ref::<InvalidPayloadPointer>().is_some(), "Error is incorrect");
+    }
+
+    #[test]
+    fn from_bytes_long_buffer() {
+        // The adaptation field runs past the end of a packet but still fits in the buffer.
+        let mut buf = vec![0xFF; 200];
+        buf[..6].copy_from_slice(&[0x47, 0x41, 0x00, 0x30, 190, 0x00]);
+        buf[195] = 0x00;
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+        assert_eq!(packet.payload().unwrap().get_current_data().len(), 0,
+            "Payload is incorrect");
+    }
+
+    #[test]
+    fn from_bytes_random() {
+        // Xorshift so that the same inputs are used on every run. Only the result matters, as long
+        // as neither parsing nor reading the payload panics.
+        let mut state: u32 = 0x2545F491;
+        for _ in 0..10_000 {
+            let mut buf = [0u8; PACKET_SIZE];
+            for byte in buf.iter_mut() {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                *byte = state as u8;
+            }
+            buf[0] = header::SYNC_BYTE;
+
+            if let Ok(packet) = TSPacket::from_bytes(&buf) {
+                let _ = packet.payload().map(|p| (p.get_current_data(), p.get_start_data()));
+            }
+        }
+    }
+
+    #[test]
+    fn builder() {
+        // Adaptation field with a PCR.
+        let af_bytes = [0x07, 0x10, 0x00, 0x00, 0x2A, 0x30, 0x7E, 0x96];
+        let adaptation_field = DataAdaptationField::from_bytes(&af_bytes).unwrap();
+        let header = TSHeader::builder().pid(0x100).pusi(true).continuity_counter(5);
+        let packet = TSPacket::builder(header)
+            .adaptation_field(adaptation_field)
+            .payload(&[0x00, 0xAA, 0xBB])
+            .build()
+            .unwrap();
+
+        let buf = packet.to_bytes().unwrap();
+        let parsed = TSPacket::from_bytes(&buf).unwrap();
+        assert_eq!(parsed.header().pid(), 0x100, "PID is incorrect");
+        assert!(parsed.header().pusi(), "Payload Unit Start Indicator is incorrect");
+        assert_eq!(parsed.header().continuity_counter(), 5, "Continuity Counter is incorrect");
+        assert_eq!(parsed.header().adaptation_field_control(),
+            AdaptationFieldControl::AdaptationAndPayload, "Adaptation field control is incorrect");
+        assert_eq!(parsed.pcr(), packet.pcr(), "PCR is incorrect");
+        assert!(parsed.pcr().is_some(), "PCR is missing");
+        assert_eq!(parsed.payload().unwrap().data(), [0xAA, 0xBB], "Payload data is incorrect");
+        assert_eq!(parsed, packet, "Re-parsed packet is incorrect");
+    }
+
+    #[test]
+    fn builder_payload_too_large() {
+        let header = TSHeader::builder().pid(0x100);
+        let result = TSPacket::builder(header).payload(&[0xAA; PACKET_SIZE]).build();
+        assert!(result.is_err(), "Packet result is incorrect");
+    }
+
+    #[test]
+    fn display() {
+        let mut buf = vec![0xAA; PACKET_SIZE];
+        buf[..12].copy_from_slice(&[0x47, 0x01, 0x23, 0x30, 0x07, 0x10, 0x00, 0x00, 0x2A, 0x30,
+            0x7E, 0x96]);
+        let packet = TSPacket::from_bytes(&buf).unwrap();
+
+        let display = packet.to_string();
+        assert!(display.contains("PID: 291"), "PID is missing: {}", display);
+        assert!(display.contains("Adaptation Field: data"), "Adaptation field is missing: {}",
+            display);
+        assert!(display.contains(&format!("PCR: {}", packet.pcr().unwrap())),
+            "PCR is missing: {}", display);
+        assert!(display.contains("Payload Length: 176"), "Payload length is missing: {}", display);
+    }
+}
@@ -0,0 +1,319 @@
+//! This module keeps track of the information stored in the adaptation extension field of the
+//! adaptation field.
+use alloc::boxed::Box;
+use alloc::vec;
+use core::error::Error;
+
+use bitvec::prelude::*;
+
+use crate::errors::not_enough_data::NotEnoughData;
+use crate::pes::{read_timestamp, write_timestamp};
+
+/// The adaptation extension length and flags fields are 1 byte each.
+const EXTENSION_HEADER_SIZE: usize = 2;
+
+/// The LTW valid flag and LTW offset fields take up 2 bytes.
+const LTW_SIZE: usize = 2;
+
+/// The reserved bits and piecewise rate field take up 3 bytes.
+const PIECEWISE_RATE_SIZE: usize = 3;
+
+/// The splice type and DTS of the next access unit take up 5 bytes.
+const SEAMLESS_SPLICE_SIZE: usize = 5;
+
+/// Splice information carried by an adaptation extension with the seamless splice flag set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeamlessSplice {
+    /// Parameters of the splice as defined by the video format.
+    splice_type: u8,
+    /// Decoding time of the first access unit after the splice point in 90kHz ticks.
+    dts_next_au: u64,
+}
+
+impl SeamlessSplice {
+    /// Return the splice type.
+    ///
+    /// The meaning of the splice type depends on the video format and the profile and level of the
+    /// stream.
+    pub fn splice_type(&self) -> u8 {
+        self.splice_type
+    }
+
+    /// Return the decoding time of the first access unit after the splice point in 90kHz ticks.
+    pub fn dts_next_au(&self) -> u64 {
+        self.dts_next_au
+    }
+}
+
+/// Optional data found at the end of a data adaptation field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdaptationExtension {
+    /// Number of bytes that make up the adaptation extension after this length field.
+    adaptation_extension_length: u8,
+    /// Set when the legal time window fields are present.
+    ltw_flag: bool,
+    /// Set when the piecewise rate field is present.
+    piecewise_rate_flag: bool,
+    /// Set when the splice type and DTS of the next access unit are present.
+    seamless_splice_flag: bool,
+    /// Set when the legal time window offset is valid.
+    ///
+    /// Is `None` if the LTW Flag is `false`.
+    ltw_valid_flag: Option<bool>,
+    /// Legal time window offset in units of `(300 / fs)` seconds.
+    ///
+    /// Is `None` if the LTW Flag is `false`.
+    ltw_offset: Option<u16>,
+    /// Rate of the stream in units of 50 bytes per second.
+    ///
+    /// Is `None` if the Piecewise Rate Flag is `false`.
+    piecewise_rate: Option<u32>,
+    /// Parameters of the splice as defined by the video format.
+    ///
+    /// Is `None` if the Seamless Splice Flag is `false`.
+    splice_type: Option<u8>,
+    /// Decoding time of the first access unit after the splice point in 90kHz ticks.
+    ///
+    /// Is `None` if the Seamless Splice Flag is `false`.
+    dts_next_au: Option<u64>,
+}
+
+impl AdaptationExtension {
+    /// Parse the adaptation extension from the passed in buffer.
+    ///
+    /// The buffer must start with the adaptation extension length field.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if buf.len() < EXTENSION_HEADER_SIZE {
+            return Err(Box::new(NotEnoughData { expected: EXTENSION_HEADER_SIZE, found: buf.len() }))
+        }
+
+        let adaptation_extension_length = buf[0];
+        let limit = 1 + adaptation_extension_length as usize;
+        if buf.len() < limit {
+            return Err(Box::new(NotEnoughData { expected: limit, found: buf.len() }))
+        }
+
+        let flags = buf[1].view_bits::<Msb0>();
+        let ltw_flag = flags[0];
+        let piecewise_rate_flag = flags[1];
+        let seamless_splice_flag = flags[2];
+
+        let mut read_idx = EXTENSION_HEADER_SIZE;
+
+        let (ltw_valid_flag, ltw_offset) = if ltw_flag {
+            let bits = Self::read_bits(buf, &mut read_idx, LTW_SIZE, limit)?;
+            (Some(bits[0]), Some(bits[1..16].load_be()))
+        } else {
+            (None, None)
+        };
+
+        let piecewise_rate = if piecewise_rate_flag {
+            let bits = Self::read_bits(buf, &mut read_idx, PIECEWISE_RATE_SIZE, limit)?;
+            Some(bits[2..24].load_be())
+        } else {
+            None
+        };
+
+        let (splice_type, dts_next_au) = if seamless_splice_flag {
+            // The splice type shares its byte with the first bits of the DTS.
+            let splice_type_idx = read_idx;
+            let dts_next_au = read_timestamp(buf, &mut read_idx, limit)?;
+            let splice_type: u8 = buf[splice_type_idx].view_bits::<Msb0>()[0..4].load_be();
+            (Some(splice_type), Some(dts_next_au))
+        } else {
+            (None, None)
+        };
+
+        Ok(AdaptationExtension {
+            adaptation_extension_length,
+            ltw_flag,
+            piecewise_rate_flag,
+            seamless_splice_flag,
+            ltw_valid_flag,
+            ltw_offset,
+            piecewise_rate,
+            splice_type,
+            dts_next_au,
+        })
+    }
+
+    /// Serialize the adaptation extension, starting with the adaptation extension length field.
+    ///
+    /// The length is recomputed from the fields that are present, so any bytes that were not
+    /// parsed are not included. Reserved bits are set to `1`.
+    pub fn to_bytes(&self) -> Box<[u8]> {
+        let mut buf = vec![0, 0];
+
+        let flags = buf[1].view_bits_mut::<Msb0>();
+        flags.set(0, self.ltw_offset.is_some());
+        flags.set(1, self.piecewise_rate.is_some());
+        flags.set(2, self.dts_next_au.is_some());
+        flags[3..8].store_be(0x1Fu8);
+
+        if let Some(ltw_offset) = self.ltw_offset {
+            let mut field = [0; LTW_SIZE];
+            let bits = field.view_bits_mut::<Msb0>();
+            bits.set(0, self.ltw_valid_flag.unwrap_or_default());
+            bits[1..16].store_be(ltw_offset);
+            buf.extend_from_slice(&field);
+        }
+
+        if let Some(piecewise_rate) = self.piecewise_rate {
+            let mut field = [0; PIECEWISE_RATE_SIZE];
+            let bits = field.view_bits_mut::<Msb0>();
+            bits[0..2].store_be(0x3u8);
+            bits[2..24].store_be(piecewise_rate);
+            buf.extend_from_slice(&field);
+        }
+
+        if let Some(dts_next_au) = self.dts_next_au {
+            let field: [u8; SEAMLESS_SPLICE_SIZE] =
+                write_timestamp(self.splice_type.unwrap_or_default(), dts_next_au);
+            buf.extend_from_slice(&field);
+        }
+
+        buf[0] = (buf.len() - 1) as u8;
+        buf.into_boxed_slice()
+    }
+
+    /// Return the number of bytes that [`AdaptationExtension::to_bytes`] produces.
+    pub fn encoded_length(&self) -> usize {
+        let mut length = EXTENSION_HEADER_SIZE;
+        if self.ltw_offset.is_some() {
+            length += LTW_SIZE;
+        }
+        if self.piecewise_rate.is_some() {
+            length += PIECEWISE_RATE_SIZE;
+        }
+        if self.dts_next_au.is_some() {
+            length += SEAMLESS_SPLICE_SIZE;
+        }
+        length
+    }
+
+    /// Read a fixed size field, making sure it ends before the limit.
+    fn read_bits<'a>(
+        buf: &'a [u8],
+        read_idx: &mut usize,
+        read_size: usize,
+        limit: usize,
+    ) -> Result<&'a BitSlice<u8, Msb0>, Box<dyn Error>> {
+        let end = *read_idx + read_size;
+        if end > limit {
+            return Err(Box::new(NotEnoughData { expected: end, found: limit }))
+        }
+
+        let bits = buf[*read_idx..end].view_bits::<Msb0>();
+        *read_idx = end;
+
+        Ok(bits)
+    }
+
+    /// Return the number of bytes that make up the adaptation extension after the length field.
+    pub fn adaptation_extension_length(&self) -> u8 {
+        self.adaptation_extension_length
+    }
+
+    /// Return if the legal time window fields are present.
+    pub fn ltw_flag(&self) -> bool {
+        self.ltw_flag
+    }
+
+    /// Return if the piecewise rate field is present.
+    pub fn piecewise_rate_flag(&self) -> bool {
+        self.piecewise_rate_flag
+    }
+
+    /// Return if the splice type and DTS of the next access unit are present.
+    pub fn seamless_splice_flag(&self) -> bool {
+        self.seamless_splice_flag
+    }
+
+    /// Return if the legal time window offset is valid, if present.
+    pub fn ltw_valid_flag(&self) -> Option<bool> {
+        self.ltw_valid_flag
+    }
+
+    /// Return the legal time window offset, if present.
+    pub fn ltw_offset(&self) -> Option<u16> {
+        self.ltw_offset
+    }
+
+    /// Return the piecewise rate in units of 50 bytes per second, if present.
+    pub fn piecewise_rate(&self) -> Option<u32> {
+        self.piecewise_rate
+    }
+
+    /// Return the splice type, if present.
+    ///
+    /// The meaning of the splice type depends on the video format and the profile and level of the
+    /// stream.
+    pub fn splice_type(&self) -> Option<u8> {
+        self.splice_type
+    }
+
+    /// Return the decoding time of the first access unit after the splice point in 90kHz ticks, if
+    /// present.
+    pub fn dts_next_au(&self) -> Option<u64> {
+        self.dts_next_au
+    }
+
+    /// Return the splice type and DTS of the next access unit together, if present.
+    pub fn seamless_splice(&self) -> Option<SeamlessSplice> {
+        Some(SeamlessSplice {
+            splice_type: self.splice_type?,
+            dts_next_au: self.dts_next_au?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes_seamless_splice() {
+        // Seamless splice flag set with a splice type of `0xA` and a DTS of `0x123456789`.
+        let buf = [0x06, 0x20, 0xA9, 0x8D, 0x15, 0xCF, 0x13];
+        let extension = AdaptationExtension::from_bytes(&buf).unwrap();
+
+        assert_eq!(extension.adaptation_extension_length(), 6,
+            "Adaptation extension length is incorrect");
+        assert!(extension.seamless_splice_flag(), "Seamless splice flag is incorrect");
+        assert_eq!(extension.splice_type(), Some(0xA), "Splice type is incorrect");
+        assert_eq!(extension.dts_next_au(), Some(0x123456789), "DTS of next AU is incorrect");
+        let splice = extension.seamless_splice().expect("Seamless splice is missing");
+        assert_eq!((splice.splice_type(), splice.dts_next_au()), (0xA, 0x123456789),
+            "Seamless splice is incorrect");
+        assert_eq!(extension.ltw_offset(), None, "LTW offset is incorrect");
+        assert_eq!(extension.piecewise_rate(), None, "Piecewise rate is incorrect");
+    }
+
+    #[test]
+    fn from_bytes_all_fields() {
+        let buf = [0x0B, 0xE0, 0x80, 0x64, 0xC0, 0x01, 0x00, 0x29, 0x8D, 0x15, 0xCF, 0x13];
+        let extension = AdaptationExtension::from_bytes(&buf).unwrap();
+
+        assert_eq!(extension.ltw_valid_flag(), Some(true), "LTW valid flag is incorrect");
+        assert_eq!(extension.ltw_offset(), Some(100), "LTW offset is incorrect");
+        assert_eq!(extension.piecewise_rate(), Some(256), "Piecewise rate is incorrect");
+        assert_eq!(extension.splice_type(), Some(2), "Splice type is incorrect");
+        assert_eq!(extension.dts_next_au(), Some(0x123456789), "DTS of next AU is incorrect");
+    }
+
+    #[test]
+    fn from_bytes_truncated() {
+        // Seamless splice flag set but the length only covers part of the DTS.
+        let buf = [0x03, 0x20, 0xA9, 0x8D];
+        assert!(AdaptationExtension::from_bytes(&buf).is_err(), "Truncated extension was parsed");
+    }
+
+    #[test]
+    fn to_bytes_round_trip() {
+        let buf = [0x0B, 0xFF, 0x80, 0x64, 0xC0, 0x01, 0x00, 0x29, 0x8D, 0x15, 0xCF, 0x13];
+        let extension = AdaptationExtension::from_bytes(&buf).unwrap();
+
+        assert_eq!(extension.encoded_length(), buf.len(), "Encoded length is incorrect");
+        assert_eq!(&*extension.to_bytes(), &buf, "Serialized adaptation extension is incorrect");
+    }
+}
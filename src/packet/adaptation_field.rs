@@ -1,28 +1,129 @@
 //! This module keep track of all the information stored in the adaptation field of the
 //! transport stream packet header.
 
-use std::fmt::{Display, Formatter};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt::{Display, Formatter};
+use core::time::Duration;
 
-use bitvec::{field::BitField, order::Msb0, vec::BitVec};
+use bitvec::{field::BitField, order::Msb0, vec::BitVec, view::BitView};
+
+use crate::errors::adaptation_field_overrun::AdaptationFieldOverrun;
+use crate::errors::not_enough_data::NotEnoughData;
+use crate::packet::adaptation_extension::AdaptationExtension;
+use crate::packet::{self, HEADER_SIZE, PACKET_SIZE};
 
 #[cfg(feature = "log")]
-use log::trace;
+use log::{debug, trace};
 
 /// The PCR field and OPCR field are 6 bytes in size.
 pub const PCR_SIZE: u8 = 6;
 
+/// Frequency of the PCR clock in Hz.
+pub const PCR_FREQUENCY: u64 = 27_000_000;
+
+/// The PCR is made up of a 33 bit base multiplied by `300` plus the extension, so it wraps back to
+/// `0` at this value.
+pub const PCR_WRAP: u64 = (1 << 33) * 300;
+
+/// Return how far a PCR moved from `from` to `to` in 27MHz ticks, accounting for wrapping.
+///
+/// PCR values wrap back to `0` after [`PCR_WRAP`], so the shortest distance between them is used.
+/// A negative value means that `to` comes before `from`.
+pub fn pcr_difference(from: u64, to: u64) -> i64 {
+    let difference = (to % PCR_WRAP + PCR_WRAP - from % PCR_WRAP) % PCR_WRAP;
+    if difference > PCR_WRAP / 2 {
+        difference as i64 - PCR_WRAP as i64
+    } else {
+        difference as i64
+    }
+}
+
+/// A program clock reference, which counts 27MHz ticks.
+///
+/// This is returned by [`DataAdaptationField::pcr_timestamp`] and converts the PCR to the 90kHz
+/// clock used by PTS and DTS values, or to a [`Duration`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Pcr(u64);
+
+impl Pcr {
+    /// Create a PCR from a number of 27MHz ticks.
+    pub fn new(ticks: u64) -> Self {
+        Pcr(ticks)
+    }
+
+    /// Return the PCR in 27MHz ticks, which is `base * 300 + extension`.
+    pub fn as_27mhz(&self) -> u64 {
+        self.0
+    }
+
+    /// Return the PCR in 90kHz ticks, which is the PCR base.
+    ///
+    /// This is the same clock as PTS and DTS values, so the two can be compared directly.
+    pub fn as_90khz(&self) -> u64 {
+        self.0 / 300
+    }
+
+    /// Return the time since the PCR was last `0`.
+    pub fn as_duration(&self) -> Duration {
+        let nanos = (self.0 % PCR_FREQUENCY) * 1_000 / 27;
+        Duration::new(self.0 / PCR_FREQUENCY, nanos as u32)
+    }
+}
+
+/// An original program clock reference, which counts 27MHz ticks.
+///
+/// This is returned by [`DataAdaptationField::opcr_timestamp`]. The OPCR is encoded the same way as
+/// a [`Pcr`], so it is converted the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Opcr(u64);
+
+impl Opcr {
+    /// Create an OPCR from a number of 27MHz ticks.
+    pub fn new(ticks: u64) -> Self {
+        Opcr(ticks)
+    }
+
+    /// Return the OPCR in 27MHz ticks, which is `base * 300 + extension`.
+    pub fn as_27mhz(&self) -> u64 {
+        self.0
+    }
+
+    /// Return the OPCR in 90kHz ticks, which is the OPCR base.
+    pub fn as_90khz(&self) -> u64 {
+        Pcr(self.0).as_90khz()
+    }
+
+    /// Return the time since the OPCR was last `0`.
+    pub fn as_duration(&self) -> Duration {
+        Pcr(self.0).as_duration()
+    }
+}
+
 /// The splice countdown field is 1 byte in size.
 pub const SPLICE_COUNTDOWN_SIZE: u8 = 1;
 
 /// The length of the transport private data length field is 1 byte in size.
 pub const TRANSPORT_PRIVATE_DATA_LENGTH_LENGTH: u8 = 1;
 
+/// The adaptation field length and flags fields are 1 byte each.
+const FLAGS_SIZE: usize = 1;
+
+/// The tag and length of each field in the transport private data take up 2 bytes.
+const PRIVATE_DATA_FIELD_HEADER_SIZE: usize = 2;
+
+/// Adaptation fields are padded with this value to fill the packet.
+pub(crate) const STUFFING_BYTE: u8 = 0xFF;
+
 /// This is created because an adaptation field can either be full of metadata as expected ***OR***
 /// it can be a single stuffing byte. I don't want operations that work on a real adaptation field
 /// to work on a stuffing adaptation field but I don't want to make the adaptation field `None`
 /// either because the the `adaptation_control_field` still says the adaptation field is present.
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum AdaptationField {
     /// Data adaptation fields are what you think of when looking at an adaptation field and contain
     /// actual data
@@ -34,7 +135,7 @@ pub enum AdaptationField {
 /// All of this information is shamelessly stolen from wikipedia, my lord and savior.
 /// This [article](https://en.wikipedia.org/wiki/MPEG_transport_stream) in particular. Please donate
 /// to wikipedia if you have the means.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DataAdaptationField {
     /// Number of bytes that make up the adaptation field.
     /// 
@@ -81,10 +182,18 @@ pub struct DataAdaptationField {
     ///
     /// Is `None` if the Transport Private Data Flag is `false`.
     transport_private_data: Option<Box<[u8]>>,
+    /// Adaptation extension data.
+    ///
+    /// Is `None` if the Adaptation Field Extension Flag is `false` or the extension could not be
+    /// parsed.
+    adaptation_extension: Option<AdaptationExtension>,
+    /// The raw bytes of the adaptation field, starting with the adaptation field length.
+    raw: Box<[u8]>,
 }
 
 impl DataAdaptationField {
     /// Create a new adaptation field.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         adaptation_field_length: u8,
         discontinuity_indicator: bool,
@@ -100,9 +209,10 @@ impl DataAdaptationField {
         splice_countdown: Option<i8>,
         transport_private_data_length: Option<u8>,
         transport_private_data: Option<Box<[u8]>>,
+        adaptation_extension: Option<AdaptationExtension>,
     ) -> Self {
 
-        Self {
+        let mut af = Self {
             adaptation_field_length,
             discontinuity_indicator,
             random_access_indicator,
@@ -117,11 +227,27 @@ impl DataAdaptationField {
             splice_countdown,
             transport_private_data_length,
             transport_private_data,
-        }
+            adaptation_extension,
+            raw: Box::default(),
+        };
+
+        // Serialize the fields so that the adaptation field length is kept as is.
+        let available = PACKET_SIZE - HEADER_SIZE as usize - 1;
+        let payload_length = available.saturating_sub(adaptation_field_length as usize);
+        af.raw = af.to_bytes(payload_length).unwrap_or_default();
+        af
     }
 
     /// Parse the adaptation field from the passed in buffer
-    pub fn from_bytes(buf: &mut [u8]) -> Self {
+    ///
+    /// Returns an [`AdaptationFieldOverrun`] error if the adaptation field length is longer than
+    /// the rest of the buffer, or if the fields that the flags say are present don't fit in the
+    /// adaptation field. Both happen with corrupt or misaligned streams.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if buf.is_empty() {
+            return Err(Box::new(NotEnoughData { expected: 1, found: 0 }));
+        }
+
         #[cfg(feature = "log")]
         trace!("adaptation field bytes: {:02X?}", buf);
 
@@ -139,13 +265,19 @@ impl DataAdaptationField {
         // Increment the read index since we just read a byte
         read_idx += 1;
 
+        // Every field has to be read from within the adaptation field, and the adaptation field
+        // has to fit in the buffer.
+        let end = read_idx + adaptation_field_length as usize;
+        if end > buf.len() {
+            return Err(Box::new(AdaptationFieldOverrun {
+                length: adaptation_field_length as usize,
+                available: buf.len() - read_idx,
+            }));
+        }
+
         // Check if any of the dynamic fields are set. If these pop during testing I'll have to
         // implement them, but otherwise I'll leave them until necessary.
-        let adaptation_field_required: BitVec<u8, Msb0> =
-            BitVec::from_slice(&buf[read_idx..read_idx + 1]);
-
-        // Increment the read index since we just read a byte
-        read_idx += 1;
+        let adaptation_field_required = Self::read_data(buf, &mut read_idx, FLAGS_SIZE, end)?;
 
         let pcr_flag = adaptation_field_required[3];
         let opcr_flag = adaptation_field_required[4];
@@ -153,18 +285,16 @@ impl DataAdaptationField {
         let transport_private_data_flag = adaptation_field_required[6];
         let adaptation_field_extension_flag = adaptation_field_required[7];
 
-        let pcr = Self::read_pcr_data(&pcr_flag, buf, &mut read_idx);
-        let opcr = Self::read_pcr_data(&opcr_flag, buf, &mut read_idx);
+        let pcr = Self::read_pcr_data(&pcr_flag, buf, &mut read_idx, end)?;
+        let opcr = Self::read_pcr_data(&opcr_flag, buf, &mut read_idx, end)?;
 
-        let splice_countdown = match Self::read_data_conditionally(
+        let splice_countdown = Self::read_data_conditionally(
             &splicing_point_flag,
             buf,
             &mut read_idx,
             SPLICE_COUNTDOWN_SIZE as usize,
-        ) {
-            Some(bits) => Some(bits.load()),
-            None => None,
-        };
+            end,
+        )?.map(|bits| bits.load_be());
 
         // Putting this in the outer scope, so we can use the value in the TSAdapterField
         // constructor below.
@@ -175,12 +305,13 @@ impl DataAdaptationField {
             buf,
             &mut read_idx,
             TRANSPORT_PRIVATE_DATA_LENGTH_LENGTH as usize,
-        ) {
+            end,
+        )? {
             Some(bits) => {
-                let length: u8 = bits.load();
+                let length: u8 = bits.load_be();
 
                 transport_private_data = Some(Box::from(
-                    Self::read_data(buf, &mut read_idx, length as usize).as_raw_slice(),
+                    Self::read_data(buf, &mut read_idx, length as usize, end)?.as_raw_slice(),
                 ));
 
                 Some(length)
@@ -192,11 +323,23 @@ impl DataAdaptationField {
             }
         };
 
-        // We currently do nothing with the adaptation extension field.
-        // TODO: Add support for adaptation extension field.
         #[cfg(feature = "log")]
         trace!("Packet has adaptation extension field {}", adaptation_field_extension_flag);
 
+        // The adaptation extension can't extend past the end of the adaptation field.
+        let adaptation_extension = if adaptation_field_extension_flag && read_idx < end {
+            let extension = AdaptationExtension::from_bytes(&buf[read_idx..end]);
+
+            #[cfg(feature = "log")]
+            if let Err(e) = &extension {
+                debug!("Could not parse adaptation extension: {}", e);
+            }
+
+            extension.ok()
+        } else {
+            None
+        };
+
         let af = DataAdaptationField {
             adaptation_field_length,
             discontinuity_indicator: adaptation_field_required[0],
@@ -212,55 +355,189 @@ impl DataAdaptationField {
             splice_countdown,
             transport_private_data_length,
             transport_private_data,
+            adaptation_extension,
+            raw: Box::from(&buf[..end]),
         };
 
         #[cfg(feature = "log")]
         trace!("{}", af);
 
-        return af;
+        Ok(af)
     }
 
     fn read_data_conditionally(
         flag: &bool,
-        buf: &mut [u8],
+        buf: &[u8],
         read_idx: &mut usize,
         read_size: usize,
-    ) -> Option<BitVec<u8, Msb0>> {
+        end: usize,
+    ) -> Result<Option<BitVec<u8, Msb0>>, AdaptationFieldOverrun> {
         if !flag {
-            return None;
+            return Ok(None);
         }
 
-        Some(Self::read_data(buf, read_idx, read_size))
+        Self::read_data(buf, read_idx, read_size, end).map(Some)
     }
 
-    fn read_data(buf: &mut [u8], read_idx: &mut usize, read_size: usize) -> BitVec<u8, Msb0> {
+    /// Read `read_size` bytes starting at `read_idx`, without reading past `end`, which is the end
+    /// of the adaptation field.
+    fn read_data(
+        buf: &[u8],
+        read_idx: &mut usize,
+        read_size: usize,
+        end: usize,
+    ) -> Result<BitVec<u8, Msb0>, AdaptationFieldOverrun> {
+        if *read_idx + read_size > end {
+            return Err(AdaptationFieldOverrun {
+                length: *read_idx + read_size - 1,
+                available: end - 1,
+            });
+        }
+
         // Read the  data from the given buffer location
         let bits: BitVec<u8, Msb0> = BitVec::from_slice(&buf[*read_idx..*read_idx + read_size]);
 
         // Increment the read index since we just read a `PCR_SIZE` amount of bytes.
         *read_idx += read_size;
 
-        return bits;
+        Ok(bits)
     }
 
-    /// Read the PCR (or OPCR) data from a starting index
-    fn read_pcr_data(flag: &bool, buf: &mut [u8], read_idx: &mut usize) -> Option<u64> {
-        let pcr_bits = match Self::read_data_conditionally(flag, buf, read_idx, PCR_SIZE as usize) {
+    /// Serialize the adaptation field so that it fills the packet around a payload of the given
+    /// length.
+    ///
+    /// The adaptation field length is recomputed from the fields that are present plus the
+    /// stuffing needed to fill the packet, rather than trusting the stored value. The flags are
+    /// also set from the fields that are present. This makes it safe to serialize an adaptation
+    /// field after it has been edited.
+    /// # Parameters
+    /// - `payload_length`: number of payload bytes that follow the adaptation field in the packet.
+    pub fn to_bytes(&self, payload_length: usize) -> Result<Box<[u8]>, Box<dyn Error>> {
+        let adaptation_field_length = self.length_for_payload(payload_length)?;
+
+        let mut buf = vec![adaptation_field_length, 0];
+        let flags = buf[1].view_bits_mut::<Msb0>();
+        flags.set(0, self.discontinuity_indicator);
+        flags.set(1, self.random_access_indicator);
+        flags.set(2, self.elementary_stream_priority_indicator);
+        flags.set(3, self.pcr.is_some());
+        flags.set(4, self.opcr.is_some());
+        flags.set(5, self.splice_countdown.is_some());
+        flags.set(6, self.transport_private_data.is_some());
+        flags.set(7, self.adaptation_extension.is_some());
+
+        if let Some(pcr) = self.pcr {
+            buf.extend_from_slice(&Self::write_pcr_data(pcr));
+        }
+        if let Some(opcr) = self.opcr {
+            buf.extend_from_slice(&Self::write_pcr_data(opcr));
+        }
+        if let Some(splice_countdown) = self.splice_countdown {
+            buf.push(splice_countdown as u8);
+        }
+        if let Some(transport_private_data) = &self.transport_private_data {
+            buf.push(transport_private_data.len() as u8);
+            buf.extend_from_slice(transport_private_data);
+        }
+        if let Some(adaptation_extension) = &self.adaptation_extension {
+            buf.extend_from_slice(&adaptation_extension.to_bytes());
+        }
+
+        buf.resize(adaptation_field_length as usize + 1, STUFFING_BYTE);
+        Ok(buf.into_boxed_slice())
+    }
+
+    /// Recompute the adaptation field length so that the adaptation field fills the packet around
+    /// a payload of the given length.
+    ///
+    /// This should be run after editing the adaptation field so that the stored length matches
+    /// what [`DataAdaptationField::to_bytes`] produces.
+    /// # Parameters
+    /// - `payload_length`: number of payload bytes that follow the adaptation field in the packet.
+    pub fn recompute_length(&mut self, payload_length: usize) -> Result<(), Box<dyn Error>> {
+        self.adaptation_field_length = self.length_for_payload(payload_length)?;
+        Ok(())
+    }
+
+    /// Return the adaptation field length needed to fill the packet around a payload of the given
+    /// length.
+    fn length_for_payload(&self, payload_length: usize) -> Result<u8, Box<dyn Error>> {
+        let content_length = self.content_length();
+        // The adaptation field length field itself isn't counted in the adaptation field length.
+        let available = (PACKET_SIZE - HEADER_SIZE as usize - 1).saturating_sub(payload_length);
+
+        if available < content_length {
+            return Err(Box::new(NotEnoughData { expected: content_length, found: available }))
+        }
+
+        Ok(available as u8)
+    }
+
+    /// Return the number of bytes that the fields which are present take up after the adaptation
+    /// field length field.
+    ///
+    /// This covers the flags, PCR, OPCR, splice countdown, transport private data, and adaptation
+    /// extension, which is everything in the adaptation field except the stuffing. It is computed
+    /// from the fields that are present, so it matches what [`DataAdaptationField::to_bytes`]
+    /// writes even after the adaptation field has been edited. Reserved bytes at the end of a
+    /// parsed adaptation extension aren't counted since they aren't written back out.
+    pub fn content_length(&self) -> usize {
+        let mut length = FLAGS_SIZE;
+        if self.pcr.is_some() {
+            length += PCR_SIZE as usize;
+        }
+        if self.opcr.is_some() {
+            length += PCR_SIZE as usize;
+        }
+        if self.splice_countdown.is_some() {
+            length += SPLICE_COUNTDOWN_SIZE as usize;
+        }
+        if let Some(transport_private_data) = &self.transport_private_data {
+            length += TRANSPORT_PRIVATE_DATA_LENGTH_LENGTH as usize + transport_private_data.len();
+        }
+        if let Some(adaptation_extension) = &self.adaptation_extension {
+            length += adaptation_extension.encoded_length();
+        }
+        length
+    }
+
+    /// Write the PCR (or OPCR) data in 27MHz ticks as a 33 bit base, 6 reserved bits, and a 9 bit
+    /// extension.
+    fn write_pcr_data(pcr: u64) -> [u8; PCR_SIZE as usize] {
+        let mut buf = [0; PCR_SIZE as usize];
+        let bits = buf.view_bits_mut::<Msb0>();
+        bits[0..33].store_be((pcr / 300) & 0x1_FFFF_FFFF);
+        bits[33..39].store_be(0x3Fu8);
+        bits[39..48].store_be(pcr % 300);
+        buf
+    }
+
+    /// Read the PCR (or OPCR) data from a starting index.
+    ///
+    /// Both fields are encoded the same way, so this is used for both of them.
+    fn read_pcr_data(
+        flag: &bool,
+        buf: &[u8],
+        read_idx: &mut usize,
+        end: usize,
+    ) -> Result<Option<u64>, AdaptationFieldOverrun> {
+        let pcr_bits = match Self::read_data_conditionally(flag, buf, read_idx, PCR_SIZE as usize,
+            end)? {
             Some(bits) => bits,
             None => {
                 // Return early if there is no field to be read, as seen by reading the flag.
-                return None;
+                return Ok(None);
             }
         };
 
         // The first 33 bits are the "base" value which gets multiplied by `300`. This is defined in
         // the MPEG/TS standard.
-        let base: u64 = pcr_bits[0..34].load();
+        let base: u64 = pcr_bits[0..33].load_be();
         // The next 6 bits are reserved, so we will ignore them and the last 9 bits are the
         // "extension" which get added to the multiplied base.
-        let extension: u64 = pcr_bits[39..48].load();
+        let extension: u64 = pcr_bits[39..48].load_be();
 
-        return Some(base * 300 + extension);
+        Ok(Some(base * 300 + extension))
     }
 
     /// Returns the number of bytes that make up the adaptation field length
@@ -268,17 +545,217 @@ impl DataAdaptationField {
         self.adaptation_field_length
     }
 
+    /// Return the number of stuffing bytes at the end of the adaptation field.
+    ///
+    /// This is the adaptation field length minus [`DataAdaptationField::content_length`]. It is `0`
+    /// if the adaptation field length is too short to hold the fields that are present.
+    pub fn stuffing_bytes(&self) -> usize {
+        (self.adaptation_field_length as usize).saturating_sub(self.content_length())
+    }
+
+    /// Return if this packet is in a discontinuity state with respect to either the continuity
+    /// counter or the program clock reference.
+    pub fn discontinuity_indicator(&self) -> bool {
+        self.discontinuity_indicator
+    }
+
+    /// Return if the stream may be decoded without errors starting at this packet.
+    pub fn random_access_indicator(&self) -> bool {
+        self.random_access_indicator
+    }
+
+    /// Return if this packet's payload is higher priority than other payloads on the same PID.
+    pub fn elementary_stream_priority_indicator(&self) -> bool {
+        self.elementary_stream_priority_indicator
+    }
+
+    /// Return if the PCR field is present.
+    pub fn pcr_flag(&self) -> bool {
+        self.pcr_flag
+    }
+
+    /// Return if the OPCR field is present.
+    pub fn opcr_flag(&self) -> bool {
+        self.opcr_flag
+    }
+
+    /// Return if the splice countdown field is present.
+    pub fn splicing_point_flag(&self) -> bool {
+        self.splicing_point_flag
+    }
+
+    /// Return if the transport private data fields are present.
+    pub fn transport_private_data_flag(&self) -> bool {
+        self.transport_private_data_flag
+    }
+
     /// Return if the header indicates that this packet contains an adaptation
     /// extension field.
     pub fn has_adaptation_extension_field(&self) -> bool {
         self.adaptation_field_extension_flag
     }
+
+    /// Returns the program clock reference in 27MHz ticks if one is present.
+    pub fn pcr(&self) -> Option<u64> {
+        self.pcr
+    }
+
+    /// Returns the program clock reference as a [`Pcr`] if one is present.
+    pub fn pcr_timestamp(&self) -> Option<Pcr> {
+        self.pcr.map(Pcr)
+    }
+
+    /// Returns the adaptation extension if one is present and could be parsed.
+    pub fn adaptation_extension(&self) -> Option<&AdaptationExtension> {
+        self.adaptation_extension.as_ref()
+    }
+
+    /// Returns the original program clock reference in 27MHz ticks if one is present.
+    pub fn opcr(&self) -> Option<u64> {
+        self.opcr
+    }
+
+    /// Returns the original program clock reference as an [`Opcr`] if one is present.
+    pub fn opcr_timestamp(&self) -> Option<Opcr> {
+        self.opcr.map(Opcr)
+    }
+
+    /// Return the OPCR minus the PCR in 27MHz ticks, accounting for wrapping.
+    ///
+    /// When a stream is copied or spliced into another, the OPCR keeps the timing of the original
+    /// stream, so this is the offset between the original timing and the current timing.
+    ///
+    /// Returns `None` if either the PCR or the OPCR is not present.
+    pub fn opcr_pcr_offset(&self) -> Option<i64> {
+        Some(pcr_difference(self.pcr?, self.opcr?))
+    }
+
+    /// Returns the splice countdown if one is present.
+    pub fn splice_countdown(&self) -> Option<i8> {
+        self.splice_countdown
+    }
+
+    /// Returns the length of the transport private data if it is present.
+    pub fn transport_private_data_length(&self) -> Option<u8> {
+        self.transport_private_data_length
+    }
+
+    /// Returns the transport private data if it is present.
+    pub fn transport_private_data(&self) -> Option<&[u8]> {
+        self.transport_private_data.as_deref()
+    }
+
+    /// Split the transport private data into tag/length/value fields.
+    ///
+    /// Transport private data has no fixed format, but it's commonly a sequence of fields that each
+    /// start with a 1 byte tag and a 1 byte length, the same as a descriptor. The raw bytes are
+    /// still returned by [`DataAdaptationField::transport_private_data`].
+    ///
+    /// Returns an empty `Vec` if there is no transport private data. If a field's length runs past
+    /// the end of the transport private data then that field is left out, along with any bytes
+    /// after it.
+    pub fn transport_private_data_parsed(&self) -> Vec<PrivateDataField> {
+        let Some(buf) = self.transport_private_data.as_deref() else {
+            return Vec::new();
+        };
+
+        let mut fields = Vec::new();
+        let mut read_idx = 0;
+        while read_idx + PRIVATE_DATA_FIELD_HEADER_SIZE <= buf.len() {
+            let tag = buf[read_idx];
+            let length = buf[read_idx + 1] as usize;
+            let data_start = read_idx + PRIVATE_DATA_FIELD_HEADER_SIZE;
+            let data_end = data_start + length;
+
+            if data_end > buf.len() {
+                #[cfg(feature = "log")]
+                debug!("Transport private data field with tag {:#04X} overruns the private data",
+                    tag);
+                break
+            }
+
+            fields.push(PrivateDataField { tag, data: Box::from(&buf[data_start..data_end]) });
+            read_idx = data_end;
+        }
+
+        fields
+    }
+
+    /// Return an arbitrary range of bits from the raw adaptation field bytes as a big-endian value.
+    ///
+    /// This is an escape hatch for inspecting bits that aren't exposed by any other accessor.
+    /// Bits are numbered starting at `0` for the most significant bit of the adaptation field
+    /// length (MSB0), so `raw_bits(8, 8)` returns the flags byte. The bits come from the bytes the
+    /// adaptation field was parsed from or created with, so changes made with the setters aren't
+    /// reflected.
+    ///
+    /// Returns `None` if `len` is `0` or more than [`crate::packet::MAX_RAW_BITS`], or if the
+    /// range extends past the end of the adaptation field.
+    pub fn raw_bits(&self, start: usize, len: usize) -> Option<u32> {
+        packet::raw_bits(&self.raw, start, len)
+    }
+
+    /// Set if this packet is in a discontinuity state.
+    pub fn set_discontinuity_indicator(&mut self, discontinuity_indicator: bool) {
+        self.discontinuity_indicator = discontinuity_indicator;
+    }
+
+    /// Set if the stream may be decoded without errors starting at this packet.
+    pub fn set_random_access_indicator(&mut self, random_access_indicator: bool) {
+        self.random_access_indicator = random_access_indicator;
+    }
+
+    /// Set if this packet's payload is higher priority than other payloads on the same PID.
+    pub fn set_elementary_stream_priority_indicator(&mut self, priority: bool) {
+        self.elementary_stream_priority_indicator = priority;
+    }
+
+    /// Set or remove the program clock reference in 27MHz ticks.
+    pub fn set_pcr(&mut self, pcr: Option<u64>) {
+        self.pcr_flag = pcr.is_some();
+        self.pcr = pcr;
+    }
+
+    /// Set or remove the original program clock reference in 27MHz ticks.
+    pub fn set_opcr(&mut self, opcr: Option<u64>) {
+        self.opcr_flag = opcr.is_some();
+        self.opcr = opcr;
+    }
+
+    /// Set or remove the splice countdown.
+    pub fn set_splice_countdown(&mut self, splice_countdown: Option<i8>) {
+        self.splicing_point_flag = splice_countdown.is_some();
+        self.splice_countdown = splice_countdown;
+    }
+
+    /// Set or remove the transport private data.
+    ///
+    /// Returns an error if there are more than 255 bytes of data, as that can't fit in the
+    /// transport private data length field.
+    pub fn set_transport_private_data(
+        &mut self,
+        transport_private_data: Option<Box<[u8]>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let length = transport_private_data.as_ref().map(|data| u8::try_from(data.len()))
+            .transpose()?;
+
+        self.transport_private_data_flag = transport_private_data.is_some();
+        self.transport_private_data_length = length;
+        self.transport_private_data = transport_private_data;
+        Ok(())
+    }
+
+    /// Set or remove the adaptation extension.
+    pub fn set_adaptation_extension(&mut self, adaptation_extension: Option<AdaptationExtension>) {
+        self.adaptation_field_extension_flag = adaptation_extension.is_some();
+        self.adaptation_extension = adaptation_extension;
+    }
 }
 
 /// How many stuffing bytes exist in an adaptation field with a length field of `0`
 pub const STUFFING_ADAPTATION_FIELD_LENGTH: u8 = 1;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 /// An adaptation field with a length of `0` is a StuffingAdaptationField. It contains 1 byte of
 /// stuffing per the standard.
 pub struct StuffingAdaptationField {
@@ -290,7 +767,7 @@ pub struct StuffingAdaptationField {
 impl StuffingAdaptationField {
     /// Create a new stuffing adaptation field.
     pub fn new() -> StuffingAdaptationField {
-        return StuffingAdaptationField {
+        StuffingAdaptationField {
             adaptation_field_length: STUFFING_ADAPTATION_FIELD_LENGTH
         }
     }
@@ -304,8 +781,37 @@ impl StuffingAdaptationField {
     }
 }
 
+impl Default for StuffingAdaptationField {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A tag/length/value field from the transport private data of an adaptation field.
+///
+/// These are returned by [`DataAdaptationField::transport_private_data_parsed`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrivateDataField {
+    /// Identifies the type of the field.
+    tag: u8,
+    /// Contents of the field after the length byte.
+    data: Box<[u8]>,
+}
+
+impl PrivateDataField {
+    /// Return the tag that identifies the type of the field.
+    pub fn tag(&self) -> u8 {
+        self.tag
+    }
+
+    /// Return the contents of the field after the length byte.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
 impl Display for DataAdaptationField {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         let msg = format!("\n\
             Discontinuity: {}\n\
             Random Access: {}\n\
@@ -324,6 +830,227 @@ impl Display for DataAdaptationField {
             self.transport_private_data_flag,
             self.adaptation_field_extension_flag,
         );
-        write!(f, "{}", msg)
+        write!(f, "{}", msg)?;
+
+        if let Some(pcr) = self.pcr {
+            write!(f, "\nPCR: {}", pcr)?;
+        }
+        if let Some(opcr) = self.opcr {
+            write!(f, "\nOPCR: {}", opcr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn from_bytes_signed_splice_countdown() {
+        // Splicing point and transport private data flags set.
+        let buf = [0x06, 0x06, 0xFD, 0x03, 0xAA, 0xBB, 0xCC];
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+
+        assert_eq!(af.adaptation_field_length(), 6, "Adaptation field length is incorrect");
+        assert_eq!(af.splice_countdown(), Some(-3), "Splice countdown is incorrect");
+        assert_eq!(af.transport_private_data_length(), Some(3),
+            "Transport private data length is incorrect");
+        assert_eq!(af.transport_private_data(), Some([0xAA, 0xBB, 0xCC].as_slice()),
+            "Transport private data is incorrect");
+    }
+
+    #[test]
+    fn transport_private_data_parsed() {
+        // Transport private data flag set with 2 fields.
+        let buf = [0x0B, 0x02, 0x09, 0xA1, 0x02, 0x11, 0x22, 0xB2, 0x03, 0x33, 0x44, 0x55];
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+
+        let fields = af.transport_private_data_parsed();
+        assert_eq!(fields.len(), 2, "Number of private data fields is incorrect");
+        assert_eq!(fields[0].tag(), 0xA1, "First field tag is incorrect");
+        assert_eq!(fields[0].data(), [0x11, 0x22], "First field data is incorrect");
+        assert_eq!(fields[1].tag(), 0xB2, "Second field tag is incorrect");
+        assert_eq!(fields[1].data(), [0x33, 0x44, 0x55], "Second field data is incorrect");
+        assert_eq!(af.transport_private_data().map(|data| data.len()), Some(9),
+            "Transport private data is incorrect");
+    }
+
+    #[test_case([0x02, 0x02, 0x00, 0xFF, 0xFF, 0xFF], 0; "Empty")]
+    #[test_case([0x05, 0x02, 0x03, 0xA1, 0x01, 0x11], 1; "Single")]
+    #[test_case([0x05, 0x02, 0x03, 0xA1, 0x05, 0x11], 0; "Overrun")]
+    #[test_case([0x05, 0x02, 0x03, 0xA1, 0x00, 0xB2], 1; "Truncated header")]
+    fn transport_private_data_parsed_edges(buf: [u8; 6], expected: usize) {
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+        assert_eq!(af.transport_private_data_parsed().len(), expected,
+            "Number of private data fields is incorrect");
+    }
+
+    #[test]
+    fn display() {
+        // PCR and OPCR flags set.
+        let buf = [0x0D, 0x18, 0x00, 0x00, 0x2A, 0x30, 0x7E, 0x96, 0x00, 0x00, 0x00, 0x00,
+            0x7E, 0x01];
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+
+        let display = af.to_string();
+        assert!(display.contains("PCR Flag: true"), "PCR flag is missing: {}", display);
+        assert!(display.contains(&format!("\nPCR: {}", af.pcr().unwrap())),
+            "PCR is missing: {}", display);
+        assert!(display.contains("\nOPCR: 1"), "OPCR is missing: {}", display);
+    }
+
+    #[test]
+    fn from_bytes_adaptation_extension() {
+        // Adaptation field extension flag set with a seamless splice extension.
+        let buf = [0x08, 0x01, 0x06, 0x20, 0xA9, 0x8D, 0x15, 0xCF, 0x13];
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+
+        let extension = af.adaptation_extension().expect("Adaptation extension is missing");
+        assert_eq!(extension.splice_type(), Some(0xA), "Splice type is incorrect");
+        assert_eq!(extension.dts_next_au(), Some(0x123456789), "DTS of next AU is incorrect");
+    }
+
+    // Each PCR is a 33 bit base, 6 reserved bits that are all set, and a 9 bit extension.
+    #[test_case([0x00, 0x00, 0x00, 0x00, 0x7E, 0x00], 0; "Zero")]
+    #[test_case([0x00, 0x00, 0x00, 0x00, 0xFE, 0x01], 301; "Base and extension of 1")]
+    #[test_case([0x91, 0xA2, 0xB3, 0xC4, 0xFE, 0xAB], 0x1_2345_6789 * 300 + 0xAB; "Mixed bits")]
+    #[test_case([0x00, 0x00, 0xAF, 0xC8, 0x7E, 0x96], 27_000_150; "One second")]
+    #[test_case([0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x2B], PCR_WRAP - 1; "Largest")]
+    fn from_bytes_pcr(pcr: [u8; PCR_SIZE as usize], expected: u64) {
+        let mut buf = vec![0x07, 0x10];
+        buf.extend_from_slice(&pcr);
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+
+        assert_eq!(af.pcr(), Some(expected), "PCR is incorrect");
+        assert_eq!(DataAdaptationField::write_pcr_data(expected), pcr, "Written PCR is incorrect");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn pcr_timestamp() {
+        let buf = [0x07, 0x10, 0x00, 0x00, 0xAF, 0xC8, 0x7E, 0x96];
+        let pcr = DataAdaptationField::from_bytes(&buf).unwrap().pcr_timestamp().unwrap();
+
+        assert_eq!(pcr.as_27mhz(), 27_000_150, "27MHz PCR is incorrect");
+        assert_eq!(pcr.as_90khz(), 90_000, "90kHz PCR is incorrect");
+        assert_eq!(pcr.as_duration(), Duration::new(1, 5_555), "PCR duration is incorrect");
+    }
+
+    #[test]
+    fn opcr_timestamp() {
+        let buf = [
+            0x0D, 0x18,
+            0x00, 0x00, 0xAF, 0xC8, 0x7E, 0x96, // PCR
+            0x00, 0x01, 0x5F, 0x90, 0x7E, 0x01, // OPCR
+        ];
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+        let pcr = af.pcr_timestamp().unwrap();
+        let opcr = af.opcr_timestamp().unwrap();
+
+        assert_eq!(pcr.as_27mhz(), 27_000_150, "27MHz PCR is incorrect");
+        assert_eq!(opcr.as_27mhz(), 54_000_001, "27MHz OPCR is incorrect");
+        assert_eq!(opcr.as_90khz(), 180_000, "90kHz OPCR is incorrect");
+        assert_eq!(opcr.as_duration(), Duration::new(2, 37), "OPCR duration is incorrect");
+    }
+
+    // All 8 bits of the byte after the adaptation field length are flags. There is no reserved bit
+    // in this byte, so every flag has to line up with exactly one bit.
+    #[test_case(vec![0x01, 0x00], false; "No flags set")]
+    #[test_case(vec![
+        0x11, 0xFF,
+        0x00, 0x00, 0x00, 0x00, 0x7E, 0x00, // PCR
+        0x00, 0x00, 0x00, 0x00, 0x7E, 0x00, // OPCR
+        0x00, // Splice countdown
+        0x00, // Transport private data length
+        0x01, 0x00, // Adaptation extension
+    ], true; "All flags set")]
+    fn from_bytes_flags(buf: Vec<u8>, expected: bool) {
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+
+        assert_eq!(af.discontinuity_indicator(), expected, "Discontinuity indicator is incorrect");
+        assert_eq!(af.random_access_indicator(), expected, "Random access indicator is incorrect");
+        assert_eq!(af.elementary_stream_priority_indicator(), expected,
+            "Elementary stream priority indicator is incorrect");
+        assert_eq!(af.pcr_flag(), expected, "PCR flag is incorrect");
+        assert_eq!(af.opcr_flag(), expected, "OPCR flag is incorrect");
+        assert_eq!(af.splicing_point_flag(), expected, "Splicing point flag is incorrect");
+        assert_eq!(af.transport_private_data_flag(), expected,
+            "Transport private data flag is incorrect");
+        assert_eq!(af.has_adaptation_extension_field(), expected,
+            "Adaptation extension flag is incorrect");
+        assert_eq!(af.adaptation_extension().is_some(), expected,
+            "Adaptation extension is incorrect");
+    }
+
+    #[test_case(&[0x06, 0x06, 0xFD, 0x03, 0xAA, 0xBB, 0xCC], 6, 0; "No stuffing")]
+    #[test_case(&[0x07, 0x02, 0x01, 0xAA, 0xFF, 0xFF, 0xFF, 0xFF], 3, 4; "Stuffing")]
+    #[test_case(&[0x01, 0x00], 1, 0; "Flags only")]
+    fn content_length(buf: &[u8], content_length: usize, stuffing_bytes: usize) {
+        let af = DataAdaptationField::from_bytes(buf).unwrap();
+        assert_eq!(af.content_length(), content_length, "Content length is incorrect");
+        assert_eq!(af.stuffing_bytes(), stuffing_bytes, "Stuffing bytes are incorrect");
+    }
+
+    #[test]
+    fn to_bytes_unchanged() {
+        let buf = [0x06, 0x06, 0xFD, 0x03, 0xAA, 0xBB, 0xCC];
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+
+        // The adaptation field fills everything but the header and the payload.
+        let payload_length = PACKET_SIZE - HEADER_SIZE as usize - buf.len();
+        assert_eq!(&*af.to_bytes(payload_length).unwrap(), &buf,
+            "Serialized adaptation field is incorrect");
+    }
+
+    #[test]
+    fn to_bytes_after_mutation() {
+        let buf = [0x06, 0x06, 0xFD, 0x03, 0xAA, 0xBB, 0xCC];
+        let mut af = DataAdaptationField::from_bytes(&buf).unwrap();
+        af.set_transport_private_data(None).unwrap();
+        af.set_random_access_indicator(true);
+
+        // Removing the private data leaves room for a larger payload.
+        let payload_length = 180;
+        af.recompute_length(payload_length).unwrap();
+        assert_eq!(af.adaptation_field_length(), 3, "Adaptation field length is incorrect");
+
+        let serialized = af.to_bytes(payload_length).unwrap();
+        assert_eq!(&*serialized, &[0x03, 0x44, 0xFD, 0xFF],
+            "Serialized adaptation field is incorrect");
+
+        let parsed = DataAdaptationField::from_bytes(&serialized).unwrap();
+        assert_eq!(parsed.adaptation_field_length(), 3, "Adaptation field length is incorrect");
+        assert!(parsed.random_access_indicator(), "Random access indicator is incorrect");
+        assert_eq!(parsed.splice_countdown(), Some(-3), "Splice countdown is incorrect");
+        assert_eq!(parsed.transport_private_data(), None, "Transport private data is incorrect");
+    }
+
+    #[test]
+    fn to_bytes_payload_too_large() {
+        let buf = [0x06, 0x06, 0xFD, 0x03, 0xAA, 0xBB, 0xCC];
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+        assert!(af.to_bytes(180).is_err(), "Adaptation field was serialized without enough room");
+    }
+
+    #[test]
+    fn raw_bits() {
+        let buf = [0x06, 0x06, 0xFD, 0x03, 0xAA, 0xBB, 0xCC, 0x00, 0x00];
+        let af = DataAdaptationField::from_bytes(&buf).unwrap();
+        assert_eq!(af.raw_bits(8, 8), Some(0x06), "Flags are incorrect");
+        assert_eq!(af.raw_bits(32, 24), Some(0xAABBCC), "Private data bits are incorrect");
+        // Bytes after the end of the adaptation field aren't included.
+        assert_eq!(af.raw_bits(56, 8), None, "Bits past the end were returned");
+    }
+
+    #[test_case(Some(27_000_000), Some(27_090_000), Some(90_000); "OPCR ahead")]
+    #[test_case(Some(27_090_000), Some(27_000_000), Some(-90_000); "OPCR behind")]
+    #[test_case(Some(PCR_WRAP - 300), Some(300), Some(600); "Wrapped")]
+    #[test_case(Some(27_000_000), None, None; "No OPCR")]
+    #[test_case(None, Some(27_000_000), None; "No PCR")]
+    fn opcr_pcr_offset(pcr: Option<u64>, opcr: Option<u64>, expected: Option<i64>) {
+        let af = DataAdaptationField::new(13, false, false, false, pcr.is_some(), opcr.is_some(),
+            false, false, false, pcr, opcr, None, None, None, None);
+        assert_eq!(af.opcr_pcr_offset(), expected, "OPCR to PCR offset is incorrect");
+    }
+}
@@ -1,26 +1,37 @@
 //! This module keeps track of all the information stored in the header of a
 //! transport stream packet.
 
+use alloc::boxed::Box;
+use alloc::format;
 use crate::errors::invalid_first_byte::InvalidFirstByte;
+use crate::errors::invalid_header_field::InvalidHeaderField;
+use crate::errors::not_enough_data::NotEnoughData;
+use crate::packet::{self, HEADER_SIZE};
 use crate::AdaptationFieldControl::{AdaptationAndPayload, AdaptationField, Payload};
 use crate::TransportScramblingControl::{EvenKey, NoScrambling, OddKey};
 use crate::{AdaptationFieldControl, TransportScramblingControl};
-use std::error::Error;
-use std::fmt::{Display, Formatter};
-use bitvec::macros::internal::funty::Fundamental;
+use core::error::Error;
+use core::fmt::{Display, Formatter};
 use bitvec::field::BitField;
 use bitvec::order::Msb0;
 use bitvec::vec::BitVec;
+use bitvec::view::BitView;
 #[cfg(feature = "log")]
 use log::trace;
 
 /// All transport stream packets start with a SYNC byte.
 pub const SYNC_BYTE: u8 = 0x47;
 
+/// PIDs are 13 bits, so this is the largest PID that a header can hold.
+pub const MAX_PID: u16 = 0x1FFF;
+
+/// Continuity counters are 4 bits, so they wrap back to `0` at this value.
+pub const CONTINUITY_COUNTER_MODULUS: u8 = 16;
+
 /// All of this information is shamelessly stolen from wikipedia, my lord and savior.
 /// This [article](https://en.wikipedia.org/wiki/MPEG_transport_stream) in particular. Please donate
 /// to wikipedia if you have the means.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct TSHeader {
     /// TEI: Transport error indicator is true when a packet is set when a demodulator cannot
     /// correct invalid_first_byte and indicates that the packet is corrupt.
@@ -49,10 +60,14 @@ pub struct TSHeader {
     adaptation_field_control: AdaptationFieldControl,
     /// Continuity counter is used for determining the sequence of data in each PID.
     continuity_counter: u8,
+    /// The raw bytes of the header.
+    raw: [u8; HEADER_SIZE as usize],
 }
 
 impl TSHeader {
     /// Create a new header
+    ///
+    /// Returns an error if a value doesn't fit in its field of the header.
     pub fn new(
         tei: bool,
         pusi: bool,
@@ -61,7 +76,7 @@ impl TSHeader {
         tsc: u8,
         adaptation_field_control: u8,
         continuity_counter: u8,
-    ) -> Self {
+    ) -> Result<Self, Box<dyn Error>> {
         #[cfg(feature = "log")]
         {
             trace!("pid: [{}]", pid);
@@ -69,68 +84,77 @@ impl TSHeader {
             trace!("continuity_counter: [{}]", continuity_counter);
         }
 
-        TSHeader {
+        if pid > MAX_PID {
+            return Err(Box::new(InvalidHeaderField { field: "pid", value: pid }))
+        }
+
+        if continuity_counter >= CONTINUITY_COUNTER_MODULUS {
+            return Err(Box::new(InvalidHeaderField {
+                field: "continuity_counter",
+                value: continuity_counter as u16,
+            }))
+        }
+
+        let scrambling = scrambling_control(tsc)?;
+        let field = field_control(adaptation_field_control)?;
+
+        let mut raw = [SYNC_BYTE, 0, 0, 0];
+        let bits = raw.view_bits_mut::<Msb0>();
+        bits.set(8, tei);
+        bits.set(9, pusi);
+        bits.set(10, transport_priority);
+        bits[11..24].store_be(pid);
+        bits[24..26].store_be(tsc);
+        bits[26..28].store_be(adaptation_field_control);
+        bits[28..32].store_be(continuity_counter);
+
+        Ok(TSHeader {
             tei,
             pusi,
             transport_priority,
             pid,
-            tsc: match tsc {
-                0 => NoScrambling,
-                1 => TransportScramblingControl::Reserved,
-                2 => EvenKey,
-                3 => OddKey,
-                _ => panic!("Invalid TSC value [{}]", tsc),
-            },
-            adaptation_field_control: match adaptation_field_control {
-                0 => AdaptationFieldControl::Reserved,
-                1 => Payload,
-                2 => AdaptationField,
-                3 => AdaptationAndPayload,
-                _ => panic!(
-                    "Invalid adaptation field control value [{}]",
-                    adaptation_field_control
-                ),
-            },
+            tsc: scrambling,
+            adaptation_field_control: field,
             continuity_counter,
-        }
+            raw,
+        })
+    }
+
+    /// Return a builder for a header, which is easier to read than the positional arguments of
+    /// [`TSHeader::new`].
+    pub fn builder() -> TSHeaderBuilder {
+        TSHeaderBuilder::default()
     }
 
     /// Get the packet header from raw bytes.
-    pub fn from_bytes(buf: &Box<[u8]>) -> Result<TSHeader, Box<dyn Error>> {
-        let bytes: BitVec<u8, Msb0> = BitVec::from_slice(buf).to_bitvec();
+    pub fn from_bytes(buf: &[u8]) -> Result<TSHeader, Box<dyn Error>> {
+        if buf.len() < HEADER_SIZE as usize {
+            return Err(Box::new(NotEnoughData { expected: HEADER_SIZE as usize, found: buf.len() }))
+        }
+
+        let bytes: BitVec<u8, Msb0> = BitVec::from_slice(&buf[..HEADER_SIZE as usize]);
 
         // Check if the first byte is SYNC byte.
-        if bytes[0..8].load::<u8>() != SYNC_BYTE {
+        if bytes[0..8].load_be::<u8>() != SYNC_BYTE {
             return Err(Box::new(InvalidFirstByte { byte: buf[0] }));
         }
 
         #[cfg(feature = "log")]
         trace!("header bytes: {:b}", bytes);
 
-        // Get the header information from the header bytes
+        // Get the header information from the header bytes. After the 8 bits of the SYNC byte come
+        // TEI, PUSI, and transport priority at bits 8, 9, and 10, the 13 bit PID at bits 11 to 23,
+        // the 2 bit TSC at bits 24 and 25, the 2 bit adaptation field control at bits 26 and 27,
+        // and the 4 bit continuity counter at bits 28 to 31.
         let header = TSHeader {
             tei: bytes[8],
             pusi: bytes[9],
             transport_priority: bytes[10],
             pid: bytes[11..24].to_bitvec().load_be(),
-            tsc: match bytes[24..26].to_bitvec().load_be() {
-                0 => NoScrambling,
-                1 => TransportScramblingControl::Reserved,
-                2 => EvenKey,
-                3 => OddKey,
-                default => panic!("Invalid TSC value [{}]", default),
-            },
-            adaptation_field_control: match bytes[26..28].to_bitvec().load_be::<u8>() {
-                0 => AdaptationFieldControl::Reserved,
-                1 => Payload,
-                2 => AdaptationField,
-                3 => AdaptationAndPayload,
-                default => panic!(
-                    "Invalid adaptation field control value [{}]",
-                    default
-                ),
-            },
+            tsc: scrambling_control(bytes[24..26].to_bitvec().load_be::<u8>())?,
+            adaptation_field_control: field_control(bytes[26..28].to_bitvec().load_be::<u8>())?,
             continuity_counter: bytes[28..32].load_be(),
+            raw: buf[..HEADER_SIZE as usize].try_into()?,
         };
 
         #[cfg(feature = "log")]
@@ -171,18 +195,12 @@ impl TSHeader {
 
     /// Return whether this packet has an adaptation field or not
     pub fn has_adaptation_field(&self) -> bool {
-        match self.adaptation_field_control {
-            AdaptationField | AdaptationAndPayload => true,
-            _ => false
-        }
+        matches!(self.adaptation_field_control, AdaptationField | AdaptationAndPayload)
     }
 
     /// Return whether this packet has a payload or not
     pub fn has_payload(&self) -> bool {
-        match self.adaptation_field_control {
-            Payload | AdaptationAndPayload => true,
-            _ => false
-        }
+        matches!(self.adaptation_field_control, Payload | AdaptationAndPayload)
     }
 
     /// Returns the continuity counter.
@@ -190,11 +208,34 @@ impl TSHeader {
         self.continuity_counter
     }
 
-    
+    /// Return an arbitrary range of bits from the raw header bytes as a big-endian value.
+    ///
+    /// This is an escape hatch for inspecting bits that aren't exposed by any other accessor.
+    /// Bits are numbered starting at `0` for the most significant bit of the SYNC byte (MSB0), so
+    /// `raw_bits(11, 13)` returns the PID.
+    ///
+    /// Returns `None` if `len` is `0` or more than [`packet::MAX_RAW_BITS`], or if the range
+    /// extends past the end of the header.
+    pub fn raw_bits(&self, start: usize, len: usize) -> Option<u32> {
+        packet::raw_bits(&self.raw, start, len)
+    }
+
+    /// Return the raw bytes of the header.
+    pub(crate) fn raw_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Set if the transport error indicator is set.
+    ///
+    /// This updates the raw bytes of the header as well.
+    pub fn set_tei(&mut self, tei: bool) {
+        self.tei = tei;
+        self.raw.view_bits_mut::<Msb0>().set(8, tei);
+    }
 }
 
 impl Display for TSHeader {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         let msg = format!("\n\
             TEI: {}\n\
             PUSI: {}\n\
@@ -215,6 +256,135 @@ impl Display for TSHeader {
     }
 }
 
+/// Builder for a [`TSHeader`], created with [`TSHeader::builder`].
+///
+/// Every field starts out cleared or `0`, except for the adaptation field control which starts out
+/// as [`AdaptationFieldControl::Payload`]. The values are validated by [`TSHeaderBuilder::build`].
+///
+/// # Example
+///
+/// ```
+/// use ts_analyzer::packet::header::TSHeader;
+///
+/// let header = TSHeader::builder().pid(0x100).pusi(true).continuity_counter(3).build().unwrap();
+/// assert_eq!(header.pid(), 0x100);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct TSHeaderBuilder {
+    /// Transport error indicator of the header.
+    tei: bool,
+    /// Payload unit start indicator of the header.
+    pusi: bool,
+    /// Transport priority of the header.
+    transport_priority: bool,
+    /// Packet identifier of the header.
+    pid: u16,
+    /// Transport scrambling control of the header.
+    tsc: TransportScramblingControl,
+    /// Adaptation field control of the header.
+    adaptation_field_control: AdaptationFieldControl,
+    /// Continuity counter of the header.
+    continuity_counter: u8,
+}
+
+impl Default for TSHeaderBuilder {
+    fn default() -> Self {
+        TSHeaderBuilder {
+            tei: false,
+            pusi: false,
+            transport_priority: false,
+            pid: 0,
+            tsc: NoScrambling,
+            adaptation_field_control: Payload,
+            continuity_counter: 0,
+        }
+    }
+}
+
+impl TSHeaderBuilder {
+    /// Set the transport error indicator.
+    pub fn tei(mut self, tei: bool) -> Self {
+        self.tei = tei;
+        self
+    }
+
+    /// Set the payload unit start indicator.
+    pub fn pusi(mut self, pusi: bool) -> Self {
+        self.pusi = pusi;
+        self
+    }
+
+    /// Set the transport priority.
+    pub fn transport_priority(mut self, transport_priority: bool) -> Self {
+        self.transport_priority = transport_priority;
+        self
+    }
+
+    /// Set the PID. It has to be at most [`MAX_PID`].
+    pub fn pid(mut self, pid: u16) -> Self {
+        self.pid = pid;
+        self
+    }
+
+    /// Set the transport scrambling control.
+    pub fn tsc(mut self, tsc: TransportScramblingControl) -> Self {
+        self.tsc = tsc;
+        self
+    }
+
+    /// Set the adaptation field control.
+    pub fn adaptation_field_control(
+        mut self,
+        adaptation_field_control: AdaptationFieldControl,
+    ) -> Self {
+        self.adaptation_field_control = adaptation_field_control;
+        self
+    }
+
+    /// Set the continuity counter. It has to be less than [`CONTINUITY_COUNTER_MODULUS`].
+    pub fn continuity_counter(mut self, continuity_counter: u8) -> Self {
+        self.continuity_counter = continuity_counter;
+        self
+    }
+
+    /// Create the header.
+    ///
+    /// Returns an error if a value doesn't fit in its field of the header.
+    pub fn build(self) -> Result<TSHeader, Box<dyn Error>> {
+        TSHeader::new(
+            self.tei,
+            self.pusi,
+            self.transport_priority,
+            self.pid,
+            self.tsc as u8,
+            self.adaptation_field_control as u8,
+            self.continuity_counter,
+        )
+    }
+}
+
+/// Return the transport scrambling control for the 2 bit value found in a header.
+fn scrambling_control(value: u8) -> Result<TransportScramblingControl, InvalidHeaderField> {
+    match value {
+        0 => Ok(NoScrambling),
+        1 => Ok(TransportScramblingControl::Reserved),
+        2 => Ok(EvenKey),
+        3 => Ok(OddKey),
+        _ => Err(InvalidHeaderField { field: "tsc", value: value as u16 }),
+    }
+}
+
+/// Return the adaptation field control for the 2 bit value found in a header.
+fn field_control(value: u8) -> Result<AdaptationFieldControl, InvalidHeaderField> {
+    match value {
+        0 => Ok(AdaptationFieldControl::Reserved),
+        1 => Ok(Payload),
+        2 => Ok(AdaptationField),
+        3 => Ok(AdaptationAndPayload),
+        _ => Err(InvalidHeaderField { field: "adaptation_field_control", value: value as u16 }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -224,9 +394,9 @@ mod tests {
     fn from_bytes() {
         let buf: Box<[u8]> = Box::new([0x47, 0x01, 0x00, 0x1A]);
         let header = TSHeader::from_bytes(&buf).unwrap();
-        assert_eq!(header.tei(), false, "Transport Error Indicator is incorrect");
-        assert_eq!(header.pusi(), false, "Payload Unit Start Indicator is incorrect");
-        assert_eq!(header.transport_priority(), false, "Transport Priority is incorrect");
+        assert!(!header.tei(), "Transport Error Indicator is incorrect");
+        assert!(!header.pusi(), "Payload Unit Start Indicator is incorrect");
+        assert!(!header.transport_priority(), "Transport Priority is incorrect");
         assert_eq!(header.pid(), 256, "Transport Priority is incorrect");
         assert_eq!(header.adaptation_field_control(), Payload, "Transport Priority is incorrect");
         assert_eq!(header.continuity_counter(), 10, "Transport Priority is incorrect");
@@ -236,11 +406,160 @@ mod tests {
     fn from_bytes2() {
         let buf: Box<[u8]> = Box::new([0x47, 0xE1, 0x00, 0x3B]);
         let header = TSHeader::from_bytes(&buf).unwrap();
-        assert_eq!(header.tei(), true, "Transport Error Indicator is incorrect");
-        assert_eq!(header.pusi(), true, "Payload Unit Start Indicator is incorrect");
-        assert_eq!(header.transport_priority(), true, "Transport Priority is incorrect");
+        assert!(header.tei(), "Transport Error Indicator is incorrect");
+        assert!(header.pusi(), "Payload Unit Start Indicator is incorrect");
+        assert!(header.transport_priority(), "Transport Priority is incorrect");
         assert_eq!(header.pid(), 256, "Transport Priority is incorrect");
         assert_eq!(header.adaptation_field_control(), AdaptationAndPayload, "Transport Priority is incorrect");
         assert_eq!(header.continuity_counter(), 11, "Transport Priority is incorrect");
     }
-}
\ No newline at end of file
+
+    // Only one flag is set in each case so that reading the wrong bit for a flag is caught. TEI is
+    // the first bit after the SYNC byte, followed by PUSI and then the transport priority.
+    #[test_case(0x81, true, false, false; "TEI")]
+    #[test_case(0x41, false, true, false; "PUSI")]
+    #[test_case(0x21, false, false, true; "Transport priority")]
+    fn from_bytes_flags(byte: u8, tei: bool, pusi: bool, transport_priority: bool) {
+        let buf: Box<[u8]> = Box::new([0x47, byte, 0x00, 0x10]);
+        let header = TSHeader::from_bytes(&buf).unwrap();
+        assert_eq!(header.tei(), tei, "Transport Error Indicator is incorrect");
+        assert_eq!(header.pusi(), pusi, "Payload Unit Start Indicator is incorrect");
+        assert_eq!(header.transport_priority(), transport_priority,
+            "Transport Priority is incorrect");
+        assert_eq!(header.pid(), 0x100, "PID is incorrect");
+    }
+
+    // Every field is set to a value that differs from its neighbours so that a range which is off
+    // by one bit is caught.
+    #[test_case([0x47, 0x40, 0x00, 0x10], false, true, false, 0x0000, NoScrambling, Payload, 0;
+        "PAT")]
+    #[test_case([0x47, 0x1F, 0xFF, 0x10], false, false, false, 0x1FFF, NoScrambling, Payload, 0;
+        "Null packet")]
+    #[test_case([0x47, 0xB0, 0x01, 0xEF], true, false, true, 0x1001, OddKey, AdaptationField, 15;
+        "Alternating flags")]
+    #[test_case([0x47, 0x00, 0x21, 0x65], false, false, false, 0x0021,
+        TransportScramblingControl::Reserved, AdaptationField, 5; "Low PID")]
+    #[allow(clippy::too_many_arguments)]
+    fn from_bytes_layout(
+        buf: [u8; 4],
+        tei: bool,
+        pusi: bool,
+        transport_priority: bool,
+        pid: u16,
+        tsc: TransportScramblingControl,
+        adaptation_field_control: AdaptationFieldControl,
+        continuity_counter: u8,
+    ) {
+        let header = TSHeader::from_bytes(&buf).unwrap();
+        assert_eq!(header.tei(), tei, "Transport Error Indicator is incorrect");
+        assert_eq!(header.pusi(), pusi, "Payload Unit Start Indicator is incorrect");
+        assert_eq!(header.transport_priority(), transport_priority,
+            "Transport Priority is incorrect");
+        assert_eq!(header.pid(), pid, "PID is incorrect");
+        assert_eq!(header.tsc(), tsc, "Transport Scrambling Control is incorrect");
+        assert_eq!(header.adaptation_field_control(), adaptation_field_control,
+            "Adaptation Field Control is incorrect");
+        assert_eq!(header.continuity_counter(), continuity_counter,
+            "Continuity Counter is incorrect");
+    }
+
+    // The PID spans 2 bytes and would be read as a different value if the bytes were loaded in the
+    // wrong order.
+    #[test]
+    fn from_bytes_big_endian_pid() {
+        let buf: Box<[u8]> = Box::new([0x47, 0x12, 0x34, 0x1A]);
+        let header = TSHeader::from_bytes(&buf).unwrap();
+        assert_eq!(header.pid(), 0x1234, "PID is incorrect");
+        assert_eq!(header.adaptation_field_control(), Payload, "Adaptation Field Control is incorrect");
+        assert_eq!(header.continuity_counter(), 0xA, "Continuity Counter is incorrect");
+    }
+
+    #[test_case(0x10, NoScrambling; "No scrambling")]
+    #[test_case(0x50, TransportScramblingControl::Reserved; "Reserved")]
+    #[test_case(0x90, EvenKey; "Even key")]
+    #[test_case(0xD0, OddKey; "Odd key")]
+    fn from_bytes_tsc(last_byte: u8, expected: TransportScramblingControl) {
+        let buf: Box<[u8]> = Box::new([0x47, 0x01, 0x00, last_byte]);
+        let header = TSHeader::from_bytes(&buf).unwrap();
+        assert_eq!(header.tsc(), expected, "Transport Scrambling Control is incorrect");
+    }
+
+    #[test_case(0, 8, Some(0x47); "SYNC byte")]
+    #[test_case(11, 13, Some(0x1234); "PID")]
+    #[test_case(28, 4, Some(0xA); "Continuity counter")]
+    #[test_case(30, 4, None; "Past the end")]
+    #[test_case(0, 0, None; "Empty")]
+    fn raw_bits(start: usize, len: usize, expected: Option<u32>) {
+        let buf: Box<[u8]> = Box::new([0x47, 0x12, 0x34, 0x1A]);
+        let header = TSHeader::from_bytes(&buf).unwrap();
+        assert_eq!(header.raw_bits(start, len), expected, "Raw bits are incorrect");
+    }
+
+    #[test]
+    fn set_tei() {
+        let buf: Box<[u8]> = Box::new([0x47, 0xE1, 0x00, 0x3B]);
+        let mut header = TSHeader::from_bytes(&buf).unwrap();
+        header.set_tei(false);
+
+        assert!(!header.tei(), "Transport Error Indicator is incorrect");
+        assert_eq!(header.raw_bits(0, 32), Some(0x4761003B), "Raw bits are incorrect");
+    }
+
+    #[test]
+    fn new_raw_bits() {
+        let header = TSHeader::new(false, true, false, 0x1234, 0, 1, 0xA).unwrap();
+        assert_eq!(header.raw_bits(0, 32), Some(0x4752341A), "Raw bits are incorrect");
+    }
+
+    #[test_case(0x2000, 0, 1, 0; "PID")]
+    #[test_case(0x100, 4, 1, 0; "TSC")]
+    #[test_case(0x100, 0, 4, 0; "Adaptation field control")]
+    #[test_case(0x100, 0, 1, 16; "Continuity counter")]
+    fn new_invalid_field(pid: u16, tsc: u8, adaptation_field_control: u8, cc: u8) {
+        let header = TSHeader::new(false, false, false, pid, tsc, adaptation_field_control, cc);
+        assert!(header.is_err(), "Header result is incorrect");
+    }
+
+    #[test]
+    fn builder() {
+        let header = TSHeader::builder()
+            .tei(true)
+            .pid(0x1234)
+            .tsc(OddKey)
+            .adaptation_field_control(AdaptationAndPayload)
+            .continuity_counter(9)
+            .build()
+            .unwrap();
+        let expected = TSHeader::new(true, false, false, 0x1234, 3, 3, 9).unwrap();
+        assert_eq!(header, expected, "Header is incorrect");
+    }
+
+    #[test_case(TSHeader::builder().pid(MAX_PID + 1); "PID")]
+    #[test_case(TSHeader::builder().continuity_counter(CONTINUITY_COUNTER_MODULUS); "CC")]
+    fn builder_invalid(builder: TSHeaderBuilder) {
+        assert!(builder.build().is_err(), "Header result is incorrect");
+    }
+
+    #[test]
+    fn from_bytes_short() {
+        assert!(TSHeader::from_bytes(&[SYNC_BYTE, 0x01]).is_err(), "Header result is incorrect");
+    }
+
+    #[test]
+    fn from_bytes_random() {
+        // Xorshift so that the same inputs are used on every run.
+        let mut state: u32 = 0x2545F491;
+        for _ in 0..10_000 {
+            let mut buf = [0u8; packet::PACKET_SIZE];
+            for byte in buf.iter_mut() {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                *byte = state as u8;
+            }
+            buf[0] = SYNC_BYTE;
+
+            assert!(TSHeader::from_bytes(&buf).is_ok(), "Header result is incorrect");
+        }
+    }
+}
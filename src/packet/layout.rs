@@ -0,0 +1,233 @@
+//! Describes how transport stream packets are laid out in the source being read.
+use alloc::boxed::Box;
+use core::error::Error;
+use core::ops::Range;
+
+use crate::packet::header::SYNC_BYTE;
+use crate::packet::{TSPacket, PACKET_SIZE};
+
+/// Size of the arrival timestamp prefix of M2TS packets.
+const ARRIVAL_TIMESTAMP_SIZE: usize = 4;
+
+/// The arrival timestamp is held in the low 30 bits of the M2TS prefix. The top 2 bits are the copy
+/// permission indicator.
+const ARRIVAL_TIMESTAMP_MASK: u32 = 0x3FFF_FFFF;
+
+/// Arrival timestamps count at the same 27MHz as the PCR and wrap back to `0` after this value.
+pub const ARRIVAL_TIMESTAMP_WRAP: u64 = 1 << 30;
+
+/// Number of consecutive packets that must start with a SYNC byte for a source to be detected as a
+/// transport stream by [`TSReader::probe_format`](crate::reader::TSReader::probe_format).
+pub const PROBE_PACKET_COUNT: usize = 5;
+
+/// Number of bytes that are read at a time while searching for a SYNC byte.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) const SYNC_SEARCH_CHUNK_SIZE: u64 = 4096;
+
+/// Packet layouts that [`detect_layout`] checks for, in the order they are checked.
+const PROBE_LAYOUTS: [PacketLayout; 3] = [
+    PacketLayout::TS,
+    PacketLayout::M2TS,
+    PacketLayout::REED_SOLOMON,
+];
+
+/// The byte layout of every packet in a transport stream source.
+///
+/// Some formats wrap each transport stream packet with extra bytes, such as the 4-byte timestamp
+/// prefix of M2TS files or the 16 bytes of Reed-Solomon parity appended by broadcast capture
+/// equipment. A layout describes how many bytes come before (`prefix`) and after (`suffix`) the
+/// transport stream packet itself (`body`).
+///
+/// Only the 188-byte `body` of each packet is parsed as a transport stream packet. The M2TS arrival
+/// timestamp and the Reed-Solomon parity are available from
+/// [`TSPacket::arrival_timestamp`](crate::packet::TSPacket::arrival_timestamp) and
+/// [`TSPacket::fec_bytes`](crate::packet::TSPacket::fec_bytes).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PacketLayout {
+    /// Number of bytes that come before the transport stream packet.
+    prefix: usize,
+    /// Number of bytes in the transport stream packet. This is always `PACKET_SIZE`.
+    body: usize,
+    /// Number of bytes that come after the transport stream packet.
+    suffix: usize,
+}
+
+impl PacketLayout {
+    /// Plain 188-byte transport stream packets.
+    pub const TS: PacketLayout = PacketLayout::new(0, 0);
+
+    /// 192-byte M2TS packets which have a 4-byte timestamp before each transport stream packet.
+    pub const M2TS: PacketLayout = PacketLayout::new(4, 0);
+
+    /// 204-byte packets which have 16 bytes of Reed-Solomon parity after each transport stream
+    /// packet.
+    pub const REED_SOLOMON: PacketLayout = PacketLayout::new(0, 16);
+
+    /// Create a new packet layout.
+    /// # Parameters
+    /// - `prefix`: number of bytes that come before each transport stream packet.
+    /// - `suffix`: number of bytes that come after each transport stream packet.
+    pub const fn new(prefix: usize, suffix: usize) -> Self {
+        PacketLayout {
+            prefix,
+            body: PACKET_SIZE,
+            suffix,
+        }
+    }
+
+    /// Return the number of bytes that come before each transport stream packet.
+    pub fn prefix(&self) -> usize {
+        self.prefix
+    }
+
+    /// Return the number of bytes in each transport stream packet.
+    pub fn body(&self) -> usize {
+        self.body
+    }
+
+    /// Return the number of bytes that come after each transport stream packet.
+    pub fn suffix(&self) -> usize {
+        self.suffix
+    }
+
+    /// Return the total number of bytes each packet takes up in the source.
+    pub fn stride(&self) -> usize {
+        self.prefix + self.body + self.suffix
+    }
+
+    /// Return the range of bytes within a packet that make up the transport stream packet.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn body_range(&self) -> Range<usize> {
+        self.prefix..self.prefix + self.body
+    }
+
+    /// Return the range of bytes within a packet that come after the transport stream packet.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn suffix_range(&self) -> Range<usize> {
+        self.prefix + self.body..self.stride()
+    }
+
+    /// Return the arrival timestamp from the prefix of a packet in this layout.
+    ///
+    /// Returns `None` unless this is the [`PacketLayout::M2TS`] layout. A custom layout that also
+    /// has a 4-byte prefix may hold anything there, so its prefix isn't read as a timestamp.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn arrival_timestamp(&self, packet: &[u8]) -> Option<u32> {
+        if *self != PacketLayout::M2TS {
+            return None
+        }
+
+        let prefix = packet.get(..ARRIVAL_TIMESTAMP_SIZE)?;
+        Some(u32::from_be_bytes(prefix.try_into().ok()?) & ARRIVAL_TIMESTAMP_MASK)
+    }
+}
+
+impl Default for PacketLayout {
+    fn default() -> Self {
+        PacketLayout::TS
+    }
+}
+
+/// Return the number of bytes that [`detect_layout`] needs to check every probed layout.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn probe_buffer_len() -> usize {
+    let max_stride = PROBE_LAYOUTS.iter().map(|layout| layout.stride()).max().unwrap_or_default();
+    max_stride * (PROBE_PACKET_COUNT + 1)
+}
+
+/// Return the layout of the packets at the start of `buf`, if SYNC bytes recur at a consistent
+/// stride over the first [`PROBE_PACKET_COUNT`] packets of any of the probed layouts.
+pub(crate) fn detect_layout(buf: &[u8]) -> Option<PacketLayout> {
+    PROBE_LAYOUTS.into_iter().find(|layout| {
+        let stride = layout.stride();
+        let needed = stride * (PROBE_PACKET_COUNT - 1);
+
+        // The first SYNC byte has to be within the first packet.
+        (0..stride)
+            .take_while(|sync_idx| sync_idx + needed < buf.len())
+            .any(|sync_idx| (0..PROBE_PACKET_COUNT)
+                .all(|packet| buf[sync_idx + packet * stride] == SYNC_BYTE))
+    })
+}
+
+/// Search `buf` for the first SYNC byte at or after `start` that has another SYNC byte 1 stride
+/// after it.
+///
+/// A single SYNC byte could simply be payload data that matches by coincidence, so the byte 1
+/// stride away has to be a SYNC byte as well. There is always the possibility that we hit a `0x47`
+/// in the payload, look 1 stride further, and find another `0x47` but I don't have a way of
+/// accounting for that, so we're going with blind hope that this case doesn't get seen.
+/// # Returns
+/// `Ok(usize)` with the index of the SYNC byte.
+/// `Err(usize)` with the index to continue searching from once more bytes have been added to
+/// `buf`.
+pub(crate) fn find_sync(buf: &[u8], start: usize, stride: usize) -> Result<usize, usize> {
+    let mut idx = start;
+    while idx + stride < buf.len() {
+        if buf[idx] == SYNC_BYTE && buf[idx + stride] == SYNC_BYTE {
+            return Ok(idx);
+        }
+        idx += 1;
+    }
+    Err(idx)
+}
+
+/// Return the byte offset of the first complete packet given the offset of the first SYNC byte.
+///
+/// Packets start `prefix` bytes before the SYNC byte. If the first SYNC byte is too close to the
+/// start of the file for a full prefix to exist then that packet is incomplete, and we start at the
+/// next one instead.
+pub(crate) fn packet_start(sync_offset: u64, layout: PacketLayout) -> u64 {
+    let prefix = layout.prefix() as u64;
+    if sync_offset >= prefix {
+        sync_offset - prefix
+    } else {
+        sync_offset + (layout.stride() as u64 - prefix)
+    }
+}
+
+/// Parse a packet from the bytes of 1 stride of the file, including any prefix and suffix of the
+/// layout.
+pub(crate) fn parse_packet(
+    packet_buf: &[u8],
+    layout: PacketLayout,
+    offset: u64,
+) -> Result<TSPacket, Box<dyn Error>> {
+    let mut packet = TSPacket::from_bytes(&packet_buf[layout.body_range()])?;
+    packet.set_arrival_timestamp(layout.arrival_timestamp(packet_buf));
+    packet.set_file_offset(offset);
+    if layout.suffix() > 0 {
+        packet.set_fec_bytes(Some(Box::from(&packet_buf[layout.suffix_range()])));
+    }
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(PacketLayout::TS, 188; "Transport stream")]
+    #[test_case(PacketLayout::M2TS, 192; "M2TS")]
+    #[test_case(PacketLayout::REED_SOLOMON, 204; "Reed-Solomon")]
+    #[test_case(PacketLayout::new(8, 12), 208; "Custom")]
+    fn stride(layout: PacketLayout, expected: usize) {
+        assert_eq!(layout.stride(), expected, "Stride is incorrect");
+    }
+
+    #[test]
+    fn body_range() {
+        let layout = PacketLayout::new(4, 16);
+        assert_eq!(layout.body_range(), 4..192, "Body range is incorrect");
+        assert_eq!(layout.suffix_range(), 192..208, "Suffix range is incorrect");
+        assert_eq!(layout.body(), PACKET_SIZE, "Body is not a full transport stream packet");
+    }
+
+    #[test_case(PacketLayout::M2TS, &[0xC1, 0x23, 0x45, 0x67, 0x47], Some(0x0123_4567); "M2TS")]
+    #[test_case(PacketLayout::TS, &[0xC1, 0x23, 0x45, 0x67, 0x47], None; "Transport stream")]
+    #[test_case(PacketLayout::new(4, 16), &[0xC1, 0x23, 0x45, 0x67, 0x47], None;
+        "Custom layout with a 4-byte prefix")]
+    fn arrival_timestamp(layout: PacketLayout, packet: &[u8], expected: Option<u32>) {
+        assert_eq!(layout.arrival_timestamp(packet), expected, "Arrival timestamp is incorrect");
+    }
+}
@@ -1,10 +1,12 @@
 //! TSPayload keeps track of the payload data.
 
-use std::error::Error;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::error::Error;
 
 use crate::errors::payload_is_not_start::PayloadIsNotStart;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 /// Payload of a transport stream object.
 pub struct TSPayload {
     /// The raw bytes contained in the payload (excluding the Payload Pointer if one exists)
@@ -44,10 +46,26 @@ impl TSPayload {
     }
 
     /// Return a reference to the raw data stored in the payload.
-    pub fn data(&self) -> &Box<[u8]> {
+    pub fn data(&self) -> &[u8] {
         &self.data
     }
 
+    /// Return the payload bytes exactly as they appeared in the packet.
+    ///
+    /// Unlike `data()` this includes the payload pointer when the `PUSI` flag is set. Only PSI
+    /// sections use a payload pointer, so this is the data that should be used when the payload
+    /// carries the start of a PES packet instead.
+    pub fn raw_data(&self) -> Box<[u8]> {
+        let Some(index) = self.start_index else {
+            return self.data.clone()
+        };
+
+        let mut raw_data = Vec::with_capacity(self.data.len() + 1);
+        raw_data.push(index);
+        raw_data.extend_from_slice(&self.data);
+        raw_data.into_boxed_slice()
+    }
+
     /// Return the continuity counter of this payload.
     pub fn continuity_counter(&self) -> u8 {
         self.continuity_counter
@@ -71,7 +89,7 @@ impl TSPayload {
             return Box::from(self.data[0..index as usize].to_vec())
         }
 
-        return self.data.clone();
+        self.data.clone()
     }
 
     /// Returns the new payload data. This is the data after the start index, if one exists.
@@ -84,10 +102,53 @@ impl TSPayload {
     }
 }
 
+/// A payload that has been reassembled from the packets of a PID.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompletedPayload {
+    /// PID that the payload was carried on.
+    pid: u16,
+    /// Byte offset in the file of the packet with the `PUSI` set that started this payload.
+    offset: u64,
+    /// Reassembled payload data.
+    data: Box<[u8]>,
+}
+
+impl CompletedPayload {
+    /// Create a new completed payload.
+    pub(crate) fn new(pid: u16, offset: u64, data: Box<[u8]>) -> Self {
+        CompletedPayload {
+            pid,
+            offset,
+            data,
+        }
+    }
+
+    /// Return the PID that the payload was carried on.
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Return the byte offset in the file of the packet that started this payload.
+    ///
+    /// This is the offset of the start of the packet, including any prefix bytes in the packet
+    /// layout.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the reassembled payload data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consume the completed payload and return its data.
+    pub fn into_data(self) -> Box<[u8]> {
+        self.data
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::any::Any;
-
     use super::*;
     use test_case::test_case;
 
@@ -106,6 +167,14 @@ mod tests {
         assert_eq!(payload.continuity_counter(), continuity_counter, "Continuity counter is not the same");
     }
 
+    #[test_case(true; "Payload contains start")]
+    #[test_case(false; "Payload does not contain start")]
+    fn raw_data(pusi: bool) {
+        let raw_data = [0, 0, 1, 0xE0, 4];
+        let payload = TSPayload::from_bytes(pusi, 0, Box::new(raw_data));
+        assert!(payload.raw_data().iter().eq(raw_data.iter()), "Raw data is not the same");
+    }
+
     #[test_case(true; "Payload contains start")]
     #[test_case(false; "Payload does not contain start")]
     fn is_start(is_start: bool) {
@@ -148,7 +217,10 @@ mod tests {
 
         match payload.get_start_data() {
             Ok(data) => assert!(data.iter().eq(expected_data.unwrap().iter()), "Start data is incorrect"),
-            Err(data) => assert_eq!(data.type_id(), expected_data.unwrap_err().type_id(), "Incorrect error type"),
+            Err(data) => {
+                assert!(expected_data.is_err(), "Unexpected error: {}", data);
+                assert!(data.is::<PayloadIsNotStart>(), "Incorrect error type");
+            },
         };
     }
 }
\ No newline at end of file
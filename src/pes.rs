@@ -0,0 +1,273 @@
+//! Packetized Elementary Stream (PES) packets are how audio, video, and most metadata are carried
+//! in the payloads of transport stream packets.
+use alloc::boxed::Box;
+use core::error::Error;
+
+use bitvec::prelude::*;
+
+use crate::errors::invalid_pes_start_code::InvalidPesStartCode;
+use crate::errors::not_enough_data::NotEnoughData;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+/// Frequency of the PTS and DTS clock in Hz.
+pub const PTS_FREQUENCY: u64 = 90_000;
+
+/// PTS and DTS values are 33 bits, so they wrap back to `0` at this value.
+pub const PTS_WRAP: u64 = 1 << 33;
+
+/// Every PES packet starts with this prefix.
+pub const PES_START_CODE_PREFIX: [u8; 3] = [0x00, 0x00, 0x01];
+
+/// The start code prefix, stream ID, and PES packet length take up the first 6 bytes.
+pub(crate) const PES_HEADER_SIZE: usize = 6;
+
+/// The flags and PES header data length fields of the optional header take up 3 bytes.
+const OPTIONAL_HEADER_SIZE: usize = 3;
+
+/// PTS and DTS fields are 5 bytes in size.
+const TIMESTAMP_SIZE: usize = 5;
+
+/// Stream IDs which are not followed by the optional PES header.
+const STREAM_IDS_WITHOUT_HEADER: [u8; 8] = [
+    0xBC, // Program stream map
+    0xBE, // Padding stream
+    0xBF, // Private stream 2
+    0xF0, // ECM stream
+    0xF1, // EMM stream
+    0xF2, // DSM-CC stream
+    0xF8, // H.222.1 type E stream
+    0xFF, // Program stream directory
+];
+
+/// A PES packet parsed from a reassembled payload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PesPacket {
+    /// Type of elementary stream that this packet belongs to.
+    stream_id: u8,
+    /// Number of bytes in the packet after this field. A value of `0` means that the length is not
+    /// specified, which is allowed for video streams.
+    packet_length: u16,
+    /// Presentation timestamp in 90kHz ticks.
+    ///
+    /// Is `None` if the packet doesn't contain a PTS.
+    pts: Option<u64>,
+    /// Decoding timestamp in 90kHz ticks.
+    ///
+    /// Is `None` if the packet doesn't contain a DTS.
+    dts: Option<u64>,
+    /// Elementary stream data that comes after the PES header.
+    payload: Box<[u8]>,
+}
+
+impl PesPacket {
+    /// Parse a PES packet from bytes that start with the PES start code prefix.
+    ///
+    /// If fewer bytes are given than the PES packet length calls for, the payload contains whatever
+    /// data was available. This allows the header to be parsed from the first transport stream
+    /// packet of a PES packet.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Box<dyn Error>> {
+        #[cfg(feature = "log")]
+        trace!("Parsing PES packet from bytes: {:02X?}", buf);
+
+        if buf.len() < PES_HEADER_SIZE {
+            return Err(Box::new(NotEnoughData { expected: PES_HEADER_SIZE, found: buf.len() }))
+        }
+
+        if buf[0..3] != PES_START_CODE_PREFIX {
+            return Err(Box::new(InvalidPesStartCode { prefix: [buf[0], buf[1], buf[2]] }))
+        }
+
+        let stream_id = buf[3];
+        let packet_length: u16 = buf[4..6].view_bits::<Msb0>().load_be();
+
+        // A length of `0` means the packet continues until the end of the data.
+        let end = match packet_length {
+            0 => buf.len(),
+            length => buf.len().min(PES_HEADER_SIZE + length as usize),
+        };
+
+        if STREAM_IDS_WITHOUT_HEADER.contains(&stream_id) {
+            return Ok(PesPacket {
+                stream_id,
+                packet_length,
+                pts: None,
+                dts: None,
+                payload: Box::from(&buf[PES_HEADER_SIZE..end]),
+            })
+        }
+
+        let header_end = PES_HEADER_SIZE + OPTIONAL_HEADER_SIZE;
+        if buf.len() < header_end {
+            return Err(Box::new(NotEnoughData { expected: header_end, found: buf.len() }))
+        }
+
+        let flags = buf[PES_HEADER_SIZE..header_end].view_bits::<Msb0>();
+        let pts_dts_flags: u8 = flags[8..10].load_be();
+        let header_data_length = buf[header_end - 1] as usize;
+
+        let payload_start = header_end + header_data_length;
+        if buf.len() < payload_start {
+            return Err(Box::new(NotEnoughData { expected: payload_start, found: buf.len() }))
+        }
+
+        // `0b10` means only the PTS is present and `0b11` means both the PTS and DTS are present.
+        let mut read_idx = header_end;
+        let pts = if pts_dts_flags & 0b10 != 0 {
+            Some(read_timestamp(buf, &mut read_idx, payload_start)?)
+        } else {
+            None
+        };
+        let dts = if pts_dts_flags == 0b11 {
+            Some(read_timestamp(buf, &mut read_idx, payload_start)?)
+        } else {
+            None
+        };
+
+        Ok(PesPacket {
+            stream_id,
+            packet_length,
+            pts,
+            dts,
+            payload: Box::from(&buf[payload_start.min(end)..end]),
+        })
+    }
+
+    /// Return the stream ID of this packet.
+    pub fn stream_id(&self) -> u8 {
+        self.stream_id
+    }
+
+    /// Return the PES packet length. A value of `0` means the length was not specified.
+    pub fn packet_length(&self) -> u16 {
+        self.packet_length
+    }
+
+    /// Return the presentation timestamp in 90kHz ticks if one is present.
+    pub fn pts(&self) -> Option<u64> {
+        self.pts
+    }
+
+    /// Return the decoding timestamp in 90kHz ticks if one is present.
+    pub fn dts(&self) -> Option<u64> {
+        self.dts
+    }
+
+    /// Return the elementary stream data that comes after the PES header.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// Return how far a PTS or DTS moved from `from` to `to` in 90kHz ticks, accounting for wrapping.
+///
+/// Timestamps wrap back to `0` after [`PTS_WRAP`], so the shortest distance between them is used.
+/// A negative value means that `to` comes before `from`.
+pub fn pts_difference(from: u64, to: u64) -> i64 {
+    let difference = (to % PTS_WRAP + PTS_WRAP - from % PTS_WRAP) % PTS_WRAP;
+    if difference > PTS_WRAP / 2 {
+        difference as i64 - PTS_WRAP as i64
+    } else {
+        difference as i64
+    }
+}
+
+/// Read a 33-bit PTS or DTS value.
+///
+/// Timestamps are split into 3 pieces of 3, 15, and 15 bits with marker bits between them:
+/// `xxxx TTT1 TTTTTTTT TTTTTTT1 TTTTTTTT TTTTTTT1`.
+pub(crate) fn read_timestamp(buf: &[u8], read_idx: &mut usize, limit: usize) -> Result<u64, Box<dyn Error>> {
+    let end = *read_idx + TIMESTAMP_SIZE;
+    if end > limit {
+        return Err(Box::new(NotEnoughData { expected: end, found: limit }))
+    }
+
+    let bits = buf[*read_idx..end].view_bits::<Msb0>();
+    let high: u64 = bits[4..7].load_be();
+    let middle: u64 = bits[8..23].load_be();
+    let low: u64 = bits[24..39].load_be();
+
+    *read_idx = end;
+
+    Ok(high << 30 | middle << 15 | low)
+}
+
+/// Write a 33-bit PTS or DTS value in the same format that [`read_timestamp`] reads.
+///
+/// The `prefix` fills the 4 bits before the timestamp and the marker bits are all set.
+pub(crate) fn write_timestamp(prefix: u8, timestamp: u64) -> [u8; TIMESTAMP_SIZE] {
+    let mut buf = [0; TIMESTAMP_SIZE];
+    let bits = buf.view_bits_mut::<Msb0>();
+    bits[0..4].store_be(prefix);
+    bits[4..7].store_be((timestamp >> 30) & 0x7);
+    bits.set(7, true);
+    bits[8..23].store_be((timestamp >> 15) & 0x7FFF);
+    bits.set(23, true);
+    bits[24..39].store_be(timestamp & 0x7FFF);
+    bits.set(39, true);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn from_bytes_pts() {
+        // Video PES with a PTS of 0x1_2345_6789.
+        let buf = [
+            0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x80, 0x05, 0x29, 0x8D, 0x15, 0xCF, 0x13,
+            0xAA, 0xBB,
+        ];
+        let pes = PesPacket::from_bytes(&buf).unwrap();
+
+        assert_eq!(pes.stream_id(), 0xE0, "Stream ID is incorrect");
+        assert_eq!(pes.packet_length(), 0, "Packet length is incorrect");
+        assert_eq!(pes.pts(), Some(0x1_2345_6789), "PTS is incorrect");
+        assert_eq!(pes.dts(), None, "DTS should not be present");
+        assert_eq!(pes.payload(), &[0xAA, 0xBB], "Payload is incorrect");
+    }
+
+    #[test]
+    fn from_bytes_pts_dts() {
+        // Video PES with a PTS of 133200 and a DTS of 126000, followed by an H.264 access unit
+        // delimiter, in the layout that FFmpeg writes for frames that are reordered.
+        let buf = [
+            0x00, 0x00, 0x01, 0xE0, 0x00, 0x13, 0x84, 0xC0, 0x0A, 0x31, 0x00, 0x09, 0x10, 0xA1,
+            0x11, 0x00, 0x07, 0xD8, 0x61, 0x00, 0x00, 0x00, 0x01, 0x09, 0xF0, 0xFF, 0xFF,
+        ];
+        let pes = PesPacket::from_bytes(&buf).unwrap();
+
+        assert_eq!(pes.stream_id(), 0xE0, "Stream ID is incorrect");
+        assert_eq!(pes.packet_length(), 0x13, "Packet length is incorrect");
+        assert_eq!(pes.pts(), Some(133_200), "PTS is incorrect");
+        assert_eq!(pes.dts(), Some(126_000), "DTS is incorrect");
+        assert_eq!(pes.payload(), &[0x00, 0x00, 0x00, 0x01, 0x09, 0xF0], "Payload is incorrect");
+    }
+
+    #[test]
+    fn from_bytes_invalid_start_code() {
+        let buf = [0x00, 0x01, 0xFC, 0x01, 0x10, 0x84];
+        let result = PesPacket::from_bytes(&buf);
+        assert!(result.is_err_and(|e| e.is::<InvalidPesStartCode>()), "Start code was not checked");
+    }
+
+    #[test]
+    fn write_timestamp_round_trip() {
+        let buf = write_timestamp(0x2, 0x123456789);
+        assert_eq!(buf, [0x29, 0x8D, 0x15, 0xCF, 0x13], "Timestamp bytes are incorrect");
+
+        let timestamp = read_timestamp(&buf, &mut 0, buf.len()).unwrap();
+        assert_eq!(timestamp, 0x123456789, "Timestamp did not round trip");
+    }
+
+    #[test_case(0, 90_000, 90_000; "Forward")]
+    #[test_case(90_000, 0, -90_000; "Backward")]
+    #[test_case(PTS_WRAP - 9_000, 9_000, 18_000; "Forward over wrap")]
+    #[test_case(9_000, PTS_WRAP - 9_000, -18_000; "Backward over wrap")]
+    fn pts_difference(from: u64, to: u64, expected: i64) {
+        assert_eq!(super::pts_difference(from, to), expected, "PTS difference is incorrect");
+    }
+}
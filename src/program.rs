@@ -0,0 +1,87 @@
+//! A high-level summary of a program in a transport stream, combining its PAT entry with its PMT.
+use alloc::vec::Vec;
+use crate::psi::pmt::{ElementaryStream, ProgramMapTable};
+
+/// Everything needed to list a program and its streams.
+///
+/// This is yielded by [`crate::reader::TSReader::programs`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramInfo {
+    /// Number of the program.
+    program_number: u16,
+    /// PID that the program's PMT is sent on.
+    pmt_pid: u16,
+    /// PID that carries the PCR for the program.
+    pcr_pid: Option<u16>,
+    /// Elementary streams of the program.
+    streams: Vec<ElementaryStream>,
+}
+
+impl ProgramInfo {
+    /// Build the program info from the PMT PID listed in the PAT and the program's PMT.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(pmt_pid: u16, pmt: &ProgramMapTable) -> Self {
+        ProgramInfo {
+            program_number: pmt.program_number(),
+            pmt_pid,
+            pcr_pid: pmt.pcr_pid(),
+            streams: pmt.streams().to_vec(),
+        }
+    }
+
+    /// Return the number of the program.
+    pub fn program_number(&self) -> u16 {
+        self.program_number
+    }
+
+    /// Return the PID that the program's PMT is sent on.
+    pub fn pmt_pid(&self) -> u16 {
+        self.pmt_pid
+    }
+
+    /// Return the PID that carries the PCR for the program.
+    ///
+    /// Returns `None` if the program has no PCR.
+    pub fn pcr_pid(&self) -> Option<u16> {
+        self.pcr_pid
+    }
+
+    /// Return the elementary streams of the program.
+    pub fn streams(&self) -> &[ElementaryStream] {
+        &self.streams
+    }
+}
+
+/// A problem with how the PAT and PMTs of a transport stream describe its programs.
+///
+/// These are found by [`crate::reader::TSReader::validate_program_structure`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StructureDefect {
+    /// No PAT was found, so none of the programs could be checked.
+    MissingPat,
+    /// No packets were found on the PMT PID that the PAT lists for a program.
+    MissingPmt {
+        /// Number of the program.
+        program_number: u16,
+        /// PID that the PAT lists for the program's PMT.
+        pmt_pid: u16,
+    },
+    /// Packets were found on the PMT PID that the PAT lists for a program, but none of them held a
+    /// PMT that could be parsed.
+    InvalidPmt {
+        /// Number of the program.
+        program_number: u16,
+        /// PID that the PAT lists for the program's PMT.
+        pmt_pid: u16,
+    },
+    /// The PMT of a program doesn't have a PCR PID, so its streams can't be synchronized.
+    MissingPcrPid {
+        /// Number of the program.
+        program_number: u16,
+    },
+    /// The PMT of a program doesn't list any elementary streams.
+    NoStreams {
+        /// Number of the program.
+        program_number: u16,
+    },
+}
@@ -0,0 +1,256 @@
+//! Program Specific Information (PSI) tables which describe the programs that make up a transport
+//! stream.
+//!
+//! PSI tables are sent as sections in the payloads of transport stream packets. Use a
+//! [`SectionAssembler`](section::SectionAssembler) to collect the sections for a PID and then parse
+//! them with the table type that is expected on that PID.
+pub mod section;
+pub mod pat;
+pub mod pmt;
+pub mod descriptor;
+pub mod mgt;
+pub mod vct;
+
+use alloc::boxed::Box;
+use core::error::Error;
+use core::ops::RangeInclusive;
+
+use bitvec::prelude::*;
+
+use crate::errors::invalid_crc::InvalidCrc;
+use crate::errors::invalid_payload_pointer::InvalidPayloadPointer;
+use crate::errors::invalid_table_id::InvalidTableId;
+use crate::errors::not_enough_data::NotEnoughData;
+use crate::errors::payload_is_not_start::PayloadIsNotStart;
+use crate::packet::payload::TSPayload;
+use crate::packet::NULL_PACKET_PID;
+
+/// The Program Association Table is always sent on PID `0`.
+pub const PAT_PID: u16 = 0x0000;
+
+/// PIDs `0x0000` to `0x001F` are reserved for tables that don't need to be listed in the PAT, such
+/// as the Conditional Access Table and the DVB service information tables.
+const RESERVED_TABLE_PIDS: RangeInclusive<u16> = 0x0000..=0x001F;
+
+/// The ATSC PSIP base tables are always sent on PID `0x1FFB`.
+pub const ATSC_PSIP_PID: u16 = 0x1FFB;
+
+/// The DVB Network Information Table is sent on PID `0x0010`.
+pub const NIT_PID: u16 = 0x0010;
+
+/// The DVB Service Description Table and Bouquet Association Table are sent on PID `0x0011`.
+pub const SDT_PID: u16 = 0x0011;
+
+/// The DVB Event Information Table is sent on PID `0x0012`.
+pub const EIT_PID: u16 = 0x0012;
+
+/// Table IDs of the DVB service information tables. This covers the NIT, SDT, BAT, and EIT.
+const DVB_SI_TABLE_IDS: RangeInclusive<u8> = 0x40..=0x6F;
+
+/// Table IDs of the ATSC PSIP base tables. This covers the MGT, TVCT, CVCT, RRT, and STT.
+const ATSC_PSIP_TABLE_IDS: RangeInclusive<u8> = 0xC7..=0xCD;
+
+/// The table ID, section syntax indicator, and section length fields take up the first 3 bytes of
+/// every section.
+pub const SECTION_HEADER_SIZE: usize = 3;
+
+/// Sections using the long section syntax have 5 more bytes of header after the section length.
+pub(crate) const LONG_SECTION_HEADER_SIZE: usize = SECTION_HEADER_SIZE + 5;
+
+/// The CRC at the end of a long section is 4 bytes in size.
+pub(crate) const CRC_SIZE: usize = 4;
+
+/// Return if this PID has a meaning defined by a standard, so it doesn't need to be listed in the
+/// PAT or a PMT.
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+pub(crate) fn is_well_known_pid(pid: u16) -> bool {
+    RESERVED_TABLE_PIDS.contains(&pid) || pid == ATSC_PSIP_PID || pid == NULL_PACKET_PID
+}
+
+/// Broadcast standard that a transport stream follows, which decides the service information
+/// tables it carries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamProfile {
+    /// The stream carries DVB service information, such as the SDT, NIT, or EIT.
+    Dvb,
+    /// The stream carries ATSC PSIP tables, such as the MGT or TVCT.
+    Atsc,
+    /// No service information was found, so the stream only has the MPEG-2 PSI tables.
+    Unknown,
+}
+
+impl StreamProfile {
+    /// Return the profile implied by a section with the given table ID being sent on a PID.
+    ///
+    /// Both the PID and the table ID have to match, since the table IDs of the service information
+    /// tables are reused for private data on other PIDs.
+    /// # Parameters
+    /// - `pid`: PID that the section was sent on.
+    /// - `table_id`: First byte of the section.
+    pub fn from_table(pid: u16, table_id: u8) -> StreamProfile {
+        match pid {
+            NIT_PID | SDT_PID | EIT_PID if DVB_SI_TABLE_IDS.contains(&table_id) => {
+                StreamProfile::Dvb
+            },
+            ATSC_PSIP_PID if ATSC_PSIP_TABLE_IDS.contains(&table_id) => StreamProfile::Atsc,
+            _ => StreamProfile::Unknown,
+        }
+    }
+}
+
+/// The fields that are common to every section that uses the long section syntax.
+pub(crate) struct LongSection<'a> {
+    /// Meaning depends on the table. For example this is the transport stream ID in a PAT and the
+    /// program number in a PMT.
+    pub table_id_extension: u16,
+    /// Version of the table that this section belongs to.
+    pub version_number: u8,
+    /// Set when this section applies now rather than being the next version of the table.
+    pub current_next_indicator: bool,
+    /// Table specific data that comes between the header and the CRC.
+    pub body: &'a [u8],
+}
+
+impl<'a> LongSection<'a> {
+    /// Parse the common fields from a section, verifying its table ID and CRC.
+    pub(crate) fn parse(section: &'a [u8], table_id: u8) -> Result<Self, Box<dyn Error>> {
+        let length = section_length(section)?;
+        if length < LONG_SECTION_HEADER_SIZE + CRC_SIZE {
+            return Err(Box::new(NotEnoughData {
+                expected: LONG_SECTION_HEADER_SIZE + CRC_SIZE,
+                found: length,
+            }))
+        }
+
+        if section[0] != table_id {
+            return Err(Box::new(InvalidTableId { expected: table_id, found: section[0] }))
+        }
+
+        let section = &section[0..length];
+        if crc32(section) != 0 {
+            let crc = section[length - CRC_SIZE..length].view_bits::<Msb0>().load_be();
+            return Err(Box::new(InvalidCrc { crc }))
+        }
+
+        let bits = section.view_bits::<Msb0>();
+        Ok(LongSection {
+            table_id_extension: bits[24..40].load_be(),
+            version_number: bits[42..47].load_be(),
+            current_next_indicator: bits[47],
+            body: &section[LONG_SECTION_HEADER_SIZE..length - CRC_SIZE],
+        })
+    }
+}
+
+/// Return the total number of bytes in a section, including the 3 byte section header.
+///
+/// Returns an error if the buffer is too small to hold the entire section.
+pub(crate) fn section_length(section: &[u8]) -> Result<usize, Box<dyn Error>> {
+    if section.len() < SECTION_HEADER_SIZE {
+        return Err(Box::new(NotEnoughData { expected: SECTION_HEADER_SIZE, found: section.len() }))
+    }
+
+    let length = SECTION_HEADER_SIZE +
+        section[0..SECTION_HEADER_SIZE].view_bits::<Msb0>()[12..24].load_be::<usize>();
+    if section.len() < length {
+        return Err(Box::new(NotEnoughData { expected: length, found: section.len() }))
+    }
+
+    Ok(length)
+}
+
+/// Return the section that starts in a payload, following the payload pointer to find it.
+///
+/// Returns an error if the payload doesn't start a section or the whole section isn't in the
+/// payload.
+pub(crate) fn section_from_payload(payload: &TSPayload) -> Result<&[u8], Box<dyn Error>> {
+    let Some(pointer) = payload.start_index() else {
+        return Err(Box::new(PayloadIsNotStart))
+    };
+
+    let data = payload.data();
+    let Some(section) = data.get(pointer as usize..) else {
+        return Err(Box::new(InvalidPayloadPointer { pointer, remainder: data.len() as u8 }))
+    };
+
+    let length = section_length(section)?;
+    Ok(&section[..length])
+}
+
+/// Calculate the MPEG-2 CRC32 of the given data.
+///
+/// Running this over an entire section, including the CRC at the end, returns `0` when the section
+/// is intact.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFF_u32;
+    for byte in data {
+        crc ^= (*byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x80000000 != 0 {
+                (crc << 1) ^ 0x04C11DB7
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn crc32_valid_section() {
+        let section = [
+            0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1,
+            0x04, 0xB2,
+        ];
+        assert_eq!(crc32(&section[0..12]), 0x2AB104B2, "CRC is incorrect");
+        assert_eq!(crc32(&section), 0, "CRC of intact section is not 0");
+    }
+
+    #[test]
+    fn parse_invalid_crc() {
+        let section = [
+            0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1,
+            0x04, 0xB3,
+        ];
+        let result = LongSection::parse(&section, 0x00);
+        assert!(result.is_err_and(|e| e.is::<InvalidCrc>()), "Corrupt CRC was not detected");
+    }
+
+    #[test]
+    fn parse_invalid_table_id() {
+        let section = [
+            0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1,
+            0x04, 0xB2,
+        ];
+        let result = LongSection::parse(&section, 0x02);
+        assert!(result.is_err_and(|e| e.is::<InvalidTableId>()), "Wrong table ID was not detected");
+    }
+
+    #[test_case(PAT_PID, true; "PAT")]
+    #[test_case(0x0011, true; "DVB SDT")]
+    #[test_case(ATSC_PSIP_PID, true; "ATSC PSIP")]
+    #[test_case(NULL_PACKET_PID, true; "Null packets")]
+    #[test_case(0x0020, false; "First unreserved")]
+    #[test_case(0x1000, false; "PMT")]
+    fn is_well_known(pid: u16, expected: bool) {
+        assert_eq!(is_well_known_pid(pid), expected, "Well known PID detection is incorrect");
+    }
+
+    #[test_case(SDT_PID, 0x42, StreamProfile::Dvb; "DVB SDT")]
+    #[test_case(NIT_PID, 0x40, StreamProfile::Dvb; "DVB NIT")]
+    #[test_case(EIT_PID, 0x4E, StreamProfile::Dvb; "DVB EIT")]
+    #[test_case(ATSC_PSIP_PID, 0xC7, StreamProfile::Atsc; "ATSC MGT")]
+    #[test_case(ATSC_PSIP_PID, 0xC8, StreamProfile::Atsc; "ATSC TVCT")]
+    #[test_case(PAT_PID, 0x00, StreamProfile::Unknown; "PAT")]
+    #[test_case(SDT_PID, 0xC7, StreamProfile::Unknown; "ATSC table on DVB PID")]
+    #[test_case(0x1000, 0x42, StreamProfile::Unknown; "DVB table on other PID")]
+    fn stream_profile_from_table(pid: u16, table_id: u8, expected: StreamProfile) {
+        assert_eq!(StreamProfile::from_table(pid, table_id), expected, "Profile is incorrect");
+    }
+}
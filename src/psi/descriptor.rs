@@ -0,0 +1,345 @@
+//! Descriptors carry extra information about a program or an elementary stream in PSI tables.
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::error::Error;
+
+use bitvec::prelude::*;
+
+use crate::errors::not_enough_data::NotEnoughData;
+
+/// Tag of the registration descriptor which identifies the format of a program or stream.
+pub const REGISTRATION_DESCRIPTOR_TAG: u8 = 0x05;
+
+/// Tag of the maximum bitrate descriptor which gives the peak bitrate of a program or stream.
+pub const MAXIMUM_BITRATE_DESCRIPTOR_TAG: u8 = 0x0E;
+
+/// Tag of the DVB component descriptor which describes the type of a stream component.
+pub const COMPONENT_DESCRIPTOR_TAG: u8 = 0x50;
+
+/// Tag of the DVB stream identifier descriptor which assigns a component tag to a stream.
+pub const STREAM_IDENTIFIER_DESCRIPTOR_TAG: u8 = 0x52;
+
+/// The maximum bitrate is given in units of 50 bytes per second, which is 400 bits per second.
+const MAXIMUM_BITRATE_UNIT: u32 = 50 * 8;
+
+/// The reserved bits and maximum bitrate field of a maximum bitrate descriptor are 3 bytes in size.
+const MAXIMUM_BITRATE_SIZE: usize = 3;
+
+/// The descriptor tag and descriptor length fields take up 2 bytes.
+const DESCRIPTOR_HEADER_SIZE: usize = 2;
+
+/// The format identifier of a registration descriptor is 4 bytes in size.
+const FORMAT_IDENTIFIER_SIZE: usize = 4;
+
+/// The stream content, component type, component tag, and language fields of a component
+/// descriptor take up 6 bytes.
+const COMPONENT_HEADER_SIZE: usize = 6;
+
+/// A stream component described by a DVB component descriptor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component {
+    /// Extends the stream content for newer types of components. This is `0xF` in streams that
+    /// predate it.
+    stream_content_ext: u8,
+    /// Type of the stream, such as `0x01` for MPEG-2 video or `0x02` for MPEG-1 Layer 2 audio.
+    stream_content: u8,
+    /// Type of the component within the stream content, such as its aspect ratio or audio mode.
+    component_type: u8,
+    /// Matches the component tag of a stream identifier descriptor in the PMT.
+    component_tag: u8,
+    /// ISO 639-2 language code of the component, such as `eng`.
+    language: [u8; 3],
+    /// Text that describes the component.
+    text: Box<[u8]>,
+}
+
+impl Component {
+    /// Return the extension of the stream content.
+    pub fn stream_content_ext(&self) -> u8 {
+        self.stream_content_ext
+    }
+
+    /// Return the type of the stream.
+    pub fn stream_content(&self) -> u8 {
+        self.stream_content
+    }
+
+    /// Return the type of the component within the stream content.
+    pub fn component_type(&self) -> u8 {
+        self.component_type
+    }
+
+    /// Return the component tag which links this to a stream in the PMT.
+    pub fn component_tag(&self) -> u8 {
+        self.component_tag
+    }
+
+    /// Return the ISO 639-2 language code of the component.
+    pub fn language(&self) -> [u8; 3] {
+        self.language
+    }
+
+    /// Return the text that describes the component.
+    ///
+    /// The text uses the DVB character encoding, which is usually ASCII compatible.
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    /// Return a human readable description of the component such as `16:9 HD video, 25 Hz` or
+    /// `MPEG-1 Layer 2 stereo audio`.
+    ///
+    /// Returns `None` for combinations of stream content and component type that are reserved or
+    /// user defined.
+    pub fn description(&self) -> Option<&'static str> {
+        let description = match (self.stream_content, self.component_type) {
+            (0x01, 0x01) => "MPEG-2 4:3 SD video, 25 Hz",
+            (0x01, 0x02) => "MPEG-2 16:9 SD video with pan vectors, 25 Hz",
+            (0x01, 0x03) => "MPEG-2 16:9 SD video, 25 Hz",
+            (0x01, 0x04) => "MPEG-2 wider than 16:9 SD video, 25 Hz",
+            (0x01, 0x05) => "MPEG-2 4:3 SD video, 30 Hz",
+            (0x01, 0x06) => "MPEG-2 16:9 SD video with pan vectors, 30 Hz",
+            (0x01, 0x07) => "MPEG-2 16:9 SD video, 30 Hz",
+            (0x01, 0x08) => "MPEG-2 wider than 16:9 SD video, 30 Hz",
+            (0x01, 0x09) => "MPEG-2 4:3 HD video, 25 Hz",
+            (0x01, 0x0A) => "MPEG-2 16:9 HD video with pan vectors, 25 Hz",
+            (0x01, 0x0B) => "MPEG-2 16:9 HD video, 25 Hz",
+            (0x01, 0x0C) => "MPEG-2 wider than 16:9 HD video, 25 Hz",
+            (0x01, 0x0D) => "MPEG-2 4:3 HD video, 30 Hz",
+            (0x01, 0x0E) => "MPEG-2 16:9 HD video with pan vectors, 30 Hz",
+            (0x01, 0x0F) => "MPEG-2 16:9 HD video, 30 Hz",
+            (0x01, 0x10) => "MPEG-2 wider than 16:9 HD video, 30 Hz",
+            (0x02, 0x01) => "MPEG-1 Layer 2 mono audio",
+            (0x02, 0x02) => "MPEG-1 Layer 2 dual mono audio",
+            (0x02, 0x03) => "MPEG-1 Layer 2 stereo audio",
+            (0x02, 0x04) => "MPEG-1 Layer 2 multilingual multichannel audio",
+            (0x02, 0x05) => "MPEG-1 Layer 2 surround sound audio",
+            (0x02, 0x40) => "MPEG-1 Layer 2 audio description for the visually impaired",
+            (0x02, 0x41) => "MPEG-1 Layer 2 audio for the hard of hearing",
+            (0x02, 0x42) => "MPEG-1 Layer 2 receiver mixed supplementary audio",
+            (0x02, 0x47) => "MPEG-1 Layer 2 receiver mixed audio description",
+            (0x02, 0x48) => "MPEG-1 Layer 2 broadcast mixed audio description",
+            (0x03, 0x01) => "EBU Teletext subtitles",
+            (0x03, 0x02) => "Associated EBU Teletext",
+            (0x03, 0x03) => "VBI data",
+            (0x03, 0x10) => "DVB subtitles",
+            (0x03, 0x11) => "DVB subtitles for 4:3 displays",
+            (0x03, 0x12) => "DVB subtitles for 16:9 displays",
+            (0x03, 0x13) => "DVB subtitles for 2.21:1 displays",
+            (0x03, 0x14) => "DVB subtitles for HD displays",
+            (0x03, 0x20) => "DVB subtitles for the hard of hearing",
+            (0x03, 0x21) => "DVB subtitles for the hard of hearing on 4:3 displays",
+            (0x03, 0x22) => "DVB subtitles for the hard of hearing on 16:9 displays",
+            (0x03, 0x23) => "DVB subtitles for the hard of hearing on 2.21:1 displays",
+            (0x03, 0x24) => "DVB subtitles for the hard of hearing on HD displays",
+            // The component type of AC-3 audio is defined by the AC-3 descriptor instead.
+            (0x04, _) => "AC-3 audio",
+            (0x05, 0x01) => "H.264 4:3 SD video, 25 Hz",
+            (0x05, 0x03) => "H.264 16:9 SD video, 25 Hz",
+            (0x05, 0x04) => "H.264 wider than 16:9 SD video, 25 Hz",
+            (0x05, 0x05) => "H.264 4:3 SD video, 30 Hz",
+            (0x05, 0x07) => "H.264 16:9 SD video, 30 Hz",
+            (0x05, 0x08) => "H.264 wider than 16:9 SD video, 30 Hz",
+            (0x05, 0x0B) => "H.264 16:9 HD video, 25 Hz",
+            (0x05, 0x0C) => "H.264 wider than 16:9 HD video, 25 Hz",
+            (0x05, 0x0F) => "H.264 16:9 HD video, 30 Hz",
+            (0x05, 0x10) => "H.264 wider than 16:9 HD video, 30 Hz",
+            (0x06, 0x01) => "HE-AAC mono audio",
+            (0x06, 0x03) => "HE-AAC stereo audio",
+            (0x06, 0x05) => "HE-AAC surround sound audio",
+            (0x06, 0x40) => "HE-AAC audio description for the visually impaired",
+            (0x06, 0x41) => "HE-AAC audio for the hard of hearing",
+            (0x06, 0x42) => "HE-AAC receiver mixed supplementary audio",
+            (0x06, 0x43) => "HE-AACv2 stereo audio",
+            (0x06, 0x44) => "HE-AACv2 audio description for the visually impaired",
+            (0x06, 0x45) => "HE-AACv2 audio for the hard of hearing",
+            (0x06, 0x46) => "HE-AACv2 receiver mixed supplementary audio",
+            (0x06, 0x47) => "HE-AAC receiver mixed audio description",
+            (0x06, 0x48) => "HE-AAC broadcast mixed audio description",
+            // The component type of DTS audio is defined by the DTS descriptor instead.
+            (0x07, _) => "DTS audio",
+            _ => return None,
+        };
+
+        Some(description)
+    }
+}
+
+/// A single descriptor from a descriptor loop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Descriptor {
+    /// Identifies the type of the descriptor.
+    tag: u8,
+    /// Contents of the descriptor after the length field.
+    data: Box<[u8]>,
+}
+
+impl Descriptor {
+    /// Parse every descriptor in a descriptor loop.
+    ///
+    /// The buffer should contain exactly the bytes covered by the loop's length field.
+    pub(crate) fn parse_loop(buf: &[u8]) -> Result<Vec<Descriptor>, Box<dyn Error>> {
+        let mut descriptors = Vec::new();
+        let mut read_idx = 0;
+
+        while read_idx < buf.len() {
+            if buf.len() < read_idx + DESCRIPTOR_HEADER_SIZE {
+                return Err(Box::new(NotEnoughData {
+                    expected: read_idx + DESCRIPTOR_HEADER_SIZE,
+                    found: buf.len(),
+                }))
+            }
+
+            let tag = buf[read_idx];
+            let length = buf[read_idx + 1] as usize;
+            let data_start = read_idx + DESCRIPTOR_HEADER_SIZE;
+            let data_end = data_start + length;
+
+            if buf.len() < data_end {
+                return Err(Box::new(NotEnoughData { expected: data_end, found: buf.len() }))
+            }
+
+            descriptors.push(Descriptor {
+                tag,
+                data: Box::from(&buf[data_start..data_end]),
+            });
+
+            read_idx = data_end;
+        }
+
+        Ok(descriptors)
+    }
+
+    /// Return the tag that identifies the type of the descriptor.
+    pub fn tag(&self) -> u8 {
+        self.tag
+    }
+
+    /// Return the contents of the descriptor after the length field.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Return the format identifier if this is a registration descriptor.
+    ///
+    /// Format identifiers are usually 4 ASCII characters such as `KLVA` for KLV metadata.
+    pub fn format_identifier(&self) -> Option<[u8; 4]> {
+        if self.tag != REGISTRATION_DESCRIPTOR_TAG {
+            return None
+        }
+
+        self.data.get(..FORMAT_IDENTIFIER_SIZE)?.try_into().ok()
+    }
+
+    /// Return the maximum bitrate in bits per second if this is a maximum bitrate descriptor.
+    ///
+    /// The 22-bit field after 2 reserved bits counts units of 50 bytes per second, which is
+    /// converted to bits per second.
+    pub fn max_bitrate(&self) -> Option<u32> {
+        if self.tag != MAXIMUM_BITRATE_DESCRIPTOR_TAG {
+            return None
+        }
+
+        let bits = self.data.get(..MAXIMUM_BITRATE_SIZE)?.view_bits::<Msb0>();
+        Some(bits[2..24].load_be::<u32>() * MAXIMUM_BITRATE_UNIT)
+    }
+
+    /// Return the component tag if this is a DVB stream identifier descriptor.
+    ///
+    /// The component descriptors of the EIT refer to streams in the PMT by this tag.
+    pub fn component_tag(&self) -> Option<u8> {
+        if self.tag != STREAM_IDENTIFIER_DESCRIPTOR_TAG {
+            return None
+        }
+
+        self.data.first().copied()
+    }
+
+    /// Return the described stream component if this is a DVB component descriptor.
+    ///
+    /// These are found in the EIT and PMT of DVB streams.
+    pub fn component(&self) -> Option<Component> {
+        if self.tag != COMPONENT_DESCRIPTOR_TAG || self.data.len() < COMPONENT_HEADER_SIZE {
+            return None
+        }
+
+        Some(Component {
+            stream_content_ext: self.data[0] >> 4,
+            stream_content: self.data[0] & 0x0F,
+            component_type: self.data[1],
+            component_tag: self.data[2],
+            language: self.data[3..COMPONENT_HEADER_SIZE].try_into().ok()?,
+            text: Box::from(&self.data[COMPONENT_HEADER_SIZE..]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn parse_loop() {
+        let buf = [0x05, 0x04, b'K', b'L', b'V', b'A', 0x26, 0x01, 0xFF];
+        let descriptors = Descriptor::parse_loop(&buf).unwrap();
+
+        assert_eq!(descriptors.len(), 2, "Incorrect number of descriptors");
+        assert_eq!(descriptors[0].tag(), REGISTRATION_DESCRIPTOR_TAG, "Tag is incorrect");
+        assert_eq!(descriptors[0].format_identifier(), Some(*b"KLVA"),
+            "Format identifier is incorrect");
+        assert_eq!(descriptors[1].tag(), 0x26, "Tag is incorrect");
+        assert_eq!(descriptors[1].data(), &[0xFF], "Data is incorrect");
+        assert_eq!(descriptors[1].format_identifier(), None, "Format identifier is incorrect");
+    }
+
+    #[test]
+    fn parse_loop_truncated() {
+        let buf = [0x05, 0x04, b'K', b'L'];
+        assert!(Descriptor::parse_loop(&buf).is_err(), "Truncated descriptor was parsed");
+    }
+
+    #[test_case(&[0x0E, 0x03, 0xC0, 0x27, 0x10], Some(4_000_000); "Maximum bitrate")]
+    #[test_case(&[0x0E, 0x03, 0xFF, 0xFF, 0xFF], Some(0x3F_FFFF * 400); "Largest bitrate")]
+    #[test_case(&[0x0E, 0x02, 0xC0, 0x27], None; "Too short")]
+    #[test_case(&[0x05, 0x04, b'K', b'L', b'V', b'A'], None; "Other descriptor")]
+    fn max_bitrate(buf: &[u8], expected: Option<u32>) {
+        let descriptors = Descriptor::parse_loop(buf).unwrap();
+        assert_eq!(descriptors[0].max_bitrate(), expected, "Maximum bitrate is incorrect");
+    }
+
+    #[test]
+    fn component() {
+        let buf = [0x50, 0x0A, 0xF1, 0x0B, 0x01, b'e', b'n', b'g', b'H', b'D', b'T', b'V'];
+        let descriptors = Descriptor::parse_loop(&buf).unwrap();
+        let component = descriptors[0].component().unwrap();
+
+        assert_eq!(component.stream_content_ext(), 0xF, "Stream content extension is incorrect");
+        assert_eq!(component.stream_content(), 0x01, "Stream content is incorrect");
+        assert_eq!(component.component_type(), 0x0B, "Component type is incorrect");
+        assert_eq!(component.component_tag(), 0x01, "Component tag is incorrect");
+        assert_eq!(&component.language(), b"eng", "Language is incorrect");
+        assert_eq!(component.text(), b"HDTV", "Text is incorrect");
+        assert_eq!(component.description(), Some("MPEG-2 16:9 HD video, 25 Hz"),
+            "Description is incorrect");
+    }
+
+    #[test_case(&[0x50, 0x05, 0xF2, 0x03, 0x02, b'e', b'n'], None; "Too short")]
+    #[test_case(&[0x50, 0x06, 0xF2, 0x03, 0x02, b'd', b'e', b'u'],
+        Some("MPEG-1 Layer 2 stereo audio"); "Stereo audio")]
+    #[test_case(&[0x50, 0x06, 0xF2, 0xB0, 0x02, b'd', b'e', b'u'], None; "User defined")]
+    #[test_case(&[0x0E, 0x03, 0xC0, 0x27, 0x10], None; "Other descriptor")]
+    fn component_description(buf: &[u8], expected: Option<&str>) {
+        let descriptors = Descriptor::parse_loop(buf).unwrap();
+        let description = descriptors[0].component().and_then(|c| c.description());
+        assert_eq!(description, expected, "Description is incorrect");
+    }
+
+    #[test_case(&[0x52, 0x01, 0x21], Some(0x21); "Stream identifier")]
+    #[test_case(&[0x52, 0x00], None; "Too short")]
+    #[test_case(&[0x26, 0x01, 0x21], None; "Other descriptor")]
+    fn component_tag(buf: &[u8], expected: Option<u8>) {
+        let descriptors = Descriptor::parse_loop(buf).unwrap();
+        assert_eq!(descriptors[0].component_tag(), expected, "Component tag is incorrect");
+    }
+}
@@ -0,0 +1,231 @@
+//! The ATSC Master Guide Table (MGT) lists the types, PIDs, and versions of every other PSIP table
+//! in a transport stream.
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::error::Error;
+
+use bitvec::prelude::*;
+
+use crate::errors::not_enough_data::NotEnoughData;
+use crate::psi::descriptor::Descriptor;
+use crate::psi::LongSection;
+
+/// Table ID of every Master Guide Table section.
+pub const MGT_TABLE_ID: u8 = 0xC7;
+
+/// The protocol version and tables defined fields take up 3 bytes.
+const MGT_HEADER_SIZE: usize = 3;
+
+/// The table type, PID, version number, number of bytes, and descriptors length fields of each
+/// table take up 11 bytes.
+const TABLE_HEADER_SIZE: usize = 11;
+
+/// The descriptors length field at the end of the table takes up 2 bytes.
+const DESCRIPTORS_LENGTH_SIZE: usize = 2;
+
+/// A PSIP table listed in a Master Guide Table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuideEntry {
+    /// Type of the table, such as `0x0000` for the terrestrial VCT or `0x0100` for EIT-0.
+    table_type: u16,
+    /// PID that the table is sent on.
+    pid: u16,
+    /// Version of the table.
+    version_number: u8,
+    /// Total number of bytes in all of the sections of the table.
+    number_bytes: u32,
+    /// Descriptors that describe the table.
+    descriptors: Vec<Descriptor>,
+}
+
+impl GuideEntry {
+    /// Return the type of the table.
+    pub fn table_type(&self) -> u16 {
+        self.table_type
+    }
+
+    /// Return the PID that the table is sent on.
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Return the version of the table.
+    pub fn version_number(&self) -> u8 {
+        self.version_number
+    }
+
+    /// Return the total number of bytes in all of the sections of the table.
+    pub fn number_bytes(&self) -> u32 {
+        self.number_bytes
+    }
+
+    /// Return the descriptors that describe the table.
+    pub fn descriptors(&self) -> &[Descriptor] {
+        &self.descriptors
+    }
+}
+
+/// The ATSC Master Guide Table which maps every other PSIP table to the PID it is sent on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MasterGuideTable {
+    /// Version of this table. This is incremented every time the table changes.
+    version_number: u8,
+    /// Set when this table currently applies. If it isn't set then this is the next version of the
+    /// table that will apply.
+    current_next_indicator: bool,
+    /// Version of the PSIP protocol that this table follows.
+    protocol_version: u8,
+    /// PSIP tables that are listed in this table.
+    tables: Vec<GuideEntry>,
+    /// Descriptors that describe the whole table.
+    descriptors: Vec<Descriptor>,
+}
+
+impl MasterGuideTable {
+    /// Parse an MGT from a complete section.
+    ///
+    /// The section should start with the table ID and end with the CRC, which is what a
+    /// [`SectionAssembler`](crate::psi::section::SectionAssembler) returns.
+    pub fn from_bytes(section: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let section = LongSection::parse(section, MGT_TABLE_ID)?;
+        let body = section.body;
+
+        if body.len() < MGT_HEADER_SIZE {
+            return Err(Box::new(NotEnoughData { expected: MGT_HEADER_SIZE, found: body.len() }))
+        }
+
+        let bits = body.view_bits::<Msb0>();
+        let protocol_version: u8 = bits[0..8].load_be();
+        let tables_defined: u16 = bits[8..24].load_be();
+
+        let mut read_idx = MGT_HEADER_SIZE;
+        let mut tables = Vec::with_capacity(tables_defined as usize);
+        for _ in 0..tables_defined {
+            if body.len() < read_idx + TABLE_HEADER_SIZE {
+                return Err(Box::new(NotEnoughData {
+                    expected: read_idx + TABLE_HEADER_SIZE,
+                    found: body.len(),
+                }))
+            }
+
+            let bits = body[read_idx..read_idx + TABLE_HEADER_SIZE].view_bits::<Msb0>();
+            let descriptors_length: usize = bits[76..88].load_be();
+            let descriptors_start = read_idx + TABLE_HEADER_SIZE;
+            let descriptors_end = descriptors_start + descriptors_length;
+
+            if descriptors_end > body.len() {
+                return Err(Box::new(NotEnoughData { expected: descriptors_end, found: body.len() }))
+            }
+
+            tables.push(GuideEntry {
+                table_type: bits[0..16].load_be(),
+                pid: bits[19..32].load_be(),
+                version_number: bits[35..40].load_be(),
+                number_bytes: bits[40..72].load_be(),
+                descriptors: Descriptor::parse_loop(&body[descriptors_start..descriptors_end])?,
+            });
+
+            read_idx = descriptors_end;
+        }
+
+        if body.len() < read_idx + DESCRIPTORS_LENGTH_SIZE {
+            return Err(Box::new(NotEnoughData {
+                expected: read_idx + DESCRIPTORS_LENGTH_SIZE,
+                found: body.len(),
+            }))
+        }
+
+        let bits = body[read_idx..read_idx + DESCRIPTORS_LENGTH_SIZE].view_bits::<Msb0>();
+        let descriptors_start = read_idx + DESCRIPTORS_LENGTH_SIZE;
+        let descriptors_end = descriptors_start + bits[4..16].load_be::<usize>();
+        if descriptors_end > body.len() {
+            return Err(Box::new(NotEnoughData { expected: descriptors_end, found: body.len() }))
+        }
+
+        Ok(MasterGuideTable {
+            version_number: section.version_number,
+            current_next_indicator: section.current_next_indicator,
+            protocol_version,
+            tables,
+            descriptors: Descriptor::parse_loop(&body[descriptors_start..descriptors_end])?,
+        })
+    }
+
+    /// Return the version of this table.
+    pub fn version_number(&self) -> u8 {
+        self.version_number
+    }
+
+    /// Return if this table currently applies.
+    pub fn current_next_indicator(&self) -> bool {
+        self.current_next_indicator
+    }
+
+    /// Return the version of the PSIP protocol that this table follows.
+    pub fn protocol_version(&self) -> u8 {
+        self.protocol_version
+    }
+
+    /// Return the PSIP tables that are listed in this table.
+    pub fn tables(&self) -> &[GuideEntry] {
+        &self.tables
+    }
+
+    /// Return the descriptors that describe the whole table.
+    pub fn descriptors(&self) -> &[Descriptor] {
+        &self.descriptors
+    }
+
+    /// Return the PID that the table with the given type is sent on.
+    pub fn pid(&self, table_type: u16) -> Option<u16> {
+        self.tables.iter()
+            .find(|table| table.table_type == table_type)
+            .map(|table| table.pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes() {
+        // The terrestrial VCT on the base PID and EIT-0 on PID 0x1D00.
+        let mut section = vec![
+            0xC7, 0xF0, 0x24, 0x00, 0x00, 0xC3, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0xFF,
+            0xFB, 0xE1, 0x00, 0x00, 0x01, 0x00, 0xF0, 0x00, 0x01, 0x00, 0xFD, 0x00, 0xE5, 0x00,
+            0x00, 0x04, 0x00, 0xF0, 0x00, 0xF0, 0x00,
+        ];
+        let crc = crate::psi::crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        let mgt = MasterGuideTable::from_bytes(&section).unwrap();
+
+        assert_eq!(mgt.version_number(), 1, "Version number is incorrect");
+        assert!(mgt.current_next_indicator(), "Current next indicator is incorrect");
+        assert_eq!(mgt.protocol_version(), 0, "Protocol version is incorrect");
+        assert_eq!(mgt.tables().len(), 2, "Incorrect number of tables");
+        assert_eq!(mgt.tables()[0].table_type(), 0x0000, "Table type is incorrect");
+        assert_eq!(mgt.tables()[0].pid(), 0x1FFB, "Table PID is incorrect");
+        assert_eq!(mgt.tables()[0].version_number(), 1, "Table version number is incorrect");
+        assert_eq!(mgt.tables()[0].number_bytes(), 256, "Number of bytes is incorrect");
+        assert_eq!(mgt.tables()[1].table_type(), 0x0100, "Table type is incorrect");
+        assert_eq!(mgt.tables()[1].version_number(), 5, "Table version number is incorrect");
+        assert_eq!(mgt.tables()[1].number_bytes(), 1024, "Number of bytes is incorrect");
+        assert_eq!(mgt.pid(0x0100), Some(0x1D00), "EIT-0 PID is incorrect");
+        assert_eq!(mgt.pid(0x0001), None, "PID of missing table is incorrect");
+        assert!(mgt.descriptors().is_empty(), "Descriptors are incorrect");
+    }
+
+    #[test]
+    fn from_bytes_truncated() {
+        // The table claims to list 2 tables but only has the first one.
+        let mut section = vec![
+            0xC7, 0xF0, 0x17, 0x00, 0x00, 0xC3, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0xFF,
+            0xFB, 0xE1, 0x00, 0x00, 0x01, 0x00, 0xF0, 0x00,
+        ];
+        let crc = crate::psi::crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        let result = MasterGuideTable::from_bytes(&section);
+        assert!(result.is_err_and(|e| e.is::<NotEnoughData>()), "Truncated table was parsed");
+    }
+}
@@ -0,0 +1,186 @@
+//! The Program Association Table (PAT) lists every program in a transport stream and the PID
+//! that each program's Program Map Table is sent on.
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::error::Error;
+
+use bitvec::prelude::*;
+
+use crate::errors::not_enough_data::NotEnoughData;
+use crate::packet::payload::TSPayload;
+use crate::psi::{self, LongSection};
+
+/// Table ID of every Program Association Table section.
+pub const PAT_TABLE_ID: u8 = 0x00;
+
+/// Each program in the PAT is described by 4 bytes.
+const PROGRAM_ENTRY_SIZE: usize = 4;
+
+/// Program number `0` is reserved for the PID of the Network Information Table.
+const NETWORK_PROGRAM_NUMBER: u16 = 0;
+
+/// The Program Association Table which maps program numbers to the PID of their Program Map Table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramAssociationTable {
+    /// ID of the transport stream that this table describes.
+    transport_stream_id: u16,
+    /// Version of this table. This is incremented every time the table changes.
+    version_number: u8,
+    /// Set when this table currently applies. If it isn't set then this is the next version of the
+    /// table that will apply.
+    current_next_indicator: bool,
+    /// PID of the Network Information Table.
+    ///
+    /// Is `None` if the table does not contain an entry for program number `0`.
+    network_pid: Option<u16>,
+    /// Pairs of program numbers and the PIDs of their Program Map Table.
+    programs: Vec<(u16, u16)>,
+}
+
+impl ProgramAssociationTable {
+    /// Parse a PAT from a complete section.
+    ///
+    /// The section should start with the table ID and end with the CRC, which is what a
+    /// [`SectionAssembler`](crate::psi::section::SectionAssembler) returns.
+    pub fn from_bytes(section: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let section = LongSection::parse(section, PAT_TABLE_ID)?;
+
+        if section.body.len() % PROGRAM_ENTRY_SIZE != 0 {
+            return Err(Box::new(NotEnoughData {
+                expected: section.body.len().next_multiple_of(PROGRAM_ENTRY_SIZE),
+                found: section.body.len(),
+            }))
+        }
+
+        let mut network_pid = None;
+        let mut programs = Vec::new();
+        for entry in section.body.chunks_exact(PROGRAM_ENTRY_SIZE) {
+            let bits = entry.view_bits::<Msb0>();
+            let program_number: u16 = bits[0..16].load_be();
+            let pid: u16 = bits[19..32].load_be();
+
+            if program_number == NETWORK_PROGRAM_NUMBER {
+                network_pid = Some(pid);
+            } else {
+                programs.push((program_number, pid));
+            }
+        }
+
+        Ok(ProgramAssociationTable {
+            transport_stream_id: section.table_id_extension,
+            version_number: section.version_number,
+            current_next_indicator: section.current_next_indicator,
+            network_pid,
+            programs,
+        })
+    }
+
+    /// Parse a PAT from the payload of the packet that starts its section.
+    ///
+    /// The payload pointer is followed to the start of the section, so this can be given the
+    /// payload of a packet on PID `0` with the `PUSI` flag set. The whole section has to be in the
+    /// payload, which is the case for nearly every PAT. Use a
+    /// [`SectionAssembler`](crate::psi::section::SectionAssembler) for sections that span several
+    /// packets.
+    pub fn from_payload(payload: &TSPayload) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(psi::section_from_payload(payload)?)
+    }
+
+    /// Return the ID of the transport stream that this table describes.
+    pub fn transport_stream_id(&self) -> u16 {
+        self.transport_stream_id
+    }
+
+    /// Return the version of this table.
+    pub fn version_number(&self) -> u8 {
+        self.version_number
+    }
+
+    /// Return if this table currently applies.
+    pub fn current_next_indicator(&self) -> bool {
+        self.current_next_indicator
+    }
+
+    /// Return the PID of the Network Information Table if the table lists one.
+    pub fn network_pid(&self) -> Option<u16> {
+        self.network_pid
+    }
+
+    /// Return pairs of program numbers and the PID of each program's Program Map Table.
+    ///
+    /// This does not include the Network Information Table entry. Use `network_pid()` for that.
+    pub fn programs(&self) -> &[(u16, u16)] {
+        &self.programs
+    }
+
+    /// Return the PID of the Program Map Table for the given program number.
+    pub fn pmt_pid(&self, program_number: u16) -> Option<u16> {
+        self.programs.iter()
+            .find(|(number, _)| *number == program_number)
+            .map(|(_, pid)| *pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::payload_is_not_start::PayloadIsNotStart;
+    use crate::packet::{TSPacket, PACKET_SIZE};
+
+    #[test]
+    fn from_bytes() {
+        let section = [
+            0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1,
+            0x04, 0xB2,
+        ];
+        let pat = ProgramAssociationTable::from_bytes(&section).unwrap();
+
+        assert_eq!(pat.transport_stream_id(), 1, "Transport stream ID is incorrect");
+        assert_eq!(pat.version_number(), 0, "Version number is incorrect");
+        assert!(pat.current_next_indicator(), "Current next indicator is incorrect");
+        assert_eq!(pat.network_pid(), None, "Network PID is incorrect");
+        assert_eq!(pat.programs(), &[(1, 0x1000)], "Programs are incorrect");
+        assert_eq!(pat.pmt_pid(1), Some(0x1000), "PMT PID is incorrect");
+    }
+
+    #[test]
+    fn from_payload() {
+        // A PAT packet captured from a stream muxed by FFmpeg.
+        let mut packet = vec![0xFF; PACKET_SIZE];
+        packet[..21].copy_from_slice(&[
+            0x47, 0x40, 0x00, 0x10, 0x00, 0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00,
+            0x01, 0xF0, 0x00, 0x2A, 0xB1, 0x04, 0xB2,
+        ]);
+        let payload = TSPacket::from_bytes(&packet).unwrap().payload().unwrap();
+        let pat = ProgramAssociationTable::from_payload(&payload).unwrap();
+
+        assert_eq!(pat.transport_stream_id(), 1, "Transport stream ID is incorrect");
+        assert_eq!(pat.programs(), &[(1, 0x1000)], "Programs are incorrect");
+    }
+
+    #[test]
+    fn from_payload_not_start() {
+        let payload = TSPayload::from_bytes(false, 0, Box::new([0x00, 0xB0, 0x0D]));
+        let result = ProgramAssociationTable::from_payload(&payload);
+        assert!(result.is_err_and(|e| e.is::<PayloadIsNotStart>()),
+            "Payload without a start was parsed");
+    }
+
+    // Every field spans multiple bytes and would be read as a different value if the bytes were
+    // loaded in the wrong order.
+    #[test]
+    fn from_bytes_big_endian() {
+        let mut section = vec![
+            0x00, 0xB0, 0x11, 0x12, 0x34, 0xC3, 0x00, 0x00, 0x00, 0x00, 0xE0, 0x10, 0x12, 0x34,
+            0xEA, 0xBC,
+        ];
+        let crc = crate::psi::crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        let pat = ProgramAssociationTable::from_bytes(&section).unwrap();
+
+        assert_eq!(pat.transport_stream_id(), 0x1234, "Transport stream ID is incorrect");
+        assert_eq!(pat.version_number(), 1, "Version number is incorrect");
+        assert_eq!(pat.network_pid(), Some(0x0010), "Network PID is incorrect");
+        assert_eq!(pat.programs(), &[(0x1234, 0x0ABC)], "Programs are incorrect");
+    }
+}
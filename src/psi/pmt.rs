@@ -0,0 +1,353 @@
+//! The Program Map Table (PMT) lists the elementary streams that make up a program.
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::error::Error;
+
+use bitvec::prelude::*;
+
+use crate::errors::not_enough_data::NotEnoughData;
+use crate::packet::payload::TSPayload;
+use crate::packet::NULL_PACKET_PID;
+use crate::psi::descriptor::Descriptor;
+use crate::psi::{self, LongSection};
+
+/// Table ID of every Program Map Table section.
+pub const PMT_TABLE_ID: u8 = 0x02;
+
+/// The PCR PID and program info length fields take up 4 bytes.
+const PROGRAM_HEADER_SIZE: usize = 4;
+
+/// The stream type, elementary PID, and ES info length fields take up 5 bytes.
+const STREAM_HEADER_SIZE: usize = 5;
+
+/// Stream types that carry video.
+const VIDEO_STREAM_TYPES: [u8; 7] = [
+    0x01, // MPEG-1 video
+    0x02, // MPEG-2 video
+    0x10, // MPEG-4 part 2 video
+    0x1B, // H.264 video
+    0x20, // H.264 SVC sub-bitstream
+    0x24, // H.265 video
+    0x42, // AVS video
+];
+
+/// Stream type of metadata carried in PES packets, which is used for synchronous KLV metadata.
+const METADATA_STREAM_TYPE: u8 = 0x15;
+
+/// Stream type of private data carried in PES packets, which is used for asynchronous KLV metadata.
+const PRIVATE_PES_STREAM_TYPE: u8 = 0x06;
+
+/// Format identifier of the registration descriptor for KLV metadata.
+pub const KLV_FORMAT_IDENTIFIER: [u8; 4] = *b"KLVA";
+
+/// An elementary stream listed in a Program Map Table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElementaryStream {
+    /// Type of data that is carried in this stream.
+    stream_type: u8,
+    /// PID that this stream is carried on.
+    elementary_pid: u16,
+    /// Descriptors that describe this stream.
+    descriptors: Vec<Descriptor>,
+}
+
+impl ElementaryStream {
+    /// Return the type of data that is carried in this stream.
+    pub fn stream_type(&self) -> u8 {
+        self.stream_type
+    }
+
+    /// Return the PID that this stream is carried on.
+    pub fn elementary_pid(&self) -> u16 {
+        self.elementary_pid
+    }
+
+    /// Return the descriptors that describe this stream.
+    pub fn descriptors(&self) -> &[Descriptor] {
+        &self.descriptors
+    }
+
+    /// Return the maximum bitrate of this stream in bits per second.
+    ///
+    /// Returns `None` if the stream doesn't have a maximum bitrate descriptor.
+    pub fn max_bitrate(&self) -> Option<u32> {
+        self.descriptors.iter().find_map(|descriptor| descriptor.max_bitrate())
+    }
+
+    /// Return the component tag of this stream, which links it to the component descriptors of
+    /// the EIT.
+    ///
+    /// Returns `None` if the stream doesn't have a stream identifier descriptor.
+    pub fn component_tag(&self) -> Option<u8> {
+        self.descriptors.iter().find_map(|descriptor| descriptor.component_tag())
+    }
+
+    /// Return if the stream type indicates that this stream carries video.
+    pub fn is_video(&self) -> bool {
+        VIDEO_STREAM_TYPES.contains(&self.stream_type)
+    }
+
+    /// Return if this stream carries KLV metadata.
+    ///
+    /// Synchronous metadata is carried with the metadata stream type. Asynchronous metadata is
+    /// carried with the private PES data stream type and a `KLVA` registration descriptor.
+    pub fn is_klv(&self) -> bool {
+        match self.stream_type {
+            METADATA_STREAM_TYPE => true,
+            PRIVATE_PES_STREAM_TYPE => self.descriptors.iter()
+                .any(|descriptor| descriptor.format_identifier() == Some(KLV_FORMAT_IDENTIFIER)),
+            _ => false,
+        }
+    }
+}
+
+/// The Program Map Table which describes the elementary streams of a single program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramMapTable {
+    /// Program that this table describes.
+    program_number: u16,
+    /// Version of this table. This is incremented every time the table changes.
+    version_number: u8,
+    /// Set when this table currently applies. If it isn't set then this is the next version of the
+    /// table that will apply.
+    current_next_indicator: bool,
+    /// PID of the packets that carry the PCR for this program. Set to `0x1FFF` if the program has
+    /// no PCR.
+    pcr_pid: u16,
+    /// Descriptors that describe the whole program.
+    program_descriptors: Vec<Descriptor>,
+    /// Elementary streams that make up this program.
+    streams: Vec<ElementaryStream>,
+}
+
+impl ProgramMapTable {
+    /// Parse a PMT from a complete section.
+    ///
+    /// The section should start with the table ID and end with the CRC, which is what a
+    /// [`SectionAssembler`](crate::psi::section::SectionAssembler) returns.
+    pub fn from_bytes(section: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let section = LongSection::parse(section, PMT_TABLE_ID)?;
+        let body = section.body;
+
+        if body.len() < PROGRAM_HEADER_SIZE {
+            return Err(Box::new(NotEnoughData { expected: PROGRAM_HEADER_SIZE, found: body.len() }))
+        }
+
+        let bits = body.view_bits::<Msb0>();
+        let pcr_pid: u16 = bits[3..16].load_be();
+        let program_info_length: usize = bits[20..32].load_be();
+
+        let mut read_idx = PROGRAM_HEADER_SIZE + program_info_length;
+        if read_idx > body.len() {
+            return Err(Box::new(NotEnoughData { expected: read_idx, found: body.len() }))
+        }
+
+        let program_descriptors = Descriptor::parse_loop(&body[PROGRAM_HEADER_SIZE..read_idx])?;
+
+        let mut streams = Vec::new();
+        while read_idx < body.len() {
+            if body.len() < read_idx + STREAM_HEADER_SIZE {
+                return Err(Box::new(NotEnoughData {
+                    expected: read_idx + STREAM_HEADER_SIZE,
+                    found: body.len(),
+                }))
+            }
+
+            let bits = body[read_idx..read_idx + STREAM_HEADER_SIZE].view_bits::<Msb0>();
+            let es_info_length: usize = bits[28..40].load_be();
+            let descriptors_start = read_idx + STREAM_HEADER_SIZE;
+            let descriptors_end = descriptors_start + es_info_length;
+
+            if descriptors_end > body.len() {
+                return Err(Box::new(NotEnoughData { expected: descriptors_end, found: body.len() }))
+            }
+
+            streams.push(ElementaryStream {
+                stream_type: bits[0..8].load_be(),
+                elementary_pid: bits[11..24].load_be(),
+                descriptors: Descriptor::parse_loop(&body[descriptors_start..descriptors_end])?,
+            });
+
+            read_idx = descriptors_end;
+        }
+
+        Ok(ProgramMapTable {
+            program_number: section.table_id_extension,
+            version_number: section.version_number,
+            current_next_indicator: section.current_next_indicator,
+            pcr_pid,
+            program_descriptors,
+            streams,
+        })
+    }
+
+    /// Parse a PMT from the payload of the packet that starts its section.
+    ///
+    /// The payload pointer is followed to the start of the section, so this can be given the
+    /// payload of a packet on the PMT PID that the PAT lists for the program, with the `PUSI` flag
+    /// set. The whole section has to be in the payload. Use a
+    /// [`SectionAssembler`](crate::psi::section::SectionAssembler) for sections that span several
+    /// packets.
+    pub fn from_payload(payload: &TSPayload) -> Result<Self, Box<dyn Error>> {
+        Self::from_bytes(psi::section_from_payload(payload)?)
+    }
+
+    /// Return the program that this table describes.
+    pub fn program_number(&self) -> u16 {
+        self.program_number
+    }
+
+    /// Return the version of this table.
+    pub fn version_number(&self) -> u8 {
+        self.version_number
+    }
+
+    /// Return if this table currently applies.
+    pub fn current_next_indicator(&self) -> bool {
+        self.current_next_indicator
+    }
+
+    /// Return the PID that carries the PCR for this program.
+    ///
+    /// Returns `None` if the program has no PCR.
+    pub fn pcr_pid(&self) -> Option<u16> {
+        if self.pcr_pid == NULL_PACKET_PID {
+            return None
+        }
+
+        Some(self.pcr_pid)
+    }
+
+    /// Return the descriptors that describe the whole program.
+    ///
+    /// These come from the program info loop before the stream loop, and are separate from the
+    /// descriptors of each stream. A registration descriptor here identifies the format of the
+    /// whole program, such as `KLVA` for programs carrying KLV metadata.
+    pub fn program_descriptors(&self) -> &[Descriptor] {
+        &self.program_descriptors
+    }
+
+    /// Return the elementary streams that make up this program.
+    pub fn streams(&self) -> &[ElementaryStream] {
+        &self.streams
+    }
+
+    /// Return the first video stream in this program.
+    pub fn video_stream(&self) -> Option<&ElementaryStream> {
+        self.streams.iter().find(|stream| stream.is_video())
+    }
+
+    /// Return the first stream in this program that carries KLV metadata.
+    pub fn klv_stream(&self) -> Option<&ElementaryStream> {
+        self.streams.iter().find(|stream| stream.is_klv())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{TSPacket, PACKET_SIZE};
+    use test_case::test_case;
+
+    #[test]
+    fn from_bytes() {
+        let section = [
+            0x02, 0xB0, 0x17, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x00, 0xF0, 0x00, 0x1B, 0xE1,
+            0x00, 0xF0, 0x00, 0x0F, 0xE1, 0x01, 0xF0, 0x00, 0x2F, 0x44, 0xB9, 0x9B,
+        ];
+        let pmt = ProgramMapTable::from_bytes(&section).unwrap();
+
+        assert_eq!(pmt.program_number(), 1, "Program number is incorrect");
+        assert_eq!(pmt.pcr_pid(), Some(0x100), "PCR PID is incorrect");
+        assert_eq!(pmt.streams().len(), 2, "Incorrect number of streams");
+        assert_eq!(pmt.streams()[0].stream_type(), 0x1B, "Video stream type is incorrect");
+        assert_eq!(pmt.streams()[0].elementary_pid(), 0x100, "Video PID is incorrect");
+        assert_eq!(pmt.streams()[1].stream_type(), 0x0F, "Audio stream type is incorrect");
+        assert_eq!(pmt.streams()[1].elementary_pid(), 0x101, "Audio PID is incorrect");
+        assert_eq!(pmt.video_stream().map(|s| s.elementary_pid()), Some(0x100),
+            "Video stream is incorrect");
+        assert!(pmt.program_descriptors().is_empty(), "Program descriptors are incorrect");
+    }
+
+    #[test]
+    fn from_payload() {
+        // A PMT packet captured from a stream muxed by FFmpeg, with H.264 video and AAC audio.
+        let mut packet = vec![0xFF; PACKET_SIZE];
+        packet[..31].copy_from_slice(&[
+            0x47, 0x50, 0x00, 0x10, 0x00, 0x02, 0xB0, 0x17, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1,
+            0x00, 0xF0, 0x00, 0x1B, 0xE1, 0x00, 0xF0, 0x00, 0x0F, 0xE1, 0x01, 0xF0, 0x00, 0x2F,
+            0x44, 0xB9, 0x9B,
+        ]);
+        let payload = TSPacket::from_bytes(&packet).unwrap().payload().unwrap();
+        let pmt = ProgramMapTable::from_payload(&payload).unwrap();
+
+        assert_eq!(pmt.pcr_pid(), Some(0x100), "PCR PID is incorrect");
+        let streams = pmt.streams().iter()
+            .map(|stream| (stream.stream_type(), stream.elementary_pid()))
+            .collect::<Vec<_>>();
+        assert_eq!(streams, vec![(0x1B, 0x100), (0x0F, 0x101)], "Streams are incorrect");
+    }
+
+    #[test]
+    fn from_bytes_descriptors() {
+        // A program registration descriptor for `KLVA` and a metadata stream with a descriptor of
+        // its own.
+        let mut section = vec![
+            0x02, 0xB0, 0x1B, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x00, 0xF0, 0x06, 0x05, 0x04,
+            b'K', b'L', b'V', b'A', 0x15, 0xE1, 0x02, 0xF0, 0x03, 0x26, 0x01, 0xFF,
+        ];
+        let crc = crate::psi::crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        let pmt = ProgramMapTable::from_bytes(&section).unwrap();
+
+        assert_eq!(pmt.program_descriptors().len(), 1, "Incorrect number of program descriptors");
+        assert_eq!(pmt.program_descriptors()[0].format_identifier(), Some(*b"KLVA"),
+            "Program format identifier is incorrect");
+        assert_eq!(pmt.streams().len(), 1, "Incorrect number of streams");
+        assert_eq!(pmt.streams()[0].elementary_pid(), 0x102, "Metadata PID is incorrect");
+        assert_eq!(pmt.streams()[0].descriptors().len(), 1,
+            "Incorrect number of stream descriptors");
+        assert_eq!(pmt.streams()[0].descriptors()[0].tag(), 0x26, "Stream descriptor is incorrect");
+        assert_eq!(pmt.klv_stream().map(|s| s.elementary_pid()), Some(0x102),
+            "KLV stream is incorrect");
+    }
+
+    #[test]
+    fn component_tag() {
+        // A video stream with a stream identifier descriptor and an audio stream without one.
+        let mut section = vec![
+            0x02, 0xB0, 0x1A, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x00, 0xF0, 0x00, 0x1B, 0xE1,
+            0x00, 0xF0, 0x03, 0x52, 0x01, 0x21, 0x0F, 0xE1, 0x01, 0xF0, 0x00,
+        ];
+        let crc = crate::psi::crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        let pmt = ProgramMapTable::from_bytes(&section).unwrap();
+
+        assert_eq!(pmt.streams()[0].component_tag(), Some(0x21),
+            "Video component tag is incorrect");
+        assert_eq!(pmt.streams()[1].component_tag(), None, "Audio component tag is incorrect");
+    }
+
+    #[test_case(0x15, &[]; "Synchronous metadata")]
+    #[test_case(0x06, &[0x05, 0x04, b'K', b'L', b'V', b'A']; "Asynchronous metadata")]
+    fn is_klv(stream_type: u8, descriptors: &[u8]) {
+        let stream = ElementaryStream {
+            stream_type,
+            elementary_pid: 0x102,
+            descriptors: Descriptor::parse_loop(descriptors).unwrap(),
+        };
+        assert!(stream.is_klv(), "KLV stream was not detected");
+    }
+
+    #[test_case(0x06, &[]; "Private data without a registration descriptor")]
+    #[test_case(0x06, &[0x05, 0x04, b'A', b'C', b'-', b'3']; "Other format identifier")]
+    #[test_case(0x1B, &[0x05, 0x04, b'K', b'L', b'V', b'A']; "Video")]
+    fn is_not_klv(stream_type: u8, descriptors: &[u8]) {
+        let stream = ElementaryStream {
+            stream_type,
+            elementary_pid: 0x102,
+            descriptors: Descriptor::parse_loop(descriptors).unwrap(),
+        };
+        assert!(!stream.is_klv(), "Stream was incorrectly detected as KLV");
+    }
+}
@@ -0,0 +1,200 @@
+//! Reassembles PSI sections from the payloads of transport stream packets.
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use crate::packet::payload::TSPayload;
+use crate::psi::section_length;
+
+#[cfg(feature = "log")]
+use log::trace;
+
+/// Sections are followed by `0xFF` stuffing bytes when they do not fill the rest of a packet.
+const STUFFING_BYTE: u8 = 0xFF;
+
+/// Collects the payloads of a single PID and returns the PSI sections they contain.
+///
+/// A section can be spread across multiple packets and a single packet can contain the end of one
+/// section and the start of one or more others. When the `PUSI` flag is set the payload pointer
+/// indicates where the first new section starts. Any data before that point finishes the section
+/// that was already in progress.
+#[derive(Clone, Debug, Default)]
+pub struct SectionAssembler {
+    /// Bytes of the section that is currently being assembled.
+    buffer: Vec<u8>,
+    /// Set when the start of a section has been seen, and we are waiting on the rest of it.
+    in_progress: bool,
+}
+
+impl SectionAssembler {
+    /// Create a new section assembler.
+    pub fn new() -> Self {
+        SectionAssembler {
+            buffer: Vec::new(),
+            in_progress: false,
+        }
+    }
+
+    /// Add the payload of the next packet on this PID and return any sections that were completed
+    /// by it.
+    ///
+    /// The returned sections start at the `table_id` and end with the last byte of the CRC (or the
+    /// last byte of the section for tables that do not have one).
+    pub fn push(&mut self, payload: &TSPayload) -> Vec<Box<[u8]>> {
+        let mut sections = Vec::new();
+        let data = payload.data();
+
+        let Some(pointer) = payload.start_index().map(usize::from) else {
+            // Without the `PUSI` flag this payload can only continue a section that has already
+            // been started.
+            if self.in_progress {
+                self.buffer.extend_from_slice(data);
+                self.take_completed(&mut sections);
+            }
+
+            return sections;
+        };
+
+        // A payload pointer past the end of the payload means the packet is corrupt, so neither the
+        // section in progress nor the new one can be trusted.
+        if pointer > data.len() {
+            #[cfg(feature = "log")]
+            trace!("Discarding section data after invalid payload pointer {}", pointer);
+            self.reset();
+            return sections;
+        }
+        let (current_data, start_data) = data.split_at(pointer);
+
+        // The bytes before the payload pointer finish the section that was in progress.
+        if self.in_progress {
+            self.buffer.extend_from_slice(current_data);
+            self.take_completed(&mut sections);
+        }
+
+        // A new section starts at the payload pointer. If the previous section still wasn't
+        // complete then its data was lost and there is no way to recover it.
+        #[cfg(feature = "log")]
+        if self.in_progress {
+            trace!("Discarding incomplete section of {} bytes", self.buffer.len());
+        }
+        self.buffer.clear();
+        self.buffer.extend_from_slice(start_data);
+        self.in_progress = true;
+        self.take_completed(&mut sections);
+
+        sections
+    }
+
+    /// Discard any partially assembled section.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.in_progress = false;
+    }
+
+    /// Move every complete section out of the buffer.
+    fn take_completed(&mut self, sections: &mut Vec<Box<[u8]>>) {
+        loop {
+            // Once stuffing has started there are no more sections until the next payload pointer.
+            if self.buffer.first().is_none_or(|byte| *byte == STUFFING_BYTE) {
+                self.reset();
+                return
+            }
+
+            let Ok(length) = section_length(&self.buffer) else {
+                // We need more data before this section is complete.
+                return
+            };
+
+            let section: Box<[u8]> = self.buffer.drain(0..length).collect();
+
+            #[cfg(feature = "log")]
+            trace!("Completed section: {:02X?}", section);
+
+            sections.push(section);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAT: [u8; 16] = [
+        0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1, 0x04,
+        0xB2,
+    ];
+
+    /// Build payload data with a payload pointer of `0` followed by `data` and stuffing.
+    fn start_payload(data: &[u8]) -> TSPayload {
+        let mut bytes = vec![0];
+        bytes.extend_from_slice(data);
+        bytes.resize(184, STUFFING_BYTE);
+        TSPayload::from_bytes(true, 0, bytes.into_boxed_slice())
+    }
+
+    #[test]
+    fn single_packet() {
+        let mut assembler = SectionAssembler::new();
+        let sections = assembler.push(&start_payload(&PAT));
+
+        assert_eq!(sections.len(), 1, "Incorrect number of sections");
+        assert!(sections[0].iter().eq(PAT.iter()), "Section is incorrect: {:02X?}", sections[0]);
+    }
+
+    #[test]
+    fn multiple_packets() {
+        let mut assembler = SectionAssembler::new();
+        let first = TSPayload::from_bytes(true, 0, Box::new([0, 0x00, 0xB0, 0x0D, 0x00, 0x01]));
+        let second = TSPayload::from_bytes(false, 1, Box::from(&PAT[5..PAT.len()]));
+
+        assert!(assembler.push(&first).is_empty(), "Section completed before all data was added");
+
+        let sections = assembler.push(&second);
+        assert_eq!(sections.len(), 1, "Incorrect number of sections");
+        assert!(sections[0].iter().eq(PAT.iter()), "Section is incorrect: {:02X?}", sections[0]);
+    }
+
+    #[test]
+    fn continuation_without_start() {
+        let mut assembler = SectionAssembler::new();
+        let payload = TSPayload::from_bytes(false, 0, Box::new(PAT));
+
+        assert!(assembler.push(&payload).is_empty(), "Section returned without a start");
+    }
+
+    #[test]
+    fn completes_and_starts() {
+        let mut next = PAT;
+        next[5] = 0xC3;
+
+        let mut assembler = SectionAssembler::new();
+        let mut first = vec![0];
+        first.extend_from_slice(&PAT[0..10]);
+        let first = TSPayload::from_bytes(true, 0, first.into_boxed_slice());
+        assert!(assembler.push(&first).is_empty(), "Section completed before all data was added");
+
+        // The payload pointer skips the end of the first section, and the next section starts
+        // after it.
+        let mut second = vec![6];
+        second.extend_from_slice(&PAT[10..PAT.len()]);
+        second.extend_from_slice(&next);
+        second.resize(184, STUFFING_BYTE);
+        let sections = assembler.push(&TSPayload::from_bytes(true, 1, second.into_boxed_slice()));
+
+        assert_eq!(sections.len(), 2, "Incorrect number of sections");
+        assert!(sections[0].iter().eq(PAT.iter()), "Finished section is incorrect: {:02X?}",
+            sections[0]);
+        assert!(sections[1].iter().eq(next.iter()), "Started section is incorrect: {:02X?}",
+            sections[1]);
+    }
+
+    #[test]
+    fn invalid_pointer() {
+        let mut assembler = SectionAssembler::new();
+        let first = TSPayload::from_bytes(true, 0, Box::new([0, 0x00, 0xB0, 0x0D, 0x00, 0x01]));
+        let second = TSPayload::from_bytes(true, 1, Box::new([0xB7, 0xC1, 0x00]));
+        let third = TSPayload::from_bytes(false, 2, Box::from(&PAT[5..PAT.len()]));
+
+        assert!(assembler.push(&first).is_empty(), "Section completed before all data was added");
+        assert!(assembler.push(&second).is_empty(), "Section returned from an invalid pointer");
+        assert!(assembler.push(&third).is_empty(), "Section in progress was kept");
+    }
+}
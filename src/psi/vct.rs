@@ -0,0 +1,323 @@
+//! The ATSC Virtual Channel Table (VCT) maps the channel numbers and names that viewers see to the
+//! programs of a transport stream. Terrestrial broadcasts send a TVCT and cable systems send a
+//! CVCT, which share the same layout.
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::error::Error;
+
+use bitvec::prelude::*;
+
+use crate::errors::not_enough_data::NotEnoughData;
+use crate::psi::descriptor::Descriptor;
+use crate::psi::LongSection;
+
+/// Table ID of every Terrestrial Virtual Channel Table section.
+pub const TVCT_TABLE_ID: u8 = 0xC8;
+
+/// Table ID of every Cable Virtual Channel Table section.
+pub const CVCT_TABLE_ID: u8 = 0xC9;
+
+/// The protocol version and number of channels fields take up 2 bytes.
+const VCT_HEADER_SIZE: usize = 2;
+
+/// The fixed size fields of each channel, from the short name to the descriptors length, take up
+/// 32 bytes.
+const CHANNEL_HEADER_SIZE: usize = 32;
+
+/// The short name of a channel is made up of 7 UTF-16 code units.
+const SHORT_NAME_SIZE: usize = 14;
+
+/// The additional descriptors length field at the end of the table takes up 2 bytes.
+const DESCRIPTORS_LENGTH_SIZE: usize = 2;
+
+/// A virtual channel listed in a Virtual Channel Table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VirtualChannel {
+    /// Name of the channel, with the trailing null characters removed.
+    short_name: String,
+    /// Major channel number, such as the `7` in channel `7.1`.
+    major_channel_number: u16,
+    /// Minor channel number, such as the `1` in channel `7.1`.
+    minor_channel_number: u16,
+    /// Modulation mode of the carrier that the channel is sent on.
+    modulation_mode: u8,
+    /// Transport stream ID of the multiplex that carries the channel.
+    channel_tsid: u16,
+    /// Program number of the channel in the PAT of its multiplex.
+    program_number: u16,
+    /// Set when the channel is scrambled.
+    access_controlled: bool,
+    /// Set when the channel can't be tuned to by entering its channel number.
+    hidden: bool,
+    /// Type of service carried on the channel, such as `0x02` for digital television.
+    service_type: u8,
+    /// ID that links the channel to its events in the EIT.
+    source_id: u16,
+    /// Descriptors that describe the channel.
+    descriptors: Vec<Descriptor>,
+}
+
+impl VirtualChannel {
+    /// Return the name of the channel.
+    pub fn short_name(&self) -> &str {
+        &self.short_name
+    }
+
+    /// Return the major channel number, such as the `7` in channel `7.1`.
+    pub fn major_channel_number(&self) -> u16 {
+        self.major_channel_number
+    }
+
+    /// Return the minor channel number, such as the `1` in channel `7.1`.
+    pub fn minor_channel_number(&self) -> u16 {
+        self.minor_channel_number
+    }
+
+    /// Return the modulation mode of the carrier that the channel is sent on.
+    pub fn modulation_mode(&self) -> u8 {
+        self.modulation_mode
+    }
+
+    /// Return the transport stream ID of the multiplex that carries the channel.
+    pub fn channel_tsid(&self) -> u16 {
+        self.channel_tsid
+    }
+
+    /// Return the program number of the channel in the PAT of its multiplex.
+    pub fn program_number(&self) -> u16 {
+        self.program_number
+    }
+
+    /// Return if the channel is scrambled.
+    pub fn access_controlled(&self) -> bool {
+        self.access_controlled
+    }
+
+    /// Return if the channel can't be tuned to by entering its channel number.
+    pub fn hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Return the type of service carried on the channel.
+    pub fn service_type(&self) -> u8 {
+        self.service_type
+    }
+
+    /// Return the ID that links the channel to its events in the EIT.
+    pub fn source_id(&self) -> u16 {
+        self.source_id
+    }
+
+    /// Return the descriptors that describe the channel.
+    pub fn descriptors(&self) -> &[Descriptor] {
+        &self.descriptors
+    }
+}
+
+/// The ATSC Virtual Channel Table which lists the channels carried in a transport stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VirtualChannelTable {
+    /// Table ID of the section, which tells a terrestrial table apart from a cable one.
+    table_id: u8,
+    /// ID of the transport stream that this table describes.
+    transport_stream_id: u16,
+    /// Version of this table. This is incremented every time the table changes.
+    version_number: u8,
+    /// Set when this table currently applies. If it isn't set then this is the next version of the
+    /// table that will apply.
+    current_next_indicator: bool,
+    /// Version of the PSIP protocol that this table follows.
+    protocol_version: u8,
+    /// Channels that are listed in this table.
+    channels: Vec<VirtualChannel>,
+    /// Descriptors that describe the whole table.
+    additional_descriptors: Vec<Descriptor>,
+}
+
+impl VirtualChannelTable {
+    /// Parse a TVCT or CVCT from a complete section.
+    ///
+    /// The section should start with the table ID and end with the CRC, which is what a
+    /// [`SectionAssembler`](crate::psi::section::SectionAssembler) returns.
+    pub fn from_bytes(section: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let table_id = match section.first() {
+            Some(&CVCT_TABLE_ID) => CVCT_TABLE_ID,
+            _ => TVCT_TABLE_ID,
+        };
+        let section = LongSection::parse(section, table_id)?;
+        let body = section.body;
+
+        if body.len() < VCT_HEADER_SIZE {
+            return Err(Box::new(NotEnoughData { expected: VCT_HEADER_SIZE, found: body.len() }))
+        }
+
+        let protocol_version = body[0];
+        let num_channels = body[1];
+
+        let mut read_idx = VCT_HEADER_SIZE;
+        let mut channels = Vec::with_capacity(num_channels as usize);
+        for _ in 0..num_channels {
+            if body.len() < read_idx + CHANNEL_HEADER_SIZE {
+                return Err(Box::new(NotEnoughData {
+                    expected: read_idx + CHANNEL_HEADER_SIZE,
+                    found: body.len(),
+                }))
+            }
+
+            let header = &body[read_idx..read_idx + CHANNEL_HEADER_SIZE];
+            let bits = header.view_bits::<Msb0>();
+            let descriptors_length: usize = bits[246..256].load_be();
+            let descriptors_start = read_idx + CHANNEL_HEADER_SIZE;
+            let descriptors_end = descriptors_start + descriptors_length;
+
+            if descriptors_end > body.len() {
+                return Err(Box::new(NotEnoughData { expected: descriptors_end, found: body.len() }))
+            }
+
+            channels.push(VirtualChannel {
+                short_name: decode_short_name(&header[..SHORT_NAME_SIZE]),
+                major_channel_number: bits[116..126].load_be(),
+                minor_channel_number: bits[126..136].load_be(),
+                modulation_mode: bits[136..144].load_be(),
+                channel_tsid: bits[176..192].load_be(),
+                program_number: bits[192..208].load_be(),
+                access_controlled: bits[210],
+                hidden: bits[211],
+                service_type: bits[218..224].load_be(),
+                source_id: bits[224..240].load_be(),
+                descriptors: Descriptor::parse_loop(&body[descriptors_start..descriptors_end])?,
+            });
+
+            read_idx = descriptors_end;
+        }
+
+        if body.len() < read_idx + DESCRIPTORS_LENGTH_SIZE {
+            return Err(Box::new(NotEnoughData {
+                expected: read_idx + DESCRIPTORS_LENGTH_SIZE,
+                found: body.len(),
+            }))
+        }
+
+        let bits = body[read_idx..read_idx + DESCRIPTORS_LENGTH_SIZE].view_bits::<Msb0>();
+        let descriptors_start = read_idx + DESCRIPTORS_LENGTH_SIZE;
+        let descriptors_end = descriptors_start + bits[6..16].load_be::<usize>();
+        if descriptors_end > body.len() {
+            return Err(Box::new(NotEnoughData { expected: descriptors_end, found: body.len() }))
+        }
+
+        Ok(VirtualChannelTable {
+            table_id,
+            transport_stream_id: section.table_id_extension,
+            version_number: section.version_number,
+            current_next_indicator: section.current_next_indicator,
+            protocol_version,
+            channels,
+            additional_descriptors: Descriptor::parse_loop(
+                &body[descriptors_start..descriptors_end])?,
+        })
+    }
+
+    /// Return if this is a Cable Virtual Channel Table rather than a terrestrial one.
+    pub fn is_cable(&self) -> bool {
+        self.table_id == CVCT_TABLE_ID
+    }
+
+    /// Return the ID of the transport stream that this table describes.
+    pub fn transport_stream_id(&self) -> u16 {
+        self.transport_stream_id
+    }
+
+    /// Return the version of this table.
+    pub fn version_number(&self) -> u8 {
+        self.version_number
+    }
+
+    /// Return if this table currently applies.
+    pub fn current_next_indicator(&self) -> bool {
+        self.current_next_indicator
+    }
+
+    /// Return the version of the PSIP protocol that this table follows.
+    pub fn protocol_version(&self) -> u8 {
+        self.protocol_version
+    }
+
+    /// Return the channels that are listed in this table.
+    pub fn channels(&self) -> &[VirtualChannel] {
+        &self.channels
+    }
+
+    /// Return the descriptors that describe the whole table.
+    pub fn additional_descriptors(&self) -> &[Descriptor] {
+        &self.additional_descriptors
+    }
+}
+
+/// Decode a short name from its UTF-16 code units, removing the null characters that pad it.
+///
+/// Invalid code units are replaced with `U+FFFD` rather than failing the whole table.
+fn decode_short_name(buf: &[u8]) -> String {
+    let units = buf.chunks_exact(2).map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+    let name: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    String::from(name.trim_end_matches('\0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::invalid_table_id::InvalidTableId;
+    use test_case::test_case;
+
+    /// Build a VCT section with a single channel `7.1` named `KABC`.
+    fn vct_section(table_id: u8) -> Vec<u8> {
+        let mut section = vec![
+            table_id, 0xF0, 0x2D, 0x12, 0x34, 0xC1, 0x00, 0x00, 0x00, 0x01, 0x00, b'K', 0x00,
+            b'A', 0x00, b'B', 0x00, b'C', 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x1C, 0x01,
+            0x04, 0x00, 0x00, 0x00, 0x00, 0x12, 0x34, 0x00, 0x03, 0x0D, 0xC2, 0x00, 0x05, 0xFC,
+            0x00, 0xFC, 0x00,
+        ];
+        let crc = crate::psi::crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        section
+    }
+
+    #[test_case(TVCT_TABLE_ID, false; "Terrestrial")]
+    #[test_case(CVCT_TABLE_ID, true; "Cable")]
+    fn from_bytes(table_id: u8, cable: bool) {
+        let vct = VirtualChannelTable::from_bytes(&vct_section(table_id)).unwrap();
+
+        assert_eq!(vct.is_cable(), cable, "Table type is incorrect");
+        assert_eq!(vct.transport_stream_id(), 0x1234, "Transport stream ID is incorrect");
+        assert_eq!(vct.version_number(), 0, "Version number is incorrect");
+        assert_eq!(vct.protocol_version(), 0, "Protocol version is incorrect");
+        assert_eq!(vct.channels().len(), 1, "Incorrect number of channels");
+
+        let channel = &vct.channels()[0];
+        assert_eq!(channel.short_name(), "KABC", "Short name is incorrect");
+        assert_eq!(channel.major_channel_number(), 7, "Major channel number is incorrect");
+        assert_eq!(channel.minor_channel_number(), 1, "Minor channel number is incorrect");
+        assert_eq!(channel.modulation_mode(), 0x04, "Modulation mode is incorrect");
+        assert_eq!(channel.channel_tsid(), 0x1234, "Channel TSID is incorrect");
+        assert_eq!(channel.program_number(), 3, "Program number is incorrect");
+        assert!(!channel.access_controlled(), "Access controlled is incorrect");
+        assert!(!channel.hidden(), "Hidden is incorrect");
+        assert_eq!(channel.service_type(), 0x02, "Service type is incorrect");
+        assert_eq!(channel.source_id(), 5, "Source ID is incorrect");
+        assert!(vct.additional_descriptors().is_empty(), "Additional descriptors are incorrect");
+    }
+
+    #[test]
+    fn from_bytes_invalid_table_id() {
+        let result = VirtualChannelTable::from_bytes(&vct_section(0xC7));
+        assert!(result.is_err_and(|e| e.is::<InvalidTableId>()), "Wrong table ID was not detected");
+    }
+
+    #[test_case(&[0x00, 0x41, 0x00, 0x42, 0x00, 0x00], "AB"; "Null padding")]
+    #[test_case(&[0x00, 0x41, 0xD8, 0x00, 0x00, 0x42], "A\u{FFFD}B"; "Unpaired surrogate")]
+    fn short_name(buf: &[u8], expected: &str) {
+        assert_eq!(decode_short_name(buf), expected, "Short name is incorrect");
+    }
+}
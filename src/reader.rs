@@ -1,110 +1,375 @@
 //! A module for reading the transport stream.
+//!
+//! Methods that read from the file return a `Result` so that I/O and parsing errors can be told
+//! apart from reaching the end of the file. Most of them also have an `_unchecked` counterpart
+//! that returns an `Option` instead and treats any error as the end of the file, for more concise
+//! code where the difference doesn't matter. Iterators follow the same pattern by yielding
+//! `Result`s, with their `_unchecked` counterparts yielding the values until the first error.
+//!
+//! # Sources
+//!
+//! A [`TSReader`] can read from any source that implements [`Read`], so it doesn't need to own a
+//! `File`. Sources that can't seek, including any [`BufRead`] such as a `&[u8]`, a
+//! `BufReader<TcpStream>`, or stdin, are read from start to finish with
+//! [`TSReader::from_stream`]. Almost every method works the same for these sources. Only the
+//! following need the source to implement [`Seek`] as well:
+//! - [`TSReader::new`] and [`TSReader::with_layout`], which rewind after detecting the packet
+//!   layout and measure the length of the source for [`TSReader::alignment_remainder`].
+//! - [`TSReader::set_window`], [`TSReader::seek_to_packet`], [`TSReader::seek_to_pcr`], and
+//!   [`TSReader::reset`], which jump to a different part of the source.
+//! - [`TSReader::verify_alignment`], which skips from one packet's SYNC byte to the next.
+//!
+//! [`TSReader`]: crate::reader::TSReader
+//! [`Read`]: std::io::Read
+//! [`BufRead`]: std::io::BufRead
+//! [`Seek`]: std::io::Seek
+//! [`TSReader::from_stream`]: crate::reader::TSReader::from_stream
+//! [`TSReader::new`]: crate::reader::TSReader::new
+//! [`TSReader::with_layout`]: crate::reader::TSReader::with_layout
+//! [`TSReader::alignment_remainder`]: crate::reader::TSReader::alignment_remainder
+//! [`TSReader::set_window`]: crate::reader::TSReader::set_window
+//! [`TSReader::seek_to_packet`]: crate::reader::TSReader::seek_to_packet
+//! [`TSReader::seek_to_pcr`]: crate::reader::TSReader::seek_to_pcr
+//! [`TSReader::reset`]: crate::reader::TSReader::reset
+//! [`TSReader::verify_alignment`]: crate::reader::TSReader::verify_alignment
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Read, Seek, SeekFrom, StdinLock, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::demux::{self, DemuxResult, DemuxedStream, PayloadKind};
+use crate::errors::empty_window::EmptyWindow;
+use crate::errors::missing_pcr::MissingPcr;
 use crate::errors::no_sync_byte_found::NoSyncByteFound;
-use crate::packet::{TSPacket, PACKET_SIZE};
-use crate::packet::header::SYNC_BYTE;
-use crate::packet::payload::TSPayload;
-use crate::helpers::tracked_payload::{self, TrackedPayload};
+use crate::errors::packet_out_of_range::PacketOutOfRange;
+use crate::errors::sync_lost::SyncLost;
+use crate::packet::{TSPacket, NULL_PACKET_PID};
+use crate::packet::adaptation_field::{pcr_difference, PCR_FREQUENCY, PCR_WRAP};
+use crate::packet::layout::{
+    detect_layout, find_sync, packet_start, parse_packet, probe_buffer_len, PacketLayout,
+    SYNC_SEARCH_CHUNK_SIZE,
+};
+use crate::packet::header::{CONTINUITY_COUNTER_MODULUS, SYNC_BYTE};
+use crate::helpers::tracked_continuity::TrackedContinuity;
+use crate::helpers::tracked_payload::TrackedPayload;
+use crate::helpers::tracked_pcr_clock::TrackedPcrClock;
+use crate::helpers::tracked_programs::TrackedPrograms;
+use crate::klv::KlvUnit;
+use crate::pes::{self, PesPacket, PTS_FREQUENCY};
+use crate::program::{ProgramInfo, StructureDefect};
+use crate::psi::{self, StreamProfile, PAT_PID};
+use crate::psi::section::SectionAssembler;
+use crate::segment::{self, Segment, PLAYLIST_FILENAME};
+use crate::splice::{SplicePhase, SplicePoint};
+use crate::stats::{
+    AlignmentReport, BitrateProfile, BitrateWindow, ContinuityAnomaly, ContinuityBreakdown,
+    OverheadRatio, PacketStats, PcrIntervalHistogram, PtsDiscontinuity, PtsPcrDelay,
+    RecoveryReport, StreamStats,
+};
+
+#[cfg(feature = "async")]
+use std::thread;
+#[cfg(feature = "async")]
+use tokio::sync::mpsc;
 
 #[cfg(feature = "log")]
-use log::{info,debug,trace};
-use memmem::{Searcher, TwoWaySearcher};
+use log::{info,debug,trace,warn};
+
+pub use crate::packet::layout::PROBE_PACKET_COUNT;
+pub use crate::packet::payload::CompletedPayload;
+
+/// Number of packets that [`TSReader::detect_profile`] reads before giving up. This covers a few
+/// seconds of a typical broadcast multiplex, which is longer than the maximum interval between
+/// repetitions of the DVB SDT and the ATSC MGT.
+pub const PROFILE_PROBE_PACKET_COUNT: usize = 100_000;
+
+/// Header line written by [`TSReader::write_summary`], naming the tab separated columns.
+pub const SUMMARY_HEADER: &str = "offset\tpid\tcc\tpusi\ttei\tpriority\ttsc\tafc\tdisc\trai\tpcr";
+
+/// Callback that is run when a splice point is found.
+type SpliceCallback = Box<dyn FnMut(&SplicePoint) + Send>;
+
+/// Callback that is run with the payload data of every packet on a PID.
+type PayloadChunkCallback = Box<dyn FnMut(&PayloadChunk<'_>) + Send>;
+
+/// Section assembler of a PID along with the completed sections that haven't been returned yet.
+type TrackedSections = (SectionAssembler, VecDeque<Box<[u8]>>);
+
+/// Completed payloads of each PID, in the order they were completed.
+type DemuxedPayloads = HashMap<u16, Vec<Box<[u8]>>>;
+
+/// Packets of the most recent table sent on each PAT and PMT PID.
+type PsiPackets = HashMap<u16, Vec<Box<[u8]>>>;
+
+/// Number, PID, and PCR of a packet that carries a PCR.
+type PcrPacket = (u64, u16, u64);
+
+/// Index of the byte in the packet header that holds the continuity counter in its low 4 bits.
+const CONTINUITY_COUNTER_BYTE: usize = 3;
 
 /// Struct used for holding information related to reading the transport stream.
-pub struct TSReader {
+///
+/// Any source that implements [`Read`] can be used, including sources that can't seek such as
+/// stdin. See [`TSReader::from_stream`].
+pub struct TSReader<R> {
     /// Filename for the file being read. Only really used for logging.
+    #[cfg_attr(not(feature = "log"), allow(dead_code))]
     filename: String,
-    /// Buffered reader for the transport stream file.
-    buf_reader: BufReader<File>,
-    /// Sync byte alignment. A Sync byte should be found every `PACKET_SIZE` away.
+    /// Reader for the transport stream data.
+    reader: R,
+    /// Bytes that were read from the reader while searching for the first SYNC byte but haven't
+    /// been consumed yet. These are read before any more data is read from `reader`.
+    pending: Vec<u8>,
+    /// Sync byte alignment. A Sync byte should be found every `layout.stride()` away.
     sync_alignment: u64,
-    /// Counter of the number of packets read
+    /// Layout of the packets in the transport stream file.
+    layout: PacketLayout,
+    /// Byte offset in the file of the next packet to be read.
+    offset: u64,
+    /// Byte offset in the file of the packet that was most recently read.
+    current_offset: u64,
+    /// Number of bytes after the last complete packet in the file.
+    ///
+    /// Is `None` if the reader was created from a stream, since its length isn't known.
+    alignment_remainder: Option<u64>,
+    /// Byte offset in the file that reading stops at.
+    ///
+    /// Is `None` if the whole file should be read.
+    window_end: Option<u64>,
+    /// Counter of the number of packets read.
+    ///
+    /// This is set to the packet number when seeking to a packet.
     packets_read: u64,
+    /// Counter of the number of bytes read from the transport stream packets.
+    bytes_read: u64,
+    /// Counter of the number of payload data bytes read from packets that are not null packets.
+    payload_bytes_read: u64,
+    /// Counts of how the packets that have been read are structured.
+    stream_stats: StreamStats,
+    /// Counter of the number of packets read on each PID.
+    pid_packets_read: HashMap<u16, u64>,
+    /// Counter of the number of packets read on each PID that had the `PUSI` flag set.
+    pid_unit_starts: HashMap<u16, u64>,
+    /// Counter of the number of packets read on each PID that had the transport priority set.
+    pid_priority_packets: HashMap<u16, u64>,
     /// PIDs that should be tracked when querying for packets or payloads.
     /// 
     /// If empty, all PIDs are tracked. This will use more memory as there are more
     /// incomplete payloads to keep track of.
     tracked_pids: Vec<u16>,
+    /// Whether [`TSReader::next_packet`] skips null packets, regardless of the tracked PIDs.
+    skip_null_packets: bool,
+    /// Whether reading scans forward for the next SYNC byte when a packet doesn't start with one,
+    /// instead of returning a [`SyncLost`] error.
+    resync_on_error: bool,
     /// Payloads that are currently being tracked by the reader.
     tracked_payloads: Vec<TrackedPayload>,
+    /// Size in bytes of the largest payload completed on each PID.
+    max_payload_sizes: HashMap<u16, usize>,
+    /// PAT and PMTs that have been found while reading. These are tracked regardless of which PIDs
+    /// are being tracked.
+    programs: TrackedPrograms,
+    /// Section assemblers and the completed sections that haven't been returned yet for every PID
+    /// that [`TSReader::next_section`] has been called for.
+    tracked_sections: HashMap<u16, TrackedSections>,
+    /// Continuity counter checks of every PID. These are tracked regardless of which PIDs are being
+    /// tracked.
+    continuity: TrackedContinuity,
+    /// Measures the PCR clock frequency against the arrival timestamps of M2TS packets.
+    pcr_clock: TrackedPcrClock,
+    /// Callbacks to run when a splice point is found on a PID.
+    splice_callbacks: Vec<(u16, SpliceCallback)>,
+    /// Splice phase of the most recent packet on each PID that carried a splice countdown.
+    splice_phases: HashMap<u16, SplicePhase>,
+    /// Callbacks to run with the payload data of every packet on a PID.
+    payload_chunk_callbacks: Vec<(u16, PayloadChunkCallback)>,
+    /// PIDs whose current payload unit lost a packet or had a scrambled packet, so their payload
+    /// chunks aren't delivered until the next unit starts.
+    interrupted_chunk_pids: HashSet<u16>,
 }
 
-impl TSReader {
+impl<R: Read + Seek> TSReader<R> {
 
-    /// Create a new TSReader instance using the given file.
+    /// Create a new TSReader instance using the given file or other seekable source, such as an
+    /// in-memory `Cursor`.
     ///
-    /// This function also finds the first SYNC byte, so we can determine the alignment of the
-    /// transport packets.
+    /// The packet layout is detected with [`TSReader::probe_format`], so plain 188-byte packets,
+    /// 192-byte M2TS packets, and 204-byte Reed-Solomon packets can all be read. Sources that are
+    /// too short to detect are read as plain transport stream packets. The detected layout is
+    /// returned by [`TSReader::packet_layout`]. This function also finds the first SYNC byte, so we
+    /// can determine the alignment of the transport packets. Use [`TSReader::from_stream`] for
+    /// sources that can't seek.
     /// # Parameters
-    /// - `buf_reader`: a buffered reader that contains transport stream data.
-    pub fn new(filename: &str, mut buf_reader: BufReader<File>) -> Result<Self, Box<dyn Error>> {
-        // Find the first sync byte, so we can search easier by doing simple `PACKET_SIZE` buffer
-        // reads.
-        let mut read_buf = [0];
-        let sync_alignment: u64;
+    /// - `filename`: name of the source. Only used for logging.
+    /// - `reader`: a reader that contains transport stream data. Reading will be faster if it is
+    ///   buffered, such as a `BufReader<File>`.
+    pub fn new(filename: &str, mut reader: R) -> Result<Self, Box<dyn Error>> {
+        let start = reader.stream_position()?;
+        let layout = Self::probe_format(&mut reader)?.unwrap_or_default();
+        reader.seek(SeekFrom::Start(start))?;
 
-        loop {
-            let count = buf_reader.read(&mut read_buf)?;
+        #[cfg(feature = "log")]
+        debug!("Detected packet layout {:?} for file {}", layout, filename);
 
-            // Return a `NoSyncByteFound` error if no SYNC byte could be found in the reader.
-            if count == 0 {
-                return Err(Box::new(NoSyncByteFound));
-            }
+        Self::with_layout(filename, reader, layout)
+    }
 
-            // Run through this loop until we find a sync byte.
-            if read_buf[0] != SYNC_BYTE {
-                continue
-            }
+    /// Create a new TSReader instance using the given file or other seekable source and packet
+    /// layout.
+    ///
+    /// This should be used when the packets in the file are not plain 188-byte transport stream
+    /// packets. The SYNC byte search uses the stride of the given layout and only the transport
+    /// stream portion of each packet is parsed.
+    /// # Parameters
+    /// - `filename`: name of the source. Only used for logging.
+    /// - `reader`: a reader that contains transport stream data.
+    /// - `layout`: the layout of each packet in the file.
+    pub fn with_layout(
+        filename: &str,
+        reader: R,
+        layout: PacketLayout,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut ts_reader = Self::from_stream_with_layout(filename, reader, layout)?;
+
+        // Measure the file now while nothing else depends on the read position, then go back to
+        // where the SYNC byte search left off.
+        let position = ts_reader.offset + ts_reader.pending.len() as u64;
+        let end = ts_reader.reader.seek(SeekFrom::End(0))?;
+        ts_reader.reader.seek(SeekFrom::Start(position))?;
+        let remainder = end.saturating_sub(ts_reader.offset) % ts_reader.layout.stride() as u64;
+        ts_reader.alignment_remainder = Some(remainder);
+
+        #[cfg(feature = "log")]
+        debug!("File {} has {} bytes after its last complete packet", filename, remainder);
+
+        Ok(ts_reader)
+    }
+}
+
+impl TSReader<StdinLock<'static>> {
+    /// Create a new TSReader instance that reads from stdin.
+    ///
+    /// This is useful for shell pipelines such as `cat stream.ts | mytool`. Stdin can't seek, so
+    /// this goes through [`TSReader::from_stream`] and has the same limitations: the stream can
+    /// only be read once from start to finish, and offsets are counted from the first byte read
+    /// from stdin. Stdin stays locked for as long as the reader exists.
+    pub fn from_stdin() -> Result<Self, Box<dyn Error>> {
+        Self::from_stream("stdin", io::stdin().lock())
+    }
+}
+
+impl<R: Read> TSReader<R> {
+    /// Create a new TSReader instance that reads from any source, even one that can't seek.
+    ///
+    /// The SYNC byte search reads ahead instead of seeking, so this works with pipes, sockets, and
+    /// decompression readers, as well as in-memory data such as a `&[u8]`. The source doesn't need
+    /// to be buffered, but reading will be faster if it is. Offsets are counted from the first byte
+    /// read from the source. See the [module documentation](self) for the methods that can't be
+    /// used without [`Seek`].
+    /// # Parameters
+    /// - `filename`: name of the source. Only used for logging.
+    /// - `reader`: source of the transport stream data.
+    pub fn from_stream(filename: &str, reader: R) -> Result<Self, Box<dyn Error>> {
+        Self::from_stream_with_layout(filename, reader, PacketLayout::default())
+    }
+
+    /// Create a new TSReader instance that reads from any source using the given packet layout.
+    ///
+    /// See [`TSReader::from_stream`] and [`TSReader::with_layout`].
+    /// # Parameters
+    /// - `filename`: name of the source. Only used for logging.
+    /// - `reader`: source of the transport stream data.
+    /// - `layout`: the layout of each packet in the source.
+    pub fn from_stream_with_layout(
+        filename: &str,
+        mut reader: R,
+        layout: PacketLayout,
+    ) -> Result<Self, Box<dyn Error>> {
+        let stride = layout.stride();
 
-            // Note the location of this SYNC byte for later
-            let sync_pos = buf_reader.stream_position().expect("Couldn't get stream position from BufReader");
+        // Bytes that have been read from the reader but not consumed. `discarded` is the number of
+        // bytes that came before the start of this buffer, so `discarded + idx` is the offset of
+        // `buf[idx]` in the stream.
+        let mut buf: Vec<u8> = Vec::new();
+        let mut discarded: u64 = 0;
 
+        // Find the first sync byte, so we can search easier by doing simple stride sized buffer
+        // reads. A packet too close to the start for its prefix is skipped by `packet_start`.
+        let Some(idx) = search_for_sync(&mut reader, &mut buf, &mut discarded, 0, stride, 0)? else {
+            // Return a `NoSyncByteFound` error if no SYNC byte could be found in the reader.
             #[cfg(feature = "log")]
-            trace!("SYNC found at position {} for file {}", sync_pos, filename);
-
-            // If we think this is the correct alignment because we have found a SYNC byte we need
-            // to verify that this is correct by seeking 1 `PACKET_SIZE` away and verifying a SYNC
-            // byte is there. If there isn't one there then this is simply the same data as a SYNC
-            // byte by coincidence, and we need to keep looking.
-            //
-            // There is always the possibility that we hit a `0x47` in the payload, seek 1
-            // `PACKET_SIZE` further, and find another `0x47` but I don't have a way of accounting
-            // for that, so we're going with blind hope that this case doesn't get seen.
-            buf_reader.seek_relative(PACKET_SIZE as i64 - 1)?;
-            let count = buf_reader.read(&mut read_buf)?;
-
-            // If we run out of data to read while trying to verify that the SYNC byte is actually a
-            // SYNC byte and isn't part of a payload then we'll simply assume that it really is a
-            // SYNC byte as we have nothing else to go off of.
-            if count == 0 {
-                #[cfg(feature = "log")]
-                debug!("Could not find SYNC byte in file {}", filename);
-                return Err(Box::new(NoSyncByteFound));
-            }
+            debug!("Could not find SYNC byte in file {}", filename);
+            return Err(Box::new(NoSyncByteFound));
+        };
 
-            // Seek back to the original location for later reading.
-            buf_reader.seek(SeekFrom::Start(sync_pos - 1))?;
+        let sync_offset = discarded + idx as u64;
+        let sync_alignment = sync_offset + 1;
 
-            // If the byte 1 `PACKET_SIZE` away is also a SYNC byte we can be relatively sure that
-            // this alignment is correct.
-            if read_buf[0] == SYNC_BYTE {
-                sync_alignment = sync_pos;
-                break
-            }
-        }
+        #[cfg(feature = "log")]
+        trace!("SYNC found at position {} for file {}", sync_alignment, filename);
+
+        let packet_start = packet_start(sync_offset, layout);
+        buf.drain(..(packet_start - discarded) as usize);
 
         Ok(TSReader {
             filename: filename.to_string(),
-            buf_reader,
+            reader,
+            pending: buf,
             sync_alignment,
+            layout,
+            offset: packet_start,
+            current_offset: packet_start,
+            alignment_remainder: None,
+            window_end: None,
             packets_read: 0,
+            bytes_read: 0,
+            payload_bytes_read: 0,
+            stream_stats: StreamStats::default(),
+            pid_packets_read: HashMap::new(),
+            pid_unit_starts: HashMap::new(),
+            pid_priority_packets: HashMap::new(),
             tracked_pids: Vec::new(),
+            skip_null_packets: false,
+            resync_on_error: false,
             tracked_payloads: Vec::new(),
+            max_payload_sizes: HashMap::new(),
+            tracked_sections: HashMap::new(),
+            programs: TrackedPrograms::new(),
+            continuity: TrackedContinuity::new(),
+            pcr_clock: TrackedPcrClock::new(),
+            splice_callbacks: Vec::new(),
+            splice_phases: HashMap::new(),
+            payload_chunk_callbacks: Vec::new(),
+            interrupted_chunk_pids: HashSet::new(),
         })
     }
 
+    /// Detect the packet layout of a source without parsing any packets.
+    ///
+    /// This checks whether SYNC bytes recur at a consistent stride over the first
+    /// [`PROBE_PACKET_COUNT`] packets for each of the plain TS, M2TS, and Reed-Solomon layouts. It is
+    /// useful for validating user supplied files before committing to reading them. At most
+    /// `PROBE_PACKET_COUNT + 1` packets of the largest layout are read from the source.
+    /// # Returns
+    /// `Ok(Some(PacketLayout))` with the detected layout.
+    /// `Ok(None)` if the source does not look like a transport stream.
+    pub fn probe_format(reader: &mut R) -> Result<Option<PacketLayout>, Box<dyn Error>> {
+        let mut buf = Vec::with_capacity(probe_buffer_len());
+        reader.take(buf.capacity() as u64).read_to_end(&mut buf)?;
+
+        Ok(detect_layout(&buf))
+    }
+
+    /// Return if a source looks like a transport stream of any layout supported by
+    /// [`TSReader::probe_format`].
+    ///
+    /// Sources that can't be read are not valid transport streams.
+    pub fn is_valid_ts(reader: &mut R) -> bool {
+        matches!(Self::probe_format(reader), Ok(Some(_)))
+    }
+
     /// Read the next packet from the transport stream file.
     ///
     /// This function returns `None` for any `Err` in order to prevent the need for `.unwrap()`
@@ -114,7 +379,7 @@ impl TSReader {
     /// `None` if the next transport stream packet could not be parsed from the file for any
     /// reason. This includes if the entire file has been fully read.
     pub fn next_packet_unchecked(&mut self) -> Option<TSPacket> {
-        self.next_packet().unwrap_or_else(|_| None)
+        self.next_packet().unwrap_or(None)
     }
 
     /// Read the next packet from the transport stream file.
@@ -122,51 +387,346 @@ impl TSReader {
     /// `Ok(Some(TSPacket))` if the next transport stream packet could be parsed from the file.
     /// `Ok(None)` if there was no issue reading the file and no more TS packets can be read.
     pub fn next_packet(&mut self) -> Result<Option<TSPacket>, Box<dyn Error>> {
-        let mut packet_buf = [0; PACKET_SIZE];
         loop {
-            match self.buf_reader.read_exact(&mut packet_buf) {
-                Ok(_) => {},
-                Err(e) => {
-                    if e.kind() == ErrorKind::UnexpectedEof {
-                        #[cfg(feature = "log")]
-                        {
-                            info!("Finished reading file {}", self.filename);
-                        }
-                        return Ok(None);
+            let Some(packet) = self.read_packet()? else {
+                return Ok(None);
+            };
+
+            // We should only return a packet if it is in the tracked PIDs (or there are no tracked
+            // PIDs)
+            if ! self.tracked_pids.is_empty() && ! self.tracked_pids.contains(&packet.header().pid()) {
+                continue
+            }
+
+            if self.skip_null_packets && packet.header().pid() == NULL_PACKET_PID {
+                continue
+            }
+
+            return Ok(Some(packet));
+        }
+    }
+
+    /// Read the next packet from the transport stream file without consuming it.
+    ///
+    /// This function returns `None` for any `Err` in order to prevent the need for `.unwrap()`
+    /// calls in more concise code.
+    pub fn peek_packet_unchecked(&mut self) -> Option<TSPacket> {
+        self.peek_packet().unwrap_or(None)
+    }
+
+    /// Read the next packet from the transport stream file without consuming it.
+    ///
+    /// The packet's bytes are kept by the reader, so the next read returns the same packet. This
+    /// works for sources that can't seek as well. Peeking doesn't count towards
+    /// [`TSReader::packets_read`] or any other statistics, which are only updated once the packet
+    /// is read. Packets are peeked regardless of which PIDs are being tracked, so
+    /// [`TSReader::next_packet`] skips the peeked packet if its PID isn't tracked.
+    ///
+    /// A packet that doesn't start with a SYNC byte is handled the same way as when it is read, as
+    /// set by [`TSReader::set_resync_on_error`]. A [`SyncLost`] error leaves the packet unread,
+    /// while resyncing skips past the bytes before the next packet.
+    /// # Returns
+    /// `Ok(Some(TSPacket))` if the next transport stream packet could be parsed from the file.
+    /// `Ok(None)` if there was no issue reading the file and no more TS packets can be read.
+    pub fn peek_packet(&mut self) -> Result<Option<TSPacket>, Box<dyn Error>> {
+        if self.window_end.is_some_and(|end| self.offset + self.layout.stride() as u64 > end) {
+            return Ok(None);
+        }
+
+        let mut packet_buf = vec![0; self.layout.stride()];
+        if let Err(e) = self.read_exact(&mut packet_buf) {
+            if e.kind() == ErrorKind::UnexpectedEof {
+                return Ok(None);
+            }
+
+            return Err(Box::new(e));
+        }
+
+        // Put the bytes back in front of anything else that hasn't been consumed yet.
+        self.pending.splice(..0, packet_buf.iter().copied());
+
+        // The packet is checked the same way as when it is read, so that peeking at a packet that
+        // lost its SYNC byte doesn't hide the error.
+        if packet_buf[self.layout.prefix()] != SYNC_BYTE {
+            if ! self.handle_sync_lost()? {
+                return Ok(None);
+            }
+            return self.peek_packet();
+        }
+
+        #[cfg(feature = "log")]
+        trace!("Peeked packet at offset {} in file {}", self.offset, self.filename);
+
+        parse_packet(&packet_buf, self.layout, self.offset).map(Some)
+    }
+
+    /// Read packets until one on the given PID is found.
+    ///
+    /// This function returns `None` for any `Err` in order to prevent the need for `.unwrap()`
+    /// calls in more concise code.
+    pub fn find_next_pid_unchecked(&mut self, pid: u16) -> Option<TSPacket> {
+        self.find_next_pid(pid).unwrap_or(None)
+    }
+
+    /// Read packets until one on the given PID is found.
+    ///
+    /// Packets are read regardless of which PIDs are being tracked, and the tracked PIDs aren't
+    /// changed. The reader is left right after the returned packet, so reading can continue from
+    /// there.
+    /// # Returns
+    /// `Ok(Some(TSPacket))` with the first packet on the PID.
+    /// `Ok(None)` if the end of the file was reached without finding a packet on the PID.
+    pub fn find_next_pid(&mut self, pid: u16) -> Result<Option<TSPacket>, Box<dyn Error>> {
+        while let Some(packet) = self.read_packet()? {
+            if packet.header().pid() == pid {
+                return Ok(Some(packet));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Read the next packet from the transport stream file regardless of which PIDs are tracked.
+    ///
+    /// Every packet read from the file must go through this function so that the reader's
+    /// statistics and program tables stay up-to-date.
+    fn read_packet(&mut self) -> Result<Option<TSPacket>, Box<dyn Error>> {
+        if self.window_end.is_some_and(|end| self.offset + self.layout.stride() as u64 > end) {
+            #[cfg(feature = "log")]
+            info!("Reached the end of the window in file {}", self.filename);
+            return Ok(None);
+        }
+
+        let mut packet_buf = vec![0; self.layout.stride()];
+        match self.read_exact(&mut packet_buf) {
+            Ok(_) => {},
+            Err(e) => {
+                if e.kind() == ErrorKind::UnexpectedEof {
+                    #[cfg(feature = "log")]
+                    {
+                        info!("Finished reading file {}", self.filename);
                     }
+                    return Ok(None);
+                }
 
-                    return Err(Box::new(e));
-                },
+                return Err(Box::new(e));
+            },
+        }
+
+        // The alignment can drift partway through a file, so every packet has to be checked.
+        if packet_buf[self.layout.prefix()] != SYNC_BYTE {
+            self.pending.splice(..0, packet_buf.iter().copied());
+            if ! self.handle_sync_lost()? {
+                return Ok(None);
+            }
+            return self.read_packet();
+        }
+
+        self.packets_read += 1;
+        self.bytes_read += packet_buf.len() as u64;
+        self.current_offset = self.offset;
+        self.offset += packet_buf.len() as u64;
+        #[cfg(feature = "log")]
+        trace!("Read packet at offset {} in file {}", self.current_offset, self.filename);
+        #[cfg(feature = "log")]
+        trace!("Packets read in file {}: {}", self.filename, self.packets_read);
+
+        let packet = match parse_packet(&packet_buf, self.layout, self.current_offset) {
+            Ok(packet) => packet,
+            Err(e) => {
+                #[cfg(feature = "log")]
+                debug!("Got error from {} when trying to parse next packet from bytes {:2X?}",
+                    self.filename, packet_buf);
+                return Err(e)
+            },
+        };
+
+        *self.pid_packets_read.entry(packet.header().pid()).or_default() += 1;
+        if packet.header().pusi() && packet.has_payload() {
+            *self.pid_unit_starts.entry(packet.header().pid()).or_default() += 1;
+        }
+        if packet.header().transport_priority() {
+            *self.pid_priority_packets.entry(packet.header().pid()).or_default() += 1;
+        }
+        self.stream_stats.observe(&packet);
+
+        if packet.header().pid() != NULL_PACKET_PID {
+            if let Some(payload) = packet.payload() {
+                self.payload_bytes_read += payload.data().len() as u64;
+            }
+        }
+
+        self.programs.observe(&packet);
+        let continuity_anomaly = self.continuity.observe(&packet);
+
+        if let Some(arrival_timestamp) = packet.arrival_timestamp() {
+            if let Some(adaptation_field) = packet.data_adaptation_field() {
+                if let Some(pcr) = adaptation_field.pcr() {
+                    self.pcr_clock.observe(packet.header().pid(), arrival_timestamp, pcr,
+                        adaptation_field.discontinuity_indicator());
+                }
+            }
+        }
+
+        if let Some((assembler, sections)) = self.tracked_sections.get_mut(&packet.header().pid()) {
+            if let Some(payload) = packet.payload() {
+                sections.extend(assembler.push(&payload));
             }
+        }
+
+        // A countdown of `0` that is repeated, such as by a duplicate packet, is still the same
+        // splice point, so it is only reported the first time.
+        let previous_phase = match SplicePhase::from_packet(&packet) {
+            Some(splice_phase) => self.splice_phases.insert(packet.header().pid(), splice_phase),
+            None => None,
+        };
 
+        let splice_point = SplicePoint::from_packet(&packet, self.current_offset)
+            .filter(|_| previous_phase != Some(SplicePhase::At));
+        if let Some(splice_point) = splice_point {
             #[cfg(feature = "log")]
-            {
-                if let Ok(position) = self.buf_reader.stream_position() {
-                    trace!("Seek position in file {}: {}", self.filename, position)
+            debug!("Found splice point on PID {} at offset {} in file {}", splice_point.pid(),
+                splice_point.offset(), self.filename);
+
+            for (pid, callback) in self.splice_callbacks.iter_mut() {
+                if *pid == splice_point.pid() {
+                    callback(&splice_point);
                 }
             }
+        }
+
+        self.deliver_payload_chunks(&packet, continuity_anomaly);
 
-            self.packets_read += 1;
+        Ok(Some(packet))
+    }
+
+    /// Handle the packet at the current offset not starting with a SYNC byte.
+    ///
+    /// The bytes of the packet have to be at the front of `pending`. They are left there when a
+    /// `SyncLost` error is returned, so the reader stays at the packet.
+    /// # Returns
+    /// `Ok(true)` if the reader resynced and the packet there should be read instead.
+    /// `Ok(false)` if the end of the stream was reached while resyncing.
+    fn handle_sync_lost(&mut self) -> Result<bool, Box<dyn Error>> {
+        if ! self.resync_on_error {
             #[cfg(feature = "log")]
-            trace!("Packets read in file {}: {}", self.filename, self.packets_read);
+            debug!("Lost SYNC byte at offset {} in file {}", self.offset, self.filename);
+            return Err(Box::new(SyncLost { offset: self.offset }));
+        }
 
-            let packet = match TSPacket::from_bytes(&mut packet_buf) {
-                Ok(packet) => packet,
-                Err(e) => {
-                    #[cfg(feature = "log")]
-                    debug!("Got error from {} when trying to parse next packet from bytes {:2X?}",
-                        self.filename, packet_buf);
-                    return Err(e)
-                },
+        #[cfg(feature = "log")]
+        warn!("Lost SYNC byte at offset {} in file {}, scanning for the next one", self.offset,
+            self.filename);
+        self.scan_for_sync(Vec::new(), self.offset, self.layout.prefix() + 1)
+    }
+
+    /// Scan forward for the next position where SYNC bytes recur 1 stride apart, and continue
+    /// reading from the packet there.
+    ///
+    /// `buf` holds bytes that have already been taken from the stream, starting at byte offset
+    /// `start`, and the search starts at `buf[search_start]`. Bytes that were skipped are lost, so
+    /// the partial state of payloads and sections is cleared.
+    /// # Returns
+    /// `Ok(true)` if a SYNC byte was found.
+    /// `Ok(false)` if the end of the stream was reached first.
+    fn scan_for_sync(
+        &mut self,
+        mut buf: Vec<u8>,
+        mut start: u64,
+        search_start: usize,
+    ) -> Result<bool, Box<dyn Error>> {
+        let stride = self.layout.stride();
+        let prefix = self.layout.prefix();
+
+        // Bytes that were read ahead but not consumed come right after `buf` in the stream.
+        buf.append(&mut self.pending);
+        self.clear_partial_state();
+
+        let search = search_for_sync(&mut self.reader, &mut buf, &mut start, search_start, stride,
+            prefix)?;
+        let Some(idx) = search else {
+            self.offset = start + buf.len() as u64;
+            return Ok(false);
+        };
+
+        let packet_start = idx - prefix;
+        self.pending = buf.split_off(packet_start);
+        self.offset = start + packet_start as u64;
+
+        #[cfg(feature = "log")]
+        debug!("Found SYNC byte again at offset {} in file {}", self.offset + prefix as u64,
+            self.filename);
+
+        Ok(true)
+    }
+
+    /// Fill the buffer with the next bytes of the stream.
+    ///
+    /// Bytes left over from the SYNC byte search are used up before any more are read from the
+    /// reader.
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let count = self.pending.len().min(buf.len());
+        buf[..count].copy_from_slice(&self.pending[..count]);
+        self.pending.drain(..count);
+
+        self.reader.read_exact(&mut buf[count..])
+    }
+
+    /// Run the payload chunk callbacks registered for the PID of this packet.
+    ///
+    /// The continuity anomaly is the one that was found for this packet, if any. Duplicate packets
+    /// are not delivered again. After a continuity counter gap or a scrambled packet, the rest of
+    /// the payload unit is not delivered.
+    fn deliver_payload_chunks(&mut self, packet: &TSPacket, anomaly: Option<ContinuityAnomaly>) {
+        let pid = packet.header().pid();
+        if ! self.payload_chunk_callbacks.iter().any(|(callback_pid, _)| *callback_pid == pid) {
+            return
+        }
+
+        match anomaly {
+            Some(ContinuityAnomaly::Duplicate) => return,
+            Some(ContinuityAnomaly::Drop(_)) => {
+                self.interrupted_chunk_pids.insert(pid);
+            },
+            _ => {},
+        }
+
+        // The data of a scrambled packet is garbage until it is descrambled.
+        if packet.is_scrambled() {
+            self.interrupted_chunk_pids.insert(pid);
+            return
+        }
+
+        let Some(payload) = packet.payload() else {
+            return
+        };
+
+        // The data before the start index finishes the previous payload unit, so it needs to be
+        // delivered before the start of the new one.
+        let mut chunks = Vec::new();
+        let current_data = payload.get_current_data();
+        let interrupted = self.interrupted_chunk_pids.contains(&pid);
+        if (! payload.is_start() || ! current_data.is_empty()) && ! interrupted {
+            chunks.push((false, current_data));
+        }
+        if let Ok(start_data) = payload.get_start_data() {
+            self.interrupted_chunk_pids.remove(&pid);
+            chunks.push((true, start_data));
+        }
+
+        for (is_start, data) in chunks {
+            let chunk = PayloadChunk {
+                pid,
+                offset: self.current_offset,
+                is_start,
+                data: &data,
             };
 
-            // We should only return a packet if it is in the tracked PIDs (or there are no tracked
-            // PIDs)
-            if ! self.tracked_pids.is_empty() && ! self.tracked_pids.contains(&packet.header().pid()) {
-                continue
+            for (callback_pid, callback) in self.payload_chunk_callbacks.iter_mut() {
+                if *callback_pid == pid {
+                    callback(&chunk);
+                }
             }
-
-            return Ok(Some(packet));
         }
     }
 
@@ -179,21 +739,76 @@ impl TSReader {
     /// `None` if the next transport stream payload could not be parsed from the file for any
     /// reason. This includes if the entire file has been fully read.
     pub fn next_payload_unchecked(&mut self) -> Option<Box<[u8]>> {
-        self.next_payload().unwrap_or_else(|_| None)
+        self.next_payload().unwrap_or(None)
     }
 
     /// Read the next full payload from the file.
     ///
     /// This function parses through all transport stream packets, stores them in a buffer and
-    /// concatenates their payloads together once a payload has been complete.
+    /// concatenates their payloads together once a payload has been complete. Payloads that a
+    /// packet was lost from, which is found from a gap in the continuity counters, are skipped
+    /// since their data would be corrupt. Duplicate packets are only used once.
     pub fn next_payload(&mut self) -> Result<Option<Box<[u8]>>, Box<dyn Error>> {
+        Ok(self.next_completed_payload()?.map(CompletedPayload::into_data))
+    }
+
+    /// Read the next raw PSI section on a PID.
+    ///
+    /// This function returns `None` for any `Err` in order to prevent the need for `.unwrap()`
+    /// calls in more concise code.
+    /// # Returns
+    /// `Some(Box<[u8]>)` if the next section could be read from the file.
+    /// `None` if the next section could not be read from the file for any reason. This includes
+    /// if the entire file has been fully read.
+    pub fn next_section_unchecked(&mut self, pid: u16) -> Option<Box<[u8]>> {
+        self.next_section(pid).unwrap_or(None)
+    }
+
+    /// Read the next raw PSI section on a PID.
+    ///
+    /// The section is reassembled from the packets on the PID but isn't parsed, so it can be
+    /// handed to another section parser or forwarded as is. It starts at the `table_id` after the
+    /// payload pointer and ends with the last byte of the CRC.
+    ///
+    /// Packets on the PID are only collected once this has been called for it, so call it before
+    /// reading any packets to get every section. Packets are read regardless of which PIDs are
+    /// being tracked.
+    /// # Parameters
+    /// - `pid`: PID that carries the PSI sections.
+    pub fn next_section(&mut self, pid: u16) -> Result<Option<Box<[u8]>>, Box<dyn Error>> {
+        self.tracked_sections.entry(pid).or_default();
+
         loop {
-            let possible_packet = match self.next_packet() {
-                Ok(packet) => packet,
-                Err(e) => return Err(e),
-            };
-            
-            let Some(packet) = possible_packet else {
+            if let Some(section) = self.tracked_sections.get_mut(&pid)
+                .and_then(|(_, sections)| sections.pop_front()) {
+                return Ok(Some(section));
+            }
+
+            if self.read_packet()?.is_none() {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Read the next full payload from the file along with where it came from.
+    ///
+    /// This function returns `None` for any `Err` in order to prevent the need for `.unwrap()`
+    /// calls in more concise code.
+    /// # Returns
+    /// `Some(CompletedPayload)` if the next payload could be parsed from the file.
+    /// `None` if the next payload could not be parsed from the file for any reason. This includes
+    /// if the entire file has been fully read.
+    pub fn next_completed_payload_unchecked(&mut self) -> Option<CompletedPayload> {
+        self.next_completed_payload().unwrap_or(None)
+    }
+
+    /// Read the next full payload from the file along with where it came from.
+    ///
+    /// This works the same as [`TSReader::next_payload`] but also returns the PID of the payload
+    /// and the byte offset in the file of the packet that started it.
+    pub fn next_completed_payload(&mut self) -> Result<Option<CompletedPayload>, Box<dyn Error>> {
+        loop {
+            let Some(packet) = self.next_packet()? else {
                 return Ok(None);
             };
 
@@ -206,51 +821,3727 @@ impl TSReader {
         }
     }
 
-    /// Return the alignment of the SYNC bytes in this reader.
-    pub fn sync_byte_alignment(&self) -> u64 {
-        self.sync_alignment
+    /// Return the number of packets read so far.
+    ///
+    /// Every packet is counted, even if its PID is not being tracked. After
+    /// [`TSReader::seek_to_packet`] this is the number of the next packet to be read, as if every
+    /// packet before it had been read.
+    pub fn packets_read(&self) -> u64 {
+        self.packets_read
     }
 
-    /// Add a PID to the tracking list.
+    /// Return the number of bytes of packets read so far.
     ///
-    /// Only tracked PIDs are returned when running methods that gather packets or payloads. If no
-    /// PID is specified then all PIDs are returned.
-    pub fn add_tracked_pid(&mut self, pid: u16) {
-        self.tracked_pids.push(pid);
+    /// This includes the prefix and suffix of every packet in layouts that have them, but not any
+    /// bytes skipped to find the first SYNC byte. Every packet is counted, even if its PID is not
+    /// being tracked, and seeking doesn't change the count. Comparing this to the size of the file
+    /// gives the progress of a full read through it.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
     }
 
-    /// Remove this PID from being tracked.
+    /// Read through the rest of the file and summarize every packet in it.
     ///
-    /// Only tracked PIDs are returned when running methods that gather packets or payloads. If no
-    /// PID is specified then all PIDs are returned.
-    pub fn remove_tracked_pid(&mut self, pid: u16) {
-        self.tracked_pids.retain(|vec_pid| *vec_pid != pid);
+    /// The summary has the number of packets on each PID along with how many packets had an
+    /// adaptation field, the `PUSI` set, or the transport error indicator set. Packets that were
+    /// read before this was called are included, so calling it on a new reader summarizes the
+    /// whole file. Packets are read regardless of which PIDs are being tracked.
+    pub fn collect_stats(&mut self) -> Result<PacketStats, Box<dyn Error>> {
+        while self.read_packet()?.is_some() {}
+
+        let pid_packets = self.pid_packets_read.iter()
+            .map(|(pid, packets)| (*pid, *packets))
+            .collect();
+        Ok(PacketStats::new(&self.stream_stats, pid_packets))
     }
 
-    /// Add payload data from a packet to the tracked payloads list.
-    fn add_tracked_payload(&mut self, packet: &TSPacket) -> Option<Box<[u8]>> {
-        let payload = packet.payload()?;
+    /// Return the fraction of the stream read so far that was not payload data.
+    ///
+    /// Packet headers, adaptation fields, payload pointers, and null packets are all counted as
+    /// overhead. This is useful for evaluating how efficiently a stream was multiplexed. Every
+    /// packet that has been read is counted, even if its PID is not being tracked.
+    pub fn overhead_ratio(&self) -> OverheadRatio {
+        OverheadRatio::new(self.payload_bytes_read, self.bytes_read)
+    }
 
-        // Check to see if we already have an TrackedPayload object for this item PID
-        let pid = packet.header().pid();
-        
-        if let Some(index) = self.tracked_payloads.iter().position(|tp| tp.pid() == pid) {
-            let tracked_payload = &mut self.tracked_payloads[index];
-            return tracked_payload.add_and_get_complete(&payload);
-        }
+    /// Return counts of how the packets read so far are structured, such as how many carried an
+    /// adaptation field or a PCR.
+    ///
+    /// Every packet that has been read is counted, even if its PID is not being tracked.
+    pub fn stream_stats(&self) -> &StreamStats {
+        &self.stream_stats
+    }
 
-        // We cannot possibly know that a payload is complete from the first packet. In order to
-        // know that a payload is fully contained in 1 packet we need to see the `PUSI` flag set in
-        // the next packet so there is no reason to check if the packet is complete when creating a
-        // new TrackedPayload.
+    /// Return a summary of how many of the packets read so far had to be worked around.
+    ///
+    /// This combines the continuity errors of every PID with the number of packets that had the
+    /// transport error indicator set or were scrambled, to show how clean a capture is at a
+    /// glance. Every packet that has been read is counted, even if its PID is not being tracked.
+    pub fn recovery_report(&self) -> RecoveryReport {
+        RecoveryReport::new(&self.stream_stats, self.continuity.errors().values().sum())
+    }
 
-        match TrackedPayload::from_packet(packet) {
-            Ok(tp) => {
-                self.tracked_payloads.push(tp);
-            }
-            Err(_) => {}
-        };
+    /// Return the number of continuity counter errors found on each PID so far.
+    ///
+    /// An error is counted every time a packet's continuity counter isn't the one expected from the
+    /// packets before it on the same PID, which usually means that packets were lost. Packets that
+    /// repeat the previous packet once and packets without a payload are allowed by the spec and
+    /// aren't errors. Jumps flagged by the discontinuity indicator are counted separately by
+    /// [`TSReader::flagged_discontinuities`]. Null packets are not checked, and packets are
+    /// checked regardless of which PIDs are being tracked.
+    ///
+    /// PIDs without any errors are not included.
+    pub fn continuity_errors(&self) -> &HashMap<u16, u64> {
+        self.continuity.errors()
+    }
 
-        return None;
+    /// Return the continuity counter that the next packet with a payload on this PID should have.
+    ///
+    /// This is one more than the continuity counter of the most recent packet read on the PID, or
+    /// `0` if no packets have been read on it. It reflects what the reader has seen so far, so it
+    /// is only meaningful during a read pass, such as when inserting packets into a stream while
+    /// keeping it continuous. Null packets are not tracked, so this is always `0` for them.
+    pub fn next_cc(&self, pid: u16) -> u8 {
+        self.continuity.last_continuity_counter(pid)
+            .map_or(0, |continuity_counter| (continuity_counter + 1) % CONTINUITY_COUNTER_MODULUS)
     }
-}
\ No newline at end of file
+
+    /// Return the number of continuity counter jumps found on each PID so far that were flagged by
+    /// the discontinuity indicator.
+    ///
+    /// These are expected discontinuities, such as at a splice, so they aren't counted by
+    /// [`TSReader::continuity_errors`].
+    ///
+    /// PIDs without any flagged discontinuities are not included.
+    pub fn flagged_discontinuities(&self) -> &HashMap<u16, u64> {
+        self.continuity.flagged_discontinuities()
+    }
+
+    /// Return how many duplicate packets, dropped packets, and flagged discontinuities were found
+    /// on each PID so far.
+    ///
+    /// This splits the anomalies behind [`TSReader::continuity_errors`] and
+    /// [`TSReader::flagged_discontinuities`] by kind, and also counts the duplicate packets that
+    /// are allowed by the spec, so that packet loss can be measured precisely. Null packets are not
+    /// checked, and packets are checked regardless of which PIDs are being tracked.
+    ///
+    /// PIDs without any anomalies are not included.
+    pub fn continuity_breakdown(&self) -> &HashMap<u16, ContinuityBreakdown> {
+        self.continuity.breakdowns()
+    }
+
+    /// Return the number of access units started on each PID so far.
+    ///
+    /// This counts the packets on each PID that have the `PUSI` flag set, which marks the start of
+    /// a PES packet or, on PSI PIDs, of one or more sections. Encoders usually put each video frame
+    /// in its own PES packet, so for a video PID this approximates the number of frames without
+    /// parsing any PES headers. Audio PES packets often carry several frames each, so the count is
+    /// lower than the number of audio frames. Packets are counted regardless of which PIDs are
+    /// being tracked.
+    ///
+    /// PIDs without any access unit starts are not included.
+    pub fn access_unit_counts(&self) -> &HashMap<u16, u64> {
+        &self.pid_unit_starts
+    }
+
+    /// Return the size in bytes of the largest payload completed on a PID so far.
+    ///
+    /// This is useful for preallocating buffers for a PID's payloads, and for spotting abnormally
+    /// large payloads. Only payloads that the reader has reassembled, such as with
+    /// [`TSReader::next_payload`] or [`TSReader::demux_payloads`], are measured.
+    /// # Returns
+    /// `None` if no payloads have been completed on the PID.
+    pub fn max_payload_size(&self, pid: u16) -> Option<usize> {
+        self.max_payload_sizes.get(&pid).copied()
+    }
+
+    /// Return the number of packets read on each PID so far that had the transport priority set.
+    ///
+    /// The transport priority marks packets that should be handled before other packets on the
+    /// same PID, which matters for buffer modeling of prioritized streams. Use
+    /// [`TSReader::set_window`] to only count the packets in part of the file. Packets are counted
+    /// regardless of which PIDs are being tracked.
+    ///
+    /// PIDs without any priority packets are not included.
+    pub fn priority_packet_counts(&self) -> &HashMap<u16, u64> {
+        &self.pid_priority_packets
+    }
+
+    /// Return the fraction of the packets read on a PID so far that had the transport priority set.
+    /// # Returns
+    /// `None` if no packets have been read on the PID.
+    pub fn priority_packet_ratio(&self, pid: u16) -> Option<f64> {
+        let packets = *self.pid_packets_read.get(&pid)?;
+        let priority_packets = self.pid_priority_packets.get(&pid).copied().unwrap_or_default();
+        Some(priority_packets as f64 / packets as f64)
+    }
+
+    /// Return how far the PCR clock frequency is from 27MHz in parts per million, measured over the
+    /// packets read so far.
+    ///
+    /// The PCR is compared against the arrival timestamps in the 4-byte prefix of M2TS packets,
+    /// which are stamped by the recorder's own 27MHz clock as each packet arrives. The result is
+    /// only as accurate as that clock, and the arrival timestamps are the only time source that is
+    /// supported, so this is always `None` for any other [`PacketLayout`]. A positive value means
+    /// that the PCR runs fast.
+    ///
+    /// Only the PCRs on the first PID that a PCR is read from are measured. The time leading up to
+    /// a PCR flagged by the discontinuity indicator is left out of the measurement.
+    /// # Returns
+    /// `None` if fewer than two PCRs with arrival timestamps have been read.
+    pub fn pcr_frequency_ppm(&self) -> Option<f64> {
+        self.pcr_clock.frequency_ppm()
+    }
+
+    /// Return the raw bytes of the most recently seen PAT.
+    ///
+    /// This is the reassembled section exactly as it was sent, from the `table_id` through the
+    /// CRC, so it can be sent again verbatim on PID `0` by a remuxer that passes the PAT through.
+    /// Only PATs that currently apply and have a valid CRC are kept, which are the same PATs that
+    /// the reader's parsed program information comes from. A PAT that is split across several
+    /// sections only has its most recent section returned. The PAT is tracked regardless of which
+    /// PIDs are being tracked.
+    /// # Returns
+    /// `None` if no PAT has been found yet.
+    pub fn raw_pat(&self) -> Option<Box<[u8]>> {
+        self.programs.pat_section().map(Box::from)
+    }
+
+    /// Return every PID that is referenced by the PAT or a PMT but has not been seen in any packet.
+    ///
+    /// A multiplex should deliver every stream that its tables advertise, so any PID returned here
+    /// points to a defect in the stream. This should be run after the whole file has been read,
+    /// otherwise streams which simply haven't been reached yet will be included. Only the most
+    /// recent version of each table is considered, and packets are counted regardless of which PIDs
+    /// are being tracked.
+    /// # Returns
+    /// The missing PIDs in ascending order.
+    pub fn missing_pids(&self) -> Vec<u16> {
+        self.programs.referenced_pids()
+            .into_iter()
+            .filter(|pid| ! self.pid_packets_read.contains_key(pid))
+            .collect()
+    }
+
+    /// Check that the PAT and PMTs found so far describe a complete set of programs.
+    ///
+    /// Every program listed in the PAT should have a PMT that can be parsed, and every PMT should
+    /// have a PCR PID and at least one elementary stream. This should be run after the whole file
+    /// has been read, or at least after a probe pass long enough to cover every PMT, otherwise
+    /// PMTs which simply haven't been reached yet are reported as missing. Only the most recent
+    /// version of each table is considered, and packets are checked regardless of which PIDs are
+    /// being tracked.
+    /// # Returns
+    /// The defects in the order of the programs in the PAT. This is empty if no defects were found.
+    pub fn validate_program_structure(&self) -> Vec<StructureDefect> {
+        let Some(pat) = self.programs.pat() else {
+            return vec![StructureDefect::MissingPat]
+        };
+
+        let mut defects = Vec::new();
+        for &(program_number, pmt_pid) in pat.programs() {
+            let Some(pmt) = self.programs.pmt(program_number) else {
+                defects.push(if self.pid_packets_read.contains_key(&pmt_pid) {
+                    StructureDefect::InvalidPmt { program_number, pmt_pid }
+                } else {
+                    StructureDefect::MissingPmt { program_number, pmt_pid }
+                });
+                continue
+            };
+
+            if pmt.pcr_pid().is_none() {
+                defects.push(StructureDefect::MissingPcrPid { program_number });
+            }
+            if pmt.streams().is_empty() {
+                defects.push(StructureDefect::NoStreams { program_number });
+            }
+        }
+
+        defects
+    }
+
+    /// Render the PAT, programs, PMTs, and elementary streams found so far as an indented tree.
+    ///
+    /// This is meant for a quick human readable overview of a stream, such as one pasted into a
+    /// bug report, and should be run after the whole file has been read or at least after a probe
+    /// pass long enough to cover every PMT. Only the most recent version of each table is
+    /// considered. The format is stable, with one item per line and two spaces of indentation per
+    /// level. PIDs and the transport stream ID are written as 4 digit hexadecimal numbers, and
+    /// stream types as 2 digit hexadecimal numbers:
+    ///
+    /// ```text
+    /// PAT, transport stream ID 0x0001
+    ///   Network, PID 0x0010
+    ///   Program 1, PMT PID 0x1000
+    ///     PID 0x0101, stream type 0x1B, PCR
+    ///     PID 0x0102, stream type 0x0F
+    ///   Program 2, PMT PID 0x1001
+    ///     PID 0x0201, PCR only
+    ///     PID 0x0202, stream type 0x1B
+    ///   Program 3, PMT PID 0x1002
+    ///     PMT not found
+    /// ```
+    ///
+    /// The stream that carries the PCR of a program is marked with `PCR`. If the PCR is carried
+    /// on a PID which isn't one of the program's streams, that PID is listed first as `PCR only`.
+    /// If no PAT has been found yet, the tree is the single line `PAT not found`.
+    pub fn structure_tree(&self) -> String {
+        let Some(pat) = self.programs.pat() else {
+            return String::from("PAT not found\n")
+        };
+
+        let mut tree = format!("PAT, transport stream ID {:#06X}\n", pat.transport_stream_id());
+        if let Some(network_pid) = pat.network_pid() {
+            tree.push_str(&format!("  Network, PID {:#06X}\n", network_pid));
+        }
+
+        for &(program_number, pmt_pid) in pat.programs() {
+            tree.push_str(&format!("  Program {}, PMT PID {:#06X}\n", program_number, pmt_pid));
+
+            let Some(pmt) = self.programs.pmt(program_number) else {
+                tree.push_str("    PMT not found\n");
+                continue
+            };
+
+            let pcr_pid = pmt.pcr_pid();
+            if let Some(pcr_pid) = pcr_pid {
+                if ! pmt.streams().iter().any(|stream| stream.elementary_pid() == pcr_pid) {
+                    tree.push_str(&format!("    PID {:#06X}, PCR only\n", pcr_pid));
+                }
+            }
+
+            for stream in pmt.streams() {
+                tree.push_str(&format!(
+                    "    PID {:#06X}, stream type {:#04X}",
+                    stream.elementary_pid(),
+                    stream.stream_type(),
+                ));
+                if pcr_pid == Some(stream.elementary_pid()) {
+                    tree.push_str(", PCR");
+                }
+                tree.push('\n');
+            }
+        }
+
+        tree
+    }
+
+    /// Return every PID that has been seen in a packet but is not referenced by the PAT or a PMT.
+    ///
+    /// These orphan PIDs may be leftover streams or point to muxing errors. PIDs whose meaning is
+    /// defined by a standard are never orphans. This includes the PAT, the CAT, the DVB service
+    /// information tables (`0x0000` to `0x001F`), the ATSC PSIP tables, and null packets. The CAT
+    /// is not parsed, so EMM PIDs are reported as orphans. This should be run after the whole file
+    /// has been read, and only the most recent version of each table is considered.
+    /// # Returns
+    /// The orphan PIDs in ascending order.
+    pub fn orphan_pids(&self) -> Vec<u16> {
+        let referenced_pids = self.programs.referenced_pids();
+        let mut pids: Vec<u16> = self.pid_packets_read.keys()
+            .copied()
+            .filter(|pid| ! psi::is_well_known_pid(*pid) && ! referenced_pids.contains(pid))
+            .collect();
+        pids.sort_unstable();
+        pids
+    }
+
+    /// Detect whether the stream follows the DVB or ATSC broadcast standard.
+    ///
+    /// This reads through the file until the start of a DVB service information table (NIT, SDT,
+    /// BAT, or EIT) or an ATSC PSIP base table (MGT, TVCT, CVCT, RRT, or STT) is found on its
+    /// well-known PID. At most [`PROFILE_PROBE_PACKET_COUNT`] packets are read, so a stream which
+    /// only carries the MPEG-2 PSI tables doesn't have to be read until the end of the file.
+    /// Packets are read regardless of which PIDs are being tracked.
+    /// # Returns
+    /// The profile of the first service information table that was found, or
+    /// `StreamProfile::Unknown` if none was found within the probe window.
+    pub fn detect_profile(&mut self) -> Result<StreamProfile, Box<dyn Error>> {
+        for _ in 0..PROFILE_PROBE_PACKET_COUNT {
+            let Some(packet) = self.read_packet()? else {
+                break
+            };
+
+            let Some(payload) = packet.payload() else {
+                continue
+            };
+            let Some(&table_id) = payload.start_index()
+                .and_then(|index| payload.data().get(index as usize)) else {
+                continue
+            };
+
+            let profile = StreamProfile::from_table(packet.header().pid(), table_id);
+            if profile != StreamProfile::Unknown {
+                #[cfg(feature = "log")]
+                debug!("Detected {:?} profile in file {}", profile, self.filename);
+                return Ok(profile)
+            }
+        }
+
+        Ok(StreamProfile::Unknown)
+    }
+
+    /// Detect whether the stream follows the DVB or ATSC broadcast standard.
+    ///
+    /// This function returns `StreamProfile::Unknown` for any `Err` in order to prevent the need
+    /// for `.unwrap()` calls in more concise code. See [`TSReader::detect_profile`] for details.
+    pub fn detect_profile_unchecked(&mut self) -> StreamProfile {
+        self.detect_profile().unwrap_or(StreamProfile::Unknown)
+    }
+
+    /// Find the PTS of the first access unit of every program in the transport stream.
+    ///
+    /// This reads through the file until the PAT and every program's PMT have been found, and then
+    /// until a PES packet with a PTS has been found on the first video stream of every program. The
+    /// PTS values are in 90kHz ticks and are useful for finding the timing offsets between the
+    /// programs of a multiplex.
+    ///
+    /// Only PES packets that start after the program's PMT has been read are considered. Programs
+    /// without a video stream are not included in the results. Packets are read regardless of which
+    /// PIDs are being tracked.
+    /// # Returns
+    /// A map of program numbers to the PTS of their first video access unit. If the end of the file
+    /// is reached first, only the programs whose PTS was found are included.
+    pub fn first_pts_per_program(&mut self) -> Result<HashMap<u16, u64>, Box<dyn Error>> {
+        let mut first_pts = HashMap::new();
+
+        loop {
+            if self.programs.is_complete() && self.programs.pmts()
+                .filter(|pmt| pmt.video_stream().is_some())
+                .all(|pmt| first_pts.contains_key(&pmt.program_number())) {
+                return Ok(first_pts);
+            }
+
+            let Some(packet) = self.read_packet()? else {
+                return Ok(first_pts);
+            };
+
+            if ! packet.header().pusi() {
+                continue
+            }
+
+            let pid = packet.header().pid();
+            let Some(program_number) = self.programs.pmts()
+                .find(|pmt| pmt.video_stream().is_some_and(|stream| stream.elementary_pid() == pid))
+                .map(|pmt| pmt.program_number()) else {
+                continue
+            };
+
+            if first_pts.contains_key(&program_number) {
+                continue
+            }
+
+            let Some(payload) = packet.payload() else {
+                continue
+            };
+
+            if let Some(pts) = PesPacket::from_bytes(&payload.raw_data()).ok().and_then(|pes| pes.pts()) {
+                #[cfg(feature = "log")]
+                debug!("Found first PTS {} for program {} in file {}", pts, program_number, self.filename);
+                first_pts.insert(program_number, pts);
+            }
+        }
+    }
+
+    /// Measure the delay between the PTS of every PES packet in a program and the program's PCR.
+    ///
+    /// This reads through the rest of the file. Each time a PES packet with a PTS starts on one of
+    /// the program's streams, it is paired with the most recent PCR from the program's PCR PID. The
+    /// delays over time show how full the decoder's buffer is and can be used to find T-STD buffer
+    /// model violations.
+    ///
+    /// PES packets are skipped until the program's PMT and a PCR have been found. Packets are read
+    /// regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `program_number`: program to measure the delays of.
+    /// # Returns
+    /// The delay of every PES packet with a PTS, in the order they were found.
+    pub fn pts_pcr_delays(
+        &mut self,
+        program_number: u16,
+    ) -> Result<Vec<PtsPcrDelay>, Box<dyn Error>> {
+        let mut delays = Vec::new();
+        let mut last_pcr: Option<u64> = None;
+
+        while let Some(packet) = self.read_packet()? {
+            let Some(pmt) = self.programs.pmt(program_number) else {
+                continue
+            };
+
+            let pid = packet.header().pid();
+            if pmt.pcr_pid() == Some(pid) {
+                if let Some(pcr) = packet.pcr() {
+                    last_pcr = Some(pcr);
+                }
+            }
+
+            let in_program = pmt.streams().iter().any(|stream| stream.elementary_pid() == pid);
+            if ! packet.header().pusi() || ! in_program {
+                continue
+            }
+
+            let (Some(pcr), Some(payload)) = (last_pcr, packet.payload()) else {
+                continue
+            };
+
+            let pes = PesPacket::from_bytes(&payload.raw_data()).ok();
+            if let Some(pts) = pes.and_then(|pes| pes.pts()) {
+                delays.push(PtsPcrDelay::new(pid, self.current_offset, pts, pcr));
+            }
+        }
+
+        Ok(delays)
+    }
+
+    /// Find the points where the PTS of a stream jumped backward or forward by more than a
+    /// threshold.
+    ///
+    /// This reads through the rest of the file, comparing each PES packet that starts on the PID
+    /// with the one before it. Frames can be sent out of presentation order, so the comparison uses
+    /// the DTS of each PES packet, which is the same as its PTS when no DTS is present. Timestamps
+    /// wrapping back to `0` are not reported as discontinuities.
+    ///
+    /// Packets are read regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `pid`: PID of the stream to check.
+    /// - `threshold`: largest forward jump between two PES packets that isn't a discontinuity.
+    /// # Returns
+    /// Every discontinuity with the PTS values before and after it, in the order they were found.
+    pub fn pts_discontinuities(
+        &mut self,
+        pid: u16,
+        threshold: Duration,
+    ) -> Result<Vec<PtsDiscontinuity>, Box<dyn Error>> {
+        let threshold = (threshold.as_secs_f64() * PTS_FREQUENCY as f64) as i64;
+        let mut discontinuities = Vec::new();
+        // The PTS and DTS of the previous PES packet.
+        let mut previous: Option<(u64, u64)> = None;
+
+        while let Some(packet) = self.read_packet()? {
+            if packet.header().pid() != pid || ! packet.header().pusi() {
+                continue
+            }
+
+            let Some(payload) = packet.payload() else {
+                continue
+            };
+
+            let Some(pes) = PesPacket::from_bytes(&payload.raw_data()).ok() else {
+                continue
+            };
+
+            let Some(pts) = pes.pts() else {
+                continue
+            };
+            let dts = pes.dts().unwrap_or(pts);
+
+            if let Some((previous_pts, previous_dts)) = previous {
+                let jump = pes::pts_difference(previous_dts, dts);
+                if jump < 0 || jump > threshold {
+                    #[cfg(feature = "log")]
+                    debug!("PTS on PID {} jumped from {} to {} at offset {} in file {}", pid,
+                        previous_pts, pts, self.current_offset, self.filename);
+                    discontinuities.push(
+                        PtsDiscontinuity::new(pid, self.current_offset, previous_pts, pts));
+                }
+            }
+
+            previous = Some((pts, dts));
+        }
+
+        Ok(discontinuities)
+    }
+
+    /// Read the KLV metadata of a program and match each unit of it to a frame of the program's
+    /// video.
+    ///
+    /// This reads through the rest of the file, reassembling the PES packets of the program's
+    /// first KLV stream and collecting the PTS of every PES packet on its first video stream. The
+    /// streams are found from the program's PMT, so packets before the PMT are skipped.
+    ///
+    /// Synchronous metadata is matched to the video frame whose PTS is closest to its own. The
+    /// match is only kept if the two PTS values are at most `tolerance` apart, so metadata for
+    /// which no frame was sent gets no video PTS. Half of the frame interval is a good tolerance
+    /// since any metadata further from a frame than that is closer to the frame next to it.
+    /// Asynchronous metadata has no PTS of its own, so it is matched to the last video frame that
+    /// started before it in the file, regardless of the tolerance.
+    ///
+    /// Packets are read regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `program_number`: program whose video and metadata should be read.
+    /// - `tolerance`: largest distance between the PTS of metadata and the frame it is matched to.
+    /// # Returns
+    /// Every unit of metadata in the order it was read. This is empty if the program's PMT was
+    /// never found or the program doesn't carry KLV metadata.
+    pub fn klv_timeline(
+        &mut self,
+        program_number: u16,
+        tolerance: Duration,
+    ) -> Result<Vec<KlvUnit>, Box<dyn Error>> {
+        let tolerance = (tolerance.as_secs_f64() * PTS_FREQUENCY as f64) as u64;
+        let mut video_pts = Vec::new();
+        // Offset of the first packet, the video PTS before it, and the data of the KLV PES packet
+        // that is currently being reassembled.
+        let mut buffer: Option<(u64, Option<u64>, Vec<u8>)> = None;
+        let mut finished = Vec::new();
+
+        while let Some(packet) = self.read_packet()? {
+            let Some(pmt) = self.programs.pmt(program_number) else {
+                continue
+            };
+
+            let pid = packet.header().pid();
+            let is_video = pmt.video_stream().is_some_and(|stream| stream.elementary_pid() == pid);
+            let is_klv = pmt.klv_stream().is_some_and(|stream| stream.elementary_pid() == pid);
+
+            let Some(payload) = packet.payload() else {
+                continue
+            };
+
+            if is_video && payload.is_start() {
+                let pes = PesPacket::from_bytes(&payload.raw_data()).ok();
+                video_pts.extend(pes.and_then(|pes| pes.pts()));
+            } else if is_klv && payload.is_start() {
+                let previous_pts = video_pts.last().copied();
+                let data = payload.raw_data().into_vec();
+                finished.extend(buffer.replace((self.current_offset, previous_pts, data)));
+            } else if let (true, Some((_, _, data))) = (is_klv, buffer.as_mut()) {
+                data.extend_from_slice(&payload.raw_data());
+            }
+        }
+
+        // Metadata is much smaller than a frame, so the last PES packet is usually complete.
+        finished.extend(buffer);
+
+        let mut sorted_pts = video_pts;
+        sorted_pts.sort_unstable();
+
+        let mut units = Vec::new();
+        for (offset, previous_pts, data) in finished {
+            let Ok(pes) = PesPacket::from_bytes(&data) else {
+                continue
+            };
+
+            let length = pes.packet_length() as usize;
+            if length != 0 && data.len() < pes::PES_HEADER_SIZE + length {
+                #[cfg(feature = "log")]
+                debug!("Dropping unfinished KLV PES packet at offset {} in file {}", offset,
+                    self.filename);
+                continue
+            }
+
+            let matched_pts = match pes.pts() {
+                Some(pts) => closest_pts(&sorted_pts, pts)
+                    .filter(|&frame| pes::pts_difference(pts, frame).unsigned_abs() <= tolerance),
+                None => previous_pts,
+            };
+
+            units.push(KlvUnit::new(offset, pes.pts(), matched_pts, Box::from(pes.payload())));
+        }
+
+        Ok(units)
+    }
+
+    /// Measure how the transport bitrate of the stream changes over time.
+    ///
+    /// This reads through the rest of the file and splits it into consecutive windows using the
+    /// PCRs of the first PID that a PCR is seen on. A window ends at the first PCR that is at least
+    /// `window` after the PCR that started it, and its bitrate is measured from the number of
+    /// packets, on any PID, from the packet that started it up to the packet that ended it. The
+    /// window size is configurable, with 1 second being typical for checking a variable bitrate
+    /// stream against the capacity of its channel. Shorter windows show shorter bursts.
+    ///
+    /// A window is restarted when its PCR is flagged as discontinuous or jumps backward, and the
+    /// last window is left out when the file ends before it is long enough.
+    ///
+    /// Packets are read regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `window`: shortest length of time to measure each bitrate over.
+    pub fn bitrate_profile(&mut self, window: Duration) -> Result<BitrateProfile, Box<dyn Error>> {
+        let window = (window.as_secs_f64() * PCR_FREQUENCY as f64) as i64;
+        let mut windows = Vec::new();
+        let mut pcr_pid = None;
+        // The offset and PCR of the packet that started the current window.
+        let mut start: Option<(u64, u64)> = None;
+        let mut packets = 0;
+
+        while let Some(packet) = self.read_packet()? {
+            packets += 1;
+
+            let Some(pcr) = packet.pcr() else {
+                continue
+            };
+
+            if *pcr_pid.get_or_insert(packet.header().pid()) != packet.header().pid() {
+                continue
+            }
+
+            let discontinuity = packet.data_adaptation_field()
+                .is_some_and(|af| af.discontinuity_indicator());
+
+            if let Some((offset, start_pcr)) = start {
+                let duration = pcr_difference(start_pcr, pcr);
+                if discontinuity || duration < 0 {
+                    #[cfg(feature = "log")]
+                    debug!("Restarting bitrate window at PCR discontinuity at offset {} in file {}",
+                        self.current_offset, self.filename);
+                } else if duration < window.max(1) {
+                    continue
+                } else {
+                    windows.push(BitrateWindow::new(offset, packets - 1, duration as u64));
+                }
+            }
+
+            start = Some((self.current_offset, pcr));
+            packets = 1;
+        }
+
+        Ok(BitrateProfile::new(windows))
+    }
+
+    /// Measure the distribution of the time between consecutive PCRs of the stream.
+    ///
+    /// This reads through the rest of the file and measures the PCRs of the first PID that a PCR
+    /// is seen on, counting each interval in buckets of `bucket_width`. Intervals longer than
+    /// `limit` are also reported individually. [`MAX_PCR_INTERVAL`](crate::stats::MAX_PCR_INTERVAL)
+    /// is the limit set by ISO/IEC 13818-1, but some profiles require a shorter one. Intervals
+    /// leading up to a PCR that is flagged as discontinuous or jumps backward aren't measured.
+    ///
+    /// The plain distribution is available as a `Vec<(Duration, u64)>` from
+    /// [`PcrIntervalHistogram::buckets`]. The histogram itself is returned so that the intervals
+    /// longer than `limit` come with it, since a bucket that `limit` falls inside of can't tell
+    /// which of its intervals are violations.
+    ///
+    /// Packets are read regardless of which PIDs are being tracked, and the source doesn't need to
+    /// be seekable.
+    /// # Parameters
+    /// - `bucket_width`: width of each bucket of the histogram.
+    /// - `limit`: longest interval that isn't reported as a violation.
+    pub fn pcr_interval_histogram(
+        &mut self,
+        bucket_width: Duration,
+        limit: Duration,
+    ) -> Result<PcrIntervalHistogram, Box<dyn Error>> {
+        let mut histogram = PcrIntervalHistogram::new(bucket_width, limit);
+        let mut pcr_pid = None;
+        let mut last_pcr: Option<u64> = None;
+
+        while let Some(packet) = self.read_packet()? {
+            let Some(pcr) = packet.pcr() else {
+                continue
+            };
+
+            if *pcr_pid.get_or_insert(packet.header().pid()) != packet.header().pid() {
+                continue
+            }
+
+            let discontinuity = packet.data_adaptation_field()
+                .is_some_and(|af| af.discontinuity_indicator());
+
+            if let Some(last_pcr) = last_pcr.replace(pcr) {
+                if discontinuity || pcr_difference(last_pcr, pcr) < 0 {
+                    #[cfg(feature = "log")]
+                    debug!("Skipping PCR interval at discontinuity at offset {} in file {}",
+                        self.current_offset, self.filename);
+                    continue
+                }
+
+                histogram.observe(self.current_offset, segment::pcr_elapsed(last_pcr, pcr));
+            }
+        }
+
+        Ok(histogram)
+    }
+
+    /// Collect the transport private data from the adaptation fields of a PID's packets.
+    ///
+    /// This reads through the rest of the file and concatenates the transport private data of
+    /// every packet on the PID in the order they were read. Some applications spread a larger
+    /// private data stream across the adaptation fields of consecutive packets. How the bytes
+    /// should be interpreted is application specific, so they are returned as is.
+    ///
+    /// Packets are read regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `pid`: PID to collect the transport private data of.
+    pub fn private_data_stream(&mut self, pid: u16) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut data = Vec::new();
+
+        while let Some(packet) = self.read_packet()? {
+            if packet.header().pid() != pid {
+                continue
+            }
+
+            let private_data = packet.data_adaptation_field()
+                .and_then(|af| af.transport_private_data());
+            if let Some(private_data) = private_data {
+                data.extend_from_slice(private_data);
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Collect the completed payloads of several PIDs in a single pass over the file.
+    ///
+    /// This reads through the rest of the file and reassembles the payloads of every given PID the
+    /// same way as [`TSReader::next_payload`], keeping each PID's payloads in the order they were
+    /// completed. Payloads that are still being reassembled when the end of the file is reached
+    /// aren't included, the same as with `next_payload`.
+    ///
+    /// Every payload is kept in memory until the whole file has been read, so for large files this
+    /// can use about as much memory as the streams being collected take up in the file. Use
+    /// `next_completed_payload` to process payloads one at a time instead.
+    ///
+    /// Packets are read regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `pids`: PIDs to collect the payloads of.
+    /// # Returns
+    /// A map of every given PID to its payloads. PIDs without any completed payloads map to an
+    /// empty `Vec`.
+    pub fn demux_payloads(&mut self, pids: &[u16]) -> Result<DemuxedPayloads, Box<dyn Error>> {
+        let mut payloads: DemuxedPayloads = pids.iter().map(|pid| (*pid, Vec::new())).collect();
+
+        while let Some(packet) = self.read_packet()? {
+            if ! pids.contains(&packet.header().pid()) {
+                continue
+            }
+
+            if let Some(payload) = self.add_tracked_payload(&packet) {
+                payloads.entry(payload.pid()).or_default().push(payload.into_data());
+            }
+        }
+
+        Ok(payloads)
+    }
+
+    /// Split the rest of the file into the PES packets and PSI sections of every PID.
+    ///
+    /// Each PID is classified the first time a payload starts on it. PIDs listed in a PMT are
+    /// classified by their stream type, the PAT and PMT PIDs carry PSI, and any other PID is
+    /// classified by inspecting its payload with [`demux::classify_payload`]. PIDs whose payloads
+    /// can't be classified are checked again on their next payload start, and are left out of the
+    /// result if they never are.
+    ///
+    /// This does a full pass over the rest of the file and keeps everything in memory until it is
+    /// done. A PES packet that is still being reassembled at the end of the file is only included
+    /// if its PES packet length shows that it is complete.
+    ///
+    /// Packets are read regardless of which PIDs are being tracked.
+    /// # Returns
+    /// The PES packets and PSI sections of every classified PID in a [`DemuxResult`].
+    pub fn auto_demux(&mut self) -> Result<DemuxResult, Box<dyn Error>> {
+        let mut kinds: HashMap<u16, PayloadKind> = HashMap::new();
+        let mut assemblers: HashMap<u16, SectionAssembler> = HashMap::new();
+        // Data of the PES packet that is currently being reassembled on each PID.
+        let mut pes_buffers: HashMap<u16, Vec<u8>> = HashMap::new();
+        let mut streams = BTreeMap::new();
+
+        while let Some(packet) = self.read_packet()? {
+            let pid = packet.header().pid();
+            if pid == NULL_PACKET_PID {
+                continue
+            }
+
+            let Some(payload) = packet.payload() else {
+                continue
+            };
+
+            let kind = match kinds.get(&pid) {
+                Some(kind) => *kind,
+                None if payload.is_start() => match self.payload_kind(pid, &payload.raw_data()) {
+                    PayloadKind::Unknown => continue,
+                    kind => {
+                        kinds.insert(pid, kind);
+                        kind
+                    },
+                },
+                None => continue,
+            };
+
+            match kind {
+                PayloadKind::Psi => {
+                    let sections = assemblers.entry(pid).or_default().push(&payload);
+                    if let DemuxedStream::Psi(all) = streams.entry(pid)
+                        .or_insert_with(|| DemuxedStream::Psi(Vec::new()))
+                    {
+                        all.extend(sections);
+                    }
+                },
+                PayloadKind::Pes => {
+                    let finished = if payload.is_start() {
+                        pes_buffers.insert(pid, payload.raw_data().into_vec())
+                    } else {
+                        if let Some(buffer) = pes_buffers.get_mut(&pid) {
+                            buffer.extend_from_slice(&payload.raw_data());
+                        }
+                        None
+                    };
+
+                    let packets = streams.entry(pid)
+                        .or_insert_with(|| DemuxedStream::Pes(Vec::new()));
+                    if let (DemuxedStream::Pes(all), Some(buffer)) = (packets, finished) {
+                        all.extend(PesPacket::from_bytes(&buffer).ok());
+                    }
+                },
+                PayloadKind::Unknown => {},
+            }
+        }
+
+        for (pid, buffer) in pes_buffers {
+            let Ok(pes) = PesPacket::from_bytes(&buffer) else {
+                continue
+            };
+
+            let length = pes.packet_length() as usize;
+            if length == 0 || buffer.len() < pes::PES_HEADER_SIZE + length {
+                #[cfg(feature = "log")]
+                debug!("Dropping unfinished PES packet on PID {} at the end of the file", pid);
+                continue
+            }
+
+            if let Some(DemuxedStream::Pes(all)) = streams.get_mut(&pid) {
+                all.push(pes);
+            }
+        }
+
+        Ok(DemuxResult::new(streams))
+    }
+
+    /// Write a cleaned up copy of the rest of the transport stream.
+    ///
+    /// In a single pass this can strip null packets, drop packets with the transport error
+    /// indicator set, and renumber the continuity counters of every PID so that they have no gaps.
+    /// Renumbering changes the bytes of the packets, but the payloads are untouched so the output
+    /// plays the same as the input. It hides any packet loss from tools that check the continuity
+    /// counters though, so check the input first if that matters.
+    ///
+    /// Packets are written as plain 188-byte transport stream packets regardless of the packet
+    /// layout of the input. Packets are read regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `out`: writer to write the cleaned up transport stream to.
+    /// - `options`: which clean up steps to run.
+    /// # Returns
+    /// The number of packets that were written.
+    pub fn clean_to<W: Write>(
+        &mut self,
+        out: W,
+        options: CleanOptions,
+    ) -> Result<u64, Box<dyn Error>> {
+        let mut out = BufWriter::new(out);
+        let mut packets_written = 0;
+        // Continuity counter of the most recent packet written on each PID.
+        let mut continuity_counters: HashMap<u16, u8> = HashMap::new();
+
+        while let Some(packet) = self.read_packet()? {
+            let header = packet.header();
+            if options.strip_null_packets && header.pid() == NULL_PACKET_PID {
+                continue
+            }
+
+            if options.drop_tei_packets && header.tei() {
+                #[cfg(feature = "log")]
+                debug!("Dropping packet with transport error indicator set at offset {} in file {}",
+                    self.current_offset, self.filename);
+                continue
+            }
+
+            let mut raw = packet.raw_bytes().to_vec();
+            if options.renumber_continuity_counters {
+                // The continuity counter is only incremented by packets that have a payload.
+                let continuity_counter = match continuity_counters.get(&header.pid()) {
+                    Some(previous) if header.has_payload() => {
+                        (previous + 1) % CONTINUITY_COUNTER_MODULUS
+                    },
+                    Some(previous) => *previous,
+                    None => header.continuity_counter(),
+                };
+                continuity_counters.insert(header.pid(), continuity_counter);
+                raw[CONTINUITY_COUNTER_BYTE] =
+                    (raw[CONTINUITY_COUNTER_BYTE] & 0xF0) | continuity_counter;
+            }
+
+            out.write_all(&raw)?;
+            packets_written += 1;
+        }
+
+        out.flush()?;
+        Ok(packets_written)
+    }
+
+    /// Write a one line summary of every remaining packet, similar to what Wireshark's MPEG-TS
+    /// dissector shows.
+    ///
+    /// The output starts with [`SUMMARY_HEADER`] and then has one line per packet with these tab
+    /// separated columns:
+    /// - `offset`: byte offset of the packet in the file, in decimal.
+    /// - `pid`: PID of the packet, in hexadecimal such as `0x0100`.
+    /// - `cc`: continuity counter, in decimal.
+    /// - `pusi`, `tei`, `priority`: the payload unit start indicator, transport error indicator,
+    ///   and transport priority header flags, as `0` or `1`.
+    /// - `tsc`, `afc`: the transport scrambling control and adaptation field control header
+    ///   fields, in decimal.
+    /// - `disc`, `rai`: the discontinuity and random access indicators of the adaptation field, as
+    ///   `0` or `1`. These are `0` when the packet has no adaptation field.
+    /// - `pcr`: PCR in 27MHz ticks, in decimal, or `-` when the packet has no PCR.
+    ///
+    /// Lines end with `\n`. This format is stable, and any new columns will only be added at the
+    /// end of the line. Packets are read regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `out`: writer to write the summary to.
+    /// # Returns
+    /// The number of packets that were summarized.
+    pub fn write_summary<W: Write>(&mut self, out: W) -> Result<u64, Box<dyn Error>> {
+        let mut out = BufWriter::new(out);
+        let mut packets_written = 0;
+        writeln!(out, "{}", SUMMARY_HEADER)?;
+
+        while let Some(packet) = self.read_packet()? {
+            let header = packet.header();
+            let adaptation_field = packet.data_adaptation_field();
+            let pcr = adaptation_field.and_then(|af| af.pcr())
+                .map_or_else(|| String::from("-"), |pcr| pcr.to_string());
+
+            writeln!(out, "{}\t0x{:04X}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                self.current_offset,
+                header.pid(),
+                header.continuity_counter(),
+                header.pusi() as u8,
+                header.tei() as u8,
+                header.transport_priority() as u8,
+                header.tsc() as u8,
+                header.adaptation_field_control() as u8,
+                adaptation_field.is_some_and(|af| af.discontinuity_indicator()) as u8,
+                adaptation_field.is_some_and(|af| af.random_access_indicator()) as u8,
+                pcr,
+            )?;
+            packets_written += 1;
+        }
+
+        out.flush()?;
+        Ok(packets_written)
+    }
+
+    /// Write the packets that cover a range of presentation times of a stream.
+    ///
+    /// The output starts at the most recent random access point on the PID at or before the first
+    /// PES packet whose PTS is at or after `start_pts`, so that the stream can be decoded from its
+    /// first packet. This usually means that the output starts at the keyframe before `start_pts`
+    /// rather than exactly at it. If there is no random access point before that PES packet, the
+    /// output starts at the next random access point inside the range instead. The output ends
+    /// right before the first PES packet on the PID whose PTS is at or after `end_pts`, and
+    /// includes the packets of every PID in between. The PTS is compared in decode order, so with
+    /// reordered frames a few frames around either end may fall outside of the range.
+    ///
+    /// The output starts with the most recent PAT and PMT packets, so it can be played on its own.
+    /// The range may wrap past the largest PTS back to `0`, as long as it is shorter than half of
+    /// [`pes::PTS_WRAP`]. Reading stops at the packet that ends the range, so the packets after it
+    /// can still be read.
+    ///
+    /// Packets are written as plain 188-byte transport stream packets regardless of the packet
+    /// layout of the input. Packets are read regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `pid`: PID of the stream whose PTS and random access points mark the range. This should
+    ///   usually be the video PID.
+    /// - `start_pts`: PTS that the range starts at in 90kHz ticks.
+    /// - `end_pts`: PTS that the range ends at in 90kHz ticks. This PTS is not included.
+    /// - `out`: writer to write the packets to.
+    /// # Returns
+    /// The number of packets that were written, not counting the PAT and PMT packets.
+    pub fn extract_pts_range<W: Write>(
+        &mut self,
+        pid: u16,
+        start_pts: u64,
+        end_pts: u64,
+        out: W,
+    ) -> Result<u64, Box<dyn Error>> {
+        let mut out = BufWriter::new(out);
+        let length = pes::pts_difference(start_pts, end_pts);
+        let mut psi_packets = PsiPackets::new();
+        // Packets since the most recent random access point before the range starts.
+        let mut pending: Option<Vec<Box<[u8]>>> = None;
+        let mut writing = false;
+        let mut packets_written = 0;
+
+        while let Some(packet) = self.read_packet()? {
+            let pts = (packet.header().pid() == pid && packet.header().pusi())
+                .then(|| packet.payload())
+                .flatten()
+                .and_then(|payload| PesPacket::from_bytes(&payload.raw_data()).ok())
+                .and_then(|pes| pes.pts());
+            let position = pts.map(|pts| pes::pts_difference(start_pts, pts));
+
+            if position.is_some_and(|position| position >= length) {
+                #[cfg(feature = "log")]
+                debug!("Reached end of PTS range on PID {} at offset {} in file {}", pid,
+                    self.current_offset, self.filename);
+                break
+            }
+
+            if writing {
+                out.write_all(packet.raw_bytes())?;
+                packets_written += 1;
+                continue
+            }
+
+            self.remember_psi_packet(&mut psi_packets, &packet);
+            if packet.header().pid() == pid && packet.is_random_access_point() {
+                pending = Some(Vec::new());
+            }
+
+            let Some(packets) = pending.as_mut() else {
+                continue
+            };
+            packets.push(Box::from(packet.raw_bytes().as_slice()));
+
+            if position.is_some_and(|position| position >= 0) {
+                #[cfg(feature = "log")]
+                debug!("Reached start of PTS range on PID {} at offset {} in file {}", pid,
+                    self.current_offset, self.filename);
+                write_psi_packets(&mut out, &psi_packets)?;
+                for raw in packets.iter() {
+                    out.write_all(raw)?;
+                }
+                packets_written += packets.len() as u64;
+                writing = true;
+            }
+        }
+
+        out.flush()?;
+        Ok(packets_written)
+    }
+
+    /// Return an iterator over the remaining packets, which lets packets be read with a `for`
+    /// loop and iterator adapters.
+    ///
+    /// Each packet is read with [`TSReader::next_packet`], so only packets on the tracked PIDs are
+    /// yielded. If an error occurs, it is yielded and the iterator ends.
+    pub fn packets(&mut self) -> Packets<'_, R> {
+        Packets {
+            reader: self,
+            finished: false,
+        }
+    }
+
+    /// Return an iterator over the remaining packets.
+    ///
+    /// This iterator ends on the first error instead of yielding it, in order to prevent the need
+    /// for `.unwrap()` calls in more concise code. See [`TSReader::packets`] for details.
+    pub fn packets_unchecked(&mut self) -> impl Iterator<Item = TSPacket> + '_ {
+        self.packets().map_while(Result::ok)
+    }
+
+    /// Return an iterator over the remaining complete payloads, which lets payloads be read with
+    /// a `for` loop and iterator adapters.
+    ///
+    /// Each payload is read with [`TSReader::next_payload`], so only payloads on the tracked PIDs
+    /// are yielded. If an error occurs, it is yielded and the iterator ends.
+    pub fn payloads(&mut self) -> Payloads<'_, R> {
+        Payloads {
+            reader: self,
+            finished: false,
+        }
+    }
+
+    /// Return an iterator over the remaining complete payloads.
+    ///
+    /// This iterator ends on the first error instead of yielding it, in order to prevent the need
+    /// for `.unwrap()` calls in more concise code. See [`TSReader::payloads`] for details.
+    pub fn payloads_unchecked(&mut self) -> impl Iterator<Item = Box<[u8]>> + '_ {
+        self.payloads().map_while(Result::ok)
+    }
+
+    /// Return an iterator over the programs listed in the PAT.
+    ///
+    /// This reads through the file until a PAT is found, and then for each program listed in it,
+    /// reads until that program's PMT is found before yielding it. Programs are yielded in the
+    /// order they are listed in the PAT. The Network Information Table entry (program number `0`)
+    /// is not a program and isn't yielded.
+    ///
+    /// Packets are read regardless of which PIDs are being tracked. If the end of the file is
+    /// reached first, the iterator ends without yielding the remaining programs. If an error
+    /// occurs, it is yielded and the iterator ends.
+    pub fn programs(&mut self) -> Programs<'_, R> {
+        Programs {
+            reader: self,
+            pending: None,
+            finished: false,
+        }
+    }
+
+    /// Return an iterator over the programs listed in the PAT.
+    ///
+    /// This iterator ends on the first error instead of yielding it, in order to prevent the need
+    /// for `.unwrap()` calls in more concise code. See [`TSReader::programs`] for details.
+    pub fn programs_unchecked(&mut self) -> impl Iterator<Item = ProgramInfo> + '_ {
+        self.programs().map_while(Result::ok)
+    }
+
+    /// Return an iterator over GOP-like windows of the transport stream.
+    ///
+    /// Each window starts at a packet on the given PID whose adaptation field has the
+    /// `random_access_indicator` set and ends right before the next one. For a video PID this splits
+    /// the stream at keyframes, which is useful for building a keyframe index or for segment based
+    /// processing. Each window is returned as the range of bytes in the file that it covers, so it
+    /// includes the packets of every PID in that range.
+    ///
+    /// Packets before the first random access point are not part of any window. The last window
+    /// ends at the end of the last packet in the file.
+    /// # Parameters
+    /// - `pid`: PID of the stream to look for random access points on. This should usually be the
+    ///   video PID.
+    pub fn gop_windows(&mut self, pid: u16) -> GopWindows<'_, R> {
+        GopWindows {
+            reader: self,
+            pid,
+            window_start: None,
+            finished: false,
+        }
+    }
+
+    /// Return an iterator over GOP-like windows of the transport stream.
+    ///
+    /// This iterator ends on the first error instead of yielding it, in order to prevent the need
+    /// for `.unwrap()` calls in more concise code. See [`TSReader::gop_windows`] for details.
+    /// # Parameters
+    /// - `pid`: PID of the stream to look for random access points on.
+    pub fn gop_windows_unchecked(&mut self, pid: u16) -> impl Iterator<Item = Range<u64>> + '_ {
+        self.gop_windows(pid).map_while(Result::ok)
+    }
+
+    /// Split the transport stream into HLS-style segment files at random access points.
+    ///
+    /// A new segment is started at the first random access point on the given PID that comes at
+    /// least `target_duration` after the start of the current segment. Time is measured with the
+    /// PCR of the program that contains the PID, so the stream must have a PMT listing the PID, a
+    /// PCR PID, and a video PID whose adaptation fields set the `random_access_indicator`.
+    ///
+    /// Each segment is written to `dir` as `segment<N>.ts` and starts with the most recent PAT and
+    /// PMT packets, so it can be played on its own. Packets before the first random access point
+    /// that follows a PCR are not written to any segment. A playlist listing every segment is
+    /// written to [`PLAYLIST_FILENAME`] in the same directory.
+    ///
+    /// Packets are always written as plain 188-byte transport stream packets, regardless of the
+    /// layout of the file being read. Packets are read regardless of which PIDs are being tracked.
+    /// # Parameters
+    /// - `pid`: PID of the video stream to split at random access points.
+    /// - `dir`: directory to write the segments and playlist to. It is created if it doesn't exist.
+    /// - `target_duration`: minimum duration of each segment other than the last one.
+    /// # Returns
+    /// The segments that were written, in order. A `MissingPcr` error if no PCR was found for the
+    /// program that contains the PID.
+    pub fn segment_to_dir(
+        &mut self,
+        pid: u16,
+        dir: impl AsRef<Path>,
+        target_duration: Duration,
+    ) -> Result<Vec<Segment>, Box<dyn Error>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut segments = Vec::new();
+        let mut psi_packets = PsiPackets::new();
+        // Writer, path, and starting PCR of the segment currently being written.
+        let mut current: Option<(BufWriter<File>, PathBuf, u64)> = None;
+        let mut last_pcr: Option<u64> = None;
+
+        loop {
+            let Some(packet) = self.read_packet()? else {
+                break
+            };
+            let packet_pid = packet.header().pid();
+            self.remember_psi_packet(&mut psi_packets, &packet);
+
+            if self.programs.pcr_pid_for(pid) == Some(packet_pid) {
+                if let Some(pcr) = packet.pcr() {
+                    last_pcr = Some(pcr);
+                }
+            }
+
+            if packet_pid == pid && packet.is_random_access_point() {
+                if let Some(pcr) = last_pcr {
+                    let start_new = match &current {
+                        Some((_, _, start)) => segment::pcr_elapsed(*start, pcr) >= target_duration,
+                        None => true,
+                    };
+
+                    if start_new {
+                        if let Some((mut writer, path, start)) = current.take() {
+                            writer.flush()?;
+                            segments.push(Segment::new(path, segment::pcr_elapsed(start, pcr)));
+                        }
+
+                        let path = dir.join(format!("segment{}.ts", segments.len()));
+                        #[cfg(feature = "log")]
+                        debug!("Starting segment {} for file {}", path.display(), self.filename);
+                        let mut writer = BufWriter::new(File::create(&path)?);
+                        write_psi_packets(&mut writer, &psi_packets)?;
+
+                        current = Some((writer, path, pcr));
+                    }
+                }
+            }
+
+            if let Some((writer, _, _)) = current.as_mut() {
+                writer.write_all(packet.raw_bytes())?;
+            }
+        }
+
+        let Some(last_pcr) = last_pcr else {
+            return Err(Box::new(MissingPcr { pid }));
+        };
+
+        if let Some((mut writer, path, start)) = current.take() {
+            writer.flush()?;
+            segments.push(Segment::new(path, segment::pcr_elapsed(start, last_pcr)));
+        }
+
+        fs::write(dir.join(PLAYLIST_FILENAME), segment::playlist(&segments))?;
+
+        Ok(segments)
+    }
+
+    /// Run a callback every time the splice countdown on a PID reaches `0`.
+    ///
+    /// The callback is run while packets are being read by any of the reader's methods, with the
+    /// splice point of the last packet before the splice. Packets with a negative countdown come
+    /// after the splice point and don't run the callback, and neither do packets that repeat a
+    /// countdown of `0` right after it. Packets are checked regardless of which PIDs are being
+    /// tracked.
+    /// # Parameters
+    /// - `pid`: PID to watch for splice points on.
+    /// - `callback`: function to run with each splice point found.
+    pub fn on_splice_point(
+        &mut self,
+        pid: u16,
+        callback: impl FnMut(&SplicePoint) + Send + 'static,
+    ) {
+        self.splice_callbacks.push((pid, Box::new(callback)));
+    }
+
+    /// Return where the packets on a PID are relative to a splice point.
+    ///
+    /// This is the phase of the most recent packet read on the PID that carried a splice
+    /// countdown, so it tells whether the stream is counting down to a splice, is at the last
+    /// packet before it, or has passed it, along with how many packets are left or have gone by.
+    /// Packets are checked regardless of which PIDs are being tracked.
+    /// # Returns
+    /// `None` if no packet on the PID has carried a splice countdown.
+    pub fn splice_phase(&self, pid: u16) -> Option<SplicePhase> {
+        self.splice_phases.get(&pid).copied()
+    }
+
+    /// Run a callback with the payload data of every packet on a PID as soon as it is read.
+    ///
+    /// This is a low latency alternative to [`TSReader::next_payload`], which can't return a
+    /// payload until the packet that starts the next one has been read. Each chunk is delivered
+    /// without waiting, but it is up to the callback to stitch chunks together, and there is no
+    /// guarantee that a payload unit will ever be completed. Chunks that come before the first start
+    /// of a payload unit are delivered too. Concatenating the chunks from one with `is_start` set up
+    /// to the next gives the same data that `next_payload` returns.
+    ///
+    /// The callback is run while packets are being read by any of the reader's methods. Packets are
+    /// checked regardless of which PIDs are being tracked.
+    ///
+    /// Like `next_payload`, a duplicate packet is not delivered twice, and a payload unit that
+    /// loses a packet to a continuity counter gap or contains a scrambled packet is cut short.
+    /// Nothing more of that unit is delivered, and delivery picks up again at the start of the
+    /// next one.
+    /// # Parameters
+    /// - `pid`: PID to deliver the payload data of.
+    /// - `callback`: function to run with each chunk of payload data.
+    pub fn on_payload_chunk(
+        &mut self,
+        pid: u16,
+        callback: impl FnMut(&PayloadChunk<'_>) + Send + 'static,
+    ) {
+        self.payload_chunk_callbacks.push((pid, Box::new(callback)));
+    }
+
+    /// Return the alignment of the SYNC bytes in this reader.
+    pub fn sync_byte_alignment(&self) -> u64 {
+        self.sync_alignment
+    }
+
+    /// Return the number of bytes after the last complete packet in the file.
+    ///
+    /// A clean capture ends with a complete packet, so this is `0`. Anything else means that the
+    /// file was truncated partway through a packet or has trailing garbage. This is measured when
+    /// the reader is created with [`TSReader::new`] or [`TSReader::with_layout`], and is `None`
+    /// for readers created from a stream since the length of a stream isn't known.
+    pub fn alignment_remainder(&self) -> Option<u64> {
+        self.alignment_remainder
+    }
+
+    /// Return the layout of the packets being read.
+    pub fn packet_layout(&self) -> PacketLayout {
+        self.layout
+    }
+
+    /// Add a PID to the tracking list.
+    ///
+    /// Only tracked PIDs are returned when running methods that gather packets or payloads. If no
+    /// PID is specified then all PIDs are returned.
+    pub fn add_tracked_pid(&mut self, pid: u16) {
+        self.tracked_pids.push(pid);
+    }
+
+    /// Remove this PID from being tracked.
+    ///
+    /// Only tracked PIDs are returned when running methods that gather packets or payloads. If no
+    /// PID is specified then all PIDs are returned.
+    pub fn remove_tracked_pid(&mut self, pid: u16) {
+        self.tracked_pids.retain(|vec_pid| *vec_pid != pid);
+    }
+
+    /// Set whether null packets should be skipped when reading packets.
+    ///
+    /// When enabled, [`TSReader::next_packet`] and the methods built on it never return a packet
+    /// on [`NULL_PACKET_PID`]. This is independent of the tracked PIDs, so it also applies when no
+    /// PIDs are tracked. Null packets are still counted in the reader's statistics. Disabled by
+    /// default.
+    pub fn skip_null_packets(&mut self, skip: bool) {
+        self.skip_null_packets = skip;
+    }
+
+    /// Scan forward from the current position for the next packet that starts with a SYNC byte.
+    ///
+    /// The next position where SYNC bytes recur at the packet stride is found the same way
+    /// [`TSReader::new`] finds the first packet, so reading can continue cleanly after a
+    /// [`SyncLost`] error, such as in a live capture with packet loss. The scan starts at the
+    /// packet that lost its SYNC byte, so no intact packets after it are skipped. If the reader is
+    /// already aligned, it stays where it is. Any payloads and sections that were partway through
+    /// being reassembled are discarded, since the packets they were missing can't be recovered.
+    /// See [`TSReader::set_resync_on_error`] to do this automatically.
+    /// # Returns
+    /// A [`NoSyncByteFound`] error if the end of the file is reached without finding a SYNC byte.
+    pub fn resync(&mut self) -> Result<(), Box<dyn Error>> {
+        #[cfg(feature = "log")]
+        debug!("Resynchronizing at offset {} in file {}", self.offset, self.filename);
+
+        if ! self.scan_for_sync(Vec::new(), self.offset, 0)? {
+            return Err(Box::new(NoSyncByteFound));
+        }
+
+        Ok(())
+    }
+
+    /// Set whether reading scans forward for the next SYNC byte when a packet doesn't start with
+    /// one.
+    ///
+    /// The alignment of the packets can drift partway through a file, such as in a concatenation
+    /// of two captures or after a corrupt region. When disabled, reading a packet that doesn't
+    /// start with a SYNC byte returns a [`SyncLost`] error, and the reader stays at that packet
+    /// until [`TSReader::resync`] is called. When enabled, the reader scans forward for the next
+    /// position where SYNC bytes recur at the packet stride, the same way [`TSReader::new`] finds
+    /// the first packet, and reads the packet there instead. The skipped bytes are lost, so any
+    /// payloads and sections that were partway through being reassembled are discarded. Disabled
+    /// by default.
+    pub fn set_resync_on_error(&mut self, resync: bool) {
+        self.resync_on_error = resync;
+    }
+
+    /// Discard the payload that is currently being reassembled on this PID.
+    ///
+    /// This is useful when a PID is known to be corrupt and its reassembly should start over from
+    /// the next packet with the `PUSI` set. The payloads of other PIDs, the tracked PIDs, and the
+    /// read position are not affected.
+    pub fn clear_payload_state(&mut self, pid: u16) {
+        self.tracked_payloads.retain(|tracked_payload| tracked_payload.pid() != pid);
+    }
+
+    /// Add payload data from a packet to the tracked payloads list.
+    fn add_tracked_payload(&mut self, packet: &TSPacket) -> Option<CompletedPayload> {
+        let payload = packet.payload()?;
+        let offset = self.current_offset;
+        let discontinuity = packet.data_adaptation_field()
+            .is_some_and(|af| af.discontinuity_indicator());
+
+        // Check to see if we already have an TrackedPayload object for this item PID
+        let pid = packet.header().pid();
+
+        // The data of a scrambled packet is garbage until it is descrambled, so the payload that it
+        // belongs to can't be completed.
+        if packet.is_scrambled() {
+            #[cfg(feature = "log")]
+            warn!("Dropping payload on PID {} with a scrambled packet at offset {} in file {}", pid,
+                offset, self.filename);
+            self.clear_payload_state(pid);
+            return None
+        }
+        
+        if let Some(index) = self.tracked_payloads.iter().position(|tp| tp.pid() == pid) {
+            let tracked_payload = &mut self.tracked_payloads[index];
+            let completed = tracked_payload.add_and_get_complete(&payload, offset, discontinuity)?;
+
+            let max_size = self.max_payload_sizes.entry(pid).or_default();
+            *max_size = (*max_size).max(completed.data().len());
+            return Some(completed);
+        }
+
+        // We cannot possibly know that a payload is complete from the first packet. In order to
+        // know that a payload is fully contained in 1 packet we need to see the `PUSI` flag set in
+        // the next packet so there is no reason to check if the packet is complete when creating a
+        // new TrackedPayload.
+
+        if let Ok(tp) = TrackedPayload::from_packet(packet, offset) {
+            self.tracked_payloads.push(tp);
+        }
+
+        None
+    }
+
+    /// Return the kind of data that a PID carries, using the stream type that a PMT lists for it
+    /// before falling back to inspecting the raw data of a payload that starts on it.
+    fn payload_kind(&self, pid: u16, raw: &[u8]) -> PayloadKind {
+        let stream_type = self.programs.pmts()
+            .flat_map(|pmt| pmt.streams())
+            .find(|stream| stream.elementary_pid() == pid)
+            .map(|stream| stream.stream_type());
+
+        match stream_type {
+            Some(stream_type) => PayloadKind::from_stream_type(stream_type),
+            None if self.programs.is_psi_pid(pid) => PayloadKind::Psi,
+            None => demux::classify_payload(raw),
+        }
+    }
+
+    /// Keep the packet if it carries the PAT or a PMT, replacing the previous table on its PID
+    /// when it starts a new one.
+    fn remember_psi_packet(&self, psi_packets: &mut PsiPackets, packet: &TSPacket) {
+        let pid = packet.header().pid();
+        if ! self.programs.is_psi_pid(pid) {
+            return
+        }
+
+        let packets = psi_packets.entry(pid).or_default();
+        if packet.header().pusi() {
+            packets.clear();
+        }
+        packets.push(Box::from(packet.raw_bytes().as_slice()));
+    }
+
+    /// Discard everything that depends on the packets that came right before the current one.
+    ///
+    /// This should be run when the reader jumps to a different part of the stream. Partially
+    /// reassembled payloads and sections are dropped and continuity counters aren't checked
+    /// against the packets before the jump.
+    fn clear_partial_state(&mut self) {
+        self.tracked_payloads.clear();
+        self.tracked_sections.values_mut().for_each(|(assembler, _)| assembler.reset());
+        self.continuity.forget_counters();
+        self.pcr_clock.forget_last();
+        self.splice_phases.clear();
+        self.interrupted_chunk_pids.clear();
+    }
+}
+
+impl<R: Read + Seek> TSReader<R> {
+    /// Confine reading to the packets that are entirely inside a range of bytes in the file.
+    ///
+    /// This seeks to the first packet that starts at or after `start`, and reading stops before the
+    /// first packet that doesn't end by `end`. It is useful for looking into just a slice of a
+    /// large file, such as the area around a known error.
+    ///
+    /// Payloads and sections that were partially reassembled before the seek are dropped.
+    /// # Parameters
+    /// - `start`: byte offset in the file that the window starts at.
+    /// - `end`: byte offset in the file that the window ends at. This byte is not included.
+    /// # Returns
+    /// An `EmptyWindow` error if the window doesn't contain a complete packet.
+    pub fn set_window(&mut self, start: u64, end: u64) -> Result<(), Box<dyn Error>> {
+        let stride = self.layout.stride() as u64;
+
+        // Every packet starts a multiple of the stride away from the first one.
+        let sync_offset = self.sync_alignment - 1;
+        let first_packet = (sync_offset + stride - self.layout.prefix() as u64) % stride;
+        let packet_start = start + (first_packet + stride - start % stride) % stride;
+        if packet_start + stride > end {
+            return Err(Box::new(EmptyWindow { start, end }));
+        }
+
+        #[cfg(feature = "log")]
+        debug!("Seeking to offset {} for window [{}, {}) in file {}", packet_start, start, end,
+            self.filename);
+
+        self.reader.seek(SeekFrom::Start(packet_start))?;
+        self.pending.clear();
+        self.offset = packet_start;
+        self.current_offset = packet_start;
+        self.window_end = Some(end);
+        self.clear_partial_state();
+
+        Ok(())
+    }
+
+    /// Seek to a packet by its number, counting from `0` at the first packet in the file.
+    ///
+    /// Packets are numbered from the first packet that was found when the reader was created, so
+    /// any bytes before it aren't counted. The packet read counter is set to `index` as if every
+    /// packet before it had been read. Payloads and sections that were partially reassembled
+    /// before the seek are dropped.
+    /// # Parameters
+    /// - `index`: number of the packet to seek to.
+    /// # Returns
+    /// A [`PacketOutOfRange`] error if the file doesn't have a complete packet with that number.
+    pub fn seek_to_packet(&mut self, index: u64) -> Result<(), Box<dyn Error>> {
+        let stride = self.layout.stride() as u64;
+        let first_packet = self.first_packet_offset();
+        let packet_start = first_packet + index * stride;
+
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        if packet_start + stride > end {
+            // Put the reader back where it was so that reading can carry on.
+            self.reader.seek(SeekFrom::Start(self.offset + self.pending.len() as u64))?;
+            let packets = end.saturating_sub(first_packet) / stride;
+            return Err(Box::new(PacketOutOfRange { index, packets }));
+        }
+
+        #[cfg(feature = "log")]
+        debug!("Seeking to packet {} at offset {} in file {}", index, packet_start, self.filename);
+
+        self.reader.seek(SeekFrom::Start(packet_start))?;
+        self.pending.clear();
+        self.offset = packet_start;
+        self.current_offset = packet_start;
+        self.packets_read = index;
+        self.clear_partial_state();
+
+        Ok(())
+    }
+
+    /// Go back to the first packet in the file so that it can be read again, without detecting
+    /// the packet layout and SYNC byte alignment again.
+    ///
+    /// This is useful for a second pass over the file, such as reading the PAT and PMTs first and
+    /// then extracting payloads. The packet read counter is set to `0`, the window set by
+    /// [`TSReader::set_window`] is removed, and payloads and sections that were partially
+    /// reassembled are dropped. The tracked PIDs are preserved, as are the program tables, the
+    /// statistics, and the error counts gathered so far.
+    pub fn reset(&mut self) -> Result<(), Box<dyn Error>> {
+        let first_packet = self.first_packet_offset();
+
+        #[cfg(feature = "log")]
+        debug!("Resetting to the first packet at offset {} in file {}", first_packet,
+            self.filename);
+
+        self.reader.seek(SeekFrom::Start(first_packet))?;
+        self.pending.clear();
+        self.offset = first_packet;
+        self.current_offset = first_packet;
+        self.window_end = None;
+        self.packets_read = 0;
+        self.clear_partial_state();
+
+        Ok(())
+    }
+
+    /// Seek to the packet with the last PCR at or before a target PCR, for seeking to a time.
+    ///
+    /// This does a binary search over the file, reading only the packets between each sampled
+    /// offset and the next PCR, so it doesn't need to scan the whole file. Only the PCRs on the PID
+    /// of the first PCR in the file are used, since each program has its own clock. PCRs are
+    /// compared by how far they are past the first PCR, so a PCR that wraps back to `0` partway
+    /// through the file is handled. A target before the first PCR lands on the first PCR.
+    ///
+    /// The result is approximate. It is the nearest PCR-bearing packet at or before the target,
+    /// assuming the PCR only increases through the file, so PCR discontinuities can make it land
+    /// somewhere else. Payloads and sections that were partially reassembled before the seek are
+    /// dropped.
+    /// # Parameters
+    /// - `target`: PCR to seek to, in 27MHz ticks.
+    /// # Returns
+    /// The PCR of the packet that the reader was moved to, which is the next packet read. Returns
+    /// `None` without moving the reader if the file has no PCRs.
+    pub fn seek_to_pcr(&mut self, target: u64) -> Result<Option<u64>, Box<dyn Error>> {
+        let stride = self.layout.stride() as u64;
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        let packets = end.saturating_sub(self.first_packet_offset()) / stride;
+
+        let Some((mut low, pid, first_pcr)) = self.find_pcr(0, packets, None)? else {
+            // Put the reader back where it was so that reading can carry on.
+            self.reader.seek(SeekFrom::Start(self.offset + self.pending.len() as u64))?;
+            return Ok(None)
+        };
+
+        let elapsed = |pcr: u64| ((pcr + PCR_WRAP - first_pcr % PCR_WRAP) % PCR_WRAP) as i64;
+        let target_elapsed = pcr_difference(first_pcr, target);
+        let mut best_pcr = first_pcr;
+
+        // The PCR packet at `low` is at or before the target, and every PCR packet from `high` on
+        // is after it.
+        let mut high = packets;
+        while high - low > 1 {
+            let middle = low + (high - low) / 2;
+            match self.find_pcr(middle, high, Some(pid))? {
+                Some((index, _, pcr)) if elapsed(pcr) <= target_elapsed => {
+                    low = index;
+                    best_pcr = pcr;
+                },
+                _ => high = middle,
+            }
+        }
+
+        #[cfg(feature = "log")]
+        debug!("Found PCR {} at packet {} for target PCR {} in file {}", best_pcr, low, target,
+            self.filename);
+
+        self.seek_to_packet(low)?;
+        Ok(Some(best_pcr))
+    }
+
+    /// Find the first packet with a PCR from packet `start` up to, but not including, packet
+    /// `end`.
+    ///
+    /// Packets are read straight from the source, so they aren't counted in any statistics.
+    /// Packets that can't be parsed are skipped.
+    /// # Parameters
+    /// - `pid`: PID that the PCR has to be on, or `None` for any PID.
+    /// # Returns
+    /// The number of the packet, its PID, and its PCR.
+    fn find_pcr(
+        &mut self,
+        start: u64,
+        end: u64,
+        pid: Option<u16>,
+    ) -> Result<Option<PcrPacket>, Box<dyn Error>> {
+        let stride = self.layout.stride() as u64;
+        self.reader.seek(SeekFrom::Start(self.first_packet_offset() + start * stride))?;
+
+        let mut packet_buf = vec![0; self.layout.stride()];
+        for index in start..end {
+            self.reader.read_exact(&mut packet_buf)?;
+            let Ok(packet) = TSPacket::from_bytes(&packet_buf[self.layout.body_range()]) else {
+                continue
+            };
+
+            let packet_pid = packet.header().pid();
+            if let Some(pcr) = packet.pcr().filter(|_| pid.is_none_or(|pid| pid == packet_pid)) {
+                return Ok(Some((index, packet_pid, pcr)))
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Check that every packet in the file starts with a SYNC byte by reading only that byte.
+    ///
+    /// This seeks one stride at a time from the first packet to the end of the file, so it is a
+    /// much quicker health check of a large capture than parsing every packet. Only complete
+    /// packets are checked. A source that buffers its reads, like a `BufReader`, throws its buffer
+    /// away on every seek, so pass the file itself for the fastest check.
+    ///
+    /// The read position is put back afterwards, so reading carries on from where it was.
+    /// # Returns
+    /// An `AlignmentReport` with the offsets of the packets that didn't start with a SYNC byte.
+    pub fn verify_alignment(&mut self) -> Result<AlignmentReport, Box<dyn Error>> {
+        let report = self.read_sync_bytes();
+
+        // Put the reader back where it was so that reading can carry on.
+        self.reader.seek(SeekFrom::Start(self.offset + self.pending.len() as u64))?;
+        report
+    }
+
+    /// Read the SYNC byte position of every complete packet in the file.
+    fn read_sync_bytes(&mut self) -> Result<AlignmentReport, Box<dyn Error>> {
+        let stride = self.layout.stride() as u64;
+        let prefix = self.layout.prefix() as u64;
+        let end = self.reader.seek(SeekFrom::End(0))?;
+
+        let mut report = AlignmentReport::new();
+        let mut sync_byte = [0];
+        let mut packet_start = self.first_packet_offset();
+        while packet_start + stride <= end {
+            self.reader.seek(SeekFrom::Start(packet_start + prefix))?;
+            self.reader.read_exact(&mut sync_byte)?;
+            report.observe(packet_start, sync_byte[0] == SYNC_BYTE);
+            packet_start += stride;
+        }
+
+        #[cfg(feature = "log")]
+        debug!("Found {} misaligned packets out of {} in file {}", report.misaligned_count(),
+            report.packets(), self.filename);
+
+        Ok(report)
+    }
+
+    /// Return the byte offset of the first packet that was found when the reader was created.
+    fn first_packet_offset(&self) -> u64 {
+        packet_start(self.sync_alignment - 1, self.layout)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: Read + Send + 'static> TSReader<R> {
+    /// Read the rest of the transport stream on a separate thread and send each completed payload
+    /// to an async channel along with the PID that it was carried on.
+    ///
+    /// This lets async consumers, such as tokio tasks, process payloads without blocking on file
+    /// I/O. Payloads are reassembled the same way as [`TSReader::next_completed_payload`], so only
+    /// payloads on the tracked PIDs are sent. The channel is bounded to `capacity` payloads, and
+    /// once it is full the reading thread waits for the consumer to receive one before reading any
+    /// further, so a slow consumer slows reading down rather than using more memory.
+    ///
+    /// The channel is closed when the end of the file is reached or an error occurs, and the
+    /// reading thread stops early if the receiver is dropped. Requires the `async` feature.
+    /// # Parameters
+    /// - `capacity`: number of payloads that can be waiting in the channel. Must be at least `1`.
+    pub fn spawn_payload_channel(mut self, capacity: usize) -> mpsc::Receiver<(u16, Box<[u8]>)> {
+        let (sender, receiver) = mpsc::channel(capacity);
+
+        thread::spawn(move || {
+            loop {
+                let payload = match self.next_completed_payload() {
+                    Ok(Some(payload)) => payload,
+                    Ok(None) => break,
+                    Err(_e) => {
+                        #[cfg(feature = "log")]
+                        debug!("Stopped sending payloads from file {} after error: {}",
+                            self.filename, _e);
+                        break
+                    },
+                };
+
+                if sender.blocking_send((payload.pid(), payload.into_data())).is_err() {
+                    #[cfg(feature = "log")]
+                    debug!("Payload receiver for file {} was dropped", self.filename);
+                    break
+                }
+            }
+        });
+
+        receiver
+    }
+}
+
+/// Write the packets of the PAT and then every PMT, so that a player can find the PMTs.
+fn write_psi_packets<W: Write>(out: &mut W, psi_packets: &PsiPackets) -> io::Result<()> {
+    let mut psi_pids: Vec<&u16> = psi_packets.keys().collect();
+    psi_pids.sort_by_key(|psi_pid| (**psi_pid != PAT_PID, **psi_pid));
+    for psi_pid in psi_pids {
+        for psi_packet in &psi_packets[psi_pid] {
+            out.write_all(psi_packet)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read from `reader` into `buf` until a SYNC byte that has another SYNC byte 1 stride after it is
+/// found at or after `buf[idx]`.
+///
+/// `start` is the byte offset in the stream of `buf[0]`. Only 1 stride is kept before the byte
+/// being checked, which is enough for the prefix of a packet, so older bytes are dropped from `buf`
+/// and `start` is moved forward to match. SYNC bytes with fewer than `prefix` bytes before them in
+/// `buf` are skipped, since their packet wouldn't have room for its prefix.
+/// # Returns
+/// `Ok(Some(usize))` with the index of the SYNC byte in `buf`.
+/// `Ok(None)` if the end of the stream was reached first.
+fn search_for_sync<R: Read>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    start: &mut u64,
+    mut idx: usize,
+    stride: usize,
+    prefix: usize,
+) -> io::Result<Option<usize>> {
+    let mut eof = false;
+    loop {
+        match find_sync(buf, idx, stride) {
+            Ok(sync_idx) if sync_idx >= prefix => return Ok(Some(sync_idx)),
+            Ok(sync_idx) => {
+                idx = sync_idx + 1;
+                continue
+            },
+            Err(resume_idx) => idx = resume_idx,
+        }
+
+        if eof {
+            return Ok(None);
+        }
+
+        if idx >= 2 * stride {
+            buf.drain(..idx - stride);
+            *start += (idx - stride) as u64;
+            idx = stride;
+        }
+
+        let count = reader.by_ref().take(SYNC_SEARCH_CHUNK_SIZE).read_to_end(buf)?;
+        eof = count == 0;
+    }
+}
+
+/// Return the PTS in a sorted list that is closest to `pts`.
+fn closest_pts(sorted_pts: &[u64], pts: u64) -> Option<u64> {
+    let index = sorted_pts.partition_point(|&frame| frame < pts);
+    let before = index.checked_sub(1).map(|index| sorted_pts[index]);
+    let after = sorted_pts.get(index).copied();
+
+    match (before, after) {
+        (Some(before), Some(after)) if after - pts < pts - before => Some(after),
+        (Some(before), _) => Some(before),
+        (None, after) => after,
+    }
+}
+
+/// Payload data from a single packet, delivered by [`TSReader::on_payload_chunk`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PayloadChunk<'a> {
+    /// PID that the payload was carried on.
+    pid: u16,
+    /// Byte offset in the file of the packet that the data came from.
+    offset: u64,
+    /// Set when this data is the start of a new payload unit.
+    is_start: bool,
+    /// Payload data from the packet.
+    data: &'a [u8],
+}
+
+impl PayloadChunk<'_> {
+    /// Return the PID that the payload was carried on.
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Return the byte offset in the file of the packet that the data came from.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return if this data is the start of a new payload unit.
+    pub fn is_start(&self) -> bool {
+        self.is_start
+    }
+
+    /// Return the payload data from the packet.
+    pub fn data(&self) -> &[u8] {
+        self.data
+    }
+}
+
+/// Which clean up steps [`TSReader::clean_to`] should run.
+///
+/// Every step is enabled by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CleanOptions {
+    /// Leave out null packets.
+    pub strip_null_packets: bool,
+    /// Leave out packets that have the transport error indicator set.
+    pub drop_tei_packets: bool,
+    /// Renumber the continuity counters of every PID so that they have no gaps.
+    pub renumber_continuity_counters: bool,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        CleanOptions {
+            strip_null_packets: true,
+            drop_tei_packets: true,
+            renumber_continuity_counters: true,
+        }
+    }
+}
+
+/// Iterator over the programs listed in the PAT of a transport stream.
+///
+/// This is created by [`TSReader::programs`].
+pub struct Programs<'a, R> {
+    /// Reader that the packets are read from.
+    reader: &'a mut TSReader<R>,
+    /// Program numbers and PMT PIDs from the PAT that haven't been yielded yet, in reverse order.
+    ///
+    /// Is `None` until the PAT has been found.
+    pending: Option<Vec<(u16, u16)>>,
+    /// Set once the end of the file has been reached or an error has been returned.
+    finished: bool,
+}
+
+impl<R: Read> Programs<'_, R> {
+    /// Find the next program without checking whether the iteration has already finished.
+    fn next_program(&mut self) -> Result<Option<ProgramInfo>, Box<dyn Error>> {
+        while self.pending.is_none() {
+            if let Some(pat) = self.reader.programs.pat() {
+                self.pending = Some(pat.programs().iter().rev().copied().collect());
+            } else if self.reader.read_packet()?.is_none() {
+                return Ok(None);
+            }
+        }
+
+        let Some((program_number, pmt_pid)) = self.pending.as_mut().and_then(Vec::pop) else {
+            return Ok(None);
+        };
+        loop {
+            if let Some(pmt) = self.reader.programs.pmt(program_number) {
+                return Ok(Some(ProgramInfo::new(pmt_pid, pmt)));
+            }
+
+            if self.reader.read_packet()?.is_none() {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for Programs<'_, R> {
+    type Item = Result<ProgramInfo, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let program = self.next_program().transpose();
+        if !matches!(program, Some(Ok(_))) {
+            self.finished = true;
+        }
+        program
+    }
+}
+
+/// Iterator over the packets of a transport stream.
+///
+/// This is created by [`TSReader::packets`].
+pub struct Packets<'a, R> {
+    /// Reader that the packets are read from.
+    reader: &'a mut TSReader<R>,
+    /// Set once the end of the file has been reached or an error has been returned.
+    finished: bool,
+}
+
+impl<R: Read> Iterator for Packets<'_, R> {
+    type Item = Result<TSPacket, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let packet = self.reader.next_packet().transpose();
+        if !matches!(packet, Some(Ok(_))) {
+            self.finished = true;
+        }
+        packet
+    }
+}
+
+/// Iterator over the complete payloads of a transport stream.
+///
+/// This is created by [`TSReader::payloads`].
+pub struct Payloads<'a, R> {
+    /// Reader that the payloads are read from.
+    reader: &'a mut TSReader<R>,
+    /// Set once the end of the file has been reached or an error has been returned.
+    finished: bool,
+}
+
+impl<R: Read> Iterator for Payloads<'_, R> {
+    type Item = Result<Box<[u8]>, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let payload = self.reader.next_payload().transpose();
+        if !matches!(payload, Some(Ok(_))) {
+            self.finished = true;
+        }
+        payload
+    }
+}
+
+/// Iterator over the GOP-like windows of a transport stream.
+///
+/// This is created by [`TSReader::gop_windows`].
+pub struct GopWindows<'a, R> {
+    /// Reader that the packets are read from.
+    reader: &'a mut TSReader<R>,
+    /// PID to look for random access points on.
+    pid: u16,
+    /// Byte offset of the random access point that started the current window.
+    window_start: Option<u64>,
+    /// Set once the end of the file has been reached or an error has been returned.
+    finished: bool,
+}
+
+impl<R: Read> Iterator for GopWindows<'_, R> {
+    type Item = Result<Range<u64>, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            let packet = match self.reader.read_packet() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => {
+                    self.finished = true;
+                    return self.window_start.take().map(|start| Ok(start..self.reader.offset));
+                },
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                },
+            };
+
+            if packet.header().pid() != self.pid || ! packet.is_random_access_point() {
+                continue
+            }
+
+            let offset = self.reader.current_offset;
+            if let Some(start) = self.window_start.replace(offset) {
+                return Some(Ok(start..offset));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::invalid_payload_pointer::InvalidPayloadPointer;
+    use crate::packet::{HEADER_SIZE, PACKET_SIZE};
+    use std::io::Cursor;
+    use test_case::test_case;
+
+    /// Build a source of packets with the given layout starting `offset` bytes into the source.
+    fn source(layout: PacketLayout, offset: usize, packets: usize) -> Vec<u8> {
+        let mut buf = vec![0; offset];
+        for _ in 0..packets {
+            let mut packet = vec![0; layout.stride()];
+            packet[layout.prefix()] = SYNC_BYTE;
+            buf.extend(packet);
+        }
+        buf
+    }
+
+    #[test_case(PacketLayout::TS, 0; "TS")]
+    #[test_case(PacketLayout::TS, 37; "TS with leading garbage")]
+    #[test_case(PacketLayout::M2TS, 0; "M2TS")]
+    #[test_case(PacketLayout::REED_SOLOMON, 10; "Reed-Solomon")]
+    fn probe_format(layout: PacketLayout, offset: usize) {
+        let mut reader = Cursor::new(source(layout, offset, PROBE_PACKET_COUNT + 2));
+        let detected = TSReader::probe_format(&mut reader).unwrap();
+        assert_eq!(detected, Some(layout), "Detected layout is incorrect");
+    }
+
+    #[test_case(vec![0; 2048]; "No SYNC bytes")]
+    #[test_case(source(PacketLayout::TS, 0, PROBE_PACKET_COUNT - 1); "Too few packets")]
+    fn probe_format_not_ts(data: Vec<u8>) {
+        let mut reader = Cursor::new(data);
+        assert!(!TSReader::is_valid_ts(&mut reader), "Source was detected as a transport stream");
+    }
+
+    /// Build a stream of plain transport stream packets with a payload on each of the given PIDs.
+    fn packets(pids: &[u16]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for pid in pids {
+            let mut packet = vec![0; PACKET_SIZE];
+            packet[..4].copy_from_slice(&[SYNC_BYTE, (pid >> 8) as u8, *pid as u8, 0x10]);
+            buf.extend(packet);
+        }
+        buf
+    }
+
+    #[test_case(0; "Aligned")]
+    #[test_case(5000; "Leading garbage")]
+    fn from_stream(garbage: usize) {
+        let mut data = vec![0; garbage];
+        data.extend(packets(&[0x100, 0x101, 0x102]));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert_eq!(reader.sync_byte_alignment(), garbage as u64 + 1, "SYNC alignment is incorrect");
+
+        let mut pids = Vec::new();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            pids.push(packet.header().pid());
+        }
+        assert_eq!(pids, vec![0x100, 0x101, 0x102], "PIDs read are incorrect");
+    }
+
+    #[test]
+    fn new_in_memory() {
+        let data = packets(&[0x100, 0x101, 0x102]);
+        let mut reader = TSReader::new("mem", Cursor::new(data)).unwrap();
+
+        let mut pids = Vec::new();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            pids.push(packet.header().pid());
+        }
+        assert_eq!(pids, vec![0x100, 0x101, 0x102], "PIDs read are incorrect");
+    }
+
+    #[test]
+    fn from_stream_slice() {
+        let data = packets(&[0x100, 0x101, 0x102]);
+        let mut reader = TSReader::from_stream("test", data.as_slice()).unwrap();
+
+        let pids: Vec<u16> = reader.packets_unchecked()
+            .map(|packet| packet.header().pid())
+            .collect();
+        assert_eq!(pids, vec![0x100, 0x101, 0x102], "PIDs read are incorrect");
+    }
+
+    #[test]
+    fn from_stream_no_sync() {
+        let result = TSReader::from_stream("test", Cursor::new(vec![0; 2048]));
+        assert!(result.is_err_and(|e| e.is::<NoSyncByteFound>()),
+            "Missing SYNC byte was not detected");
+    }
+
+    /// Build a packet which carries a whole PSI section, appending the section's CRC.
+    fn psi_packet(pid: u16, section: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0xFF; PACKET_SIZE];
+        packet[..5].copy_from_slice(&[SYNC_BYTE, 0x40 | (pid >> 8) as u8, pid as u8, 0x10, 0x00]);
+        packet[5..5 + section.len()].copy_from_slice(section);
+        let crc = psi::crc32(section).to_be_bytes();
+        packet[5 + section.len()..9 + section.len()].copy_from_slice(&crc);
+        packet
+    }
+
+    /// Build a PMT section, without its CRC, for a program with a single stream.
+    fn pmt_section(program_number: u16, pcr_pid: u16, stream_type: u8, pid: u16) -> Vec<u8> {
+        vec![
+            0x02, 0xB0, 0x12, (program_number >> 8) as u8, program_number as u8, 0xC1, 0x00,
+            0x00, 0xE0 | (pcr_pid >> 8) as u8, pcr_pid as u8, 0xF0, 0x00, stream_type,
+            0xE0 | (pid >> 8) as u8, pid as u8, 0xF0, 0x00,
+        ]
+    }
+
+    #[test]
+    fn programs() {
+        // The PAT has a NIT entry and two programs.
+        let pat = [
+            0x00, 0xB0, 0x15, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x00, 0xE0, 0x10, 0x00, 0x01,
+            0xF0, 0x00, 0x00, 0x02, 0xF0, 0x01,
+        ];
+
+        // The PMT of program 2 is sent before the PMT of program 1.
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(psi_packet(0x1001, &pmt_section(2, 0x201, 0x0F, 0x201)));
+        data.extend(psi_packet(0x1000, &pmt_section(1, 0x101, 0x1B, 0x101)));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let programs = reader.programs().collect::<Result<Vec<ProgramInfo>, _>>().unwrap();
+        assert_eq!(programs.len(), 2, "Number of programs is incorrect");
+
+        assert_eq!(programs[0].program_number(), 1, "Program number is incorrect");
+        assert_eq!(programs[0].pmt_pid(), 0x1000, "PMT PID is incorrect");
+        assert_eq!(programs[0].pcr_pid(), Some(0x101), "PCR PID is incorrect");
+        assert_eq!(programs[0].streams()[0].stream_type(), 0x1B, "Stream type is incorrect");
+
+        assert_eq!(programs[1].program_number(), 2, "Program number is incorrect");
+        assert_eq!(programs[1].pmt_pid(), 0x1001, "PMT PID is incorrect");
+        assert_eq!(programs[1].streams()[0].elementary_pid(), 0x201, "Stream PID is incorrect");
+    }
+
+    #[test]
+    fn raw_pat() {
+        let pat = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(packets(&[NULL_PACKET_PID]));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data.clone())).unwrap();
+        assert_eq!(reader.raw_pat(), None, "PAT was found before reading");
+        while reader.next_packet().unwrap().is_some() {}
+
+        let raw_pat = reader.raw_pat().unwrap();
+        assert_eq!(&*raw_pat, &data[5..21], "Raw PAT is incorrect");
+    }
+
+    #[test]
+    fn programs_missing_pmt() {
+        let pat = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(packets(&[NULL_PACKET_PID, NULL_PACKET_PID]));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert_eq!(reader.programs_unchecked().count(), 0, "Program without a PMT was yielded");
+    }
+
+    /// Build a stream with a PAT and a PMT for program 1, which has H.264 video on PID 0x100 and
+    /// MPEG-2 AAC audio on PID 0x101. The PCR is carried on the video PID.
+    fn two_stream_psi() -> Vec<u8> {
+        let pat = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+        let pmt = [
+            0x02, 0xB0, 0x17, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x00, 0xF0, 0x00, 0x1B, 0xE1,
+            0x00, 0xF0, 0x00, 0x0F, 0xE1, 0x01, 0xF0, 0x00,
+        ];
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(psi_packet(0x1000, &pmt));
+        data
+    }
+
+    #[test]
+    fn missing_pids() {
+        let mut data = two_stream_psi();
+        data.extend(packets(&[0x100, NULL_PACKET_PID, 0x100]));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert!(reader.missing_pids().is_empty(), "PIDs are missing before the PAT was read");
+        while reader.next_packet().unwrap().is_some() {}
+
+        assert_eq!(reader.missing_pids(), vec![0x101], "Missing PIDs are incorrect");
+    }
+
+    #[test]
+    fn orphan_pids() {
+        // PIDs 0x200 and 0x300 aren't in the PAT or PMT. The SDT, ATSC PSIP, and null PIDs are
+        // defined by a standard so they aren't orphans.
+        let mut data = two_stream_psi();
+        data.extend(packets(&[0x100, 0x300, psi::SDT_PID, 0x101, psi::ATSC_PSIP_PID,
+            NULL_PACKET_PID, 0x200, 0x300]));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        while reader.next_packet().unwrap().is_some() {}
+
+        assert_eq!(reader.orphan_pids(), vec![0x200, 0x300], "Orphan PIDs are incorrect");
+    }
+
+    #[test_case(psi::SDT_PID, 0x42, StreamProfile::Dvb; "DVB")]
+    #[test_case(psi::ATSC_PSIP_PID, 0xC7, StreamProfile::Atsc; "ATSC")]
+    #[test_case(PAT_PID, 0x00, StreamProfile::Unknown; "Raw")]
+    fn detect_profile(pid: u16, table_id: u8, expected: StreamProfile) {
+        let pat = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(packets(&[0x100, 0x100]));
+        data.extend(psi_packet(pid, &[table_id, 0xF0, 0x05, 0x00, 0x01, 0xC1, 0x00, 0x00]));
+        data.extend(packets(&[0x100]));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert_eq!(reader.detect_profile().unwrap(), expected, "Profile is incorrect");
+    }
+
+    #[test]
+    fn validate_program_structure() {
+        // The PAT lists four programs. Program 1 is valid, program 2 has no PCR PID and no
+        // streams, program 3 has a corrupt PMT, and program 4's PMT PID is never sent.
+        let pat = [
+            0x00, 0xB0, 0x19, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x00, 0x02,
+            0xF0, 0x01, 0x00, 0x03, 0xF0, 0x02, 0x00, 0x04, 0xF0, 0x03,
+        ];
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(psi_packet(0x1000, &pmt_section(1, 0x101, 0x1B, 0x101)));
+        data.extend(psi_packet(0x1001, &[
+            0x02, 0xB0, 0x0D, 0x00, 0x02, 0xC1, 0x00, 0x00, 0xFF, 0xFF, 0xF0, 0x00,
+        ]));
+        let mut corrupt = psi_packet(0x1002, &pmt_section(3, 0x301, 0x1B, 0x301));
+        corrupt[10] ^= 0xFF;
+        data.extend(corrupt);
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert_eq!(reader.validate_program_structure(), vec![StructureDefect::MissingPat],
+            "Missing PAT was not reported");
+        while reader.next_packet().unwrap().is_some() {}
+
+        assert_eq!(reader.validate_program_structure(), vec![
+            StructureDefect::MissingPcrPid { program_number: 2 },
+            StructureDefect::NoStreams { program_number: 2 },
+            StructureDefect::InvalidPmt { program_number: 3, pmt_pid: 0x1002 },
+            StructureDefect::MissingPmt { program_number: 4, pmt_pid: 0x1003 },
+        ], "Defects are incorrect");
+    }
+
+    #[test]
+    fn structure_tree() {
+        // The PAT has a NIT entry and three programs. Program 2 carries its PCR on a PID of its
+        // own, and program 3's PMT is never sent.
+        let pat = [
+            0x00, 0xB0, 0x19, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x00, 0xE0, 0x10, 0x00, 0x01,
+            0xF0, 0x00, 0x00, 0x02, 0xF0, 0x01, 0x00, 0x03, 0xF0, 0x02,
+        ];
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(psi_packet(0x1000, &pmt_section(1, 0x101, 0x1B, 0x101)));
+        data.extend(psi_packet(0x1001, &pmt_section(2, 0x201, 0x0F, 0x202)));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert_eq!(reader.structure_tree(), "PAT not found\n", "Structure tree is incorrect");
+        while reader.next_packet().unwrap().is_some() {}
+
+        assert_eq!(reader.structure_tree(), concat!(
+            "PAT, transport stream ID 0x0001\n",
+            "  Network, PID 0x0010\n",
+            "  Program 1, PMT PID 0x1000\n",
+            "    PID 0x0101, stream type 0x1B, PCR\n",
+            "  Program 2, PMT PID 0x1001\n",
+            "    PID 0x0201, PCR only\n",
+            "    PID 0x0202, stream type 0x0F\n",
+            "  Program 3, PMT PID 0x1002\n",
+            "    PMT not found\n",
+        ), "Structure tree is incorrect");
+    }
+
+    /// Build a packet which starts a video PES packet with the given PTS.
+    fn pes_packet(pid: u16, pts: u64) -> Vec<u8> {
+        let mut packet = vec![0xFF; PACKET_SIZE];
+        packet[..4].copy_from_slice(&[SYNC_BYTE, 0x40 | (pid >> 8) as u8, pid as u8, 0x10]);
+        packet[4..13].copy_from_slice(&[0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x80, 0x05]);
+        packet[13..18].copy_from_slice(&pes::write_timestamp(0x2, pts));
+        packet
+    }
+
+    #[test]
+    fn first_pts_per_program() {
+        // Programs 1 and 2 carry video, and program 3 only carries audio.
+        let pat = [
+            0x00, 0xB0, 0x15, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x00, 0x02,
+            0xF0, 0x01, 0x00, 0x03, 0xF0, 0x02,
+        ];
+
+        // The PES packet before the PMT is skipped since its program isn't known yet.
+        let mut data = pes_packet(0x101, 1_000);
+        data.extend(psi_packet(PAT_PID, &pat));
+        data.extend(psi_packet(0x1000, &pmt_section(1, 0x101, 0x1B, 0x101)));
+        data.extend(psi_packet(0x1001, &pmt_section(2, 0x201, 0x1B, 0x201)));
+        data.extend(psi_packet(0x1002, &pmt_section(3, 0x301, 0x0F, 0x301)));
+        data.extend(pes_packet(0x301, 2_000));
+        data.extend(pes_packet(0x201, 5_000));
+        data.extend(pes_packet(0x101, 3_000));
+        data.extend(pes_packet(0x101, 6_000));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let first_pts = reader.first_pts_per_program().unwrap();
+        assert_eq!(first_pts, HashMap::from([(1, 3_000), (2, 5_000)]), "First PTS are incorrect");
+
+        // Reading stops as soon as every video program has a PTS.
+        assert_eq!(reader.packets_read(), 8, "Number of packets read is incorrect");
+    }
+
+    /// Build the packets of a KLV metadata PES packet, with a PTS if one is given.
+    fn klv_packets(pid: u16, pts: Option<u64>, klv: &[u8]) -> Vec<u8> {
+        let header_length = if pts.is_some() { 5 } else { 0 };
+        let mut pes = vec![0x00, 0x00, 0x01, 0xFC];
+        pes.extend(((3 + header_length + klv.len()) as u16).to_be_bytes());
+        pes.extend([0x80, if pts.is_some() { 0x80 } else { 0x00 }, header_length as u8]);
+        if let Some(pts) = pts {
+            pes.extend(pes::write_timestamp(0x2, pts));
+        }
+        pes.extend_from_slice(klv);
+
+        let mut data = Vec::new();
+        for (index, chunk) in pes.chunks(PACKET_SIZE - HEADER_SIZE as usize).enumerate() {
+            let pusi = if index == 0 { 0x40 } else { 0x00 };
+            data.extend([SYNC_BYTE, pusi | (pid >> 8) as u8, pid as u8, 0x10 | index as u8]);
+            data.extend_from_slice(chunk);
+            data.resize(data.len().next_multiple_of(PACKET_SIZE), 0xFF);
+        }
+        data
+    }
+
+    #[test]
+    fn klv_timeline() {
+        let pat = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+        // Program 1 has H.264 video on PID 0x101 and synchronous KLV metadata on PID 0x102.
+        let pmt = [
+            0x02, 0xB0, 0x17, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x01, 0xF0, 0x00, 0x1B, 0xE1,
+            0x01, 0xF0, 0x00, 0x15, 0xE1, 0x02, 0xF0, 0x00,
+        ];
+        let large = [0xAB; 300];
+
+        // Metadata before the PMT is skipped since its PID isn't known yet.
+        let mut data = klv_packets(0x102, Some(0), &[0x01]);
+        data.extend(psi_packet(PAT_PID, &pat));
+        data.extend(psi_packet(0x1000, &pmt));
+        data.extend(pes_packet(0x101, 3000));
+        data.extend(klv_packets(0x102, Some(2900), &[0x06, 0x0E, 0x2B]));
+        data.extend(klv_packets(0x102, None, &[0x02]));
+        data.extend(pes_packet(0x101, 6000));
+        data.extend(klv_packets(0x102, Some(20_000), &large));
+        data.extend(pes_packet(0x101, 9000));
+        data.extend(klv_packets(0x102, Some(8000), &[0x03]));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let units = reader.klv_timeline(1, Duration::from_millis(20)).unwrap();
+
+        let timeline = units.iter()
+            .map(|unit| (unit.pts(), unit.video_pts(), unit.data().len()))
+            .collect::<Vec<_>>();
+        assert_eq!(timeline, vec![
+            (Some(2900), Some(3000), 3),
+            (None, Some(3000), 1),
+            (Some(20_000), None, large.len()),
+            (Some(8000), Some(9000), 1),
+        ], "Timeline is incorrect");
+        assert_eq!(units[0].offset(), 4 * PACKET_SIZE as u64, "Offset is incorrect");
+        assert_eq!(units[2].data(), &large[..], "Reassembled data is incorrect");
+    }
+
+    #[test_case(&[], 10, None; "Empty")]
+    #[test_case(&[100, 200], 10, Some(100); "Before first")]
+    #[test_case(&[100, 200], 500, Some(200); "After last")]
+    #[test_case(&[100, 200], 160, Some(200); "Closer to later")]
+    #[test_case(&[100, 200], 140, Some(100); "Closer to earlier")]
+    #[test_case(&[100, 200], 150, Some(100); "Halfway")]
+    fn closest_pts(sorted_pts: &[u64], pts: u64, expected: Option<u64>) {
+        assert_eq!(super::closest_pts(sorted_pts, pts), expected, "Closest PTS is incorrect");
+    }
+
+    #[test]
+    fn pts_discontinuities() {
+        let pts_values = [pes::PTS_WRAP - 3_000, 0, 3_000, 6_000, 3_000, 6_000, 906_000];
+        let mut data = Vec::new();
+        for pts in pts_values {
+            data.extend(pes_packet(0x100, pts));
+            data.extend(packets(&[0x101]));
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let discontinuities = reader.pts_discontinuities(0x100, Duration::from_secs(1)).unwrap();
+
+        let jumps: Vec<(u64, u64, u64)> = discontinuities.iter()
+            .map(|d| (d.offset(), d.previous_pts(), d.pts()))
+            .collect();
+        let stride = 2 * PACKET_SIZE as u64;
+        assert_eq!(jumps, vec![(4 * stride, 6_000, 3_000), (6 * stride, 6_000, 906_000)],
+            "PTS discontinuities are incorrect");
+    }
+
+    #[test]
+    fn pts_pcr_delays() {
+        // The PCR is 9,000 ticks before the PTS wraps. The first PES packet after it has wrapped
+        // to be half a second ahead, and the second one is 9,000 ticks behind the PCR.
+        let pcr_base = pes::PTS_WRAP - 9_000;
+        let mut data = program_1_psi();
+        data.extend(pes_packet(0x101, 1_000));
+        data.extend(pcr_packet(0x100, pcr_base));
+        data.extend(pes_packet(0x101, 36_000));
+        data.extend(pes_packet(0x102, 36_000));
+        data.extend(pes_packet(0x101, pcr_base - 9_000));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let delays = reader.pts_pcr_delays(1).unwrap();
+
+        let found: Vec<(u64, u64, u64, i64)> = delays.iter()
+            .map(|delay| (delay.offset(), delay.pts(), delay.pcr(), delay.delay()))
+            .collect();
+        let packet_size = PACKET_SIZE as u64;
+        assert_eq!(found, vec![
+            (4 * packet_size, 36_000, pcr_base * 300, 45_000 * 300),
+            (6 * packet_size, pcr_base - 9_000, pcr_base * 300, -9_000 * 300),
+        ], "Delays are incorrect");
+        assert_eq!(delays[0].pid(), 0x101, "PID is incorrect");
+        assert_eq!(delays[0].delay_secs(), 0.5, "Delay in seconds is incorrect");
+    }
+
+    #[test]
+    fn clean_to() {
+        let mut data = packets(&[0x100, NULL_PACKET_PID, 0x100, 0x100, 0x100, 0x101]);
+        let set_byte = |data: &mut Vec<u8>, packet: usize, idx: usize, value: u8| {
+            data[packet * PACKET_SIZE + idx] = value;
+        };
+        set_byte(&mut data, 0, 3, 0x15);
+        // Gap in the continuity counter.
+        set_byte(&mut data, 2, 3, 0x19);
+        // Transport error indicator set.
+        set_byte(&mut data, 3, 1, 0x81);
+        set_byte(&mut data, 3, 3, 0x1A);
+        // Adaptation field only, which doesn't increment the continuity counter.
+        set_byte(&mut data, 4, 3, 0x2F);
+        set_byte(&mut data, 4, 4, 183);
+        set_byte(&mut data, 5, 3, 0x13);
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let mut out = Vec::new();
+        let written = reader.clean_to(&mut out, CleanOptions::default()).unwrap();
+        assert_eq!(written, 4, "Number of packets written is incorrect");
+
+        let mut cleaned = TSReader::from_stream("cleaned", Cursor::new(out)).unwrap();
+        let mut packets = Vec::new();
+        while let Some(packet) = cleaned.next_packet().unwrap() {
+            packets.push((packet.header().pid(), packet.header().continuity_counter()));
+        }
+        assert_eq!(packets, vec![(0x100, 5), (0x100, 6), (0x100, 6), (0x101, 3)],
+            "Cleaned packets are incorrect");
+    }
+
+    #[test]
+    fn private_data_stream() {
+        let mut data = packets(&[0x100, 0x101, 0x100, 0x100]);
+        // The first three packets have 2 bytes of transport private data each.
+        for (packet, private_data) in [(0, [0xAA, 0xBB]), (1, [0x11, 0x22]), (2, [0xCC, 0xDD])] {
+            let start = packet * PACKET_SIZE;
+            data[start + 3] = 0x30;
+            data[start + 4..start + 9].copy_from_slice(&[0x04, 0x02, 0x02, private_data[0],
+                private_data[1]]);
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let private_data = reader.private_data_stream(0x100).unwrap();
+        assert_eq!(private_data, vec![0xAA, 0xBB, 0xCC, 0xDD], "Private data stream is incorrect");
+    }
+
+    #[test]
+    fn clear_payload_state() {
+        let mut data = Vec::new();
+        let mut counters = HashMap::new();
+        for (pid, value) in [(0x100, 0xAA), (0x101, 0x11), (0x100, 0xBB), (0x101, 0x22),
+            (0x100, 0xCC), (0x100, 0xDD)] {
+            let continuity_counter = counters.entry(pid).or_insert(0);
+            let mut packet = vec![value; PACKET_SIZE];
+            packet[..5].copy_from_slice(&[SYNC_BYTE, 0x40 | (pid >> 8) as u8, pid as u8,
+                0x10 | *continuity_counter, 0]);
+            *continuity_counter += 1;
+            data.extend(packet);
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let payload = reader.next_completed_payload().unwrap().unwrap();
+        assert_eq!((payload.pid(), payload.data()[0]), (0x100, 0xAA), "First payload is incorrect");
+
+        // The payload starting with 0xBB is being reassembled and should be dropped.
+        reader.clear_payload_state(0x100);
+
+        let mut payloads = Vec::new();
+        while let Some(payload) = reader.next_completed_payload().unwrap() {
+            payloads.push((payload.pid(), payload.data()[0]));
+        }
+        assert_eq!(payloads, vec![(0x101, 0x11), (0x100, 0xCC)], "Payloads are incorrect");
+    }
+
+    #[test]
+    fn demux_payloads() {
+        let mut data = Vec::new();
+        let mut counters = HashMap::new();
+        for (pid, value) in [(0x100, 0xAA), (0x101, 0x11), (0x102, 0x77), (0x100, 0xBB),
+            (0x101, 0x22), (0x100, 0xCC)] {
+            let continuity_counter = counters.entry(pid).or_insert(0);
+            let mut packet = vec![value; PACKET_SIZE];
+            packet[..5].copy_from_slice(&[SYNC_BYTE, 0x40 | (pid >> 8) as u8, pid as u8,
+                0x10 | *continuity_counter, 0]);
+            *continuity_counter += 1;
+            data.extend(packet);
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let payloads = reader.demux_payloads(&[0x100, 0x101, 0x103]).unwrap();
+        let first_bytes = |pid| payloads[&pid].iter().map(|payload| payload[0]).collect::<Vec<_>>();
+
+        assert_eq!(payloads.len(), 3, "Number of PIDs is incorrect");
+        assert_eq!(first_bytes(0x100), vec![0xAA, 0xBB], "PID 0x100 payloads are incorrect");
+        assert_eq!(first_bytes(0x101), vec![0x11], "PID 0x101 payloads are incorrect");
+        assert!(payloads[&0x103].is_empty(), "PID without packets has payloads");
+    }
+
+    #[test]
+    fn max_payload_size() {
+        let mut data = packets(&[0x100, 0x100, 0x100, 0x100, 0x101, 0x100]);
+        for packet in [0, 1, 3, 5] {
+            data[packet * PACKET_SIZE + 1] |= 0x40;
+        }
+        for (packet, continuity_counter) in [(1, 1), (2, 2), (3, 3), (5, 4)] {
+            data[packet * PACKET_SIZE + 3] |= continuity_counter;
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.demux_payloads(&[0x100, 0x101]).unwrap();
+
+        // The payload pointer is removed from the first packet of every payload.
+        let max_size = 2 * (PACKET_SIZE - HEADER_SIZE as usize) - 1;
+        assert_eq!(reader.max_payload_size(0x100), Some(max_size), "Max payload size is incorrect");
+        assert_eq!(reader.max_payload_size(0x101), None, "Incomplete payload was measured");
+    }
+
+    #[test]
+    fn auto_demux() {
+        let pat = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(psi_packet(0x1000, &pmt_section(1, 0x100, 0x1B, 0x100)));
+        data.extend(pes_packet(0x100, 3_000));
+        data.extend(packets(&[0x100]));
+        // PID 0x200 isn't listed in the PMT, so it is classified by its payload.
+        data.extend(psi_packet(0x200, &pmt_section(2, 0x101, 0x0F, 0x101)));
+        // PID 0x300 never starts a payload, so it can't be classified.
+        data.extend(packets(&[0x300]));
+        data.extend(pes_packet(0x100, 6_000));
+        data.extend(pes_packet(0x100, 9_000));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let result = reader.auto_demux().unwrap();
+
+        assert_eq!(result.streams().keys().copied().collect::<Vec<_>>(), vec![0x00, 0x100, 0x200,
+            0x1000], "Demultiplexed PIDs are incorrect");
+        assert!(matches!(result.stream(0x00), Some(DemuxedStream::Psi(sections))
+            if sections.len() == 1), "PAT sections are incorrect");
+        assert!(matches!(result.stream(0x200), Some(DemuxedStream::Psi(sections))
+            if sections.len() == 1 && sections[0][0] == 0x02), "PID 0x200 sections are incorrect");
+        let Some(DemuxedStream::Pes(pes_packets)) = result.stream(0x100) else {
+            panic!("PID 0x100 isn't PES");
+        };
+        // The last PES packet has no length, so it can't be known to be complete.
+        let pts = pes_packets.iter().map(PesPacket::pts).collect::<Vec<_>>();
+        assert_eq!(pts, vec![Some(3_000), Some(6_000)], "PES packets are incorrect");
+        assert_eq!(pes_packets[0].payload().len(), 2 * (PACKET_SIZE - 4) - 14,
+            "PES payload length is incorrect");
+        assert_eq!(result.stream(0x300), None, "Unclassified PID was demultiplexed");
+    }
+
+    #[test]
+    fn access_unit_counts() {
+        let mut data = packets(&[0x100, 0x100, 0x100, 0x101, 0x102, NULL_PACKET_PID]);
+        for (packet, pusi) in [(0, 0x40), (2, 0x41), (3, 0x41), (4, 0x41)] {
+            data[packet * PACKET_SIZE + 1] |= pusi;
+        }
+        // The packet on PID 0x102 only has an adaptation field, so it can't start a unit.
+        data[4 * PACKET_SIZE + 3] = 0x20;
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        while reader.next_packet().unwrap().is_some() {}
+
+        let expected = HashMap::from([(0x100, 2), (0x101, 1)]);
+        assert_eq!(reader.access_unit_counts(), &expected, "Access unit counts are incorrect");
+    }
+
+    #[test]
+    fn priority_packet_counts() {
+        let mut data = packets(&[0x100, 0x100, 0x100, 0x100, 0x101]);
+        for packet in [0, 2, 3] {
+            data[packet * PACKET_SIZE + 1] |= 0x20;
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        while reader.next_packet().unwrap().is_some() {}
+
+        let expected = HashMap::from([(0x100, 3)]);
+        assert_eq!(reader.priority_packet_counts(), &expected, "Priority counts are incorrect");
+        assert_eq!(reader.priority_packet_ratio(0x100), Some(0.75), "Ratio is incorrect");
+        assert_eq!(reader.priority_packet_ratio(0x101), Some(0.0), "Ratio is incorrect");
+        assert_eq!(reader.priority_packet_ratio(0x102), None, "Ratio of unseen PID is incorrect");
+    }
+
+    #[test]
+    fn write_summary() {
+        let mut data = packets(&[0x100, NULL_PACKET_PID]);
+        data[1] |= 0x60;
+        data[3] = 0x35;
+        data[4..6].copy_from_slice(&[0x01, 0xC0]);
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(reader.write_summary(&mut out).unwrap(), 2, "Number of packets is incorrect");
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n{}\n{}\n", SUMMARY_HEADER,
+            "0\t0x0100\t5\t1\t0\t1\t0\t3\t1\t1\t-",
+            "188\t0x1FFF\t0\t0\t0\t0\t0\t1\t0\t0\t-"), "Summary is incorrect");
+    }
+
+    #[test]
+    fn splice_phase() {
+        let countdowns = [2, 1, 0, -1, -2];
+        let mut data = packets(&[0x100; 5]);
+        for (packet, countdown) in countdowns.iter().enumerate() {
+            let start = packet * PACKET_SIZE;
+            data[start + 3] = 0x20 | packet as u8;
+            data[start + 4..start + 7].copy_from_slice(&[0x02, 0x04, *countdown as u8]);
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert_eq!(reader.splice_phase(0x100), None, "Phase was found before reading");
+
+        let mut phases = Vec::new();
+        while reader.next_packet().unwrap().is_some() {
+            phases.push(reader.splice_phase(0x100).unwrap());
+        }
+        assert_eq!(phases, vec![
+            SplicePhase::Before { packets_until_splice: 2 },
+            SplicePhase::Before { packets_until_splice: 1 },
+            SplicePhase::At,
+            SplicePhase::After { packets_since_splice: 1 },
+            SplicePhase::After { packets_since_splice: 2 },
+        ], "Splice phases are incorrect");
+    }
+
+    #[test]
+    fn on_splice_point() {
+        // The packet at the splice point is sent twice, which shouldn't report it again.
+        let countdowns = [3, 2, 1, 0, 0, -1];
+        let mut data = packets(&[0x100, 0x101, 0x100, 0x100, 0x100, 0x100, 0x100]);
+        for (packet, countdown) in [0, 2, 3, 4, 5, 6].into_iter().zip(countdowns) {
+            let start = packet * PACKET_SIZE;
+            data[start + 3] = 0x20;
+            data[start + 4..start + 7].copy_from_slice(&[0x02, 0x04, countdown as u8]);
+        }
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.on_splice_point(0x100, move |splice_point| sender.send(*splice_point).unwrap());
+        reader.on_splice_point(0x101, |_| panic!("Splice point found on the wrong PID"));
+        while reader.next_packet().unwrap().is_some() {}
+
+        let splice_points: Vec<SplicePoint> = receiver.try_iter().collect();
+        assert_eq!(splice_points.len(), 1, "Number of splice points is incorrect");
+        assert_eq!(splice_points[0].pid(), 0x100, "Splice point PID is incorrect");
+        assert_eq!(splice_points[0].offset(), 4 * PACKET_SIZE as u64,
+            "Splice point offset is incorrect");
+    }
+
+    /// Build a packet on PID 0x100 filled with the given value. Packets with the `PUSI` set start
+    /// their payload right after the payload pointer.
+    fn chunk_packet(pusi: bool, continuity_counter: u8, value: u8) -> Vec<u8> {
+        let mut packet = vec![value; PACKET_SIZE];
+        let pusi = if pusi { 0x40 } else { 0x00 };
+        packet[..4].copy_from_slice(&[SYNC_BYTE, pusi | 0x01, 0x00, 0x10 | continuity_counter]);
+        if pusi != 0 {
+            packet[4] = 0;
+        }
+        packet
+    }
+
+    #[test]
+    fn on_payload_chunk() {
+        let mut data = chunk_packet(true, 0, 0xAA);
+        data.extend(packets(&[0x101]));
+        data.extend(chunk_packet(false, 1, 0xBB));
+        data.extend(chunk_packet(false, 2, 0xCC));
+        data.extend(chunk_packet(true, 3, 0xDD));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.on_payload_chunk(0x100, move |chunk| {
+            sender.send((chunk.offset(), chunk.is_start(), chunk.data().to_vec())).unwrap()
+        });
+        let payload = reader.next_payload().unwrap().unwrap();
+
+        // Every chunk is delivered as soon as its packet is read, before the payload is complete.
+        let chunks: Vec<(u64, bool, Vec<u8>)> = receiver.try_iter().collect();
+        let offsets: Vec<u64> = chunks.iter().map(|(offset, _, _)| *offset).collect();
+        let packet_size = PACKET_SIZE as u64;
+        assert_eq!(offsets, vec![0, 2 * packet_size, 3 * packet_size, 4 * packet_size],
+            "Chunk offsets are incorrect");
+        assert!(chunks[0].1 && chunks[3].1, "Chunks don't start payload units");
+
+        let stitched: Vec<u8> = chunks[..3].iter().flat_map(|(_, _, data)| data.clone()).collect();
+        assert_eq!(stitched, payload.into_vec(), "Chunks don't match the payload");
+    }
+
+    #[test]
+    fn on_payload_chunk_interrupted() {
+        // The first payload unit is sent with a duplicate packet, then loses the packet with
+        // continuity counter 2. The second unit has a scrambled packet.
+        let mut data = chunk_packet(true, 0, 0xAA);
+        data.extend(chunk_packet(false, 1, 0xBB));
+        data.extend(chunk_packet(false, 1, 0xBB));
+        data.extend(chunk_packet(false, 3, 0xDD));
+        data.extend(chunk_packet(false, 4, 0xEE));
+        data.extend(chunk_packet(true, 5, 0x55));
+        let mut scrambled = chunk_packet(false, 6, 0x66);
+        scrambled[3] |= 0x80;
+        data.extend(scrambled);
+        data.extend(chunk_packet(false, 7, 0x77));
+        data.extend(chunk_packet(true, 8, 0x88));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.on_payload_chunk(0x100, move |chunk| {
+            sender.send((chunk.is_start(), chunk.data()[0])).unwrap()
+        });
+        while reader.next_packet().unwrap().is_some() {}
+
+        let chunks: Vec<(bool, u8)> = receiver.try_iter().collect();
+        assert_eq!(chunks, vec![(true, 0xAA), (false, 0xBB), (true, 0x55), (true, 0x88)],
+            "Chunks are incorrect");
+    }
+
+    #[test]
+    fn continuity_errors() {
+        // The continuity counter on PID 0x100 skips from 1 to 3.
+        let mut data = packets(&[0x100, 0x101, 0x100, 0x100]);
+        data[2 * PACKET_SIZE + 3] = 0x11;
+        data[3 * PACKET_SIZE + 3] = 0x13;
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        while reader.next_packet().unwrap().is_some() {}
+
+        assert_eq!(reader.continuity_errors().get(&0x100), Some(&1),
+            "Number of continuity errors is incorrect");
+        assert_eq!(reader.continuity_errors().get(&0x101), None,
+            "Continuity errors were found on a continuous PID");
+    }
+
+    #[test]
+    fn continuity_breakdown() {
+        // PID 0x100 repeats continuity counter 0 and then skips from 0 to 2.
+        let mut data = packets(&[0x100, 0x100, 0x101, 0x100]);
+        data[3 * PACKET_SIZE + 3] = 0x12;
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        while reader.next_packet().unwrap().is_some() {}
+
+        let breakdown = reader.continuity_breakdown()[&0x100];
+        assert_eq!(breakdown.duplicates(), 1, "Number of duplicates is incorrect");
+        assert_eq!(breakdown.drops(), 1, "Number of drops is incorrect");
+        assert_eq!(breakdown.dropped_packets(), 1, "Number of dropped packets is incorrect");
+        assert_eq!(reader.continuity_breakdown().get(&0x101), None,
+            "Anomalies were found on a continuous PID");
+    }
+
+    #[test]
+    fn recovery_report() {
+        // The continuity counter on PID 0x100 skips from 1 to 3 and PID 0x101 has TEI set.
+        let mut data = packets(&[0x100, 0x101, 0x100, 0x100, 0x101]);
+        data[2 * PACKET_SIZE + 3] = 0x11;
+        data[3 * PACKET_SIZE + 3] = 0x13;
+        data[4 * PACKET_SIZE + 1] |= 0x80;
+        data[4 * PACKET_SIZE + 3] = 0xD1;
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert!(reader.recovery_report().is_clean(), "Report is not clean before reading");
+        while reader.next_packet().unwrap().is_some() {}
+
+        let report = reader.recovery_report();
+        assert_eq!(report.packets(), 5, "Number of packets is incorrect");
+        assert_eq!(report.continuity_errors(), 1, "Number of continuity errors is incorrect");
+        assert_eq!(report.tei_packets(), 1, "Number of TEI packets is incorrect");
+        assert_eq!(report.scrambled_packets(), 1, "Number of scrambled packets is incorrect");
+        assert!(! report.is_clean(), "Report is clean");
+    }
+
+    #[test]
+    fn next_cc() {
+        let mut data = packets(&[0x100, 0x100, 0x101]);
+        data[3] = 0x1E;
+        data[PACKET_SIZE + 3] = 0x1F;
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        while reader.next_packet().unwrap().is_some() {}
+
+        assert_eq!(reader.next_cc(0x100), 0, "Next continuity counter did not wrap");
+        assert_eq!(reader.next_cc(0x101), 1, "Next continuity counter is incorrect");
+        assert_eq!(reader.next_cc(0x102), 0, "Next continuity counter of a new PID is incorrect");
+    }
+
+    #[test]
+    fn next_section() {
+        let pat = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(packets(&[NULL_PACKET_PID]));
+        data.extend(psi_packet(PAT_PID, &pat));
+
+        let mut expected = pat.to_vec();
+        expected.extend(psi::crc32(&pat).to_be_bytes());
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        for _ in 0..2 {
+            let section = reader.next_section(PAT_PID).unwrap();
+            assert_eq!(section.as_deref(), Some(&expected[..]), "Section is incorrect");
+        }
+        assert!(reader.next_section(PAT_PID).unwrap().is_none(), "Extra section was returned");
+    }
+
+    #[test]
+    fn set_window() {
+        let pids: Vec<u16> = (0x100..0x10A).collect();
+        let mut reader = TSReader::from_stream("test", Cursor::new(packets(&pids))).unwrap();
+
+        let packet_size = PACKET_SIZE as u64;
+        reader.set_window(3 * packet_size - 10, 6 * packet_size + 5).unwrap();
+
+        let mut pids = Vec::new();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            pids.push(packet.header().pid());
+        }
+        assert_eq!(pids, vec![0x103, 0x104, 0x105], "PIDs in the window are incorrect");
+    }
+
+    #[test_case(10, PACKET_SIZE as u64 + 100; "Shorter than a packet")]
+    #[test_case(500, 500; "Start at end")]
+    #[test_case(500, 200; "Start after end")]
+    fn set_window_empty(start: u64, end: u64) {
+        let mut reader = TSReader::from_stream("test", Cursor::new(packets(&[0x100; 4]))).unwrap();
+        let error = reader.set_window(start, end).unwrap_err();
+        let empty_window = error.downcast_ref::<crate::EmptyWindow>().expect("Error is incorrect");
+        assert_eq!((empty_window.start, empty_window.end), (start, end), "Window is incorrect");
+    }
+
+    /// Build an adaptation only packet which carries a PCR with the given base.
+    fn pcr_packet(pid: u16, base: u64) -> Vec<u8> {
+        let mut packet = vec![0xFF; PACKET_SIZE];
+        packet[..6].copy_from_slice(&[SYNC_BYTE, (pid >> 8) as u8, pid as u8, 0x20, 0xB7, 0x10]);
+        packet[6..10].copy_from_slice(&((base >> 1) as u32).to_be_bytes());
+        packet[10..12].copy_from_slice(&[((base & 1) << 7) as u8 | 0x7E, 0x00]);
+        packet
+    }
+
+    #[test_case(4, 4_500, 4; "Between PCRs after wrapping")]
+    #[test_case(1, 0, 1; "Exact PCR before wrapping")]
+    #[test_case(0, -9_000, 0; "Before the first PCR")]
+    #[test_case(9, 90_000, 9; "After the last PCR")]
+    fn seek_to_pcr(pcr: u64, offset: i64, expected: u64) {
+        // The PCR base wraps back to `0` after the third PCR, and there is a PCR for another
+        // program on PID 0x200 that should be ignored.
+        let start = (1 << 33) - 3 * 9_000;
+        let base = |index: u64| (start + index * 9_000) % (1 << 33);
+        let mut data = Vec::new();
+        for index in 0..10 {
+            data.extend(pcr_packet(0x100, base(index)));
+            data.extend(packets(&[0x101, 0x101]));
+        }
+        data.extend(pcr_packet(0x200, 0));
+
+        let target = (base(pcr) * 300).checked_add_signed(offset * 300).unwrap() % PCR_WRAP;
+        let mut reader = TSReader::new("test", Cursor::new(data)).unwrap();
+        let found = reader.seek_to_pcr(target).unwrap();
+
+        assert_eq!(found, Some(base(expected) * 300), "Found PCR is incorrect");
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.pcr(), found, "Reader didn't land on the found PCR");
+    }
+
+    #[test]
+    fn seek_to_pcr_without_pcrs() {
+        let mut reader = TSReader::new("test", Cursor::new(packets(&[0x100, 0x101]))).unwrap();
+        assert_eq!(reader.seek_to_pcr(0).unwrap(), None, "PCR was found");
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.header().pid(), 0x100, "Seeking without PCRs moved the reader");
+    }
+
+    #[test]
+    fn bitrate_profile() {
+        // 3 packets after the first PCR, then 1 packet after each of the next two.
+        let mut data = pcr_packet(0x100, 0);
+        data.extend(packets(&[0x101, 0x101, 0x101]));
+        data.extend(pcr_packet(0x100, 45_000));
+        data.extend(packets(&[0x101]));
+        data.extend(pcr_packet(0x100, 90_000));
+        data.extend(packets(&[0x101]));
+        data.extend(pcr_packet(0x100, 135_000));
+
+        let first = pcr_packet(0x100, 0);
+        let second = pcr_packet(0x100, 45_000);
+        let step = TSPacket::from_bytes(&second).unwrap().pcr().unwrap()
+            - TSPacket::from_bytes(&first).unwrap().pcr().unwrap();
+        let window = Duration::from_secs_f64(step as f64 / PCR_FREQUENCY as f64);
+        let bitrate = |packets: u64| {
+            BitrateWindow::new(0, packets, step).bitrate()
+        };
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let profile = reader.bitrate_profile(window).unwrap();
+
+        let packets: Vec<u64> = profile.windows().iter().map(|window| window.packets()).collect();
+        assert_eq!(packets, vec![4, 2, 2], "Window packet counts are incorrect");
+        assert_eq!(profile.windows()[1].offset(), 4 * PACKET_SIZE as u64,
+            "Window offset is incorrect");
+        assert_eq!(profile.peak(), Some(bitrate(4)), "Peak bitrate is incorrect");
+        assert_eq!(profile.minimum(), Some(bitrate(2)), "Minimum bitrate is incorrect");
+        assert!((profile.average().unwrap() - bitrate(8) / 3.0).abs() < 1e-6,
+            "Average bitrate is incorrect");
+    }
+
+    /// Build a random access point packet which starts a PES packet with the given PTS.
+    fn random_access_pes_packet(pid: u16, pts: u64) -> Vec<u8> {
+        let mut packet = vec![0xFF; PACKET_SIZE];
+        packet[..6].copy_from_slice(&[SYNC_BYTE, 0x40 | (pid >> 8) as u8, pid as u8, 0x30, 0x01,
+            0x40]);
+        packet[6..15].copy_from_slice(&[0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0x80, 0x05]);
+        packet[15..20].copy_from_slice(&pes::write_timestamp(0x2, pts));
+        packet
+    }
+
+    #[test_case(0; "Without wrapping")]
+    #[test_case(pes::PTS_WRAP - 6_000; "Wrapping")]
+    fn extract_pts_range(base: u64) {
+        let pts = |pts: u64| (base + pts) % pes::PTS_WRAP;
+        let mut data = Vec::new();
+        for (pts, random_access) in [(pts(0), true), (pts(3_000), false), (pts(6_000), true),
+            (pts(9_000), false), (pts(12_000), true)] {
+            if random_access {
+                data.extend(random_access_pes_packet(0x100, pts));
+            } else {
+                data.extend(pes_packet(0x100, pts));
+            }
+            data.extend(packets(&[0x101]));
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data.clone())).unwrap();
+        let mut out = Vec::new();
+        let written = reader.extract_pts_range(0x100, pts(7_000), pts(12_000), &mut out).unwrap();
+
+        assert_eq!(written, 4, "Number of packets written is incorrect");
+        assert_eq!(out, &data[4 * PACKET_SIZE..8 * PACKET_SIZE], "Extracted packets are incorrect");
+        let next = reader.next_packet().unwrap().unwrap();
+        assert_eq!(next.header().pid(), 0x101, "Reading didn't stop at the end of the range");
+    }
+
+    #[test]
+    fn gop_windows() {
+        // Random access points on PID 0x100 at packets 1, 4, and 7. The one on PID 0x200 is
+        // ignored.
+        let mut data = packets(&[0x101]);
+        data.extend(random_access_pes_packet(0x100, 0));
+        data.extend(packets(&[0x101, 0x100]));
+        data.extend(random_access_pes_packet(0x100, 3_000));
+        data.extend(packets(&[0x101]));
+        data.extend(random_access_pes_packet(0x200, 3_000));
+        data.extend(random_access_pes_packet(0x100, 6_000));
+        data.extend(packets(&[0x101]));
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let mut windows = reader.gop_windows(0x100);
+        let mut found = Vec::new();
+        for window in windows.by_ref() {
+            let window = window.unwrap();
+            found.push((window.start, (window.end - window.start) / PACKET_SIZE as u64));
+        }
+        let packet_size = PACKET_SIZE as u64;
+        assert_eq!(found, vec![(packet_size, 3), (4 * packet_size, 3), (7 * packet_size, 2)],
+            "Windows are incorrect");
+        assert!(windows.next().is_none(), "Iterator continued after the last window");
+    }
+
+    #[test]
+    fn gop_windows_without_random_access() {
+        let mut reader = TSReader::from_stream("test", Cursor::new(packets(&[0x100; 3]))).unwrap();
+        assert_eq!(reader.gop_windows_unchecked(0x100).count(), 0, "Windows were found");
+    }
+
+    /// Build a stream with a PAT and a PMT for program 1, which has its PCR on PID 0x100 and video
+    /// on PID 0x101.
+    fn program_1_psi() -> Vec<u8> {
+        let pat = [0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00];
+        let mut data = psi_packet(PAT_PID, &pat);
+        data.extend(psi_packet(0x1000, &pmt_section(1, 0x100, 0x1B, 0x101)));
+        data
+    }
+
+    /// Return a directory for a test to write files to, removing anything left from an earlier run.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ts-analyzer-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn segment_to_dir() {
+        // The random access point before the first PCR isn't written to a segment. The one half a
+        // second into the second segment doesn't start a new one.
+        let mut data = program_1_psi();
+        data.extend(random_access_pes_packet(0x101, 0));
+        data.extend(pcr_packet(0x100, 0));
+        data.extend(random_access_pes_packet(0x101, 3_000));
+        data.extend(packets(&[0x101]));
+        data.extend(pcr_packet(0x100, 90_000));
+        data.extend(random_access_pes_packet(0x101, 93_000));
+        data.extend(packets(&[0x101]));
+        data.extend(pcr_packet(0x100, 135_000));
+        data.extend(random_access_pes_packet(0x101, 138_000));
+        data.extend(pcr_packet(0x100, 180_000));
+
+        let dir = test_dir("segment_to_dir");
+        let mut reader = TSReader::from_stream("test", Cursor::new(data.clone())).unwrap();
+        let segments = reader.segment_to_dir(0x101, &dir, Duration::from_secs(1)).unwrap();
+
+        let durations: Vec<Duration> = segments.iter().map(Segment::duration).collect();
+        assert_eq!(durations, vec![Duration::from_secs(1); 2], "Segment durations are incorrect");
+
+        // Every segment starts with the PAT and PMT followed by a random access point.
+        let psi = &data[..2 * PACKET_SIZE];
+        for (segment, (start, end)) in segments.iter().zip([(4, 7), (7, 12)]) {
+            let written = fs::read(segment.path()).unwrap();
+            assert_eq!(&written[..2 * PACKET_SIZE], psi, "Segment doesn't start with the PSI");
+            assert_eq!(&written[2 * PACKET_SIZE..], &data[start * PACKET_SIZE..end * PACKET_SIZE],
+                "Segment packets are incorrect");
+        }
+        assert_eq!(segments[1].path(), dir.join("segment1.ts"), "Segment path is incorrect");
+
+        let playlist = fs::read_to_string(dir.join(PLAYLIST_FILENAME)).unwrap();
+        assert_eq!(playlist.matches("#EXTINF:1.000,").count(), 2, "Playlist is incorrect");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn segment_to_dir_missing_pcr() {
+        let mut data = program_1_psi();
+        data.extend(random_access_pes_packet(0x101, 0));
+        data.extend(packets(&[0x101]));
+
+        let dir = test_dir("segment_to_dir_missing_pcr");
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let error = reader.segment_to_dir(0x101, &dir, Duration::from_secs(1)).unwrap_err();
+        let missing_pcr = error.downcast_ref::<crate::MissingPcr>().expect("Error is incorrect");
+        assert_eq!(missing_pcr.pid, 0x101, "PID is incorrect");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn seek_to_packet() {
+        let pids: Vec<u16> = (0x100..0x10A).collect();
+        let mut data = vec![0; 5];
+        data.extend(packets(&pids));
+
+        let mut reader = TSReader::new("test", Cursor::new(data)).unwrap();
+        reader.seek_to_packet(7).unwrap();
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.header().pid(), 0x107, "Packet after seeking forward is incorrect");
+
+        reader.seek_to_packet(2).unwrap();
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.header().pid(), 0x102, "Packet after seeking backward is incorrect");
+        assert_eq!(reader.packets_read(), 3, "Number of packets read is incorrect");
+
+        let error = reader.seek_to_packet(10).expect_err("Seeking past the end was allowed");
+        let out_of_range = error.downcast_ref::<crate::PacketOutOfRange>()
+            .expect("Error is incorrect");
+        assert_eq!(out_of_range.index, 10, "Packet number is incorrect");
+        assert_eq!(out_of_range.packets, 10, "Number of packets is incorrect");
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.header().pid(), 0x103, "Failed seek moved the reader");
+    }
+
+    #[test]
+    fn reset() {
+        let pids: Vec<u16> = (0x100..0x108).collect();
+        let mut data = vec![0; 5];
+        data.extend(packets(&pids));
+
+        let mut reader = TSReader::new("test", Cursor::new(data)).unwrap();
+        reader.add_tracked_pid(0x102);
+        reader.add_tracked_pid(0x105);
+        let first_pass: Vec<u16> = reader.packets()
+            .map(|packet| packet.unwrap().header().pid())
+            .collect();
+        assert_eq!(reader.packets_read(), 8, "Number of packets read is incorrect");
+
+        reader.reset().unwrap();
+        assert_eq!(reader.packets_read(), 0, "Packet read counter was not reset");
+
+        let second_pass: Vec<u16> = reader.packets()
+            .map(|packet| packet.unwrap().header().pid())
+            .collect();
+        assert_eq!(first_pass, vec![0x102, 0x105], "PIDs read are incorrect");
+        assert_eq!(second_pass, first_pass, "Second pass is different from the first");
+        assert_eq!(reader.packets_read(), 8, "Number of packets read is incorrect");
+    }
+
+    #[test]
+    fn verify_alignment() {
+        let pids: Vec<u16> = (0x100..0x108).collect();
+        let mut data = vec![0; 5];
+        data.extend(packets(&pids));
+        // Corrupt the SYNC bytes of 2 packets and leave a partial packet at the end.
+        data[5 + 3 * PACKET_SIZE] = 0x00;
+        data[5 + 6 * PACKET_SIZE] = 0x00;
+        data.extend([SYNC_BYTE; 10]);
+
+        let mut reader = TSReader::new("test", Cursor::new(data)).unwrap();
+        reader.next_packet().unwrap();
+        let report = reader.verify_alignment().unwrap();
+
+        let expected = vec![5 + 3 * PACKET_SIZE as u64, 5 + 6 * PACKET_SIZE as u64];
+        assert_eq!(report.packets(), 8, "Number of packets checked is incorrect");
+        assert_eq!(report.misaligned_offsets(), expected, "Misaligned offsets are incorrect");
+        assert_eq!(report.first_misaligned(), Some(expected[0]), "First misaligned is incorrect");
+        assert!(! report.is_aligned(), "Misaligned file was reported as aligned");
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.header().pid(), 0x101, "Verifying alignment moved the reader");
+    }
+
+    #[test_case(0, Some(0); "Clean capture")]
+    #[test_case(100, Some(100); "Truncated")]
+    fn alignment_remainder(trailing: usize, expected: Option<u64>) {
+        let mut data = vec![0; 5];
+        data.extend(packets(&[0x100, 0x101, 0x102]));
+        data.extend(vec![SYNC_BYTE; trailing]);
+
+        let mut reader = TSReader::new("test", Cursor::new(data.clone())).unwrap();
+        assert_eq!(reader.alignment_remainder(), expected, "Alignment remainder is incorrect");
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.header().pid(), 0x100, "Measuring the file moved the reader");
+
+        let reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert_eq!(reader.alignment_remainder(), None, "Stream alignment remainder is incorrect");
+    }
+
+    #[test]
+    fn packets_read() {
+        let data = packets(&[0x100, 0x101, 0x100, 0x102, 0x100]);
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.add_tracked_pid(0x101);
+        assert_eq!(reader.packets_read(), 0, "Number of packets read is incorrect");
+
+        reader.next_packet().unwrap();
+        assert_eq!(reader.packets_read(), 2, "Number of packets read is incorrect");
+        assert_eq!(reader.bytes_read(), 2 * PACKET_SIZE as u64,
+            "Number of bytes read is incorrect");
+
+        while reader.next_packet().unwrap().is_some() {}
+        assert_eq!(reader.packets_read(), 5, "Number of packets read is incorrect");
+        assert_eq!(reader.bytes_read(), 5 * PACKET_SIZE as u64,
+            "Number of bytes read is incorrect");
+    }
+
+    #[test]
+    fn overhead_ratio() {
+        let mut data = packets(&[0x100, NULL_PACKET_PID, 0x101, 0x100]);
+        // The packet on PID 0x101 has 83 bytes of adaptation field stuffing before its payload.
+        let start = 2 * PACKET_SIZE;
+        data[start + 3] = 0x30;
+        data[start + 4] = 83;
+        data[start + 6..start + 88].fill(0xFF);
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.add_tracked_pid(0x100);
+        assert_eq!(reader.overhead_ratio().value(), 0.0, "Overhead before reading is incorrect");
+        while reader.next_packet().unwrap().is_some() {}
+
+        // Null packets and untracked PIDs are counted, but only the headers, the adaptation
+        // field, and the null packet are overhead.
+        let payload_bytes = 2 * (PACKET_SIZE - HEADER_SIZE as usize) + 100;
+        let expected = 1.0 - payload_bytes as f64 / (4 * PACKET_SIZE) as f64;
+        assert_eq!(reader.overhead_ratio().value(), expected, "Overhead ratio is incorrect");
+    }
+
+    #[test_case(0; "Aligned")]
+    #[test_case(5; "Leading garbage")]
+    fn file_offset(garbage: usize) {
+        let mut data = vec![0; garbage];
+        data.extend(packets(&[0x100, 0x101, 0x102]));
+
+        let mut reader = TSReader::new("test", Cursor::new(data)).unwrap();
+        let offsets: Vec<Option<u64>> = reader.packets()
+            .map(|packet| packet.unwrap().file_offset())
+            .collect();
+        let sync_offset = reader.sync_byte_alignment() - 1;
+        assert_eq!(offsets[1], Some(sync_offset + PACKET_SIZE as u64),
+            "Second packet offset is incorrect");
+        assert_eq!(offsets, [0, 1, 2].map(|index| Some((garbage + index * PACKET_SIZE) as u64)),
+            "Packet offsets are incorrect");
+    }
+
+    #[test]
+    fn collect_stats() {
+        // The first packet has the PUSI set, the second has TEI set, and the third has an
+        // adaptation field.
+        let mut data = packets(&[0x100, 0x101, 0x100, NULL_PACKET_PID, 0x100]);
+        data[1] |= 0x40;
+        data[PACKET_SIZE + 1] |= 0x80;
+        data[2 * PACKET_SIZE + 3] = 0x30;
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.add_tracked_pid(0x101);
+        reader.next_packet().unwrap();
+        let stats = reader.collect_stats().unwrap();
+
+        assert_eq!(stats.packets(), 5, "Number of packets is incorrect");
+        assert_eq!(stats.pid_packets().iter().map(|(pid, packets)| (*pid, *packets))
+            .collect::<Vec<_>>(), vec![(0x100, 3), (0x101, 1), (NULL_PACKET_PID, 1)],
+            "Packets per PID are incorrect");
+        assert_eq!(stats.adaptation_field_packets(), 1,
+            "Number of adaptation field packets is incorrect");
+        assert_eq!(stats.pusi_packets(), 1, "Number of PUSI packets is incorrect");
+        assert_eq!(stats.tei_packets(), 1, "Number of TEI packets is incorrect");
+    }
+
+    #[test]
+    fn packets_iterator() {
+        let data = packets(&[0x100, 0x101, 0x100, 0x102]);
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.add_tracked_pid(0x100);
+        reader.add_tracked_pid(0x102);
+
+        let pids: Vec<u16> = reader.packets()
+            .map(|packet| packet.unwrap().header().pid())
+            .collect();
+        assert_eq!(pids, vec![0x100, 0x100, 0x102], "PIDs read are incorrect");
+        assert!(reader.packets().next().is_none(), "Packets were read after the end of the file");
+    }
+
+    #[test_case(false, &[], &[0x100, NULL_PACKET_PID, 0x101, NULL_PACKET_PID]; "Disabled")]
+    #[test_case(true, &[], &[0x100, 0x101]; "Enabled")]
+    #[test_case(true, &[0x101, NULL_PACKET_PID], &[0x101]; "Enabled with tracked PIDs")]
+    fn skip_null_packets(skip: bool, tracked_pids: &[u16], expected: &[u16]) {
+        let data = packets(&[0x100, NULL_PACKET_PID, 0x101, NULL_PACKET_PID]);
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.skip_null_packets(skip);
+        for pid in tracked_pids {
+            reader.add_tracked_pid(*pid);
+        }
+
+        let pids: Vec<u16> = reader.packets()
+            .map(|packet| packet.unwrap().header().pid())
+            .collect();
+        assert_eq!(pids, expected, "PIDs read are incorrect");
+        assert_eq!(reader.stream_stats().packets(), 4, "Number of packets read is incorrect");
+    }
+
+    #[test]
+    fn payloads_iterator() {
+        let mut data = Vec::new();
+        for (continuity_counter, value) in [0xAA, 0xBB, 0xCC].into_iter().enumerate() {
+            let mut packet = vec![value; PACKET_SIZE];
+            let flags = 0x10 | continuity_counter as u8;
+            packet[..5].copy_from_slice(&[SYNC_BYTE, 0x41, 0x00, flags, 0]);
+            data.extend(packet);
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let payloads: Vec<u8> = reader.payloads_unchecked().map(|payload| payload[0]).collect();
+        assert_eq!(payloads, vec![0xAA, 0xBB], "Payloads read are incorrect");
+    }
+
+    #[test]
+    fn payloads_scrambled() {
+        // The second packet is scrambled with the even key, so the payload it ends is dropped and
+        // it doesn't start one of its own.
+        let mut data = Vec::new();
+        for (continuity_counter, value) in [0xAA, 0xBB, 0xCC, 0xDD].into_iter().enumerate() {
+            let scrambling = if value == 0xBB { 0x80 } else { 0x00 };
+            let flags = scrambling | 0x10 | continuity_counter as u8;
+            let mut packet = vec![value; PACKET_SIZE];
+            packet[..5].copy_from_slice(&[SYNC_BYTE, 0x41, 0x00, flags, 0]);
+            data.extend(packet);
+        }
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let payloads: Vec<u8> = reader.payloads_unchecked().map(|payload| payload[0]).collect();
+        assert_eq!(payloads, vec![0xCC], "Payloads read are incorrect");
+    }
+
+    #[test]
+    fn peek_packet() {
+        let data = packets(&[0x100, 0x101]);
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+
+        let peeked = reader.peek_packet().unwrap().expect("Peeked packet is missing");
+        assert_eq!(peeked.header().pid(), 0x100, "Peeked PID is incorrect");
+        assert_eq!(reader.packets_read(), 0, "Packets read count is incorrect");
+
+        let packet = reader.next_packet().unwrap().expect("Packet is missing");
+        assert_eq!(packet.header().pid(), 0x100, "PID is incorrect");
+        assert_eq!(packet.file_offset(), peeked.file_offset(), "Packet offset is incorrect");
+        assert_eq!(reader.packets_read(), 1, "Packets read count is incorrect");
+
+        assert_eq!(reader.peek_packet_unchecked().map(|packet| packet.header().pid()), Some(0x101),
+            "Second peeked PID is incorrect");
+        assert_eq!(reader.next_packet_unchecked().map(|packet| packet.header().pid()), Some(0x101),
+            "Second PID is incorrect");
+        assert!(reader.peek_packet().unwrap().is_none(), "Peeked packet at the end is incorrect");
+        assert!(reader.next_packet().unwrap().is_none(), "Packet at the end is incorrect");
+    }
+
+    #[test]
+    fn find_next_pid() {
+        let data = packets(&[0x100, 0x101, 0x100, 0x102, 0x103, 0x102]);
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        reader.add_tracked_pid(0x100);
+
+        let packet = reader.find_next_pid(0x102).unwrap().expect("Packet is missing");
+        assert_eq!(packet.file_offset(), Some(3 * PACKET_SIZE as u64),
+            "Packet offset is incorrect");
+        assert_eq!(reader.packets_read(), 4, "Packets read count is incorrect");
+
+        // Only PID 0x100 is still tracked, and it doesn't appear again.
+        assert!(reader.next_packet().unwrap().is_none(),
+            "Packet after the found packet is incorrect");
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(packets(&[0x100, 0x102, 0x101])))
+            .unwrap();
+        assert_eq!(reader.find_next_pid_unchecked(0x102).map(|packet| packet.header().pid()),
+            Some(0x102), "Packet PID is incorrect");
+        assert_eq!(reader.next_packet_unchecked().map(|packet| packet.header().pid()), Some(0x101),
+            "Packet after the found packet is incorrect");
+        assert!(reader.find_next_pid(0x102).unwrap().is_none(), "Packet at the end is incorrect");
+    }
+
+    #[test]
+    fn next_payload_pointer_past_packet() {
+        // The payload pointer of the second packet points at the byte after the end of the packet.
+        let mut data = packets(&[0x100, 0x100]);
+        data[PACKET_SIZE + 1] |= 0x40;
+        data[PACKET_SIZE + 3] |= 0x01;
+        data[PACKET_SIZE + 4] = (PACKET_SIZE - HEADER_SIZE as usize) as u8;
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let error = reader.next_payload().expect_err("Payload with a bad pointer was read");
+        assert!(error.downcast_ref::<InvalidPayloadPointer>().is_some(), "Error is incorrect");
+    }
+
+    /// Build a source of packets on PIDs `0x100` to `0x103` with a junk byte after the second one.
+    fn misaligned_source() -> Vec<u8> {
+        let mut data = packets(&[0x100, 0x101, 0x102, 0x103]);
+        data.insert(2 * PACKET_SIZE, 0x00);
+        data
+    }
+
+    #[test]
+    fn sync_lost() {
+        let mut reader = TSReader::from_stream("test", Cursor::new(misaligned_source())).unwrap();
+        assert!(reader.next_packet().unwrap().is_some(), "First packet is missing");
+        assert!(reader.next_packet().unwrap().is_some(), "Second packet is missing");
+
+        // The reader stays at the misaligned packet until it is resynced.
+        for _ in 0..2 {
+            let error = reader.next_packet().expect_err("Misaligned packet was read");
+            let sync_lost = error.downcast_ref::<SyncLost>().expect("Error is incorrect");
+            assert_eq!(sync_lost.offset, 2 * PACKET_SIZE as u64, "Error offset is incorrect");
+        }
+        assert_eq!(reader.packets_read(), 2, "Packets read count is incorrect");
+
+        reader.resync().unwrap();
+        let packet = reader.next_packet().unwrap().expect("Packet after resyncing is missing");
+        assert_eq!((packet.header().pid(), packet.file_offset()),
+            (0x102, Some(2 * PACKET_SIZE as u64 + 1)), "Packet after resyncing is incorrect");
+    }
+
+    #[test]
+    fn peek_packet_sync_lost() {
+        let mut reader = TSReader::from_stream("test", Cursor::new(misaligned_source())).unwrap();
+        assert!(reader.next_packet().unwrap().is_some(), "First packet is missing");
+        assert!(reader.next_packet().unwrap().is_some(), "Second packet is missing");
+
+        // The misaligned packet isn't consumed, so peeking again returns the same error.
+        for _ in 0..2 {
+            let error = reader.peek_packet().expect_err("Misaligned packet was peeked");
+            let sync_lost = error.downcast_ref::<SyncLost>().expect("Error is incorrect");
+            assert_eq!(sync_lost.offset, 2 * PACKET_SIZE as u64, "Error offset is incorrect");
+        }
+    }
+
+    #[test]
+    fn peek_packet_resync_on_error() {
+        let mut reader = TSReader::from_stream("test", Cursor::new(misaligned_source())).unwrap();
+        reader.set_resync_on_error(true);
+        assert!(reader.next_packet().unwrap().is_some(), "First packet is missing");
+        assert!(reader.next_packet().unwrap().is_some(), "Second packet is missing");
+
+        let offset = Some(2 * PACKET_SIZE as u64 + 1);
+        let peeked = reader.peek_packet().unwrap().expect("Peeked packet is missing");
+        assert_eq!((peeked.header().pid(), peeked.file_offset()), (0x102, offset),
+            "Peeked packet is incorrect");
+        let packet = reader.next_packet().unwrap().expect("Packet is missing");
+        assert_eq!((packet.header().pid(), packet.file_offset()), (0x102, offset),
+            "Packet after peeking is incorrect");
+    }
+
+    #[test]
+    fn resync_on_error() {
+        let mut reader = TSReader::from_stream("test", Cursor::new(misaligned_source())).unwrap();
+        reader.set_resync_on_error(true);
+
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            packets.push((packet.header().pid(), packet.file_offset().unwrap()));
+        }
+
+        let size = PACKET_SIZE as u64;
+        assert_eq!(packets, vec![(0x100, 0), (0x101, size), (0x102, 2 * size + 1),
+            (0x103, 3 * size + 1)], "Packets read are incorrect");
+    }
+
+    #[test]
+    fn resync() {
+        // A payload is started on PID 0x200 before 50 junk bytes and another one is started after.
+        let mut data = packets(&[0x200, 0x100]);
+        data[1] |= 0x40;
+        data.extend([0xAB; 50]);
+        let mut after = packets(&[0x101, 0x102, 0x200, 0x103]);
+        after[2 * PACKET_SIZE + 1] |= 0x40;
+        after[2 * PACKET_SIZE + 3] = 0x11;
+        data.extend(after);
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data.clone())).unwrap();
+        assert!(reader.next_packet().unwrap().is_some(), "First packet is missing");
+        assert!(reader.next_packet().unwrap().is_some(), "Second packet is missing");
+        assert!(reader.next_packet().is_err(), "Corrupt packet was read");
+
+        reader.resync().unwrap();
+        let mut pids = Vec::new();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            pids.push(packet.header().pid());
+        }
+        assert_eq!(pids, vec![0x101, 0x102, 0x200, 0x103],
+            "Packets after resyncing are incorrect");
+
+        // The payload started before the junk bytes can't be completed after resyncing.
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        assert!(reader.next_completed_payload().is_err(), "Corrupt packet was read");
+        reader.resync().unwrap();
+        assert!(reader.next_completed_payload().unwrap().is_none(), "Payload is incorrect");
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(packets(&[0x100, 0x101])))
+            .unwrap();
+        reader.resync().unwrap();
+        assert_eq!(reader.next_packet_unchecked().map(|packet| packet.header().pid()), Some(0x100),
+            "Packet after resyncing an aligned reader is incorrect");
+        let error = reader.resync().expect_err("Resync at the end is incorrect");
+        assert!(error.downcast_ref::<crate::NoSyncByteFound>().is_some(), "Error is incorrect");
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn spawn_payload_channel() {
+        let mut data = Vec::new();
+        let mut counters = HashMap::new();
+        for (pid, value) in [(0x100, 0xAA), (0x101, 0x11), (0x100, 0xBB), (0x101, 0x22),
+            (0x100, 0xCC)] {
+            let continuity_counter = counters.entry(pid).or_insert(0);
+            let mut packet = vec![value; PACKET_SIZE];
+            packet[..5].copy_from_slice(&[SYNC_BYTE, 0x40 | (pid >> 8) as u8, pid as u8,
+                0x10 | *continuity_counter, 0]);
+            *continuity_counter += 1;
+            data.extend(packet);
+        }
+
+        let reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let mut receiver = reader.spawn_payload_channel(1);
+
+        let mut payloads = Vec::new();
+        while let Some((pid, payload)) = receiver.blocking_recv() {
+            payloads.push((pid, payload[0]));
+        }
+        assert_eq!(payloads, vec![(0x100, 0xAA), (0x101, 0x11), (0x100, 0xBB)],
+            "Payloads sent are incorrect");
+    }
+
+    /// Build a source of M2TS packets on PID `0x100` with the given arrival timestamps.
+    fn m2ts_source(arrival_timestamps: &[u32]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for arrival_timestamp in arrival_timestamps {
+            buf.extend((0xC000_0000 | arrival_timestamp).to_be_bytes());
+            let mut packet = vec![0; PACKET_SIZE];
+            packet[..4].copy_from_slice(&[SYNC_BYTE, 0x01, 0x00, 0x10]);
+            buf.extend(packet);
+        }
+        buf
+    }
+
+    #[test]
+    fn new_detects_m2ts() {
+        let arrival_timestamps: Vec<u32> = (0..8).map(|i| 0x0100_0000 + i * 27_000).collect();
+        let mut reader = TSReader::new("test", Cursor::new(m2ts_source(&arrival_timestamps)))
+            .unwrap();
+        assert_eq!(reader.packet_layout(), PacketLayout::M2TS, "Packet layout is incorrect");
+
+        let read: Vec<Option<u32>> = reader.packets_unchecked()
+            .map(|packet| packet.arrival_timestamp())
+            .collect();
+        let expected: Vec<Option<u32>> = arrival_timestamps.into_iter().map(Some).collect();
+        assert_eq!(read, expected, "Arrival timestamps are incorrect");
+    }
+
+    #[test]
+    fn new_plain_ts_has_no_arrival_timestamp() {
+        let mut reader = TSReader::new("test", Cursor::new(source(PacketLayout::TS, 0, 8)))
+            .unwrap();
+        assert_eq!(reader.packet_layout(), PacketLayout::TS, "Packet layout is incorrect");
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.arrival_timestamp(), None, "Arrival timestamp is incorrect");
+    }
+
+    /// Build a source of M2TS packets which carry PCRs on PID `0x100` with the given arrival
+    /// timestamps, PCR bases, and discontinuity indicators, followed by packets without a PCR.
+    fn m2ts_pcr_source(pcrs: &[(u32, u64, bool)]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for &(arrival_timestamp, base, discontinuity) in pcrs {
+            let mut packet = pcr_packet(0x100, base);
+            if discontinuity {
+                packet[5] |= 0x80;
+            }
+            buf.extend((0xC000_0000 | arrival_timestamp).to_be_bytes());
+            buf.extend(packet);
+        }
+        for _ in 0..PROBE_PACKET_COUNT {
+            buf.extend(0xC000_0000u32.to_be_bytes());
+            buf.extend(packets(&[0x101]));
+        }
+        buf
+    }
+
+    // One second of arrival time is 27,000,000 ticks and a 90kHz PCR base of 90,009 is 100ppm more.
+    #[test_case(&[(0, 0, false), (27_000_000, 90_009, false), (54_000_000, 180_018, false)],
+        Some(100.0); "PCR running fast")]
+    #[test_case(&[(0, 0, false), (27_000_000, 90_009, false), (54_000_000, 900_000, true),
+        (81_000_000, 990_009, false)], Some(100.0); "Jump at a discontinuity")]
+    #[test_case(&[(0, 0, false)], None; "Single PCR")]
+    #[test_case(&[(0, 0, true), (27_000_000, 900_000, true), (54_000_000, 1_800_000, true)], None;
+        "Only discontinuous PCRs")]
+    fn pcr_frequency_ppm(pcrs: &[(u32, u64, bool)], expected: Option<f64>) {
+        let mut reader = TSReader::new("test", Cursor::new(m2ts_pcr_source(pcrs))).unwrap();
+        assert_eq!(reader.packet_layout(), PacketLayout::M2TS, "Packet layout is incorrect");
+        while reader.next_packet().unwrap().is_some() {}
+
+        let ppm = reader.pcr_frequency_ppm().map(|ppm| (ppm * 1_000.0).round() / 1_000.0);
+        assert_eq!(ppm, expected, "Frequency offset is incorrect");
+    }
+
+    #[test]
+    fn pcr_frequency_ppm_plain_ts() {
+        let mut data = Vec::new();
+        for base in [0, 90_009, 180_018] {
+            data.extend(pcr_packet(0x100, base));
+        }
+        data.extend(packets(&[0x101; PROBE_PACKET_COUNT]));
+
+        let mut reader = TSReader::new("test", Cursor::new(data)).unwrap();
+        while reader.next_packet().unwrap().is_some() {}
+        assert_eq!(reader.pcr_frequency_ppm(), None, "Frequency was measured without M2TS");
+    }
+
+    #[test]
+    fn pcr_interval_histogram() {
+        // Two short intervals followed by one three times as long.
+        let mut data = Vec::new();
+        for base in [0, 45_000, 90_000, 225_000] {
+            data.extend(pcr_packet(0x100, base));
+            data.extend(packets(&[0x101]));
+        }
+
+        let first = pcr_packet(0x100, 0);
+        let second = pcr_packet(0x100, 45_000);
+        let width = segment::pcr_elapsed(TSPacket::from_bytes(&first).unwrap().pcr().unwrap(),
+            TSPacket::from_bytes(&second).unwrap().pcr().unwrap());
+
+        let mut reader = TSReader::from_stream("test", Cursor::new(data)).unwrap();
+        let histogram = reader.pcr_interval_histogram(width, width * 2).unwrap();
+
+        assert_eq!(histogram.buckets(), vec![(width, 2), (width * 3, 1)], "Buckets are incorrect");
+        assert_eq!(histogram.violations().len(), 1, "Number of violations is incorrect");
+        assert_eq!(histogram.violations()[0].offset(), 6 * PACKET_SIZE as u64,
+            "Violation offset is incorrect");
+    }
+
+    #[test]
+    fn pcr_interval_histogram_from_stream() {
+        // A slice can't seek, so this reads the way a pipe or stdin would. The first interval is
+        // exactly at the limit, the second is over it, and the jump to the last PCR is flagged.
+        let mut data = vec![0; 100];
+        for base in [0, 9_000, 18_900] {
+            data.extend(pcr_packet(0x100, base));
+        }
+        let mut discontinuity = pcr_packet(0x100, 900_000);
+        discontinuity[5] |= 0x80;
+        data.extend(discontinuity);
+        data.extend(packets(&[0x101; PROBE_PACKET_COUNT]));
+
+        let mut reader = TSReader::from_stream("test", data.as_slice()).unwrap();
+        let limit = crate::stats::MAX_PCR_INTERVAL;
+        let histogram = reader.pcr_interval_histogram(Duration::from_millis(10), limit).unwrap();
+
+        assert_eq!(histogram.buckets(),
+            vec![(Duration::from_millis(100), 1), (Duration::from_millis(110), 1)],
+            "Buckets are incorrect");
+        assert_eq!(histogram.violations().len(), 1, "Number of violations is incorrect");
+        assert_eq!(histogram.violations()[0].offset(), 100 + 2 * PACKET_SIZE as u64,
+            "Violation offset is incorrect");
+        assert_eq!(histogram.violations()[0].interval(), Duration::from_millis(110),
+            "Violation interval is incorrect");
+    }
+
+    #[test]
+    fn new_detects_reed_solomon() {
+        let mut data = Vec::new();
+        for pid in 0x100..0x108u16 {
+            let mut packet = vec![0xAB; PacketLayout::REED_SOLOMON.stride()];
+            packet[..PACKET_SIZE].copy_from_slice(&packets(&[pid]));
+            data.extend(packet);
+        }
+
+        let mut reader = TSReader::new("test", Cursor::new(data)).unwrap();
+        assert_eq!(reader.packet_layout(), PacketLayout::REED_SOLOMON,
+            "Packet layout is incorrect");
+
+        let mut pids = Vec::new();
+        for packet in reader.packets_unchecked() {
+            assert_eq!(packet.fec_bytes(), Some([0xAB; 16].as_slice()), "FEC bytes are incorrect");
+            pids.push(packet.header().pid());
+        }
+        assert_eq!(pids, (0x100..0x108).collect::<Vec<u16>>(), "PIDs read are incorrect");
+    }
+}
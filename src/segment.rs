@@ -0,0 +1,102 @@
+//! Segments of a transport stream that have been split at random access points.
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::packet::adaptation_field::{PCR_FREQUENCY, PCR_WRAP};
+
+/// Filename of the playlist that lists the segments written to a directory.
+pub const PLAYLIST_FILENAME: &str = "index.m3u8";
+
+/// A segment file written by [`TSReader::segment_to_dir`](crate::reader::TSReader::segment_to_dir).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    /// Path of the file that the segment was written to.
+    path: PathBuf,
+    /// Duration of the segment as measured by the PCR.
+    duration: Duration,
+}
+
+impl Segment {
+    /// Create a new segment.
+    pub(crate) fn new(path: PathBuf, duration: Duration) -> Self {
+        Segment {
+            path,
+            duration,
+        }
+    }
+
+    /// Return the path of the file that the segment was written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the duration of the segment as measured by the PCR.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Return the time that has passed between two PCR values, accounting for the PCR wrapping.
+pub(crate) fn pcr_elapsed(start: u64, end: u64) -> Duration {
+    let ticks = (end + PCR_WRAP - start % PCR_WRAP) % PCR_WRAP;
+    Duration::from_nanos(ticks * 1_000 / (PCR_FREQUENCY / 1_000_000))
+}
+
+/// Build an HLS media playlist listing the given segments.
+///
+/// Segments are referenced by their filename, so the playlist must be placed in the same directory
+/// as the segments.
+pub(crate) fn playlist(segments: &[Segment]) -> String {
+    let target_duration = segments.iter()
+        .map(|segment| segment.duration().as_secs_f64().ceil() as u64)
+        .max()
+        .unwrap_or(0);
+
+    let mut playlist = String::new();
+    playlist.push_str("#EXTM3U\n");
+    playlist.push_str("#EXT-X-VERSION:3\n");
+    let _ = writeln!(playlist, "#EXT-X-TARGETDURATION:{}", target_duration);
+    playlist.push_str("#EXT-X-MEDIA-SEQUENCE:0\n");
+
+    for segment in segments {
+        let filename = segment.path().file_name().unwrap_or_default().to_string_lossy();
+        let _ = writeln!(playlist, "#EXTINF:{:.3},", segment.duration().as_secs_f64());
+        let _ = writeln!(playlist, "{}", filename);
+    }
+
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0, 27_000_000, Duration::from_secs(1); "One second")]
+    #[test_case(27_000, 54_000, Duration::from_millis(1); "One millisecond")]
+    #[test_case(PCR_WRAP - 27_000_000, 27_000_000, Duration::from_secs(2); "Wrapped")]
+    fn elapsed(start: u64, end: u64, expected: Duration) {
+        assert_eq!(pcr_elapsed(start, end), expected, "Elapsed time is incorrect");
+    }
+
+    #[test]
+    fn playlist_lists_segments() {
+        let segments = [
+            Segment::new(PathBuf::from("out/segment0.ts"), Duration::from_millis(4_000)),
+            Segment::new(PathBuf::from("out/segment1.ts"), Duration::from_millis(4_500)),
+        ];
+
+        let expected = "#EXTM3U\n\
+            #EXT-X-VERSION:3\n\
+            #EXT-X-TARGETDURATION:5\n\
+            #EXT-X-MEDIA-SEQUENCE:0\n\
+            #EXTINF:4.000,\n\
+            segment0.ts\n\
+            #EXTINF:4.500,\n\
+            segment1.ts\n\
+            #EXT-X-ENDLIST\n";
+        assert_eq!(playlist(&segments), expected, "Playlist is incorrect");
+    }
+}
@@ -0,0 +1,309 @@
+//! A module for reading a transport stream that is already in memory.
+//!
+//! [`SliceTSReader`] reads packets and payloads directly from a byte slice with an internal cursor,
+//! so a buffer that has already been received doesn't need to be wrapped in a `Cursor` or any other
+//! [`Read`] implementation. The SYNC byte search and packet parsing are shared with [`TSReader`],
+//! which should be used for anything more than reading packets and payloads.
+//!
+//! Unlike [`TSReader`], this doesn't need `std`, so it can be used on embedded and WASM targets.
+//! Packets are parsed straight out of the slice, without being copied into a read buffer first.
+//!
+//! [`TSReader`]: crate::reader::TSReader
+//! [`Read`]: std::io::Read
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::error::Error;
+use crate::errors::no_sync_byte_found::NoSyncByteFound;
+use crate::helpers::tracked_payload::TrackedPayload;
+use crate::packet::TSPacket;
+use crate::packet::layout::{detect_layout, find_sync, packet_start, parse_packet, PacketLayout};
+
+#[cfg(feature = "log")]
+use log::{debug, trace};
+
+/// Struct used for reading packets and payloads from a transport stream in a byte slice.
+pub struct SliceTSReader<'a> {
+    /// Transport stream data being read.
+    data: &'a [u8],
+    /// Sync byte alignment. A Sync byte should be found every `layout.stride()` away.
+    sync_alignment: u64,
+    /// Layout of the packets in the transport stream data.
+    layout: PacketLayout,
+    /// Index in `data` of the next packet to be read.
+    offset: usize,
+    /// Counter of the number of packets read.
+    packets_read: u64,
+    /// PIDs that should be tracked when querying for packets or payloads.
+    ///
+    /// If empty, all PIDs are tracked.
+    tracked_pids: Vec<u16>,
+    /// Payloads that are currently being tracked by the reader.
+    tracked_payloads: Vec<TrackedPayload>,
+}
+
+impl<'a> SliceTSReader<'a> {
+    /// Create a new SliceTSReader instance using the given data.
+    ///
+    /// The packet layout is detected and the first SYNC byte is found the same way as
+    /// [`TSReader::new`](crate::reader::TSReader::new), so any bytes before the first packet are
+    /// skipped. Data that is too short to detect the layout of is read as plain transport stream
+    /// packets.
+    pub fn new(data: &'a [u8]) -> Result<Self, Box<dyn Error>> {
+        let layout = detect_layout(data).unwrap_or_default();
+
+        #[cfg(feature = "log")]
+        debug!("Detected packet layout {:?} for slice", layout);
+
+        Self::with_layout(data, layout)
+    }
+
+    /// Create a new SliceTSReader instance that reads packets with the given layout from the given
+    /// data.
+    pub fn with_layout(data: &'a [u8], layout: PacketLayout) -> Result<Self, Box<dyn Error>> {
+        let Ok(sync_offset) = find_sync(data, 0, layout.stride()) else {
+            #[cfg(feature = "log")]
+            debug!("Could not find SYNC byte in slice of {} bytes", data.len());
+            return Err(Box::new(NoSyncByteFound));
+        };
+
+        #[cfg(feature = "log")]
+        trace!("SYNC found at position {} in slice", sync_offset + 1);
+
+        Ok(SliceTSReader {
+            data,
+            sync_alignment: sync_offset as u64 + 1,
+            layout,
+            offset: packet_start(sync_offset as u64, layout) as usize,
+            packets_read: 0,
+            tracked_pids: Vec::new(),
+            tracked_payloads: Vec::new(),
+        })
+    }
+
+    /// Read the next packet from the data.
+    ///
+    /// This function returns `None` for any `Err` in order to prevent the need for `.unwrap()`
+    /// calls in more concise code.
+    /// # Returns
+    /// `Some(TSPacket)` if the next transport stream packet could be parsed from the data.
+    /// `None` if the next transport stream packet could not be parsed from the data for any
+    /// reason. This includes if the data has been fully read.
+    pub fn next_packet_unchecked(&mut self) -> Option<TSPacket> {
+        self.next_packet().unwrap_or(None)
+    }
+
+    /// Read the next packet from the data.
+    /// # Returns
+    /// `Ok(Some(TSPacket))` if the next transport stream packet could be parsed from the data.
+    /// `Ok(None)` if no more complete TS packets are left in the data.
+    pub fn next_packet(&mut self) -> Result<Option<TSPacket>, Box<dyn Error>> {
+        loop {
+            let Some(packet) = self.read_packet()? else {
+                return Ok(None);
+            };
+
+            // We should only return a packet if it is in the tracked PIDs (or there are no tracked
+            // PIDs)
+            if ! self.tracked_pids.is_empty() && ! self.tracked_pids.contains(&packet.header().pid()) {
+                continue
+            }
+
+            return Ok(Some(packet));
+        }
+    }
+
+    /// Read the next packet from the data regardless of which PIDs are tracked.
+    fn read_packet(&mut self) -> Result<Option<TSPacket>, Box<dyn Error>> {
+        let end = self.offset + self.layout.stride();
+        let Some(packet_bytes) = self.data.get(self.offset..end) else {
+            return Ok(None);
+        };
+
+        let offset = self.offset as u64;
+        self.offset = end;
+        self.packets_read += 1;
+
+        parse_packet(packet_bytes, self.layout, offset).map(Some)
+    }
+
+    /// Read the next complete payload from the data.
+    ///
+    /// This function returns `None` for any `Err` in order to prevent the need for `.unwrap()`
+    /// calls in more concise code.
+    pub fn next_payload_unchecked(&mut self) -> Option<Box<[u8]>> {
+        self.next_payload().unwrap_or(None)
+    }
+
+    /// Read the next complete payload from the data.
+    ///
+    /// Payloads are reassembled the same way as
+    /// [`TSReader::next_payload`](crate::reader::TSReader::next_payload), so a payload is only
+    /// complete once the packet that starts the next payload on its PID has been read.
+    /// # Returns
+    /// `Ok(Some(Box<[u8]>))` if a payload was completed.
+    /// `Ok(None)` if the data was fully read without completing another payload.
+    pub fn next_payload(&mut self) -> Result<Option<Box<[u8]>>, Box<dyn Error>> {
+        loop {
+            let Some(packet) = self.next_packet()? else {
+                return Ok(None);
+            };
+
+            if let Some(payload) = self.add_tracked_payload(&packet) {
+                return Ok(Some(payload))
+            }
+        }
+    }
+
+    /// Add the payload of a packet to the payload being reassembled on its PID and return the
+    /// payload that it completed, if any.
+    fn add_tracked_payload(&mut self, packet: &TSPacket) -> Option<Box<[u8]>> {
+        let payload = packet.payload()?;
+        let offset = packet.file_offset().unwrap_or_default();
+        let discontinuity = packet.data_adaptation_field()
+            .is_some_and(|af| af.discontinuity_indicator());
+        let pid = packet.header().pid();
+
+        // The data of a scrambled packet is garbage until it is descrambled, so the payload that it
+        // belongs to can't be completed.
+        if packet.is_scrambled() {
+            self.tracked_payloads.retain(|tp| tp.pid() != pid);
+            return None
+        }
+
+        if let Some(tracked_payload) = self.tracked_payloads.iter_mut().find(|tp| tp.pid() == pid) {
+            return tracked_payload.add_and_get_complete(&payload, offset, discontinuity)
+                .map(|completed| completed.into_data());
+        }
+
+        if let Ok(tp) = TrackedPayload::from_packet(packet, offset) {
+            self.tracked_payloads.push(tp);
+        }
+
+        None
+    }
+
+    /// Return the number of packets read so far, regardless of which PIDs are being tracked.
+    pub fn packets_read(&self) -> u64 {
+        self.packets_read
+    }
+
+    /// Return the index in the data of the next packet to be read.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Return the alignment of the SYNC bytes in this reader.
+    pub fn sync_byte_alignment(&self) -> u64 {
+        self.sync_alignment
+    }
+
+    /// Add a PID to the tracking list.
+    ///
+    /// Only tracked PIDs are returned when running methods that gather packets or payloads. If no
+    /// PID is specified then all PIDs are returned.
+    pub fn add_tracked_pid(&mut self, pid: u16) {
+        self.tracked_pids.push(pid);
+    }
+
+    /// Remove this PID from being tracked.
+    ///
+    /// Only tracked PIDs are returned when running methods that gather packets or payloads. If no
+    /// PID is specified then all PIDs are returned.
+    pub fn remove_tracked_pid(&mut self, pid: u16) {
+        self.tracked_pids.retain(|vec_pid| *vec_pid != pid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packet::header::SYNC_BYTE;
+    use crate::packet::tests::packet_1;
+    use crate::packet::PACKET_SIZE;
+    use super::*;
+
+    /// Return the bytes of `packet_1` repeated `count` times with incrementing continuity counters.
+    fn packet_1_stream(count: u8) -> Vec<u8> {
+        let (packet, _, _) = packet_1();
+        let mut data = Vec::new();
+        for continuity_counter in 0..count {
+            let mut packet = packet.to_vec();
+            packet[3] = (packet[3] & 0xF0) | continuity_counter;
+            data.extend(packet);
+        }
+        data
+    }
+
+    #[test]
+    fn next_packet() {
+        let (_, adaptation_field_control, first_payload_bytes) = packet_1();
+        let mut data = vec![0x00; 7];
+        data.extend(packet_1_stream(2));
+
+        let mut reader = SliceTSReader::new(&data).unwrap();
+        assert_eq!(reader.sync_byte_alignment(), 8, "SYNC byte alignment is incorrect");
+
+        for index in 0..2 {
+            let packet = reader.next_packet().unwrap().expect("Packet is missing");
+            assert_eq!(packet.header().pid(), 0x102, "PID is incorrect");
+            assert_eq!(packet.header().adaptation_field_control(), adaptation_field_control,
+                "Adaptation field control is incorrect");
+            assert_eq!(packet.file_offset(), Some(7 + index * PACKET_SIZE as u64),
+                "Packet offset is incorrect");
+            assert!(packet.payload().unwrap().data().starts_with(&first_payload_bytes),
+                "First payload bytes are incorrect");
+        }
+        assert!(reader.next_packet().unwrap().is_none(), "Packet at the end is incorrect");
+        assert_eq!(reader.packets_read(), 2, "Packets read count is incorrect");
+    }
+
+    #[test]
+    fn next_payload() {
+        let data = packet_1_stream(3);
+        let mut reader = SliceTSReader::new(&data).unwrap();
+
+        let mut payloads = 0;
+        while let Some(payload) = reader.next_payload().unwrap() {
+            assert_eq!(payload[..4], [0x00, 0x01, 0xFC, 0x01], "Payload is incorrect");
+            payloads += 1;
+        }
+        assert_eq!(payloads, 2, "Number of payloads is incorrect");
+    }
+
+    #[test]
+    fn tracked_pids() {
+        let mut data = packet_1_stream(1);
+        let mut packet = vec![0; PACKET_SIZE];
+        packet[..4].copy_from_slice(&[SYNC_BYTE, 0x01, 0x00, 0x10]);
+        data.extend(packet);
+
+        let mut reader = SliceTSReader::new(&data).unwrap();
+        reader.add_tracked_pid(0x100);
+        let pids: Vec<u16> = std::iter::from_fn(|| reader.next_packet_unchecked())
+            .map(|packet| packet.header().pid())
+            .collect();
+        assert_eq!(pids, vec![0x100], "Packets read are incorrect");
+    }
+
+    #[test]
+    fn new_detects_layout() {
+        let mut data = Vec::new();
+        for continuity_counter in 0..6 {
+            data.extend([0x00, 0x00, 0x00, continuity_counter]);
+            let mut packet = vec![0; PACKET_SIZE];
+            packet[..4].copy_from_slice(&[SYNC_BYTE, 0x01, 0x00, 0x10 | continuity_counter]);
+            data.extend(packet);
+        }
+
+        let mut reader = SliceTSReader::new(&data).unwrap();
+        let packet = reader.next_packet().unwrap().expect("Packet is missing");
+        assert_eq!(packet.arrival_timestamp(), Some(0), "Arrival timestamp is incorrect");
+        let packet = reader.next_packet().unwrap().expect("Packet is missing");
+        assert_eq!(packet.arrival_timestamp(), Some(1), "Arrival timestamp is incorrect");
+        assert_eq!(packet.file_offset(), Some(192), "Packet offset is incorrect");
+    }
+
+    #[test]
+    fn new_no_sync_byte() {
+        assert!(SliceTSReader::new(&[0xFF; 1000]).is_err(), "Reader result is incorrect");
+    }
+}
@@ -0,0 +1,122 @@
+//! Splice points mark where one stream can be switched out for another, such as when inserting
+//! ads into a broadcast.
+//!
+//! Packets announce an upcoming splice point with the signed `splice_countdown` field of their
+//! adaptation field. The countdown reaches `0` on the last packet before the splice point and then
+//! goes negative to count the packets since the splice point.
+use crate::packet::TSPacket;
+
+/// The exact packet where a splice countdown reached `0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SplicePoint {
+    /// PID that the splice countdown was carried on.
+    pid: u16,
+    /// Byte offset in the file of the last packet before the splice point.
+    offset: u64,
+}
+
+impl SplicePoint {
+    /// Return the splice point at this packet if its splice countdown is `0`.
+    ///
+    /// Packets with a positive countdown come before the splice point and packets with a negative
+    /// countdown come after it, so neither of them mark the splice point.
+    /// # Parameters
+    /// - `packet`: packet to check for a splice point.
+    /// - `offset`: byte offset in the file of the packet.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn from_packet(packet: &TSPacket, offset: u64) -> Option<Self> {
+        let countdown = packet.data_adaptation_field()?.splice_countdown()?;
+        if countdown != 0 {
+            return None
+        }
+
+        Some(SplicePoint {
+            pid: packet.header().pid(),
+            offset,
+        })
+    }
+
+    /// Return the PID that the splice countdown was carried on.
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Return the byte offset in the file of the last packet before the splice point.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+/// Where a packet is relative to a splice point, from the splice countdown that it carries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplicePhase {
+    /// The packet comes before the splice point.
+    Before {
+        /// Number of packets on the PID after this one that come before the splice point.
+        packets_until_splice: u8,
+    },
+    /// The packet is the last one before the splice point.
+    At,
+    /// The packet comes after the splice point.
+    After {
+        /// Number of packets on the PID since the splice point, counting this one.
+        packets_since_splice: u8,
+    },
+}
+
+impl SplicePhase {
+    /// Return the phase of a packet with the given splice countdown.
+    pub fn from_countdown(countdown: i8) -> Self {
+        match countdown {
+            1.. => SplicePhase::Before { packets_until_splice: countdown as u8 },
+            0 => SplicePhase::At,
+            _ => SplicePhase::After { packets_since_splice: countdown.unsigned_abs() },
+        }
+    }
+
+    /// Return the phase of this packet.
+    ///
+    /// Returns `None` if the packet doesn't carry a splice countdown.
+    pub fn from_packet(packet: &TSPacket) -> Option<Self> {
+        let countdown = packet.data_adaptation_field()?.splice_countdown()?;
+        Some(SplicePhase::from_countdown(countdown))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::PACKET_SIZE;
+    use test_case::test_case;
+
+    /// Build a packet on PID `0x100` with only an adaptation field that carries a splice countdown.
+    fn splice_packet(countdown: i8) -> TSPacket {
+        let mut buf = vec![0xFF; PACKET_SIZE];
+        buf[..7].copy_from_slice(&[0x47, 0x01, 0x00, 0x20, 0xB7, 0x04, countdown as u8]);
+        TSPacket::from_bytes(&buf).unwrap()
+    }
+
+    #[test_case(3, false; "Before splice")]
+    #[test_case(0, true; "At splice")]
+    #[test_case(-1, false; "After splice")]
+    #[test_case(-128, false; "Long after splice")]
+    fn from_packet(countdown: i8, expected: bool) {
+        let splice_point = SplicePoint::from_packet(&splice_packet(countdown), 376);
+
+        assert_eq!(splice_point.is_some(), expected, "Splice point detection is incorrect");
+        if let Some(splice_point) = splice_point {
+            assert_eq!(splice_point.pid(), 0x100, "Splice point PID is incorrect");
+            assert_eq!(splice_point.offset(), 376, "Splice point offset is incorrect");
+        }
+    }
+
+    #[test_case(3, SplicePhase::Before { packets_until_splice: 3 }; "Before splice")]
+    #[test_case(1, SplicePhase::Before { packets_until_splice: 1 }; "Right before splice")]
+    #[test_case(0, SplicePhase::At; "At splice")]
+    #[test_case(-1, SplicePhase::After { packets_since_splice: 1 }; "After splice")]
+    #[test_case(-128, SplicePhase::After { packets_since_splice: 128 }; "Long after splice")]
+    fn phase_from_packet(countdown: i8, expected: SplicePhase) {
+        assert_eq!(SplicePhase::from_packet(&splice_packet(countdown)), Some(expected),
+            "Splice phase is incorrect");
+    }
+}
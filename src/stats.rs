@@ -0,0 +1,812 @@
+//! Statistics that can be gathered while reading a transport stream.
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::time::Duration;
+
+use crate::packet::adaptation_field::{self, PCR_FREQUENCY};
+use crate::packet::{TSPacket, PACKET_SIZE};
+use crate::pes;
+
+/// PTS values count 90kHz ticks, so they need to be multiplied by this to be in 27MHz PCR ticks.
+const PTS_TO_PCR: u64 = 300;
+
+/// Longest time allowed between two consecutive PCRs of a program by ISO/IEC 13818-1. Some
+/// profiles, such as DVB, recommend sending them at least every 40ms instead.
+pub const MAX_PCR_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Fraction of a transport stream that is consumed by something other than payload data.
+///
+/// Overhead includes packet headers, adaptation fields, payload pointers, and null packets. The
+/// value is always in the range `[0, 1]` where `0` means every byte read was payload data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OverheadRatio(f64);
+
+impl OverheadRatio {
+    /// Calculate the overhead ratio from the number of payload bytes and the total number of bytes
+    /// read.
+    ///
+    /// A stream with no bytes read has no overhead.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(payload_bytes: u64, total_bytes: u64) -> Self {
+        if total_bytes == 0 {
+            return OverheadRatio(0.0)
+        }
+
+        let payload_ratio = payload_bytes.min(total_bytes) as f64 / total_bytes as f64;
+        OverheadRatio(1.0 - payload_ratio)
+    }
+
+    /// Return the overhead as a value in the range `[0, 1]`.
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+}
+
+impl Display for OverheadRatio {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:.2}%", self.0 * 100.0)
+    }
+}
+
+/// Counts of how the packets of a stream are structured.
+///
+/// This profiles what a stream's overhead is made of at a glance, such as how often PCRs are sent
+/// and how many packets are padded with adaptation field stuffing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// Number of packets read.
+    packets: u64,
+    /// Number of packets with an adaptation field that carries data.
+    data_adaptation_field_packets: u64,
+    /// Number of packets with an adaptation field that is a single stuffing byte.
+    stuffing_adaptation_field_packets: u64,
+    /// Number of packets with a PCR.
+    pcr_packets: u64,
+    /// Number of packets with a payload and no adaptation field.
+    payload_only_packets: u64,
+    /// Number of packets with the transport error indicator set.
+    tei_packets: u64,
+    /// Number of packets whose payload is scrambled with the even or odd key.
+    scrambled_packets: u64,
+    /// Number of packets with the `PUSI` set.
+    pusi_packets: u64,
+}
+
+impl StreamStats {
+    /// Add a packet to the counts.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn observe(&mut self, packet: &TSPacket) {
+        self.packets += 1;
+
+        if packet.header().tei() {
+            self.tei_packets += 1;
+        }
+        if packet.header().pusi() {
+            self.pusi_packets += 1;
+        }
+        if packet.is_scrambled() {
+            self.scrambled_packets += 1;
+        }
+
+        match packet.data_adaptation_field() {
+            Some(af) => {
+                self.data_adaptation_field_packets += 1;
+                if af.pcr().is_some() {
+                    self.pcr_packets += 1;
+                }
+            },
+            None if packet.has_adaptation_field() => self.stuffing_adaptation_field_packets += 1,
+            None if packet.has_payload() => self.payload_only_packets += 1,
+            None => {},
+        }
+    }
+
+    /// Return the number of packets read.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// Return the number of packets with an adaptation field that carries data.
+    pub fn data_adaptation_field_packets(&self) -> u64 {
+        self.data_adaptation_field_packets
+    }
+
+    /// Return the number of packets with an adaptation field that is a single stuffing byte.
+    pub fn stuffing_adaptation_field_packets(&self) -> u64 {
+        self.stuffing_adaptation_field_packets
+    }
+
+    /// Return the number of packets with an adaptation field of either kind.
+    pub fn adaptation_field_packets(&self) -> u64 {
+        self.data_adaptation_field_packets + self.stuffing_adaptation_field_packets
+    }
+
+    /// Return the number of packets with a PCR.
+    pub fn pcr_packets(&self) -> u64 {
+        self.pcr_packets
+    }
+
+    /// Return the number of packets with a payload and no adaptation field.
+    pub fn payload_only_packets(&self) -> u64 {
+        self.payload_only_packets
+    }
+
+    /// Return the number of packets with the transport error indicator set.
+    pub fn tei_packets(&self) -> u64 {
+        self.tei_packets
+    }
+
+    /// Return the number of packets whose payload is scrambled with the even or odd key.
+    pub fn scrambled_packets(&self) -> u64 {
+        self.scrambled_packets
+    }
+
+    /// Return the number of packets with the `PUSI` set.
+    pub fn pusi_packets(&self) -> u64 {
+        self.pusi_packets
+    }
+}
+
+/// Summary of every packet in a stream, with the number of packets on each PID.
+///
+/// This is returned by [`crate::reader::TSReader::collect_stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PacketStats {
+    /// Number of packets read.
+    packets: u64,
+    /// Number of packets read on each PID.
+    pid_packets: BTreeMap<u16, u64>,
+    /// Number of packets with an adaptation field.
+    adaptation_field_packets: u64,
+    /// Number of packets with the `PUSI` set.
+    pusi_packets: u64,
+    /// Number of packets with the transport error indicator set.
+    tei_packets: u64,
+}
+
+impl PacketStats {
+    /// Build the summary from the packet counts and the number of packets on each PID.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(stats: &StreamStats, pid_packets: BTreeMap<u16, u64>) -> Self {
+        PacketStats {
+            packets: stats.packets,
+            pid_packets,
+            adaptation_field_packets: stats.adaptation_field_packets(),
+            pusi_packets: stats.pusi_packets,
+            tei_packets: stats.tei_packets,
+        }
+    }
+
+    /// Return the number of packets read.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// Return the number of packets read on each PID, ordered by PID.
+    pub fn pid_packets(&self) -> &BTreeMap<u16, u64> {
+        &self.pid_packets
+    }
+
+    /// Return the number of packets with an adaptation field.
+    pub fn adaptation_field_packets(&self) -> u64 {
+        self.adaptation_field_packets
+    }
+
+    /// Return the number of packets with the `PUSI` set.
+    pub fn pusi_packets(&self) -> u64 {
+        self.pusi_packets
+    }
+
+    /// Return the number of packets with the transport error indicator set.
+    pub fn tei_packets(&self) -> u64 {
+        self.tei_packets
+    }
+}
+
+/// How many of the packets read had to be worked around, as a single health summary of a capture.
+///
+/// This is returned by [`crate::reader::TSReader::recovery_report`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Number of packets read.
+    packets: u64,
+    /// Number of continuity counter gaps found across every PID.
+    continuity_errors: u64,
+    /// Number of packets with the transport error indicator set.
+    tei_packets: u64,
+    /// Number of packets whose payload is scrambled with the even or odd key.
+    scrambled_packets: u64,
+}
+
+impl RecoveryReport {
+    /// Build the report from the packet counts and the total number of continuity errors.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(stats: &StreamStats, continuity_errors: u64) -> Self {
+        RecoveryReport {
+            packets: stats.packets,
+            continuity_errors,
+            tei_packets: stats.tei_packets,
+            scrambled_packets: stats.scrambled_packets,
+        }
+    }
+
+    /// Return the number of packets read.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// Return the number of continuity counter gaps found across every PID.
+    ///
+    /// Each gap means that at least one packet was lost, so the payload it was part of is
+    /// incomplete.
+    pub fn continuity_errors(&self) -> u64 {
+        self.continuity_errors
+    }
+
+    /// Return the number of packets with the transport error indicator set.
+    ///
+    /// The contents of these packets can't be trusted, such as after uncorrectable errors in the
+    /// transmission.
+    pub fn tei_packets(&self) -> u64 {
+        self.tei_packets
+    }
+
+    /// Return the number of packets whose payload is scrambled with the even or odd key.
+    ///
+    /// The payloads of these packets can't be read without descrambling them first.
+    pub fn scrambled_packets(&self) -> u64 {
+        self.scrambled_packets
+    }
+
+    /// Return if nothing had to be worked around.
+    pub fn is_clean(&self) -> bool {
+        self.continuity_errors == 0 && self.tei_packets == 0 && self.scrambled_packets == 0
+    }
+}
+
+/// How the continuity counter of a packet differed from the one that was expected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContinuityAnomaly {
+    /// The packet repeated the continuity counter of the packet before it. A packet may be sent
+    /// twice in a row, so this is not an error.
+    Duplicate,
+    /// The continuity counter skipped ahead by this many packets, which means they were lost.
+    ///
+    /// Continuity counters wrap every 16 packets, so the count is always between `1` and `15` even
+    /// if more packets were lost.
+    Drop(u8),
+    /// The continuity counter jumped, but the jump was flagged by the discontinuity indicator.
+    Discontinuity,
+}
+
+/// Counts of each kind of continuity counter anomaly found on a PID.
+///
+/// This is returned by [`crate::reader::TSReader::continuity_breakdown`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContinuityBreakdown {
+    /// Number of duplicate packets.
+    duplicates: u64,
+    /// Number of times the continuity counter skipped ahead.
+    drops: u64,
+    /// Number of packets that were lost across every drop.
+    dropped_packets: u64,
+    /// Number of jumps flagged by the discontinuity indicator.
+    discontinuities: u64,
+}
+
+impl ContinuityBreakdown {
+    /// Add an anomaly to the counts.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn observe(&mut self, anomaly: ContinuityAnomaly) {
+        match anomaly {
+            ContinuityAnomaly::Duplicate => self.duplicates += 1,
+            ContinuityAnomaly::Drop(lost) => {
+                self.drops += 1;
+                self.dropped_packets += lost as u64;
+            },
+            ContinuityAnomaly::Discontinuity => self.discontinuities += 1,
+        }
+    }
+
+    /// Return the number of duplicate packets.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// Return the number of times the continuity counter skipped ahead.
+    pub fn drops(&self) -> u64 {
+        self.drops
+    }
+
+    /// Return the number of packets that were lost across every drop.
+    ///
+    /// This is a lower bound since each drop can only account for up to 15 lost packets.
+    pub fn dropped_packets(&self) -> u64 {
+        self.dropped_packets
+    }
+
+    /// Return the number of jumps flagged by the discontinuity indicator.
+    pub fn discontinuities(&self) -> u64 {
+        self.discontinuities
+    }
+}
+
+/// The delay between the PCR and the PTS of a PES packet when the PES packet was delivered.
+///
+/// This is how long the access unit sits in the decoder's buffer before it is presented, which is
+/// what the T-STD buffer model is built on. A negative delay means that the access unit arrived
+/// after it should have been presented.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PtsPcrDelay {
+    /// PID of the stream that the PES packet was carried on.
+    pid: u16,
+    /// Byte offset in the file of the packet that started the PES packet.
+    offset: u64,
+    /// PTS of the PES packet in 90kHz ticks.
+    pts: u64,
+    /// Most recent PCR of the program when the PES packet started, in 27MHz ticks.
+    pcr: u64,
+}
+
+impl PtsPcrDelay {
+    /// Create a new PTS to PCR delay measurement.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(pid: u16, offset: u64, pts: u64, pcr: u64) -> Self {
+        PtsPcrDelay {
+            pid,
+            offset,
+            pts,
+            pcr,
+        }
+    }
+
+    /// Return the PID of the stream that the PES packet was carried on.
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Return the byte offset in the file of the packet that started the PES packet.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the PTS of the PES packet in 90kHz ticks.
+    pub fn pts(&self) -> u64 {
+        self.pts
+    }
+
+    /// Return the PCR of the program when the PES packet started, in 27MHz ticks.
+    pub fn pcr(&self) -> u64 {
+        self.pcr
+    }
+
+    /// Return the PTS minus the PCR in 27MHz ticks.
+    ///
+    /// Both clocks wrap at the same point, so the shortest distance between them is used.
+    pub fn delay(&self) -> i64 {
+        adaptation_field::pcr_difference(self.pcr, self.pts * PTS_TO_PCR)
+    }
+
+    /// Return the PTS minus the PCR in seconds.
+    pub fn delay_secs(&self) -> f64 {
+        self.delay() as f64 / PCR_FREQUENCY as f64
+    }
+}
+
+/// A point where the PTS of a stream jumped backward or forward by more than expected.
+///
+/// These usually mark a splice or an error in the stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PtsDiscontinuity {
+    /// PID of the stream that the PES packets were carried on.
+    pid: u16,
+    /// Byte offset in the file of the packet that started the PES packet after the jump.
+    offset: u64,
+    /// PTS of the PES packet before the jump in 90kHz ticks.
+    previous_pts: u64,
+    /// PTS of the PES packet after the jump in 90kHz ticks.
+    pts: u64,
+}
+
+impl PtsDiscontinuity {
+    /// Create a new PTS discontinuity.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(pid: u16, offset: u64, previous_pts: u64, pts: u64) -> Self {
+        PtsDiscontinuity {
+            pid,
+            offset,
+            previous_pts,
+            pts,
+        }
+    }
+
+    /// Return the PID of the stream that the PES packets were carried on.
+    pub fn pid(&self) -> u16 {
+        self.pid
+    }
+
+    /// Return the byte offset in the file of the packet that started the PES packet after the jump.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the PTS of the PES packet before the jump in 90kHz ticks.
+    pub fn previous_pts(&self) -> u64 {
+        self.previous_pts
+    }
+
+    /// Return the PTS of the PES packet after the jump in 90kHz ticks.
+    pub fn pts(&self) -> u64 {
+        self.pts
+    }
+
+    /// Return how far the PTS jumped in 90kHz ticks, accounting for wrapping.
+    ///
+    /// A negative value means that the PTS jumped backward.
+    pub fn jump(&self) -> i64 {
+        pes::pts_difference(self.previous_pts, self.pts)
+    }
+}
+
+/// The transport bitrate of a stream over a single window of time measured by its PCR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitrateWindow {
+    /// Byte offset in the file of the PCR packet that started the window.
+    offset: u64,
+    /// Number of packets from the PCR packet that started the window up to the one that ended it.
+    packets: u64,
+    /// Time between the PCRs that started and ended the window in 27MHz ticks.
+    duration: u64,
+}
+
+impl BitrateWindow {
+    /// Create a new bitrate window.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(offset: u64, packets: u64, duration: u64) -> Self {
+        BitrateWindow {
+            offset,
+            packets,
+            duration,
+        }
+    }
+
+    /// Return the byte offset in the file of the PCR packet that started the window.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the number of packets in the window.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// Return the length of the window in 27MHz ticks.
+    pub fn duration(&self) -> u64 {
+        self.duration
+    }
+
+    /// Return the transport bitrate over the window in bits per second.
+    pub fn bitrate(&self) -> f64 {
+        bitrate(self.packets, self.duration)
+    }
+}
+
+/// How the transport bitrate of a stream changed over time.
+///
+/// The stream is split into consecutive windows between PCRs, and the bitrate of each window is
+/// measured from the number of packets in it. This is what is used to check that a variable
+/// bitrate stream stays within the capacity of its channel.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BitrateProfile {
+    /// Every window that was measured, in the order they were found.
+    windows: Vec<BitrateWindow>,
+}
+
+impl BitrateProfile {
+    /// Create a new bitrate profile from the windows that were measured.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(windows: Vec<BitrateWindow>) -> Self {
+        BitrateProfile { windows }
+    }
+
+    /// Return every window that was measured, in the order they were found. This is the series
+    /// that the rate curve of the stream can be plotted from.
+    pub fn windows(&self) -> &[BitrateWindow] {
+        &self.windows
+    }
+
+    /// Return the highest bitrate of any window in bits per second.
+    ///
+    /// Returns `None` if no windows were measured.
+    pub fn peak(&self) -> Option<f64> {
+        self.windows.iter().map(BitrateWindow::bitrate).reduce(f64::max)
+    }
+
+    /// Return the lowest bitrate of any window in bits per second.
+    ///
+    /// Returns `None` if no windows were measured.
+    pub fn minimum(&self) -> Option<f64> {
+        self.windows.iter().map(BitrateWindow::bitrate).reduce(f64::min)
+    }
+
+    /// Return the bitrate over every window together in bits per second.
+    ///
+    /// This weighs each window by its length rather than averaging the bitrate of each window.
+    /// Returns `None` if no windows were measured.
+    pub fn average(&self) -> Option<f64> {
+        if self.windows.is_empty() {
+            return None
+        }
+
+        let packets = self.windows.iter().map(|window| window.packets).sum();
+        let duration = self.windows.iter().map(|window| window.duration).sum();
+        Some(bitrate(packets, duration))
+    }
+}
+
+/// A time between consecutive PCRs that was longer than the allowed limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PcrIntervalViolation {
+    /// Byte offset in the file of the packet with the PCR that ended the interval.
+    offset: u64,
+    /// Time between the PCRs.
+    interval: Duration,
+}
+
+impl PcrIntervalViolation {
+    /// Return the byte offset in the file of the packet with the PCR that ended the interval.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Return the time between the PCRs.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+/// Distribution of the time between consecutive PCRs of a stream.
+///
+/// Intervals are counted in buckets of a fixed width, which is what PCR jitter plots are drawn
+/// from. Intervals longer than the limit are also kept individually so that they can be found in
+/// the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PcrIntervalHistogram {
+    /// Width of each bucket.
+    bucket_width: Duration,
+    /// Longest interval that isn't a violation.
+    limit: Duration,
+    /// Number of intervals in each non-empty bucket, keyed by the index of the bucket.
+    counts: BTreeMap<u128, u64>,
+    /// Every interval that was longer than the limit, in the order they were found.
+    violations: Vec<PcrIntervalViolation>,
+}
+
+impl PcrIntervalHistogram {
+    /// Create a new empty histogram.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new(bucket_width: Duration, limit: Duration) -> Self {
+        PcrIntervalHistogram {
+            bucket_width,
+            limit,
+            counts: BTreeMap::new(),
+            violations: Vec::new(),
+        }
+    }
+
+    /// Add the interval between a PCR and the one before it.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn observe(&mut self, offset: u64, interval: Duration) {
+        let bucket = interval.as_nanos() / self.bucket_width.as_nanos().max(1);
+        *self.counts.entry(bucket).or_default() += 1;
+
+        if interval > self.limit {
+            self.violations.push(PcrIntervalViolation { offset, interval });
+        }
+    }
+
+    /// Return the width of each bucket.
+    pub fn bucket_width(&self) -> Duration {
+        self.bucket_width
+    }
+
+    /// Return the longest interval that isn't a violation.
+    pub fn limit(&self) -> Duration {
+        self.limit
+    }
+
+    /// Return the start of each bucket along with the number of intervals in it, in order of
+    /// increasing interval. Empty buckets are not included.
+    pub fn buckets(&self) -> Vec<(Duration, u64)> {
+        let width = self.bucket_width.as_nanos().max(1);
+        self.counts.iter()
+            .map(|(bucket, count)| (Duration::from_nanos((bucket * width) as u64), *count))
+            .collect()
+    }
+
+    /// Return the total number of intervals measured.
+    pub fn intervals(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Return every interval that was longer than the limit, in the order they were found.
+    pub fn violations(&self) -> &[PcrIntervalViolation] {
+        &self.violations
+    }
+}
+
+/// Where the packets of a file were found to be out of alignment.
+///
+/// This is returned by [`crate::reader::TSReader::verify_alignment`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AlignmentReport {
+    /// Number of packets that were checked.
+    packets: u64,
+    /// Byte offset in the file of every packet that didn't start with a SYNC byte.
+    misaligned_offsets: Vec<u64>,
+}
+
+impl AlignmentReport {
+    /// Create a new empty report.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn new() -> Self {
+        AlignmentReport::default()
+    }
+
+    /// Add a checked packet to the report.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn observe(&mut self, offset: u64, aligned: bool) {
+        self.packets += 1;
+        if ! aligned {
+            self.misaligned_offsets.push(offset);
+        }
+    }
+
+    /// Return the number of packets that were checked.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// Return the number of packets that didn't start with a SYNC byte.
+    pub fn misaligned_count(&self) -> usize {
+        self.misaligned_offsets.len()
+    }
+
+    /// Return the byte offset in the file of every packet that didn't start with a SYNC byte, in
+    /// the order they were found.
+    pub fn misaligned_offsets(&self) -> &[u64] {
+        &self.misaligned_offsets
+    }
+
+    /// Return the byte offset in the file of the first packet that didn't start with a SYNC byte.
+    pub fn first_misaligned(&self) -> Option<u64> {
+        self.misaligned_offsets.first().copied()
+    }
+
+    /// Return if every packet that was checked started with a SYNC byte.
+    pub fn is_aligned(&self) -> bool {
+        self.misaligned_offsets.is_empty()
+    }
+}
+
+/// Return the bitrate in bits per second of the given number of packets sent over a length of time
+/// in 27MHz ticks.
+fn bitrate(packets: u64, duration: u64) -> f64 {
+    (packets * PACKET_SIZE as u64 * 8) as f64 * PCR_FREQUENCY as f64 / duration as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::adaptation_field::PCR_WRAP;
+    use test_case::test_case;
+
+    #[test_case(0, 0, 0.0; "Nothing read")]
+    #[test_case(188, 188, 0.0; "Only payload")]
+    #[test_case(0, 188, 1.0; "Only overhead")]
+    #[test_case(141, 188, 0.25; "Mixed")]
+    fn value(payload_bytes: u64, total_bytes: u64, expected: f64) {
+        let ratio = OverheadRatio::new(payload_bytes, total_bytes);
+        assert_eq!(ratio.value(), expected, "Overhead ratio is incorrect");
+    }
+
+    #[test]
+    fn display() {
+        let ratio = OverheadRatio::new(141, 188);
+        assert_eq!(ratio.to_string(), "25.00%", "Overhead ratio is formatted incorrectly");
+    }
+
+    #[test_case(90_000, 0, 27_000_000; "PTS ahead of PCR")]
+    #[test_case(0, 27_000_000, -27_000_000; "PTS behind PCR")]
+    #[test_case(9_000, PCR_WRAP - 2_700_000, 5_400_000; "PCR wrapped")]
+    #[test_case((1 << 33) - 9_000, 2_700_000, -5_400_000; "PTS wrapped")]
+    fn delay(pts: u64, pcr: u64, expected: i64) {
+        let delay = PtsPcrDelay::new(0x100, 0, pts, pcr);
+        assert_eq!(delay.delay(), expected, "PTS to PCR delay is incorrect");
+    }
+
+    #[test]
+    fn delay_secs() {
+        let delay = PtsPcrDelay::new(0x100, 0, 45_000, 0);
+        assert_eq!(delay.delay_secs(), 0.5, "PTS to PCR delay in seconds is incorrect");
+    }
+
+    #[test_case(90_000, 0, -90_000; "Backward")]
+    #[test_case(pes::PTS_WRAP - 9_000, 9_000, 18_000; "Wrapped")]
+    fn jump(previous_pts: u64, pts: u64, expected: i64) {
+        let discontinuity = PtsDiscontinuity::new(0x100, 0, previous_pts, pts);
+        assert_eq!(discontinuity.jump(), expected, "PTS jump is incorrect");
+    }
+
+    #[test]
+    fn stream_stats() {
+        let mut packets = [
+            // Payload only.
+            vec![0x47, 0x01, 0x00, 0x10],
+            // Adaptation field with a PCR.
+            vec![0x47, 0x01, 0x00, 0x30, 0x07, 0x10, 0x00, 0x00, 0x00, 0x00, 0x7E, 0x00],
+            // Adaptation field without a PCR.
+            vec![0x47, 0x01, 0x00, 0x20, 0xB7, 0x00],
+            // Stuffing adaptation field.
+            vec![0x47, 0x01, 0x00, 0x30, 0x00],
+            // Transport error indicator set and scrambled with the odd key.
+            vec![0x47, 0x81, 0x00, 0xD0],
+            // Reserved scrambling control.
+            vec![0x47, 0x01, 0x00, 0x50],
+        ];
+
+        let mut stats = StreamStats::default();
+        for buf in packets.iter_mut() {
+            buf.resize(PACKET_SIZE, 0xFF);
+            stats.observe(&TSPacket::from_bytes(buf).unwrap());
+        }
+
+        assert_eq!(stats.packets(), 6, "Number of packets is incorrect");
+        assert_eq!(stats.data_adaptation_field_packets(), 2,
+            "Number of data adaptation fields is incorrect");
+        assert_eq!(stats.stuffing_adaptation_field_packets(), 1,
+            "Number of stuffing adaptation fields is incorrect");
+        assert_eq!(stats.adaptation_field_packets(), 3, "Number of adaptation fields is incorrect");
+        assert_eq!(stats.pcr_packets(), 1, "Number of PCRs is incorrect");
+        assert_eq!(stats.payload_only_packets(), 3, "Number of payload only packets is incorrect");
+        assert_eq!(stats.tei_packets(), 1, "Number of TEI packets is incorrect");
+        assert_eq!(stats.scrambled_packets(), 1, "Number of scrambled packets is incorrect");
+    }
+
+    #[test]
+    fn bitrate_profile() {
+        // 1000 and 3000 packets over 1 second each, and 1000 packets over 2 seconds.
+        let profile = BitrateProfile::new(vec![
+            BitrateWindow::new(0, 1_000, 27_000_000),
+            BitrateWindow::new(188_000, 3_000, 27_000_000),
+            BitrateWindow::new(752_000, 1_000, 54_000_000),
+        ]);
+
+        assert_eq!(profile.windows()[0].bitrate(), 1_504_000.0, "Window bitrate is incorrect");
+        assert_eq!(profile.peak(), Some(4_512_000.0), "Peak bitrate is incorrect");
+        assert_eq!(profile.minimum(), Some(752_000.0), "Minimum bitrate is incorrect");
+        assert_eq!(profile.average(), Some(1_880_000.0), "Average bitrate is incorrect");
+        assert_eq!(BitrateProfile::default().peak(), None, "Peak of empty profile is incorrect");
+    }
+
+    #[test]
+    fn pcr_interval_histogram() {
+        let mut histogram = PcrIntervalHistogram::new(Duration::from_millis(10), MAX_PCR_INTERVAL);
+        for (offset, millis) in [(0, 35), (188, 38), (376, 41), (564, 150)] {
+            histogram.observe(offset, Duration::from_millis(millis));
+        }
+
+        assert_eq!(histogram.buckets(), vec![
+            (Duration::from_millis(30), 2),
+            (Duration::from_millis(40), 1),
+            (Duration::from_millis(150), 1),
+        ], "Buckets are incorrect");
+        assert_eq!(histogram.intervals(), 4, "Number of intervals is incorrect");
+        let violations: Vec<(u64, Duration)> = histogram.violations().iter()
+            .map(|violation| (violation.offset(), violation.interval()))
+            .collect();
+        assert_eq!(violations, vec![(564, Duration::from_millis(150))],
+            "Violations are incorrect");
+    }
+}